@@ -0,0 +1,157 @@
+//! Live agent state shared with the tray.
+//!
+//! `run_agent` and the uploader record what they are doing in an
+//! [`AgentState`]; anything running in the same process (the tray's
+//! background watcher) holds a clone and reads snapshots of it. Updates are
+//! cheap and never block on I/O, so they happen inline on the processing path.
+
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Why the agent is holding runs instead of extracting them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// Outside the configured `[active_schedule]`
+    OutsideActiveHours,
+    /// Free space on the data drive is below `spool.min_free_disk_mb`
+    LowDisk,
+}
+
+impl std::fmt::Display for PauseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PauseReason::OutsideActiveHours => write!(f, "outside active hours"),
+            PauseReason::LowDisk => write!(f, "low disk space"),
+        }
+    }
+}
+
+/// Point-in-time view of what the agent is doing.
+#[derive(Debug, Clone, Default)]
+pub struct AgentStatus {
+    /// Run currently being classified/extracted/spooled
+    pub processing: Option<PathBuf>,
+    /// Runs spooled (or kept locally) since the agent started
+    pub processed: u64,
+    /// Runs that failed classification, extraction or spooling
+    pub failed: u64,
+    /// Most recent failed run
+    pub last_failure: Option<PathBuf>,
+    /// When a payload was last accepted by the cloud
+    pub last_upload_at: Option<DateTime<Utc>>,
+    /// Set while runs are being deferred rather than processed
+    pub paused: Option<PauseReason>,
+    /// The agent loop has returned (shut down or failed to start)
+    pub stopped: bool,
+}
+
+/// Shared handle to the live agent state. Clones see the same state.
+#[derive(Debug, Clone, Default)]
+pub struct AgentState {
+    inner: Arc<RwLock<AgentStatus>>,
+}
+
+impl AgentState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy of the current state.
+    #[cfg_attr(not(windows), allow(dead_code))] // Read by the tray
+    pub fn snapshot(&self) -> AgentStatus {
+        self.inner.read().unwrap().clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut AgentStatus)) {
+        f(&mut self.inner.write().unwrap());
+    }
+
+    /// Mark a run as in progress until the returned guard is dropped.
+    pub fn processing(&self, path: &Path) -> ProcessingGuard {
+        self.update(|s| s.processing = Some(path.to_path_buf()));
+        ProcessingGuard {
+            state: self.clone(),
+        }
+    }
+
+    pub fn record_processed(&self) {
+        self.update(|s| s.processed += 1);
+    }
+
+    pub fn record_failed(&self, path: &Path) {
+        self.update(|s| {
+            s.failed += 1;
+            s.last_failure = Some(path.to_path_buf());
+        });
+    }
+
+    pub fn record_upload(&self, at: DateTime<Utc>) {
+        self.update(|s| s.last_upload_at = Some(at));
+    }
+
+    pub fn set_paused(&self, reason: Option<PauseReason>) {
+        self.update(|s| s.paused = reason);
+    }
+
+    #[cfg_attr(not(windows), allow(dead_code))] // Set by the tray
+    pub fn set_stopped(&self) {
+        self.update(|s| s.stopped = true);
+    }
+}
+
+/// Clears [`AgentStatus::processing`] when the run is finished with, however
+/// the processing loop leaves it.
+pub struct ProcessingGuard {
+    state: AgentState,
+}
+
+impl Drop for ProcessingGuard {
+    fn drop(&mut self) {
+        self.state.update(|s| s.processing = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updates_propagate_to_reader() {
+        let state = AgentState::new();
+        let reader = state.clone();
+        assert!(reader.snapshot().processing.is_none());
+
+        let run = PathBuf::from("/data/QC_A_A1.raw");
+        std::thread::spawn({
+            let state = state.clone();
+            let run = run.clone();
+            move || {
+                let _processing = state.processing(&run);
+                state.record_processed();
+                state.record_failed(&run);
+                state.set_paused(Some(PauseReason::LowDisk));
+            }
+        })
+        .join()
+        .unwrap();
+
+        let status = reader.snapshot();
+        assert_eq!(status.processed, 1);
+        assert_eq!(status.failed, 1);
+        assert_eq!(status.last_failure, Some(run.clone()));
+        assert_eq!(status.paused, Some(PauseReason::LowDisk));
+        // The guard went out of scope with the run
+        assert!(status.processing.is_none());
+
+        let _processing = state.processing(&run);
+        assert_eq!(reader.snapshot().processing, Some(run));
+
+        let uploaded_at = Utc::now();
+        state.record_upload(uploaded_at);
+        state.set_paused(None);
+        let status = reader.snapshot();
+        assert_eq!(status.last_upload_at, Some(uploaded_at));
+        assert!(status.paused.is_none());
+    }
+}
@@ -1,53 +1,237 @@
 //! Baseline management.
 //!
 //! Baselines are primarily managed by the MD cloud, but the agent
-//! needs to track active baselines for comparison metrics.
+//! needs to track active baselines for comparison metrics. Each
+//! instrument's active baseline is cached in `{data_dir}/baselines/` with
+//! the time it was fetched, so comparisons keep working from the last good
+//! copy while the cloud is unreachable.
 
 #![allow(dead_code)]
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tracing::{debug, warn};
 
-use crate::types::{Baseline, RunMetrics, TargetMetrics};
+use crate::config::{paths, CloudConfig, ComparisonConfig};
+use crate::error::UploadError;
+use crate::types::{Baseline, BaselineContext, BaselineState, RunMetrics, TargetMetrics};
+use crate::uploader::Uploader;
+
+/// An instrument's active baseline as cached on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedBaseline {
+    pub baseline: Baseline,
+    /// When the baseline was last fetched from the cloud
+    pub fetched_at: DateTime<Utc>,
+    /// The latest refresh failed, so the cloud may have moved on
+    #[serde(default)]
+    pub stale: bool,
+}
+
+impl CachedBaseline {
+    /// Baseline context for a payload compared against this baseline.
+    pub fn context(&self) -> BaselineContext {
+        BaselineContext {
+            baseline_id: self.baseline.baseline_id.clone(),
+            baseline_established: self.baseline.established,
+            baseline_template_hash: self.baseline.template_hash.clone(),
+            fetched_at: Some(self.fetched_at),
+            stale: self.stale,
+        }
+    }
+}
 
 /// Baseline manager that caches baseline information from the cloud.
+#[derive(Clone)]
 pub struct BaselineManager {
+    /// Folder of per-instrument cache files
+    dir: PathBuf,
     /// Cached baselines by instrument ID
-    baselines: Arc<RwLock<HashMap<String, Baseline>>>,
+    baselines: Arc<RwLock<HashMap<String, CachedBaseline>>>,
+    /// Extra attempts after a failed fetch, and the delay before the first
+    /// (doubled for each one after)
+    retries: u32,
+    retry_delay: Duration,
 }
 
 impl BaselineManager {
     pub fn new() -> Self {
         Self {
+            dir: Self::cache_dir(),
             baselines: Arc::new(RwLock::new(HashMap::new())),
+            retries: 0,
+            retry_delay: Duration::ZERO,
         }
     }
 
+    /// Create a manager holding the baselines cached on disk.
+    pub fn load() -> Self {
+        let dir = Self::cache_dir();
+        migrate_legacy_cache(&paths::data_dir().join("baselines.json"), &dir);
+        Self::load_from(&dir)
+    }
+
+    /// Create a manager holding the baselines cached in `dir`.
+    pub fn load_from(dir: &Path) -> Self {
+        let mut cached = HashMap::new();
+        let entries = std::fs::read_dir(dir).into_iter().flatten().flatten();
+        for path in entries.map(|e| e.path()) {
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let entry = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<CachedBaseline>(&content).map_err(|e| e.to_string())
+                });
+            match entry {
+                Ok(entry) => {
+                    cached.insert(entry.baseline.instrument_id.clone(), entry);
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Ignoring unreadable cached baseline")
+                }
+            }
+        }
+
+        Self {
+            dir: dir.to_path_buf(),
+            baselines: Arc::new(RwLock::new(cached)),
+            ..Self::new()
+        }
+    }
+
+    /// Retry failed cloud fetches as set by `baseline_fetch_retries` and
+    /// `baseline_fetch_retry_delay_seconds`.
+    pub fn with_retry(mut self, config: &CloudConfig) -> Self {
+        self.retries = config.baseline_fetch_retries;
+        self.retry_delay = Duration::from_secs(config.baseline_fetch_retry_delay_seconds);
+        self
+    }
+
+    /// Get the path to the cache folder
+    fn cache_dir() -> PathBuf {
+        paths::data_dir().join("baselines")
+    }
+
+    /// Cache file of an instrument.
+    fn cache_path(&self, instrument_id: &str) -> PathBuf {
+        let name: String = instrument_id
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c => c,
+            })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Write an instrument's cached baseline to disk.
+    fn save(&self, entry: &CachedBaseline) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string_pretty(entry)?;
+        std::fs::write(self.cache_path(&entry.baseline.instrument_id), content)?;
+        Ok(())
+    }
+
     /// Get the active baseline for an instrument.
     pub async fn get_active(&self, instrument_id: &str) -> Option<Baseline> {
+        let baselines = self.baselines.read().await;
+        baselines.get(instrument_id).map(|c| c.baseline.clone())
+    }
+
+    /// Get the active baseline for an instrument with when it was fetched.
+    pub async fn get_cached(&self, instrument_id: &str) -> Option<CachedBaseline> {
         let baselines = self.baselines.read().await;
         baselines.get(instrument_id).cloned()
     }
 
-    /// Update the cached baseline for an instrument.
+    /// Cache a freshly fetched baseline for its instrument.
     pub async fn update(&self, baseline: Baseline) {
+        let entry = CachedBaseline {
+            baseline,
+            fetched_at: Utc::now(),
+            stale: false,
+        };
+        if let Err(e) = self.save(&entry) {
+            warn!(error = %e, "Failed to save baseline cache");
+        }
         let mut baselines = self.baselines.write().await;
-        baselines.insert(baseline.instrument_id.clone(), baseline);
+        baselines.insert(entry.baseline.instrument_id.clone(), entry);
     }
 
     /// Clear the cached baseline for an instrument.
     pub async fn clear(&self, instrument_id: &str) {
         let mut baselines = self.baselines.write().await;
         baselines.remove(instrument_id);
+        let _ = std::fs::remove_file(self.cache_path(instrument_id));
     }
 
-    /// Refresh baselines from the cloud.
-    pub async fn refresh_from_cloud(&self, _cloud_endpoint: &str) -> anyhow::Result<()> {
-        // TODO: Implement cloud API call to fetch active baselines
-        // For now, this is a no-op
-        Ok(())
+    /// Flag an instrument's cached baseline as stale after a failed refresh.
+    async fn mark_stale(&self, instrument_id: &str) {
+        let mut baselines = self.baselines.write().await;
+        let Some(entry) = baselines.get_mut(instrument_id) else {
+            return;
+        };
+        warn!(
+            instrument = instrument_id,
+            baseline_id = %entry.baseline.baseline_id,
+            fetched_at = %entry.fetched_at,
+            "Baseline refresh failed; using the cached copy"
+        );
+        if !entry.stale {
+            entry.stale = true;
+            if let Err(e) = self.save(entry) {
+                warn!(error = %e, "Failed to save baseline cache");
+            }
+        }
+    }
+
+    /// Refresh an instrument's baselines from the cloud.
+    ///
+    /// Caches the most recently established `ACTIVE` baseline (clearing the
+    /// cache when the cloud has none) and returns everything the cloud sent.
+    /// A failed fetch is retried as set by `with_retry`; when every attempt
+    /// fails the cached copy is kept and flagged stale.
+    pub async fn refresh_from_cloud(
+        &self,
+        uploader: &Uploader,
+        instrument_id: &str,
+        project_id: Option<&str>,
+    ) -> Result<Vec<Baseline>, UploadError> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        let fetched = loop {
+            match uploader.fetch_baselines(instrument_id, project_id).await {
+                Ok(fetched) => break fetched,
+                Err(e) if attempt < self.retries && worth_retrying(&e) => {
+                    attempt += 1;
+                    debug!(instrument = instrument_id, attempt, error = %e, "Retrying baseline fetch");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    self.mark_stale(instrument_id).await;
+                    return Err(e);
+                }
+            }
+        };
+
+        let active = fetched
+            .iter()
+            .filter(|b| b.state == BaselineState::Active && b.instrument_id == instrument_id)
+            .max_by_key(|b| b.established);
+        match active {
+            Some(baseline) => self.update(baseline.clone()).await,
+            None => self.clear(instrument_id).await,
+        }
+
+        Ok(fetched)
     }
 }
 
@@ -57,60 +241,302 @@ impl Default for BaselineManager {
     }
 }
 
+/// Move baselines from the single `baselines.json` that older versions kept
+/// into per-instrument files in `dir`, then delete it. Its modification time
+/// stands in for when they were fetched. Instruments that already have a
+/// cache file keep it. On any failure the old file stays, and is tried again
+/// at the next start.
+fn migrate_legacy_cache(legacy: &Path, dir: &Path) {
+    let Ok(content) = std::fs::read_to_string(legacy) else {
+        return;
+    };
+    let fetched_at = std::fs::metadata(legacy)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+
+    let result = serde_json::from_str::<HashMap<String, Baseline>>(&content)
+        .map_err(anyhow::Error::from)
+        .and_then(|legacy_baselines| {
+            let manager = BaselineManager {
+                dir: dir.to_path_buf(),
+                ..BaselineManager::new()
+            };
+            for baseline in legacy_baselines.into_values() {
+                if manager.cache_path(&baseline.instrument_id).exists() {
+                    continue;
+                }
+                manager.save(&CachedBaseline {
+                    baseline,
+                    fetched_at,
+                    stale: false,
+                })?;
+            }
+            std::fs::remove_file(legacy)?;
+            Ok(())
+        });
+    match result {
+        Ok(()) => debug!(path = %legacy.display(), "Migrated cached baselines"),
+        Err(e) => warn!(
+            path = %legacy.display(),
+            error = %e,
+            "Could not migrate cached baselines"
+        ),
+    }
+}
+
+/// Whether another attempt could fetch what this one didn't; bad
+/// credentials or certificates won't fix themselves.
+fn worth_retrying(error: &UploadError) -> bool {
+    !matches!(
+        error,
+        UploadError::Authentication(_) | UploadError::Certificate(_)
+    )
+}
+
 /// Compare run metrics against a baseline.
+///
+/// Outlier and tolerance thresholds come from `config`; with
+/// `comparison_log_space` outliers are judged on log2 fold-change. Both the
+/// linear area ratio and the log2 fold-change are summarised either way. A
+/// run whose total ion
+/// current (or base peak intensity) fell below `min_intensity_ratio` of the
+/// baseline's is flagged as a sensitivity drop and is out of tolerance.
 pub fn compare_to_baseline(
-    _run_metrics: &RunMetrics,
+    run_metrics: &RunMetrics,
     target_metrics: &[TargetMetrics],
     baseline: &Baseline,
+    config: &ComparisonConfig,
 ) -> ComparisonResult {
-    // Calculate RT shift statistics
-    let mut rt_shifts = Vec::new();
-    let mut area_ratios = Vec::new();
-    let mut outliers = Vec::new();
-
-    for target in target_metrics {
-        // Find corresponding baseline target
-        let baseline_target = baseline
-            .target_metrics
-            .iter()
-            .find(|bt| bt.target_id == target.target_id);
-
-        if let Some(bt) = baseline_target {
-            // RT shift
-            let rt_shift = target.retention_time - bt.retention_time;
-            rt_shifts.push(rt_shift);
+    let deltas = target_deltas(&baseline.target_metrics, target_metrics, config, false);
 
-            // Area ratio
-            if bt.peak_area > 0.0 {
-                let ratio = target.peak_area / bt.peak_area;
-                area_ratios.push(ratio);
-
-                // Check for outliers (>3 sigma from 1.0)
-                if (ratio - 1.0).abs() > 0.5 {
-                    outliers.push(target.target_id.clone());
-                }
-            }
-        }
-    }
+    let rt_shifts: Vec<f64> = deltas.iter().map(|d| d.rt_shift).collect();
+    let area_ratios: Vec<f64> = deltas.iter().filter_map(|d| d.area_ratio).collect();
+    let log2_fold_changes: Vec<f64> = deltas.iter().filter_map(|d| d.log2_fold_change).collect();
+    let outliers: Vec<String> = deltas
+        .iter()
+        .filter(|d| d.outlier)
+        .map(|d| d.target_id.clone())
+        .collect();
 
     // Calculate statistics
     let rt_shift_mean = mean(&rt_shifts);
     let rt_shift_std = std_dev(&rt_shifts);
     let area_ratio_mean = mean(&area_ratios);
     let area_ratio_std = std_dev(&area_ratios);
+    let log2_fold_change_mean = mean(&log2_fold_changes);
+    let log2_fold_change_std = std_dev(&log2_fold_changes);
 
-    let within_tolerance = outliers.is_empty() && rt_shift_std < 0.5;
+    let intensity_ratio = intensity_ratio(run_metrics, &baseline.run_metrics);
+    let sensitivity_drop = intensity_ratio.is_some_and(|ratio| ratio < config.min_intensity_ratio);
+
+    let within_tolerance =
+        outliers.is_empty() && rt_shift_std < config.rt_shift_std_tolerance && !sensitivity_drop;
 
     ComparisonResult {
         rt_shift_mean,
         rt_shift_std,
         area_ratio_mean,
         area_ratio_std,
+        log2_fold_change_mean,
+        log2_fold_change_std,
         outlier_targets: outliers,
+        intensity_ratio,
+        sensitivity_drop,
         within_tolerance,
     }
 }
 
+/// Ratio of the run's total ion current to the reference's, falling back to
+/// base peak intensity when either lacks a TIC.
+fn intensity_ratio(run: &RunMetrics, reference: &RunMetrics) -> Option<f64> {
+    let ratio = |run: Option<f64>, reference: Option<f64>| {
+        run.zip(reference)
+            .filter(|(_, reference)| *reference > 0.0)
+            .map(|(run, reference)| run / reference)
+    };
+    ratio(run.total_ion_current, reference.total_ion_current)
+        .or_else(|| ratio(run.base_peak_intensity, reference.base_peak_intensity))
+}
+
+/// Per-target deltas of `run` relative to `reference`, in `run` order.
+///
+/// Targets are matched by id, then by id without a duplicate occurrence
+/// suffix (a second peak "X#2" matches "X" when the reference has only
+/// one), then, with `by_sequence`, by peptide sequence so runs extracted
+/// with differently named targets still line up. Targets without a match
+/// are skipped.
+pub fn target_deltas(
+    reference: &[TargetMetrics],
+    run: &[TargetMetrics],
+    config: &ComparisonConfig,
+    by_sequence: bool,
+) -> Vec<TargetDelta> {
+    run.iter()
+        .filter_map(|target| {
+            let matched = find_match(reference, target, by_sequence)?;
+
+            // Area ratio, flagged when too far from 1.0 (or from 0 in log2)
+            let area_ratio =
+                (matched.peak_area > 0.0).then(|| target.peak_area / matched.peak_area);
+            let log2_fold_change = area_ratio.filter(|r| *r > 0.0).map(f64::log2);
+            let outlier = if config.comparison_log_space {
+                log2_fold_change
+                    .is_some_and(|fc| fc.abs() > config.log2_fold_change_outlier_threshold)
+            } else {
+                area_ratio
+                    .is_some_and(|ratio| (ratio - 1.0).abs() > config.area_ratio_outlier_threshold)
+            };
+
+            Some(TargetDelta {
+                target_id: target.target_id.clone(),
+                peptide_sequence: target.peptide_sequence.clone(),
+                rt_shift: target.retention_time - matched.retention_time,
+                area_ratio,
+                log2_fold_change,
+                mass_error_delta_ppm: target
+                    .mass_error_ppm
+                    .zip(matched.mass_error_ppm)
+                    .map(|(run, reference)| run - reference),
+                outlier,
+            })
+        })
+        .collect()
+}
+
+fn find_match<'a>(
+    reference: &'a [TargetMetrics],
+    target: &TargetMetrics,
+    by_sequence: bool,
+) -> Option<&'a TargetMetrics> {
+    let exact = || reference.iter().find(|r| r.target_id == target.target_id);
+
+    // Only an unambiguous base target, so an extra peak isn't paired with
+    // one of several reference peaks
+    let by_base_id = || {
+        let mut peaks = reference.iter().filter(|r| r.base_id() == target.base_id());
+        let only = peaks.next()?;
+        peaks.next().is_none().then_some(only)
+    };
+
+    let by_peptide = || {
+        let sequence = target.peptide_sequence.as_deref()?;
+        reference
+            .iter()
+            .find(|r| r.peptide_sequence.as_deref() == Some(sequence))
+    };
+
+    exact()
+        .or_else(by_base_id)
+        .or_else(|| by_sequence.then(by_peptide).flatten())
+}
+
+/// Difference between two runs, the second relative to the first.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunDiff {
+    pub targets: Vec<TargetDelta>,
+    /// Targets of the first run with no match in the second
+    pub only_in_a: Vec<String>,
+    /// Targets of the second run with no match in the first
+    pub only_in_b: Vec<String>,
+    pub run_metrics: Vec<MetricDelta>,
+}
+
+/// Compare two arbitrary runs target by target and on run-level metrics.
+pub fn diff_runs(
+    a: (&RunMetrics, &[TargetMetrics]),
+    b: (&RunMetrics, &[TargetMetrics]),
+    config: &ComparisonConfig,
+) -> RunDiff {
+    let (a_run, a_targets) = a;
+    let (b_run, b_targets) = b;
+
+    let unmatched = |targets: &[TargetMetrics], other: &[TargetMetrics]| {
+        targets
+            .iter()
+            .filter(|t| find_match(other, t, true).is_none())
+            .map(|t| t.target_id.clone())
+            .collect()
+    };
+
+    let metric = |name: &str, a: Option<f64>, b: Option<f64>| MetricDelta {
+        metric: name.to_string(),
+        a,
+        b,
+        delta: a.zip(b).map(|(a, b)| b - a),
+    };
+
+    RunDiff {
+        targets: target_deltas(a_targets, b_targets, config, true),
+        only_in_a: unmatched(a_targets, b_targets),
+        only_in_b: unmatched(b_targets, a_targets),
+        run_metrics: vec![
+            metric(
+                "targets_found",
+                Some(a_run.targets_found as f64),
+                Some(b_run.targets_found as f64),
+            ),
+            metric(
+                "target_recovery_pct",
+                Some(a_run.target_recovery_pct),
+                Some(b_run.target_recovery_pct),
+            ),
+            metric(
+                "median_rt_shift",
+                a_run.median_rt_shift,
+                b_run.median_rt_shift,
+            ),
+            metric(
+                "median_mass_error_ppm",
+                a_run.median_mass_error_ppm,
+                b_run.median_mass_error_ppm,
+            ),
+            metric(
+                "chromatography_score",
+                a_run.chromatography_score,
+                b_run.chromatography_score,
+            ),
+            metric(
+                "total_ion_current",
+                a_run.total_ion_current,
+                b_run.total_ion_current,
+            ),
+            metric(
+                "base_peak_intensity",
+                a_run.base_peak_intensity,
+                b_run.base_peak_intensity,
+            ),
+        ],
+    }
+}
+
+/// One target's change between two runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetDelta {
+    pub target_id: String,
+    pub peptide_sequence: Option<String>,
+    /// Retention time difference in minutes
+    pub rt_shift: f64,
+    /// Peak area ratio, if the reference area is non-zero
+    pub area_ratio: Option<f64>,
+    /// log2 of the area ratio, if both areas are non-zero
+    pub log2_fold_change: Option<f64>,
+    /// Mass error difference in ppm, if both runs have one
+    pub mass_error_delta_ppm: Option<f64>,
+    /// Area ratio beyond `area_ratio_outlier_threshold`, or log2 fold-change
+    /// beyond `log2_fold_change_outlier_threshold` in log space
+    pub outlier: bool,
+}
+
+/// A run-level metric in both runs.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDelta {
+    pub metric: String,
+    pub a: Option<f64>,
+    pub b: Option<f64>,
+    pub delta: Option<f64>,
+}
+
 /// Result of comparing a run to a baseline.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComparisonResult {
@@ -118,7 +544,16 @@ pub struct ComparisonResult {
     pub rt_shift_std: f64,
     pub area_ratio_mean: f64,
     pub area_ratio_std: f64,
+    #[serde(default)]
+    pub log2_fold_change_mean: f64,
+    #[serde(default)]
+    pub log2_fold_change_std: f64,
     pub outlier_targets: Vec<String>,
+    /// Run TIC (or base peak intensity) relative to the baseline's, when both
+    /// have one
+    pub intensity_ratio: Option<f64>,
+    /// Intensity ratio below `min_intensity_ratio`
+    pub sensitivity_drop: bool,
     pub within_tolerance: bool,
 }
 
@@ -151,6 +586,334 @@ mod tests {
         assert_eq!(mean(&[]), 0.0);
     }
 
+    fn make_target(target_id: &str, retention_time: f64, peak_area: f64) -> TargetMetrics {
+        TargetMetrics {
+            target_id: target_id.to_string(),
+            peptide_sequence: None,
+            precursor_mz: 500.0,
+            retention_time,
+            rt_expected: None,
+            rt_delta: None,
+            peak_area,
+            peak_height: 0.0,
+            peak_width_fwhm: None,
+            peak_symmetry: None,
+            tailing_factor: None,
+            mass_error_ppm: None,
+            isotope_dot_product: None,
+            detected: peak_area > 0.0,
+            excluded: false,
+        }
+    }
+
+    fn make_baseline(targets: Vec<TargetMetrics>) -> Baseline {
+        Baseline {
+            baseline_id: "base_1".to_string(),
+            instrument_id: "TIMSTOF01".to_string(),
+            method_id: None,
+            template_hash: "hash".to_string(),
+            kit_install_id: None,
+            state: crate::types::BaselineState::Active,
+            established: chrono::Utc::now(),
+            run_metrics: RunMetrics {
+                targets_found: targets.len() as u32,
+                targets_expected: targets.len() as u32,
+                target_recovery_pct: 100.0,
+                median_rt_shift: None,
+                median_mass_error_ppm: None,
+                chromatography_score: None,
+                missing_targets: Vec::new(),
+                undetected_targets: Vec::new(),
+                total_ion_current: None,
+                base_peak_intensity: None,
+                health_index: None,
+            },
+            target_metrics: targets,
+        }
+    }
+
+    #[test]
+    fn test_comparison_thresholds_are_configurable() {
+        let baseline = make_baseline(vec![
+            make_target("t1", 10.0, 1000.0),
+            make_target("t2", 20.0, 1000.0),
+        ]);
+        // t1 area +60%, RT shifts of 0.0 and 0.8 min (std ~0.57)
+        let run = vec![
+            make_target("t1", 10.0, 1600.0),
+            make_target("t2", 20.8, 1000.0),
+        ];
+
+        let defaults = ComparisonConfig::default();
+        let result = compare_to_baseline(&baseline.run_metrics, &run, &baseline, &defaults);
+        assert_eq!(result.outlier_targets, vec!["t1".to_string()]);
+        assert!(!result.within_tolerance);
+
+        let relaxed = ComparisonConfig {
+            area_ratio_outlier_threshold: 0.75,
+            rt_shift_std_tolerance: 1.0,
+            ..ComparisonConfig::default()
+        };
+        let result = compare_to_baseline(&baseline.run_metrics, &run, &baseline, &relaxed);
+        assert!(result.outlier_targets.is_empty());
+        assert!(result.within_tolerance);
+
+        // Outliers pass but RT spread is still too wide
+        let rt_strict = ComparisonConfig {
+            area_ratio_outlier_threshold: 0.75,
+            rt_shift_std_tolerance: 0.5,
+            ..ComparisonConfig::default()
+        };
+        let result = compare_to_baseline(&baseline.run_metrics, &run, &baseline, &rt_strict);
+        assert!(result.outlier_targets.is_empty());
+        assert!(!result.within_tolerance);
+    }
+
+    #[test]
+    fn test_log_space_outliers_use_log2_fold_change() {
+        let baseline = make_baseline(vec![
+            make_target("t1", 10.0, 1.0e9),
+            make_target("t2", 20.0, 1.0e5),
+            make_target("t3", 30.0, 1000.0),
+            make_target("t4", 40.0, 1000.0),
+        ]);
+        // 3x up, 4x down, 1.6x up, unchanged
+        let run = vec![
+            make_target("t1", 10.0, 3.0e9),
+            make_target("t2", 20.0, 2.5e4),
+            make_target("t3", 30.0, 1600.0),
+            make_target("t4", 40.0, 1000.0),
+        ];
+
+        let log_space = ComparisonConfig {
+            comparison_log_space: true,
+            ..ComparisonConfig::default()
+        };
+        let deltas = target_deltas(&baseline.target_metrics, &run, &log_space, false);
+        let fold_changes: Vec<f64> = deltas.iter().map(|d| d.log2_fold_change.unwrap()).collect();
+        for (actual, expected) in fold_changes
+            .iter()
+            .zip([3f64.log2(), -2.0, 1.6f64.log2(), 0.0])
+        {
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "{} vs {}",
+                actual,
+                expected
+            );
+        }
+
+        // Beyond 2-fold either way; 1.6x is within, though linearly an outlier
+        let result = compare_to_baseline(&baseline.run_metrics, &run, &baseline, &log_space);
+        assert_eq!(result.outlier_targets, vec!["t1", "t2"]);
+        let linear = compare_to_baseline(
+            &baseline.run_metrics,
+            &run,
+            &baseline,
+            &ComparisonConfig::default(),
+        );
+        assert_eq!(linear.outlier_targets, vec!["t1", "t2", "t3"]);
+
+        // Both summaries are reported in either mode
+        let expected_mean = fold_changes.iter().sum::<f64>() / 4.0;
+        assert!((result.log2_fold_change_mean - expected_mean).abs() < 1e-9);
+        assert!((linear.log2_fold_change_mean - expected_mean).abs() < 1e-9);
+        assert!((result.area_ratio_mean - (3.0 + 0.25 + 1.6 + 1.0) / 4.0).abs() < 1e-9);
+
+        // A vanished peak has a ratio of 0 but no fold-change
+        let run = vec![make_target("t1", 10.0, 0.0)];
+        let deltas = target_deltas(&baseline.target_metrics, &run, &log_space, false);
+        assert_eq!(deltas[0].area_ratio, Some(0.0));
+        assert_eq!(deltas[0].log2_fold_change, None);
+    }
+
+    #[test]
+    fn test_tic_drop_is_flagged_against_baseline() {
+        let targets = vec![make_target("t1", 10.0, 1000.0)];
+        let mut baseline = make_baseline(targets.clone());
+        baseline.run_metrics.total_ion_current = Some(4.0e10);
+        let config = ComparisonConfig::default();
+
+        let mut run_metrics = baseline.run_metrics.clone();
+        run_metrics.total_ion_current = Some(1.0e10);
+        let result = compare_to_baseline(&run_metrics, &targets, &baseline, &config);
+        assert_eq!(result.intensity_ratio, Some(0.25));
+        assert!(result.sensitivity_drop);
+        assert!(!result.within_tolerance);
+
+        run_metrics.total_ion_current = Some(3.0e10);
+        let result = compare_to_baseline(&run_metrics, &targets, &baseline, &config);
+        assert!(!result.sensitivity_drop);
+        assert!(result.within_tolerance);
+
+        // No TIC in the run: nothing to compare
+        run_metrics.total_ion_current = None;
+        let result = compare_to_baseline(&run_metrics, &targets, &baseline, &config);
+        assert_eq!(result.intensity_ratio, None);
+        assert!(result.within_tolerance);
+    }
+
+    #[test]
+    fn test_diff_runs_matches_by_id_or_sequence() {
+        let mut a = make_baseline(vec![
+            make_target("t1", 10.0, 1000.0),
+            make_target("t2", 20.0, 1000.0),
+            make_target("t3", 30.0, 1000.0),
+        ]);
+        a.target_metrics[1].peptide_sequence = Some("PEPTIDEK".to_string());
+        a.target_metrics[1].mass_error_ppm = Some(1.0);
+
+        let mut renamed = make_target("PEPTIDEK_2+", 20.5, 400.0);
+        renamed.peptide_sequence = Some("PEPTIDEK".to_string());
+        renamed.mass_error_ppm = Some(-2.0);
+        let b = vec![
+            make_target("t1", 10.1, 1100.0),
+            renamed,
+            make_target("t4", 40.0, 1.0),
+        ];
+        let mut b_run = a.run_metrics.clone();
+        b_run.targets_found = 2;
+
+        let diff = diff_runs(
+            (&a.run_metrics, &a.target_metrics),
+            (&b_run, &b),
+            &ComparisonConfig::default(),
+        );
+
+        assert_eq!(diff.targets.len(), 2);
+        assert!((diff.targets[0].rt_shift - 0.1).abs() < 1e-9);
+        assert!((diff.targets[0].area_ratio.unwrap() - 1.1).abs() < 1e-9);
+        assert!(!diff.targets[0].outlier);
+        assert_eq!(diff.targets[1].target_id, "PEPTIDEK_2+");
+        assert!((diff.targets[1].rt_shift - 0.5).abs() < 1e-9);
+        assert_eq!(diff.targets[1].mass_error_delta_ppm, Some(-3.0));
+        assert!(diff.targets[1].outlier);
+        assert_eq!(diff.only_in_a, vec!["t3".to_string()]);
+        assert_eq!(diff.only_in_b, vec!["t4".to_string()]);
+        assert_eq!(diff.run_metrics[0].delta, Some(-1.0));
+    }
+
+    #[test]
+    fn test_duplicate_target_peaks_match_their_base_id() {
+        let reference = vec![
+            make_target("PEPTIDEK_500.25", 10.0, 1000.0),
+            make_target("PEPTIDEK_500.25#2", 12.0, 500.0),
+        ];
+
+        // Each peak matches its own counterpart
+        let run = vec![
+            make_target("PEPTIDEK_500.25", 10.2, 1000.0),
+            make_target("PEPTIDEK_500.25#2", 12.4, 500.0),
+        ];
+        let deltas = target_deltas(&reference, &run, &ComparisonConfig::default(), false);
+        assert!((deltas[0].rt_shift - 0.2).abs() < 1e-9);
+        assert!((deltas[1].rt_shift - 0.4).abs() < 1e-9);
+
+        // An extra peak with no counterpart falls back to the base target,
+        // but only when the reference has a single peak of it
+        let config = ComparisonConfig::default();
+        let run = vec![make_target("PEPTIDEK_500.25#3", 10.5, 1000.0)];
+        let deltas = target_deltas(&reference[..1], &run, &config, false);
+        assert_eq!(deltas.len(), 1);
+        assert!((deltas[0].rt_shift - 0.5).abs() < 1e-9);
+        assert!(target_deltas(&reference, &run, &config, false).is_empty());
+    }
+
+    #[test]
+    fn test_baseline_comparison_does_not_match_by_sequence() {
+        let mut reference = make_target("PEPTIDEK_2+", 10.0, 1000.0);
+        reference.peptide_sequence = Some("PEPTIDEK".to_string());
+        let baseline = make_baseline(vec![reference]);
+
+        // Another charge state of the same peptide
+        let mut other_charge = make_target("PEPTIDEK_3+", 10.1, 100.0);
+        other_charge.peptide_sequence = Some("PEPTIDEK".to_string());
+        let run = [other_charge];
+
+        let config = ComparisonConfig::default();
+        let result = compare_to_baseline(&baseline.run_metrics, &run, &baseline, &config);
+        assert!(result.outlier_targets.is_empty());
+        assert_eq!(
+            target_deltas(&baseline.target_metrics, &run, &config, true).len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_falls_back_to_stale_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("baselines");
+        let manager = BaselineManager::load_from(&cache_dir);
+        manager
+            .update(make_baseline(vec![make_target("t1", 10.0, 1000.0)]))
+            .await;
+        let fetched_at = manager.get_cached("TIMSTOF01").await.unwrap().fetched_at;
+
+        // Nothing listens on the discard port
+        let cloud = CloudConfig {
+            endpoint: "http://127.0.0.1:9/v1/".to_string(),
+            baseline_fetch_retries: 1,
+            baseline_fetch_retry_delay_seconds: 0,
+            ..CloudConfig::default()
+        };
+        let spool =
+            crate::spool::Spool::in_dir(&Default::default(), &dir.path().join("spool")).unwrap();
+        let uploader = Uploader::new(&cloud, spool).unwrap();
+
+        // A fresh manager starts from the disk cache
+        let manager = BaselineManager::load_from(&cache_dir).with_retry(&cloud);
+        assert!(manager
+            .refresh_from_cloud(&uploader, "TIMSTOF01", None)
+            .await
+            .is_err());
+
+        let cached = manager.get_cached("TIMSTOF01").await.unwrap();
+        assert_eq!(cached.baseline.baseline_id, "base_1");
+        assert_eq!(cached.fetched_at, fetched_at);
+        assert!(cached.stale);
+        let context = cached.context();
+        assert_eq!(context.fetched_at, Some(fetched_at));
+        assert!(context.stale);
+
+        // The flag is kept on disk
+        let reloaded = BaselineManager::load_from(&cache_dir);
+        assert!(reloaded.get_cached("TIMSTOF01").await.unwrap().stale);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_cache_is_migrated_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("baselines.json");
+        let cache_dir = dir.path().join("baselines");
+        let baseline = make_baseline(vec![make_target("t1", 10.0, 1000.0)]);
+        let old: HashMap<_, _> = [(baseline.instrument_id.clone(), baseline)].into();
+        std::fs::write(&legacy, serde_json::to_string(&old).unwrap()).unwrap();
+
+        migrate_legacy_cache(&legacy, &cache_dir);
+        assert!(!legacy.exists());
+        let manager = BaselineManager::load_from(&cache_dir);
+        let cached = manager.get_cached("TIMSTOF01").await.unwrap();
+        assert_eq!(cached.baseline.baseline_id, "base_1");
+        assert!(!cached.stale);
+
+        // A newer per-instrument file wins over a leftover old one
+        let mut newer = make_baseline(Vec::new());
+        newer.baseline_id = "base_2".to_string();
+        manager.update(newer).await;
+        std::fs::write(&legacy, serde_json::to_string(&old).unwrap()).unwrap();
+        migrate_legacy_cache(&legacy, &cache_dir);
+        let manager = BaselineManager::load_from(&cache_dir);
+        assert_eq!(
+            manager.get_active("TIMSTOF01").await.unwrap().baseline_id,
+            "base_2"
+        );
+
+        // Unreadable content is left in place
+        std::fs::write(&legacy, b"not json").unwrap();
+        migrate_legacy_cache(&legacy, &cache_dir);
+        assert!(legacy.exists());
+    }
+
     #[test]
     fn test_std_dev() {
         let values = vec![2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
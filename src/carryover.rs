@@ -0,0 +1,287 @@
+//! Carryover checks on SAMPLE runs.
+//!
+//! SAMPLE runs are normally skipped. With an instrument's
+//! `[instruments.sample_carryover_check]`, some of them (every Nth, and/or
+//! the first after a QC run) are extracted with the result limited to the QC
+//! target list. The QC targets' total peak area in the sample, as a share of
+//! their area in the instrument's last QC run, is the carryover. It is logged
+//! and audited; the sample is never spooled as QC. Counts and the last QC
+//! areas are kept in memory, so they start over when the agent restarts.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{InstrumentConfig, SampleCarryoverConfig};
+use crate::types::TargetMetrics;
+
+/// Why a SAMPLE run is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarryoverReason {
+    /// First SAMPLE run after a QC run
+    AfterQc,
+    /// The Nth SAMPLE run since the last check
+    EveryNth,
+}
+
+impl std::fmt::Display for CarryoverReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CarryoverReason::AfterQc => write!(f, "first sample after QC"),
+            CarryoverReason::EveryNth => write!(f, "every Nth sample"),
+        }
+    }
+}
+
+/// Outcome of a carryover check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarryoverReport {
+    /// QC targets looked for
+    pub targets_checked: usize,
+    /// QC targets with a detected peak in the sample
+    pub detected: Vec<String>,
+    /// QC target area in the sample as a percentage of the last QC run's;
+    /// `None` before any QC run has been seen
+    pub carryover_pct: Option<f64>,
+}
+
+impl CarryoverReport {
+    /// Whether carryover exceeds the configured limit.
+    pub fn exceeds(&self, config: &SampleCarryoverConfig) -> bool {
+        self.carryover_pct
+            .is_some_and(|pct| pct > config.max_carryover_pct)
+    }
+}
+
+impl std::fmt::Display for CarryoverReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} QC targets detected",
+            self.detected.len(),
+            self.targets_checked
+        )?;
+        match self.carryover_pct {
+            Some(pct) => write!(f, ", carryover {:.2}% of the last QC run", pct),
+            None => write!(f, ", no QC run to compare against"),
+        }
+    }
+}
+
+/// Per-instrument state behind the checks.
+#[derive(Debug, Default)]
+struct CarryoverState {
+    /// SAMPLE runs since the last count-based check
+    samples_since_check: HashMap<String, u32>,
+    /// Instruments whose latest run was QC
+    after_qc: HashSet<String>,
+    /// Detected peak area per peptide in each instrument's last QC run
+    last_qc_areas: HashMap<String, HashMap<String, f64>>,
+}
+
+/// Thread-safe carryover check scheduler.
+#[derive(Clone, Default)]
+pub struct CarryoverTracker {
+    inner: Arc<Mutex<CarryoverState>>,
+}
+
+impl CarryoverTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember an extracted QC run: the next SAMPLE run follows QC, and its
+    /// target areas are the reference for carryover.
+    pub fn record_qc(&self, instrument_id: &str, targets: &[TargetMetrics]) {
+        let mut areas: HashMap<String, f64> = HashMap::new();
+        for target in targets.iter().filter(|t| t.detected && !t.excluded) {
+            if let Some(seq) = &target.peptide_sequence {
+                *areas.entry(seq.to_uppercase()).or_default() += target.peak_area;
+            }
+        }
+
+        let mut state = self.inner.lock().unwrap();
+        state.after_qc.insert(instrument_id.to_string());
+        state.last_qc_areas.insert(instrument_id.to_string(), areas);
+    }
+
+    /// Count a SAMPLE run and say whether it gets a carryover check.
+    pub fn qualifies(
+        &self,
+        instrument_id: &str,
+        config: &SampleCarryoverConfig,
+    ) -> Option<CarryoverReason> {
+        let mut state = self.inner.lock().unwrap();
+        let after_qc = state.after_qc.remove(instrument_id);
+        let count = state
+            .samples_since_check
+            .entry(instrument_id.to_string())
+            .or_default();
+        *count += 1;
+
+        if config.every_nth > 0 && *count >= config.every_nth {
+            *count = 0;
+            Some(CarryoverReason::EveryNth)
+        } else if config.after_qc && after_qc {
+            Some(CarryoverReason::AfterQc)
+        } else {
+            None
+        }
+    }
+
+    /// Peptides to look for: the instrument's `expected_targets`, else those
+    /// detected in its last QC run. Sorted.
+    pub fn target_list(&self, instrument: &InstrumentConfig) -> Vec<String> {
+        let mut targets = if instrument.expected_targets.is_empty() {
+            self.inner
+                .lock()
+                .unwrap()
+                .last_qc_areas
+                .get(&instrument.id)
+                .map(|areas| areas.keys().cloned().collect())
+                .unwrap_or_default()
+        } else {
+            instrument.expected_targets.clone()
+        };
+        targets.sort();
+        targets
+    }
+
+    /// Measure carryover of `target_list` in a sample's targets.
+    pub fn measure(
+        &self,
+        instrument_id: &str,
+        target_list: &[String],
+        sample: &[TargetMetrics],
+    ) -> CarryoverReport {
+        let is_listed = |seq: &str| {
+            target_list
+                .iter()
+                .any(|t| t.trim().eq_ignore_ascii_case(seq))
+        };
+
+        let mut detected = Vec::new();
+        let mut sample_area = 0.0;
+        for target in sample.iter().filter(|t| t.detected) {
+            let Some(seq) = target.peptide_sequence.as_deref().filter(|s| is_listed(s)) else {
+                continue;
+            };
+            sample_area += target.peak_area;
+            if !detected
+                .iter()
+                .any(|d: &String| d.eq_ignore_ascii_case(seq))
+            {
+                detected.push(seq.to_string());
+            }
+        }
+
+        let state = self.inner.lock().unwrap();
+        let qc_area: f64 = state
+            .last_qc_areas
+            .get(instrument_id)
+            .map(|areas| {
+                areas
+                    .iter()
+                    .filter(|(seq, _)| is_listed(seq))
+                    .map(|(_, area)| area)
+                    .sum()
+            })
+            .unwrap_or_default();
+
+        CarryoverReport {
+            targets_checked: target_list.len(),
+            detected,
+            carryover_pct: (qc_area > 0.0).then(|| sample_area / qc_area * 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(seq: &str, area: f64) -> TargetMetrics {
+        TargetMetrics {
+            target_id: seq.to_string(),
+            peptide_sequence: Some(seq.to_string()),
+            precursor_mz: 500.0,
+            retention_time: 10.0,
+            rt_expected: None,
+            rt_delta: None,
+            peak_area: area,
+            peak_height: 0.0,
+            peak_width_fwhm: None,
+            peak_symmetry: None,
+            tailing_factor: None,
+            mass_error_ppm: None,
+            isotope_dot_product: None,
+            detected: area > 0.0,
+            excluded: false,
+        }
+    }
+
+    fn config(every_nth: u32, after_qc: bool) -> SampleCarryoverConfig {
+        SampleCarryoverConfig {
+            every_nth,
+            after_qc,
+            max_carryover_pct: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_which_samples_qualify() {
+        let tracker = CarryoverTracker::new();
+        let every_third = config(3, false);
+        let checks: Vec<_> = (0..6)
+            .map(|_| tracker.qualifies("EXPLORIS01", &every_third))
+            .collect();
+        assert_eq!(
+            checks,
+            [
+                None,
+                None,
+                Some(CarryoverReason::EveryNth),
+                None,
+                None,
+                Some(CarryoverReason::EveryNth)
+            ]
+        );
+
+        // Only the sample straight after a QC run
+        let after_qc = config(0, true);
+        assert_eq!(tracker.qualifies("EXPLORIS01", &after_qc), None);
+        tracker.record_qc("EXPLORIS01", &[target("PEPTIDEA", 1000.0)]);
+        assert_eq!(
+            tracker.qualifies("EXPLORIS01", &after_qc),
+            Some(CarryoverReason::AfterQc)
+        );
+        assert_eq!(tracker.qualifies("EXPLORIS01", &after_qc), None);
+        assert_eq!(tracker.qualifies("TIMSTOF01", &after_qc), None);
+    }
+
+    #[test]
+    fn test_carryover_relative_to_last_qc() {
+        let tracker = CarryoverTracker::new();
+        let targets = vec!["PEPTIDEA".to_string(), "PEPTIDEB".to_string()];
+        let sample = [
+            target("PEPTIDEA", 15.0),
+            target("PEPTIDEB", 0.0),
+            target("OTHER", 5000.0),
+        ];
+
+        let report = tracker.measure("EXPLORIS01", &targets, &sample);
+        assert_eq!(report.detected, ["PEPTIDEA"]);
+        assert_eq!(report.carryover_pct, None);
+
+        tracker.record_qc(
+            "EXPLORIS01",
+            &[target("PEPTIDEA", 1000.0), target("peptideb", 500.0)],
+        );
+        let report = tracker.measure("EXPLORIS01", &targets, &sample);
+        assert_eq!(report.carryover_pct, Some(1.0));
+        assert!(!report.exceeds(&config(0, true)));
+        assert_eq!(
+            report.to_string(),
+            "1/2 QC targets detected, carryover 1.00% of the last QC run"
+        );
+    }
+}
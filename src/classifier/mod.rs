@@ -1,17 +1,22 @@
 //! Run classification based on filename and metadata.
 //!
 //! Classifies MS runs into control types (SSC0, QC_A, QC_B, SAMPLE, BLANK)
-//! based on filename tokens and well positions.
+//! based on filename tokens and well positions, or from the autosampler
+//! worklist when one is configured.
+
+mod worklist;
 
 use regex::Regex;
+use std::collections::HashMap;
 use std::path::Path;
 use tracing::{debug, trace};
 
-use crate::config::InstrumentConfig;
+use crate::config::{FilenameFields, InstrumentConfig};
 use crate::error::ClassificationError;
 use crate::types::{
     ClassificationConfidence, ClassificationSource, ControlType, RunClassification, WellPosition,
 };
+use worklist::WorklistRow;
 
 /// Classifier for MS runs.
 pub struct Classifier {
@@ -21,6 +26,10 @@ pub struct Classifier {
     qcb_pattern: Regex,
     blank_pattern: Regex,
     well_pattern: Regex,
+    injection_pattern: Regex,
+    /// Site-specific control names, longest first
+    aliases: Vec<(Regex, ControlType)>,
+    alias_map: HashMap<String, ControlType>,
 }
 
 impl Classifier {
@@ -42,9 +51,35 @@ impl Classifier {
             // Well pattern: letter A-H followed by 1-12, with delimiters
             well_pattern: Regex::new(r"(?i)(?:^|[_\-\s.])([A-H])(1[0-2]|[1-9])(?:$|[_\-\s.])")
                 .unwrap(),
+            // Injection index: inj3, injection_3, rep2, replicate-2, #4
+            injection_pattern: Regex::new(
+                r"(?i)(?:(?:^|[_\-\s.])(?:inj|injection|rep|replicate)[_-]?|#)(\d{1,4})(?:$|[_\-\s.])",
+            )
+            .unwrap(),
+            aliases: Vec::new(),
+            alias_map: HashMap::new(),
         }
     }
 
+    /// Also recognize site-specific control names (e.g. "iRT" for QC_A).
+    ///
+    /// Aliases match whole tokens with the same delimiters as the built-in
+    /// patterns, and are tried after them.
+    pub fn with_aliases(mut self, aliases: &HashMap<String, ControlType>) -> Self {
+        let mut names: Vec<&String> = aliases.keys().collect();
+        names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+        self.aliases = names
+            .into_iter()
+            .map(|name| {
+                let pattern = format!(r"(?i)(?:^|[_\-\s.])({})(?:$|[_\-\s.])", regex::escape(name));
+                (Regex::new(&pattern).unwrap(), aliases[name])
+            })
+            .collect();
+        self.alias_map = aliases.clone();
+        self
+    }
+
     /// Classify a run based on its file path and instrument config.
     pub fn classify(
         &self,
@@ -58,6 +93,26 @@ impl Classifier {
 
         trace!(filename = %filename, "Classifying run");
 
+        // The worklist is authoritative for files it lists
+        if let Some(pattern) = &instrument.worklist_pattern {
+            let watch_path = Path::new(&instrument.watch_path);
+            if let Some(row) = worklist::find_row(watch_path, pattern, filename) {
+                return Ok(self.classify_worklist_row(filename, row, instrument));
+            }
+            debug!(filename = %filename, "Not in worklist, classifying from filename");
+        }
+
+        // A configured field layout beats scanning the whole name
+        if let Some(fields) = &instrument.filename_fields {
+            if let Some(run) = self.classify_positional(filename, fields, instrument) {
+                return Ok(run);
+            }
+            debug!(
+                filename = %filename,
+                "Filename doesn't fit the configured field layout, scanning whole name"
+            );
+        }
+
         // Extract control type using regex (preserves QC_A, QC_B, etc.)
         let (control_type, ct_source) = self.extract_control_type(filename);
 
@@ -67,6 +122,8 @@ impl Classifier {
         // Extract plate ID
         let plate_id = self.extract_plate_id(filename);
 
+        let injection_index = self.extract_injection_index(filename);
+
         // Determine confidence based on how we found the control type
         let confidence = match (&control_type, &well_position, &ct_source) {
             (ct, Some(_), ClassificationSource::Filename) if ct.is_qc() => {
@@ -88,9 +145,12 @@ impl Classifier {
             well = ?well_position,
             confidence = ?confidence,
             source = ?ct_source,
+            injection = ?injection_index,
             "Classification result"
         );
 
+        let matched_pattern = self.matched_pattern(filename, control_type, ct_source);
+
         Ok(RunClassification {
             control_type,
             well_position,
@@ -98,26 +158,160 @@ impl Classifier {
             plate_id,
             confidence,
             source: ct_source,
+            matched_pattern,
+            injection_index,
+            reinjection_of: None,
         })
     }
 
-    /// Extract control type from filename using regex patterns.
-    fn extract_control_type(&self, filename: &str) -> (ControlType, ClassificationSource) {
-        // Check patterns in priority order
-        if self.ssc0_pattern.is_match(filename) {
-            return (ControlType::Ssc0, ClassificationSource::Filename);
-        }
+    /// Classify from a worklist row. Control-type tokens are looked for in
+    /// the sample type column first, then the sample name.
+    fn classify_worklist_row(
+        &self,
+        filename: &str,
+        row: WorklistRow,
+        instrument: &InstrumentConfig,
+    ) -> RunClassification {
+        let (control_type, matched_pattern) = [row.sample_type.as_ref(), row.sample_name.as_ref()]
+            .into_iter()
+            .flatten()
+            .find_map(|text| Some((self.control_type_token(text)?, Some(text.clone()))))
+            .unwrap_or((ControlType::Sample, row.sample_type.clone()));
+
+        let well_position = row.position.as_deref().and_then(|position| {
+            WellPosition::from_str(position).or_else(|| self.extract_well_position(position))
+        });
+
+        let injection_index = row
+            .sample_name
+            .as_deref()
+            .and_then(|name| self.extract_injection_index(name))
+            .or_else(|| self.extract_injection_index(filename));
 
-        if self.qca_pattern.is_match(filename) {
-            return (ControlType::QcA, ClassificationSource::Filename);
+        debug!(
+            filename = %filename,
+            control_type = %control_type,
+            well = ?well_position,
+            "Classified from worklist"
+        );
+
+        RunClassification {
+            control_type,
+            well_position,
+            instrument_id: instrument.id.clone(),
+            plate_id: row.plate,
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Metadata,
+            matched_pattern,
+            injection_index,
+            reinjection_of: None,
         }
+    }
 
-        if self.qcb_pattern.is_match(filename) {
-            return (ControlType::QcB, ClassificationSource::Filename);
+    /// Classify from fields at configured positions.
+    ///
+    /// Returns `None` if the name doesn't fit the layout: too few fields, or
+    /// a configured field that doesn't parse as what it should hold.
+    fn classify_positional(
+        &self,
+        filename: &str,
+        fields: &FilenameFields,
+        instrument: &InstrumentConfig,
+    ) -> Option<RunClassification> {
+        let stem = Path::new(filename)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(filename);
+        let tokens = split_fields(stem, &fields.delimiter);
+        let token = |index: usize| tokens.get(index).map(String::as_str);
+
+        if let Some(index) = fields.date {
+            parse_field_date(token(index)?)?;
         }
 
-        if self.blank_pattern.is_match(filename) {
-            return (ControlType::Blank, ClassificationSource::Filename);
+        let well_position = match fields.well {
+            Some(index) => Some(WellPosition::from_str(token(index)?)?),
+            None => None,
+        };
+
+        let (control_type, source, matched_pattern) = match fields.control {
+            Some(index) => {
+                let text = token(index)?;
+                let control_type = ControlType::from_token_with_aliases(text, &self.alias_map)?;
+                (
+                    control_type,
+                    ClassificationSource::Filename,
+                    text.to_string(),
+                )
+            }
+            None => {
+                let well = well_position.as_ref()?;
+                let control_type = self.infer_control_type_from_well(well);
+                (
+                    control_type,
+                    ClassificationSource::Position,
+                    well.to_string(),
+                )
+            }
+        };
+
+        let plate_id = match fields.plate {
+            Some(index) => Some(token(index)?.to_string()),
+            None => None,
+        };
+
+        debug!(
+            filename = %filename,
+            control_type = %control_type,
+            well = ?well_position,
+            "Classified from filename fields"
+        );
+
+        Some(RunClassification {
+            control_type,
+            well_position,
+            instrument_id: instrument.id.clone(),
+            plate_id,
+            confidence: ClassificationConfidence::High,
+            source,
+            matched_pattern: Some(matched_pattern),
+            injection_index: self.extract_injection_index(filename),
+            reinjection_of: None,
+        })
+    }
+
+    /// Filename text that produced the control type, for the audit trail.
+    fn matched_pattern(
+        &self,
+        filename: &str,
+        control_type: ControlType,
+        source: ClassificationSource,
+    ) -> Option<String> {
+        let pattern = match (source, control_type) {
+            (ClassificationSource::Filename, ControlType::Ssc0) => Some(&self.ssc0_pattern),
+            (ClassificationSource::Filename, ControlType::QcA) => Some(&self.qca_pattern),
+            (ClassificationSource::Filename, ControlType::QcB) => Some(&self.qcb_pattern),
+            (ClassificationSource::Filename, ControlType::Blank) => Some(&self.blank_pattern),
+            (ClassificationSource::Position, _) => Some(&self.well_pattern),
+            _ => None,
+        };
+        let aliases = self
+            .aliases
+            .iter()
+            .filter(|(_, ct)| source == ClassificationSource::Filename && *ct == control_type)
+            .map(|(pattern, _)| pattern);
+
+        pattern
+            .into_iter()
+            .chain(aliases)
+            .find_map(|pattern| pattern.find(filename))
+            .map(|m| m.as_str().trim_matches(|c| "_-. ".contains(c)).to_string())
+    }
+
+    /// Extract control type from filename using regex patterns.
+    fn extract_control_type(&self, filename: &str) -> (ControlType, ClassificationSource) {
+        if let Some(control_type) = self.control_type_token(filename) {
+            return (control_type, ClassificationSource::Filename);
         }
 
         // Try to infer from well position
@@ -132,6 +326,26 @@ impl Classifier {
         (ControlType::Sample, ClassificationSource::Default)
     }
 
+    /// Match a control-type token (SSC0, QC_A, QC_B, BLANK, or a configured
+    /// alias) in `text`.
+    fn control_type_token(&self, text: &str) -> Option<ControlType> {
+        // Check patterns in priority order
+        if self.ssc0_pattern.is_match(text) {
+            Some(ControlType::Ssc0)
+        } else if self.qca_pattern.is_match(text) {
+            Some(ControlType::QcA)
+        } else if self.qcb_pattern.is_match(text) {
+            Some(ControlType::QcB)
+        } else if self.blank_pattern.is_match(text) {
+            Some(ControlType::Blank)
+        } else {
+            self.aliases
+                .iter()
+                .find(|(pattern, _)| pattern.is_match(text))
+                .map(|(_, control_type)| *control_type)
+        }
+    }
+
     /// Extract well position from filename.
     fn extract_well_position(&self, filename: &str) -> Option<WellPosition> {
         if let Some(caps) = self.well_pattern.captures(filename) {
@@ -143,6 +357,14 @@ impl Classifier {
         }
     }
 
+    /// Extract the injection/replicate index from a token like `inj3`,
+    /// `rep2` or `#4`. The first such token wins.
+    fn extract_injection_index(&self, filename: &str) -> Option<u32> {
+        self.injection_pattern
+            .captures(filename)
+            .and_then(|caps| caps.get(1)?.as_str().parse().ok())
+    }
+
     /// Extract plate ID from filename (if present).
     fn extract_plate_id(&self, filename: &str) -> Option<String> {
         // Look for patterns like "plate1", "P001", "PLATE_A"
@@ -166,6 +388,36 @@ impl Classifier {
     }
 }
 
+/// Split a file stem into fields, keeping control tokens written with the
+/// delimiter (e.g. `QC_A`, `SSC_0`) as one field.
+fn split_fields(stem: &str, delimiter: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+
+    for part in stem.split(delimiter) {
+        if let Some(last) = fields.last_mut() {
+            let joined = matches!(
+                (last.to_uppercase().as_str(), part.to_uppercase().as_str()),
+                ("QC", "A" | "B") | ("SSC", "0")
+            );
+            if joined {
+                last.push_str(delimiter);
+                last.push_str(part);
+                continue;
+            }
+        }
+        fields.push(part.to_string());
+    }
+
+    fields
+}
+
+/// Parse a date field such as `20260127` or `2026-01-27`.
+fn parse_field_date(field: &str) -> Option<chrono::NaiveDate> {
+    ["%Y%m%d", "%Y-%m-%d", "%Y.%m.%d", "%y%m%d"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(field, format).ok())
+}
+
 impl Default for Classifier {
     fn default() -> Self {
         Self::new()
@@ -253,6 +505,59 @@ mod tests {
         assert_eq!(c.extract_well_position("no_well_here.raw"), None);
     }
 
+    #[test]
+    fn test_injection_index() {
+        let c = make_classifier();
+
+        for (filename, expected) in [
+            ("QCA_A1_inj3.raw", 3),
+            ("QCA_A1_INJ_12.raw", 12),
+            ("EXPLORIS01_QC_B_injection-2_2026-01-27.raw", 2),
+            ("QCB_rep2.d", 2),
+            ("QC_A.Rep-07.raw", 7),
+            ("HeLa_replicate_3_A5.raw", 3),
+            ("QCA_A1_#4.raw", 4),
+            ("QCA#4.raw", 4),
+        ] {
+            assert_eq!(
+                c.extract_injection_index(filename),
+                Some(expected),
+                "Failed for: {}",
+                filename
+            );
+        }
+
+        // No index, or index-like text inside another token
+        for filename in [
+            "TIMSTOF01_QCA_A1_2026-01-27.d",
+            "QCA_report2.raw",
+            "QCA_inject.raw",
+            "QCA_rep.raw",
+            "QCA_minj3.raw",
+            "QCA_inj3b.raw",
+        ] {
+            assert_eq!(
+                c.extract_injection_index(filename),
+                None,
+                "False match for: {}",
+                filename
+            );
+        }
+
+        let instrument: InstrumentConfig = toml::from_str(
+            "id = \"EXPLORIS01\"\n\
+             vendor = \"thermo\"\n\
+             watch_path = \"/data/EXPLORIS01\"\n\
+             template = \"qc.sky\"\n",
+        )
+        .unwrap();
+        let run = c
+            .classify(Path::new("QCA_A1_inj3.raw"), &instrument)
+            .unwrap();
+        assert_eq!(run.control_type, ControlType::QcA);
+        assert_eq!(run.injection_index, Some(3));
+    }
+
     #[test]
     fn test_inference_from_well() {
         let c = make_classifier();
@@ -281,4 +586,149 @@ mod tests {
         assert_eq!(ct, ControlType::Sample);
         assert_eq!(source, ClassificationSource::Default);
     }
+
+    #[test]
+    fn test_worklist_row_overrides_filename() {
+        let c = make_classifier();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("20260127_sequence.csv"),
+            "Sample Type,File Name,Sample ID,Position,Plate\n\
+             QC,20260127_run_042,QC_B,S1-A3,PLATE7\n\
+             Unknown,20260127_run_043,patient_17,S1-C4,PLATE7\n",
+        )
+        .unwrap();
+        let instrument: InstrumentConfig = toml::from_str(&format!(
+            "id = \"EXPLORIS01\"\n\
+             vendor = \"thermo\"\n\
+             watch_path = '{}'\n\
+             template = \"qc.sky\"\n\
+             worklist_pattern = \"*_sequence.csv\"\n",
+            dir.path().display()
+        ))
+        .unwrap();
+
+        // Listed: the row decides, even though the filename says nothing
+        let run = c
+            .classify(&dir.path().join("20260127_run_042.raw"), &instrument)
+            .unwrap();
+        assert_eq!(run.control_type, ControlType::QcB);
+        assert_eq!(run.well_position, WellPosition::new('A', 3));
+        assert_eq!(run.plate_id.as_deref(), Some("PLATE7"));
+        assert_eq!(run.source, ClassificationSource::Metadata);
+        assert_eq!(run.confidence, ClassificationConfidence::High);
+        assert_eq!(run.matched_pattern.as_deref(), Some("QC_B"));
+
+        let run = c
+            .classify(&dir.path().join("20260127_run_043.raw"), &instrument)
+            .unwrap();
+        assert_eq!(run.control_type, ControlType::Sample);
+        assert_eq!(run.source, ClassificationSource::Metadata);
+
+        // Not listed: falls back to the filename
+        let run = c
+            .classify(&dir.path().join("20260127_QC_A_A1.raw"), &instrument)
+            .unwrap();
+        assert_eq!(run.control_type, ControlType::QcA);
+        assert_eq!(run.source, ClassificationSource::Filename);
+    }
+
+    #[test]
+    fn test_positional_filename_fields() {
+        let c = make_classifier();
+        let instrument: InstrumentConfig = toml::from_str(
+            "id = \"EXPLORIS01\"\n\
+             vendor = \"thermo\"\n\
+             watch_path = \"/data/EXPLORIS01\"\n\
+             template = \"qc.sky\"\n\
+             [filename_fields]\n\
+             date = 0\n\
+             well = 1\n\
+             control = 2\n",
+        )
+        .unwrap();
+        let classify = |name: &str| c.classify(Path::new(name), &instrument).unwrap();
+
+        // {date}_{well}_{control}_{instrument}, with the control split by the delimiter
+        let run = classify("20260127_A3_QC_B_EXPLORIS01.raw");
+        assert_eq!(run.control_type, ControlType::QcB);
+        assert_eq!(run.well_position, WellPosition::new('A', 3));
+        assert_eq!(run.source, ClassificationSource::Filename);
+        assert_eq!(run.confidence, ClassificationConfidence::High);
+        assert_eq!(run.matched_pattern.as_deref(), Some("QC_B"));
+
+        // A QC-like instrument ID no longer misfires
+        let run = classify("20260127_C5_SAMPLE_QC-A-LAB.raw");
+        assert_eq!(run.control_type, ControlType::Sample);
+        assert_eq!(run.confidence, ClassificationConfidence::High);
+
+        // Names that don't fit the layout get the whole-name scan, as if no
+        // layout were configured
+        let mut plain = instrument.clone();
+        plain.filename_fields = None;
+        for name in [
+            "QC_A_A1_rerun.raw",
+            "20260127_A1.raw",
+            "20260127_Z9_QCA_X.raw",
+        ] {
+            let run = classify(name);
+            let scanned = c.classify(Path::new(name), &plain).unwrap();
+            assert_eq!(run.control_type, ControlType::QcA, "{}", name);
+            assert_eq!(run.control_type, scanned.control_type, "{}", name);
+            assert_eq!(run.source, scanned.source, "{}", name);
+            assert_eq!(run.confidence, scanned.confidence, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_control_aliases() {
+        let aliases = HashMap::from([
+            ("iRT".to_string(), ControlType::QcA),
+            ("K562".to_string(), ControlType::QcA),
+            ("HeLa-digest".to_string(), ControlType::QcB),
+        ]);
+        let c = Classifier::new().with_aliases(&aliases);
+
+        for (filename, expected) in [
+            ("EXPLORIS01_iRT_2026-01-27.raw", ControlType::QcA),
+            ("EXPLORIS01_irt_2026-01-27.raw", ControlType::QcA),
+            ("K562.EXPLORIS01.raw", ControlType::QcA),
+            ("EXPLORIS01_HeLa-digest_C7.raw", ControlType::QcB),
+        ] {
+            let (ct, source) = c.extract_control_type(filename);
+            assert_eq!(ct, expected, "Failed for: {}", filename);
+            assert_eq!(source, ClassificationSource::Filename);
+        }
+
+        // Whole tokens only: "iRT" inside another token is not a match
+        for filename in [
+            "EXPLORIS01_shirt_2026-01-27.raw",
+            "EXPLORIS01_iRTkit_C7.raw",
+            "EXPLORIS01_K5620_C7.raw",
+        ] {
+            let (ct, _) = c.extract_control_type(filename);
+            assert_eq!(ct, ControlType::Sample, "False match for: {}", filename);
+        }
+
+        // The audit trail records which alias matched
+        assert_eq!(
+            c.matched_pattern(
+                "EXPLORIS01_iRT_C7.raw",
+                ControlType::QcA,
+                ClassificationSource::Filename
+            )
+            .as_deref(),
+            Some("iRT")
+        );
+
+        // Token lookup used by positional parsing and `mdqc classify`
+        assert_eq!(
+            ControlType::from_token_with_aliases("IRT", &aliases),
+            Some(ControlType::QcA)
+        );
+        assert_eq!(
+            ControlType::from_token_with_aliases("iRTkit", &aliases),
+            None
+        );
+    }
 }
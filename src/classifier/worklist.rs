@@ -0,0 +1,184 @@
+//! Worklist (sequence) lookup.
+//!
+//! Autosampler software exports a sequence CSV listing each raw file with its
+//! sample type, position and plate. When an instrument sets
+//! `worklist_pattern`, rows from that file take precedence over filename
+//! parsing.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// A worklist row for one raw file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorklistRow {
+    pub sample_type: Option<String>,
+    pub sample_name: Option<String>,
+    pub position: Option<String>,
+    pub plate: Option<String>,
+}
+
+/// Column indices for the fields we read.
+#[derive(Default)]
+struct Columns {
+    file_name: usize,
+    sample_type: Option<usize>,
+    sample_name: Option<usize>,
+    position: Option<usize>,
+    plate: Option<usize>,
+}
+
+impl Columns {
+    /// Recognize a header row; `None` if it has no file-name column.
+    fn from_header(record: &csv::StringRecord) -> Option<Self> {
+        let mut file_name = None;
+        let mut columns = Self::default();
+
+        for (idx, header) in record.iter().enumerate() {
+            let normalized = header.to_lowercase().replace([' ', '_'], "");
+            match normalized.as_str() {
+                "filename" | "file" | "rawfile" | "rawfilename" | "datafile" => {
+                    file_name.get_or_insert(idx);
+                }
+                "sampletype" | "type" | "controltype" => {
+                    columns.sample_type.get_or_insert(idx);
+                }
+                "samplename" | "sampleid" => {
+                    columns.sample_name.get_or_insert(idx);
+                }
+                "position" | "well" | "wellposition" | "vial" | "sampleposition" => {
+                    columns.position.get_or_insert(idx);
+                }
+                "plate" | "plateid" | "platename" | "tray" => {
+                    columns.plate.get_or_insert(idx);
+                }
+                _ => {}
+            }
+        }
+
+        columns.file_name = file_name?;
+        Some(columns)
+    }
+}
+
+/// Find the row for `filename` in the worklists matching `pattern` under
+/// `watch_path`. The most recently modified worklist is searched first.
+pub fn find_row(watch_path: &Path, pattern: &str, filename: &str) -> Option<WorklistRow> {
+    let pattern_str = watch_path.join(pattern).to_string_lossy().to_string();
+    let entries = match glob::glob(&pattern_str) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(pattern = %pattern_str, error = %e, "Invalid worklist pattern");
+            return None;
+        }
+    };
+
+    let mut worklists: Vec<(PathBuf, std::time::SystemTime)> = entries
+        .flatten()
+        .filter_map(|path| {
+            let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+            Some((path, modified))
+        })
+        .collect();
+    worklists.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+    for (path, _) in worklists {
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Could not open worklist");
+                continue;
+            }
+        };
+        match parse_worklist(file, filename) {
+            Ok(Some(row)) => {
+                debug!(worklist = %path.display(), filename = %filename, "Found worklist row");
+                return Some(row);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(path = %path.display(), error = %e, "Could not parse worklist"),
+        }
+    }
+
+    None
+}
+
+/// Read a worklist CSV and return the row naming `filename`.
+///
+/// Lines before the header (e.g. Thermo's "Bracket Type=4") are skipped. The
+/// file-name cell may carry an extension or a directory; it matches the raw
+/// file's name or stem, case-insensitively.
+pub fn parse_worklist<R: Read>(reader: R, filename: &str) -> csv::Result<Option<WorklistRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+
+    let filename = filename.to_lowercase();
+    let stem = Path::new(&filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(&filename)
+        .to_string();
+
+    let mut columns: Option<Columns> = None;
+    for record in reader.records() {
+        let record = record?;
+        let Some(cols) = &columns else {
+            columns = Columns::from_header(&record);
+            continue;
+        };
+
+        let Some(cell) = get(&record, Some(cols.file_name)) else {
+            continue;
+        };
+        let cell = cell.replace('\\', "/").to_lowercase();
+        let cell_name = cell.rsplit('/').next().unwrap_or(&cell);
+        if cell_name != filename && cell_name != stem {
+            continue;
+        }
+
+        return Ok(Some(WorklistRow {
+            sample_type: get(&record, cols.sample_type),
+            sample_name: get(&record, cols.sample_name),
+            position: get(&record, cols.position),
+            plate: get(&record, cols.plate),
+        }));
+    }
+
+    Ok(None)
+}
+
+fn get(record: &csv::StringRecord, col: Option<usize>) -> Option<String> {
+    record
+        .get(col?)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thermo_sequence() {
+        let csv = "Bracket Type=4,,,,\n\
+                   Sample Type,File Name,Sample ID,Path,Position\n\
+                   Blank,20260127_blank_01,blank,D:\\Data,S1-B1\n\
+                   QC,20260127_QC_A_01,QC_A,D:\\Data,S1-A1\n\
+                   Unknown,20260127_patient_17,P17,D:\\Data,S1-C4\n";
+
+        let row = parse_worklist(csv.as_bytes(), "20260127_QC_A_01.raw")
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.sample_type.as_deref(), Some("QC"));
+        assert_eq!(row.sample_name.as_deref(), Some("QC_A"));
+        assert_eq!(row.position.as_deref(), Some("S1-A1"));
+        assert_eq!(row.plate, None);
+
+        assert!(parse_worklist(csv.as_bytes(), "not_listed.raw")
+            .unwrap()
+            .is_none());
+    }
+}
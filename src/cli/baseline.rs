@@ -3,14 +3,18 @@
 use anyhow::Result;
 use std::io::{self, Write};
 
+use crate::baseline::BaselineManager;
 use crate::cli::BaselineAction;
 use crate::config::Config;
+use crate::spool::Spool;
+use crate::uploader::Uploader;
 
 /// Run the baseline command.
 pub async fn run(action: BaselineAction) -> Result<()> {
     match action {
         BaselineAction::List { instrument } => list_baselines(instrument).await,
         BaselineAction::Show { baseline_id } => show_baseline(&baseline_id).await,
+        BaselineAction::Refresh { instrument } => refresh_baselines(instrument).await,
         BaselineAction::Reset {
             instrument,
             confirm,
@@ -86,6 +90,79 @@ async fn show_baseline(baseline_id: &str) -> Result<()> {
     Ok(())
 }
 
+async fn refresh_baselines(instrument_filter: Option<String>) -> Result<()> {
+    let config = Config::load()?;
+
+    let instruments: Vec<_> = config
+        .instruments
+        .iter()
+        .filter(|i| instrument_filter.as_ref().is_none_or(|id| i.id == *id))
+        .collect();
+    if instruments.is_empty() {
+        anyhow::bail!(
+            "No instrument found matching {}",
+            instrument_filter.as_deref().unwrap_or("(none configured)")
+        );
+    }
+
+    let spool = Spool::new(&config.spool)?;
+    let uploader = Uploader::new(&config.cloud, spool)?;
+    let manager = BaselineManager::load().with_retry(&config.cloud);
+
+    println!();
+    println!("Endpoint: {}baselines", config.cloud.endpoint);
+
+    let mut failures = 0;
+    for instrument in instruments {
+        println!();
+        println!("Baselines for {}", instrument.id);
+        println!("{}", "=".repeat(14 + instrument.id.len()));
+
+        let fetched = manager
+            .refresh_from_cloud(&uploader, &instrument.id, instrument.project_id.as_deref())
+            .await;
+        let baselines = match fetched {
+            Ok(baselines) => baselines,
+            Err(e) => {
+                failures += 1;
+                println!("Refresh failed: {}", e);
+                if let Some(cached) = manager.get_cached(&instrument.id).await {
+                    println!(
+                        "Using cached baseline {} fetched {} (stale)",
+                        cached.baseline.baseline_id,
+                        cached.fetched_at.format("%Y-%m-%d %H:%M UTC")
+                    );
+                }
+                continue;
+            }
+        };
+
+        if baselines.is_empty() {
+            println!("No baselines on the cloud (no SSC0 run registered yet)");
+        }
+        for baseline in &baselines {
+            println!(
+                "[{:<10}] {}  established {}  template {}  {} targets",
+                baseline.state.to_string(),
+                baseline.baseline_id,
+                baseline.established.format("%Y-%m-%d %H:%M UTC"),
+                baseline.template_hash,
+                baseline.target_metrics.len()
+            );
+        }
+        match manager.get_active(&instrument.id).await {
+            Some(active) => println!("Cached active baseline: {}", active.baseline_id),
+            None => println!("No active baseline cached"),
+        }
+    }
+    println!();
+
+    if failures > 0 {
+        anyhow::bail!("Baseline refresh failed for {} instrument(s)", failures);
+    }
+    Ok(())
+}
+
 async fn reset_baseline(instrument: &str, confirm: bool) -> Result<()> {
     let config = Config::load()?;
 
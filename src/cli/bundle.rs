@@ -0,0 +1,202 @@
+//! Bundle command - collect diagnostics into a zip for support tickets.
+//!
+//! Secrets are redacted from the bundled config with the same list as
+//! `mdqc config show` (see `config::redact_secrets`).
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::config::{paths, redact_secrets};
+
+/// Number of most recent log files to include.
+const MAX_LOG_FILES: usize = 5;
+
+/// Inputs gathered into a bundle.
+struct BundleInputs {
+    config_file: PathBuf,
+    log_dir: PathBuf,
+    failed_files: PathBuf,
+    spool_dir: PathBuf,
+    doctor_json: String,
+}
+
+/// Run the bundle command.
+pub async fn run(out: &str) -> Result<()> {
+    let inputs = BundleInputs {
+        config_file: paths::config_file(),
+        log_dir: paths::data_dir().join("logs"),
+        failed_files: paths::data_dir().join("failed_files.json"),
+        spool_dir: paths::spool_dir(),
+        doctor_json: super::doctor::report_json().await?,
+    };
+
+    let entries = write_bundle(Path::new(out), &inputs)?;
+
+    println!();
+    println!("Diagnostic bundle written to {}", out);
+    for entry in &entries {
+        println!("  {}", entry);
+    }
+    println!();
+    println!("Secrets in the config have been redacted. Attach this file to your support request.");
+    println!();
+
+    Ok(())
+}
+
+/// Write the bundle zip, returning the entry names.
+fn write_bundle(out: &Path, inputs: &BundleInputs) -> Result<Vec<String>> {
+    let file = std::fs::File::create(out)
+        .with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut entries = Vec::new();
+
+    let mut add = |name: String, content: &[u8]| -> Result<()> {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(content)?;
+        entries.push(name);
+        Ok(())
+    };
+
+    match std::fs::read_to_string(&inputs.config_file) {
+        Ok(content) => add(
+            "config.toml".to_string(),
+            redact_config(&content).as_bytes(),
+        )?,
+        Err(e) => add(
+            "config.toml.missing".to_string(),
+            format!("{}: {}", inputs.config_file.display(), e).as_bytes(),
+        )?,
+    }
+
+    if let Ok(content) = std::fs::read(&inputs.failed_files) {
+        add("failed_files.json".to_string(), &content)?;
+    }
+
+    add("doctor.json".to_string(), inputs.doctor_json.as_bytes())?;
+
+    let spool_counts = serde_json::json!({
+        "pending": count_files(&inputs.spool_dir.join("pending")),
+        "uploading": count_files(&inputs.spool_dir.join("uploading")),
+        "failed": count_files(&inputs.spool_dir.join("failed")),
+        "completed": count_files(&inputs.spool_dir.join("completed")),
+    });
+    add(
+        "spool_counts.json".to_string(),
+        serde_json::to_string_pretty(&spool_counts)?.as_bytes(),
+    )?;
+
+    for log in recent_logs(&inputs.log_dir) {
+        if let (Some(name), Ok(content)) = (log.file_name(), std::fs::read(&log)) {
+            add(format!("logs/{}", name.to_string_lossy()), &content)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(entries)
+}
+
+/// Redact secrets from the config; falls back to a note if it can't be parsed.
+fn redact_config(content: &str) -> String {
+    let Ok(mut value) = content.parse::<toml::Value>() else {
+        return "# config.toml could not be parsed; omitted to avoid leaking secrets\n".to_string();
+    };
+    redact_secrets(&mut value);
+    toml::to_string_pretty(&value).unwrap_or_default()
+}
+
+/// Most recently modified log files, newest first.
+fn recent_logs(log_dir: &Path) -> Vec<PathBuf> {
+    let mut logs: Vec<_> = std::fs::read_dir(log_dir)
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    logs.retain(|p| p.is_file());
+
+    logs.sort_by_key(|p| std::cmp::Reverse(std::fs::metadata(p).and_then(|m| m.modified()).ok()));
+    logs.truncate(MAX_LOG_FILES);
+    logs
+}
+
+fn count_files(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.count())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::REDACTED;
+    use std::io::Read;
+
+    #[test]
+    fn test_bundle_contents_and_redaction() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config_file = dir.path().join("config.toml");
+        std::fs::write(
+            &config_file,
+            "[cloud]\napi_token = \"super-secret-token\"\nendpoint = \"https://example.com/\"\n\
+             [influx]\nurl = \"http://influx:8086\"\ntoken = \"influx-secret\"\nbucket = \"qc\"\norg = \"lab\"\n",
+        )
+        .unwrap();
+
+        let log_dir = dir.path().join("logs");
+        std::fs::create_dir(&log_dir).unwrap();
+        std::fs::write(log_dir.join("mdqc.log.2024-01-01"), "log line").unwrap();
+
+        let failed_files = dir.path().join("failed_files.json");
+        std::fs::write(&failed_files, "{\"files\":{}}").unwrap();
+
+        let spool_dir = dir.path().join("spool");
+        std::fs::create_dir_all(spool_dir.join("pending")).unwrap();
+        std::fs::write(spool_dir.join("pending").join("a.json"), "{}").unwrap();
+
+        let inputs = BundleInputs {
+            config_file,
+            log_dir,
+            failed_files,
+            spool_dir,
+            doctor_json: "{\"healthy\":true}".to_string(),
+        };
+
+        let out = dir.path().join("bundle.zip");
+        write_bundle(&out, &inputs).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&out).unwrap()).unwrap();
+        let names: Vec<_> = archive.file_names().map(String::from).collect();
+        for expected in [
+            "config.toml",
+            "failed_files.json",
+            "doctor.json",
+            "spool_counts.json",
+            "logs/mdqc.log.2024-01-01",
+        ] {
+            assert!(names.iter().any(|n| n == expected), "missing {}", expected);
+        }
+
+        let mut config = String::new();
+        archive
+            .by_name("config.toml")
+            .unwrap()
+            .read_to_string(&mut config)
+            .unwrap();
+        assert!(!config.contains("super-secret-token"));
+        assert!(!config.contains("influx-secret"));
+        assert!(config.contains(REDACTED));
+        assert!(config.contains("https://example.com/"));
+
+        let mut counts = String::new();
+        archive
+            .by_name("spool_counts.json")
+            .unwrap()
+            .read_to_string(&mut counts)
+            .unwrap();
+        let counts: serde_json::Value = serde_json::from_str(&counts).unwrap();
+        assert_eq!(counts["pending"], 1);
+    }
+}
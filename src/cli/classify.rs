@@ -1,12 +1,23 @@
-//! Classify command - preview run classification.
+//! Classify command - preview run classification, or score the classifier
+//! against a labeled ground-truth CSV.
 
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::path::Path;
 
 use crate::classifier::Classifier;
-use crate::config::Config;
+use crate::config::{Config, InstrumentConfig};
 use crate::types::{ClassificationConfidence, ClassificationSource, ControlType};
 
+/// Control types in report order.
+const CONTROL_TYPES: [ControlType; 5] = [
+    ControlType::Ssc0,
+    ControlType::QcA,
+    ControlType::QcB,
+    ControlType::Blank,
+    ControlType::Sample,
+];
+
 /// Run the classify command.
 pub async fn run(path: &str) -> Result<()> {
     let path = Path::new(path);
@@ -25,7 +36,7 @@ pub async fn run(path: &str) -> Result<()> {
         .find(|i| path.starts_with(&i.watch_path))
         .cloned();
 
-    let classifier = Classifier::new();
+    let classifier = Classifier::new().with_aliases(&config.classifier.control_aliases);
 
     println!();
     println!("Classification Result");
@@ -50,6 +61,10 @@ pub async fn run(path: &str) -> Result<()> {
                         println!("Plate ID: {}", plate);
                     }
 
+                    if let Some(index) = result.injection_index {
+                        println!("Injection: {}", index);
+                    }
+
                     println!(
                         "Confidence: {}",
                         match result.confidence {
@@ -116,7 +131,10 @@ pub async fn run(path: &str) -> Result<()> {
 
                 // Try to find control type token
                 for part in &parts {
-                    if let Some(ct) = ControlType::from_token(part) {
+                    if let Some(ct) = ControlType::from_token_with_aliases(
+                        part,
+                        &config.classifier.control_aliases,
+                    ) {
                         println!("Detected control type: {} (from token '{}')", ct, part);
                         break;
                     }
@@ -136,3 +154,271 @@ pub async fn run(path: &str) -> Result<()> {
     println!();
     Ok(())
 }
+
+/// A file the classifier got wrong.
+#[derive(Debug, PartialEq)]
+struct Mismatch {
+    file_name: String,
+    expected: ControlType,
+    /// `None` when classification failed outright
+    actual: Option<ControlType>,
+    source: Option<ClassificationSource>,
+}
+
+/// Counts for one control type.
+#[derive(Debug, Default, PartialEq)]
+struct TypeScore {
+    expected: usize,
+    predicted: usize,
+    correct: usize,
+}
+
+impl TypeScore {
+    /// Share of runs classified as this type that really are; `None` if
+    /// none were.
+    fn precision(&self) -> Option<f64> {
+        (self.predicted > 0).then(|| self.correct as f64 / self.predicted as f64)
+    }
+
+    /// Share of runs of this type that were classified as it; `None` if the
+    /// ground truth has none.
+    fn recall(&self) -> Option<f64> {
+        (self.expected > 0).then(|| self.correct as f64 / self.expected as f64)
+    }
+}
+
+/// Classifier results over a ground-truth set.
+#[derive(Debug, Default)]
+struct Evaluation {
+    total: usize,
+    scores: HashMap<ControlType, TypeScore>,
+    mismatches: Vec<Mismatch>,
+}
+
+/// Read `file name, expected control type` rows. The columns are found by
+/// header (`file_name`/`filename`/`file` and `expected`/`control_type`),
+/// else the first two are used. Expected types accept the configured aliases.
+fn read_ground_truth(
+    content: &str,
+    aliases: &HashMap<String, ControlType>,
+) -> Result<Vec<(String, ControlType)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let headers = reader.headers().context("Missing header row")?.clone();
+    let column = |names: &[&str], fallback: usize| {
+        headers
+            .iter()
+            .position(|h| names.contains(&h.to_lowercase().replace([' ', '_', '-'], "").as_str()))
+            .unwrap_or(fallback)
+    };
+    let file_column = column(&["filename", "file", "rawfile"], 0);
+    let expected_column = column(&["expected", "controltype", "expectedcontroltype"], 1);
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        // Line numbers count the header
+        let line = i + 2;
+        let record = record.with_context(|| format!("Line {}: unreadable row", line))?;
+        let file_name = record.get(file_column).unwrap_or_default();
+        if file_name.is_empty() || file_name.starts_with('#') {
+            continue;
+        }
+        let token = record.get(expected_column).unwrap_or_default();
+        let expected = ControlType::from_token_with_aliases(token, aliases)
+            .with_context(|| format!("Line {}: unknown control type '{}'", line, token))?;
+        rows.push((file_name.to_string(), expected));
+    }
+    Ok(rows)
+}
+
+/// Classify each file name (no file access) and compare with the ground truth.
+fn evaluate_rows(
+    classifier: &Classifier,
+    instrument: &InstrumentConfig,
+    rows: &[(String, ControlType)],
+) -> Evaluation {
+    let mut evaluation = Evaluation {
+        total: rows.len(),
+        ..Evaluation::default()
+    };
+
+    for (file_name, expected) in rows {
+        let result = classifier.classify(Path::new(file_name), instrument);
+        let actual = result.as_ref().ok().map(|r| r.control_type);
+
+        evaluation.scores.entry(*expected).or_default().expected += 1;
+        if let Some(actual) = actual {
+            evaluation.scores.entry(actual).or_default().predicted += 1;
+        }
+        if actual == Some(*expected) {
+            evaluation.scores.entry(*expected).or_default().correct += 1;
+        } else {
+            evaluation.mismatches.push(Mismatch {
+                file_name: file_name.clone(),
+                expected: *expected,
+                actual,
+                source: result.ok().map(|r| r.source),
+            });
+        }
+    }
+
+    evaluation
+}
+
+fn fmt_pct(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |v| format!("{:.1}%", v * 100.0))
+}
+
+/// Run `mdqc classify --evaluate`: score the classifier over a ground-truth
+/// CSV with an instrument's classification settings. Fails when any file is
+/// misclassified, so it can guard naming-scheme changes in scripts.
+pub async fn evaluate(ground_truth: &str, instrument_id: Option<String>) -> Result<()> {
+    let content = std::fs::read_to_string(ground_truth)
+        .with_context(|| format!("Failed to read {}", ground_truth))?;
+
+    let config = Config::load().context("Failed to load configuration")?;
+    let mut instrument = match instrument_id {
+        Some(id) => config
+            .instruments
+            .iter()
+            .find(|i| i.id == id)
+            .with_context(|| format!("Instrument '{}' not found in config", id))?,
+        None => config
+            .instruments
+            .first()
+            .context("No instruments configured; add one to evaluate its classification")?,
+    }
+    .clone();
+    // File names only: the worklist would need the instrument's files
+    instrument.worklist_pattern = None;
+
+    let aliases = &config.classifier.control_aliases;
+    let rows = read_ground_truth(&content, aliases)
+        .with_context(|| format!("Invalid ground truth file {}", ground_truth))?;
+    let classifier = Classifier::new().with_aliases(aliases);
+    let evaluation = evaluate_rows(&classifier, &instrument, &rows);
+
+    println!();
+    println!("Classification Evaluation");
+    println!("=========================");
+    println!(
+        "Ground truth: {} ({} files)",
+        ground_truth, evaluation.total
+    );
+    println!("Instrument: {}", instrument.id);
+    println!();
+    println!(
+        "{:<14} {:>8} {:>9} {:>7} {:>9} {:>7}",
+        "Control type", "Expected", "Predicted", "Correct", "Precision", "Recall"
+    );
+    for control_type in CONTROL_TYPES {
+        let Some(score) = evaluation.scores.get(&control_type) else {
+            continue;
+        };
+        println!(
+            "{:<14} {:>8} {:>9} {:>7} {:>9} {:>7}",
+            control_type.to_string(),
+            score.expected,
+            score.predicted,
+            score.correct,
+            fmt_pct(score.precision()),
+            fmt_pct(score.recall())
+        );
+    }
+
+    let correct = evaluation.total - evaluation.mismatches.len();
+    println!();
+    println!(
+        "Accuracy: {}/{} ({})",
+        correct,
+        evaluation.total,
+        fmt_pct((evaluation.total > 0).then(|| correct as f64 / evaluation.total as f64))
+    );
+
+    if evaluation.mismatches.is_empty() {
+        println!();
+        return Ok(());
+    }
+
+    println!();
+    println!("Mismatches");
+    println!("----------");
+    for mismatch in &evaluation.mismatches {
+        match (mismatch.actual, mismatch.source) {
+            (Some(actual), Some(source)) => println!(
+                "  {}: expected {}, classified {} (from {:?})",
+                mismatch.file_name, mismatch.expected, actual, source
+            ),
+            _ => println!(
+                "  {}: expected {}, classification failed",
+                mismatch.file_name, mismatch.expected
+            ),
+        }
+    }
+    println!();
+
+    anyhow::bail!(
+        "{} of {} files misclassified",
+        evaluation.mismatches.len(),
+        evaluation.total
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_ground_truth_with_mismatch() {
+        let instrument: InstrumentConfig = toml::from_str(
+            "id = \"EXPLORIS01\"\nvendor = \"thermo\"\nwatch_path = '/data'\ntemplate = 'qc.sky'",
+        )
+        .unwrap();
+        let aliases = HashMap::from([("iRT".to_string(), ControlType::QcA)]);
+        let rows = read_ground_truth(
+            "File Name,Expected\n\
+             20261016_SSC0_A1.raw,SSC0\n\
+             20261016_QC_A_A2.raw,QC_A\n\
+             20261016_iRT_C7.raw,iRT\n\
+             20261016_QCB_B1.raw,QC_B\n\
+             20261016_plate3_C5.raw,SAMPLE\n\
+             20261016_hela_check_C6.raw,QC_B\n",
+            &aliases,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(
+            rows[2],
+            ("20261016_iRT_C7.raw".to_string(), ControlType::QcA)
+        );
+
+        let classifier = Classifier::new().with_aliases(&aliases);
+        let evaluation = evaluate_rows(&classifier, &instrument, &rows);
+
+        // The QC_B run without a token falls through to SAMPLE
+        assert_eq!(
+            evaluation.mismatches,
+            [Mismatch {
+                file_name: "20261016_hela_check_C6.raw".to_string(),
+                expected: ControlType::QcB,
+                actual: Some(ControlType::Sample),
+                source: Some(ClassificationSource::Default),
+            }]
+        );
+        let qc_b = &evaluation.scores[&ControlType::QcB];
+        assert_eq!(qc_b.precision(), Some(1.0));
+        assert_eq!(qc_b.recall(), Some(0.5));
+        let sample = &evaluation.scores[&ControlType::Sample];
+        assert_eq!(sample.precision(), Some(0.5));
+        assert_eq!(sample.recall(), Some(1.0));
+        assert_eq!(evaluation.scores[&ControlType::QcA].correct, 2);
+
+        assert!(read_ground_truth("file,expected\nx.raw,QC_Z\n", &aliases)
+            .unwrap_err()
+            .to_string()
+            .contains("Line 2"));
+    }
+}
@@ -3,13 +3,13 @@
 use anyhow::Result;
 
 use crate::cli::ConfigAction;
-use crate::config::{self, Config};
+use crate::config::{self, Config, DeliveryMode};
 
 /// Run the config command.
 pub async fn run(action: ConfigAction) -> Result<()> {
     match action {
         ConfigAction::Validate => validate_config().await,
-        ConfigAction::Show => show_config().await,
+        ConfigAction::Show { effective } => show_config(effective).await,
         ConfigAction::Path => show_path().await,
     }
 }
@@ -41,7 +41,12 @@ async fn validate_config() -> Result<()> {
             for inst in &config.instruments {
                 println!("    - {} ({:?})", inst.id, inst.vendor);
             }
-            println!("  Cloud endpoint: {}", config.cloud.endpoint);
+            match &config.cloud.delivery {
+                DeliveryMode::Http => println!("  Cloud endpoint: {}", config.cloud.endpoint),
+                DeliveryMode::LocalDir { path } => {
+                    println!("  Delivery folder: {}", path.display())
+                }
+            }
             println!(
                 "  Certificate: {}",
                 config
@@ -64,7 +69,7 @@ async fn validate_config() -> Result<()> {
     Ok(())
 }
 
-async fn show_config() -> Result<()> {
+async fn show_config(effective: bool) -> Result<()> {
     let config_path = config::paths::config_file();
 
     if !config_path.exists() {
@@ -72,6 +77,18 @@ async fn show_config() -> Result<()> {
         return Ok(());
     }
 
+    if effective {
+        let config = Config::load()?;
+        println!(
+            "# Effective configuration loaded from {}",
+            config_path.display()
+        );
+        println!("# Defaults filled in, watcher overrides merged, secrets redacted");
+        println!();
+        print!("{}", config.effective_toml()?);
+        return Ok(());
+    }
+
     let content = std::fs::read_to_string(&config_path)?;
     println!("{}", content);
 
@@ -0,0 +1,126 @@
+//! Diff command - compare the metrics of two spooled runs.
+//!
+//! Payloads are read from the spool, so a run can be compared as long as its
+//! payload is still pending, failed, or among the retained completed ones.
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::baseline::{self, RunDiff};
+use crate::config::Config;
+use crate::history;
+use crate::spool::Spool;
+use crate::types::QcPayload;
+
+/// Run the diff command.
+pub async fn run(run_a: &str, run_b: &str, json: bool) -> Result<()> {
+    let config = Config::load().context("Failed to load configuration")?;
+    let spool = Spool::new(&config.spool)?;
+
+    let a = load_payload(&spool, run_a)?;
+    let b = load_payload(&spool, run_b)?;
+
+    let diff = baseline::diff_runs(
+        (&a.run_metrics, &a.target_metrics),
+        (&b.run_metrics, &b.target_metrics),
+        &config.comparison,
+    );
+
+    if json {
+        let output = serde_json::json!({
+            "run_a": a.run.run_id,
+            "run_b": b.run.run_id,
+            "diff": diff,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    print_diff(&a, &b, &diff);
+    Ok(())
+}
+
+/// Load a payload by run ID, or by raw file name via the audit trail.
+fn load_payload(spool: &Spool, id: &str) -> Result<QcPayload> {
+    let run_id = match Uuid::parse_str(id) {
+        Ok(run_id) => run_id,
+        Err(_) => history::find_audit(&history::audit_log_path(), id)?
+            .map(|audit| audit.run_id)
+            .with_context(|| format!("No run found for '{}'", id))?,
+    };
+
+    spool.find_payload(&run_id)?.with_context(|| {
+        format!(
+            "No spooled payload for run {} (completed payloads are kept up to spool.completed_retention_count)",
+            run_id
+        )
+    })
+}
+
+fn print_diff(a: &QcPayload, b: &QcPayload, diff: &RunDiff) {
+    println!();
+    println!("Run Diff");
+    println!("========");
+    println!("A: {} ({})", a.run.raw_file_name, a.run.run_id);
+    println!("B: {} ({})", b.run.raw_file_name, b.run.run_id);
+    println!();
+
+    println!("Run Metrics");
+    println!("-----------");
+    for metric in &diff.run_metrics {
+        println!(
+            "  {:<24} {:>10} {:>10} {:>10}",
+            metric.metric,
+            format_value(metric.a),
+            format_value(metric.b),
+            format_signed(metric.delta)
+        );
+    }
+    println!();
+
+    println!("Targets (B vs A)");
+    println!("----------------");
+    println!(
+        "  {:<24} {:>10} {:>10} {:>10} {:>12}",
+        "target", "RT shift", "area", "log2 FC", "mass error"
+    );
+    for target in &diff.targets {
+        println!(
+            "{} {:<24} {:>10} {:>10} {:>10} {:>12}",
+            if target.outlier { "!" } else { " " },
+            target.target_id,
+            format_signed(Some(target.rt_shift)),
+            target
+                .area_ratio
+                .map(|r| format!("{:.2}x", r))
+                .unwrap_or_else(|| "-".to_string()),
+            format_signed(target.log2_fold_change),
+            format_signed(target.mass_error_delta_ppm)
+        );
+    }
+
+    let outliers = diff.targets.iter().filter(|t| t.outlier).count();
+    if outliers > 0 {
+        println!();
+        println!("! {} target(s) with area ratio outside tolerance", outliers);
+    }
+    if !diff.only_in_a.is_empty() {
+        println!("Only in A: {}", diff.only_in_a.join(", "));
+    }
+    if !diff.only_in_b.is_empty() {
+        println!("Only in B: {}", diff.only_in_b.join(", "));
+    }
+    println!();
+}
+
+fn format_value(value: Option<f64>) -> String {
+    value
+        .map(|v| format!("{:.2}", v))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_signed(value: Option<f64>) -> String {
+    value
+        .map(|v| format!("{:+.2}", v))
+        .unwrap_or_else(|| "-".to_string())
+}
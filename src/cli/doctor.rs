@@ -1,10 +1,13 @@
 //! Doctor command - system health checks.
 
 use anyhow::Result;
+use serde::Serialize;
 use std::path::Path;
 
-use crate::config::{self, Config};
+use crate::config::{self, Config, DeliveryMode};
+use crate::extractor::pe::{self, Architecture};
 use crate::extractor::skyline;
+use crate::update_check;
 
 /// ANSI color codes for terminal output.
 mod color {
@@ -15,12 +18,15 @@ mod color {
     pub const BOLD: &str = "\x1b[1m";
 }
 
+#[derive(Serialize)]
 struct CheckResult {
     status: CheckStatus,
     label: String,
     detail: Option<String>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 enum CheckStatus {
     Ok,
     Warning,
@@ -89,153 +95,136 @@ impl CheckResult {
     }
 }
 
-/// Run the doctor command.
-pub async fn run() -> Result<()> {
-    println!();
-    println!(
-        "{}MD Local QC Agent - System Health Check{}",
-        color::BOLD,
-        color::RESET
-    );
-    println!("{}", "=".repeat(45));
-    println!();
+/// A titled group of checks.
+#[derive(Serialize)]
+struct Section {
+    name: &'static str,
+    checks: Vec<CheckResult>,
+    /// Whether errors in this section make the agent unhealthy
+    #[serde(skip)]
+    blocking: bool,
+}
 
-    let mut has_errors = false;
+impl Section {
+    fn new(name: &'static str, checks: Vec<CheckResult>) -> Self {
+        Self {
+            name,
+            checks,
+            blocking: true,
+        }
+    }
 
-    // Agent version
-    CheckResult::ok_with_detail("Agent version", env!("CARGO_PKG_VERSION")).print();
+    fn has_errors(&self) -> bool {
+        self.blocking && self.checks.iter().any(|c| c.is_error())
+    }
+}
 
-    // Configuration
-    println!();
-    println!("{}Configuration{}", color::BOLD, color::RESET);
-    println!("{}", "-".repeat(20));
+/// Full doctor report, as emitted by `--json`.
+#[derive(Serialize)]
+struct Report {
+    agent_version: &'static str,
+    healthy: bool,
+    sections: Vec<Section>,
+}
+
+/// Run all checks.
+async fn collect_report() -> Report {
+    let mut sections = Vec::new();
 
     let config = match check_config() {
         Ok((result, config)) => {
-            result.print();
+            sections.push(Section::new("Configuration", vec![result]));
             Some(config)
         }
         Err(result) => {
-            has_errors = result.is_error();
-            result.print();
+            sections.push(Section::new("Configuration", vec![result]));
             None
         }
     };
 
-    // Skyline
-    println!();
-    println!("{}Skyline{}", color::BOLD, color::RESET);
-    println!("{}", "-".repeat(20));
+    sections.push(Section::new("Skyline", check_skyline(config.as_ref())));
+    sections.push(Section::new(
+        "Vendor Readers",
+        check_vendor_readers(config.as_ref()),
+    ));
 
-    let skyline_checks = check_skyline(config.as_ref());
-    for check in &skyline_checks {
-        if check.is_error() {
-            has_errors = true;
-        }
-        check.print();
+    if let Some(ref config) = config {
+        sections.push(Section::new("Templates", check_templates(config)));
+        sections.push(Section::new("Instruments", check_instruments(config)));
     }
 
-    // Vendor Readers
-    println!();
-    println!("{}Vendor Readers{}", color::BOLD, color::RESET);
-    println!("{}", "-".repeat(20));
+    sections.push(Section::new(
+        "Certificates",
+        check_certificates(config.as_ref()),
+    ));
+    sections.push(Section::new(
+        "Cloud Connectivity",
+        check_cloud_connectivity(config.as_ref()).await,
+    ));
 
-    let vendor_checks = check_vendor_readers(config.as_ref());
-    for check in &vendor_checks {
-        if check.is_error() {
-            has_errors = true;
-        }
-        check.print();
-    }
-
-    // Templates
     if let Some(ref config) = config {
-        println!();
-        println!("{}Templates{}", color::BOLD, color::RESET);
-        println!("{}", "-".repeat(20));
-
-        let template_checks = check_templates(config);
-        for check in &template_checks {
-            if check.is_error() {
-                has_errors = true;
-            }
-            check.print();
-        }
+        sections.push(Section::new("Spool", check_spool(config)));
     }
 
-    // Instruments
-    if let Some(ref config) = config {
-        println!();
-        println!("{}Instruments{}", color::BOLD, color::RESET);
-        println!("{}", "-".repeat(20));
+    // An outdated agent still works; never a blocker
+    sections.push(Section {
+        blocking: false,
+        ..Section::new("Updates", check_updates(config.as_ref()).await)
+    });
 
-        let instrument_checks = check_instruments(config);
-        for check in &instrument_checks {
-            if check.is_error() {
-                has_errors = true;
-            }
-            check.print();
-        }
+    // Windows checks are mostly warnings, not blockers
+    #[cfg(windows)]
+    sections.push(Section {
+        blocking: false,
+        ..Section::new("Windows Environment", check_windows_environment())
+    });
+
+    Report {
+        agent_version: env!("CARGO_PKG_VERSION"),
+        healthy: !sections.iter().any(|s| s.has_errors()),
+        sections,
     }
+}
 
-    // Certificates
-    println!();
-    println!("{}Certificates{}", color::BOLD, color::RESET);
-    println!("{}", "-".repeat(20));
+/// Run all checks and render the report as JSON.
+pub async fn report_json() -> Result<String> {
+    Ok(serde_json::to_string_pretty(&collect_report().await)?)
+}
 
-    let cert_checks = check_certificates(config.as_ref());
-    for check in &cert_checks {
-        if check.is_error() {
-            has_errors = true;
-        }
-        check.print();
+/// Run the doctor command.
+pub async fn run(json: bool) -> Result<()> {
+    if json {
+        println!("{}", report_json().await?);
+        return Ok(());
     }
 
-    // Cloud Connectivity
     println!();
-    println!("{}Cloud Connectivity{}", color::BOLD, color::RESET);
-    println!("{}", "-".repeat(20));
-
-    let cloud_checks = check_cloud_connectivity(config.as_ref()).await;
-    for check in &cloud_checks {
-        if check.is_error() {
-            has_errors = true;
-        }
-        check.print();
-    }
+    println!(
+        "{}MD Local QC Agent - System Health Check{}",
+        color::BOLD,
+        color::RESET
+    );
+    println!("{}", "=".repeat(45));
+    println!();
 
-    // Spool
-    if let Some(ref config) = config {
-        println!();
-        println!("{}Spool{}", color::BOLD, color::RESET);
-        println!("{}", "-".repeat(20));
+    let report = collect_report().await;
 
-        let spool_checks = check_spool(config);
-        for check in &spool_checks {
-            if check.is_error() {
-                has_errors = true;
-            }
-            check.print();
-        }
-    }
+    // Agent version
+    CheckResult::ok_with_detail("Agent version", report.agent_version).print();
 
-    // Windows-specific checks
-    #[cfg(windows)]
-    {
+    for section in &report.sections {
         println!();
-        println!("{}Windows Environment{}", color::BOLD, color::RESET);
+        println!("{}{}{}", color::BOLD, section.name, color::RESET);
         println!("{}", "-".repeat(20));
 
-        let windows_checks = check_windows_environment();
-        for check in &windows_checks {
-            // Windows checks are mostly warnings, not blockers
+        for check in &section.checks {
             check.print();
         }
     }
 
     // Summary
     println!();
-    if has_errors {
+    if !report.healthy {
         println!(
             "{}Overall: {}UNHEALTHY{} - Some checks failed",
             color::BOLD,
@@ -277,20 +266,12 @@ fn check_config() -> Result<(CheckResult, Config), CheckResult> {
 fn check_skyline(config: Option<&Config>) -> Vec<CheckResult> {
     let mut results = Vec::new();
 
-    // Find Skyline
-    // Handle "auto" path - treat it as None to trigger auto-discovery
-    let skyline_path = if let Some(config) = config {
-        config
-            .skyline
-            .path
-            .as_ref()
-            .filter(|p| !p.eq_ignore_ascii_case("auto") && !p.is_empty())
-            .map(std::path::PathBuf::from)
-    } else {
-        None
-    };
-
-    let skyline_path = skyline_path.or_else(skyline::discover_skyline);
+    // Find Skyline, discovering it for "auto"; the doctor reports what is
+    // installed now, not what an earlier check found
+    skyline::invalidate_discovery();
+    let skyline_path = config
+        .and_then(|config| config.skyline.configured_path())
+        .or_else(skyline::discover_skyline);
 
     match skyline_path {
         Some(path) if path.exists() => {
@@ -311,6 +292,8 @@ fn check_skyline(config: Option<&Config>) -> Vec<CheckResult> {
                     ));
                 }
             }
+
+            results.extend(check_architecture(&path));
         }
         Some(path) => {
             results.push(CheckResult::error(
@@ -329,6 +312,79 @@ fn check_skyline(config: Option<&Config>) -> Vec<CheckResult> {
     results
 }
 
+/// Compare the agent's architecture with SkylineCmd's, and check for the VC++
+/// runtime the vendor readers Skyline loads need.
+fn check_architecture(skyline_path: &Path) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    let agent = Architecture::of_agent();
+    results.push(CheckResult::ok_with_detail(
+        "Agent architecture",
+        agent.to_string(),
+    ));
+
+    let skyline = match pe::read_architecture(skyline_path) {
+        Ok(skyline) => skyline,
+        Err(e) => {
+            results.push(CheckResult::warning(
+                "Skyline architecture",
+                format!("could not read: {}", e),
+            ));
+            return results;
+        }
+    };
+    if agent.matches(skyline) {
+        results.push(CheckResult::ok_with_detail(
+            "Skyline architecture",
+            skyline.to_string(),
+        ));
+    } else {
+        results.push(CheckResult::warning(
+            "Skyline architecture",
+            format!(
+                "{} doesn't match the {} agent (install the agent build matching Skyline)",
+                skyline, agent
+            ),
+        ));
+    }
+
+    #[cfg(windows)]
+    {
+        let system_dir = runtime_dir(skyline);
+        let missing = pe::missing_runtime_dlls(&system_dir, skyline);
+        if missing.is_empty() {
+            results.push(CheckResult::ok_with_detail("VC++ runtime", "installed"));
+        } else {
+            results.push(CheckResult::warning(
+                "VC++ runtime",
+                format!(
+                    "{} missing from {} (install the Microsoft Visual C++ Redistributable, {})",
+                    missing.join(", "),
+                    system_dir.display(),
+                    skyline
+                ),
+            ));
+        }
+    }
+
+    results
+}
+
+/// System directory holding the runtime DLLs for `arch`: SysWOW64 for
+/// 32-bit processes on 64-bit Windows, System32 otherwise.
+#[cfg(windows)]
+fn runtime_dir(arch: Architecture) -> std::path::PathBuf {
+    let windows = std::path::PathBuf::from(
+        std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string()),
+    );
+    let wow64 = windows.join("SysWOW64");
+    if arch == Architecture::X86 && wow64.is_dir() {
+        wow64
+    } else {
+        windows.join("System32")
+    }
+}
+
 fn check_vendor_readers(_config: Option<&Config>) -> Vec<CheckResult> {
     let mut results = Vec::new();
 
@@ -357,24 +413,54 @@ fn check_templates(config: &Config) -> Vec<CheckResult> {
     let mut results = Vec::new();
     let template_dir = config::paths::template_dir();
 
+    let probes = crate::extractor::probe::ColumnProbeCache::load();
+
     for instrument in &config.instruments {
-        let template_path = template_dir.join(&instrument.template);
+        let resolved = crate::extractor::resolve_template_path(
+            &instrument.template,
+            &template_dir,
+            config.skyline.allow_template_basename_fallback,
+        );
 
-        if template_path.exists() {
+        if let Ok(template_path) = resolved {
             // Calculate hash
-            let hash = match crate::extractor::skyline::hash_template(&template_path) {
-                Ok(h) => format!("sha256:{}...", &h[..16]),
-                Err(_) => "hash error".to_string(),
+            let hash = crate::extractor::skyline::hash_template(&template_path).ok();
+            let hash_detail = match hash {
+                Some(ref h) => format!("sha256:{}...", &h[..16]),
+                None => "hash error".to_string(),
             };
 
             results.push(CheckResult::ok_with_detail(
                 &instrument.template,
-                format!("found, {}", hash),
+                format!("found, {}", hash_detail),
             ));
+            if Path::new(&instrument.template).is_absolute()
+                && !Path::new(&instrument.template).exists()
+            {
+                results.push(CheckResult::warning(
+                    format!("{} path", instrument.template),
+                    format!(
+                        "moved; using {} by file name (update the config)",
+                        template_path.display()
+                    ),
+                ));
+            }
+
+            // Report column gaps found by the agent's startup probe
+            let probe = hash.and_then(|h| probes.get(&instrument.id, &h));
+            if let Some(warning) = probe.and_then(|p| p.warning(&instrument.id)) {
+                results.push(CheckResult::warning(
+                    format!("{} report columns", instrument.template),
+                    warning,
+                ));
+            }
         } else {
             results.push(CheckResult::error(
                 &instrument.template,
-                format!("not found at {}", template_path.display()),
+                format!(
+                    "not found at {}",
+                    template_dir.join(&instrument.template).display()
+                ),
             ));
         }
     }
@@ -463,27 +549,71 @@ fn check_certificates(config: Option<&Config>) -> Vec<CheckResult> {
         }
     }
 
+    if let Some(cloud) = config.map(|c| &c.cloud) {
+        if let Some(ref path) = cloud.ca_bundle_path {
+            match cloud.ca_certificates() {
+                Ok(certs) => results.push(CheckResult::ok_with_detail(
+                    "CA bundle",
+                    format!("{} certificate(s) from {}", certs.len(), path),
+                )),
+                Err(e) => results.push(CheckResult::error("CA bundle", format!("{:#}", e))),
+            }
+        }
+
+        if cloud.insecure_skip_verify {
+            results.push(CheckResult::warning(
+                "Server verification",
+                "disabled (insecure_skip_verify)",
+            ));
+        }
+    }
+
     results
 }
 
 async fn check_cloud_connectivity(config: Option<&Config>) -> Vec<CheckResult> {
     let mut results = Vec::new();
 
+    // Payloads written to a folder never reach the endpoint
+    if let Some(DeliveryMode::LocalDir { path }) = config.map(|c| &c.cloud.delivery) {
+        results.push(if path.is_dir() {
+            CheckResult::ok_with_detail("Delivery folder", path.display().to_string())
+        } else {
+            CheckResult::warning(
+                "Delivery folder",
+                format!("{} does not exist yet", path.display()),
+            )
+        });
+        return results;
+    }
+
     let endpoint = config
         .map(|c| c.cloud.endpoint.as_str())
         .unwrap_or("https://qc-ingest.massdynamics.com/v1/");
 
     results.push(CheckResult::ok_with_detail("Endpoint", endpoint));
 
-    // Try to reach the endpoint
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build();
+    // Try to reach the endpoint, trusting the same CAs as the uploader
+    let mut builder = reqwest::Client::builder().timeout(std::time::Duration::from_secs(10));
+    if let Some(cloud) = config.map(|c| &c.cloud) {
+        let ca_certs = cloud.ca_certificates().unwrap_or_default();
+        if !ca_certs.is_empty() {
+            builder = builder.tls_built_in_root_certs(false);
+        }
+        for cert in ca_certs {
+            builder = builder.add_root_certificate(cert);
+        }
+        builder = builder.danger_accept_invalid_certs(cloud.insecure_skip_verify);
+    }
+    let client = builder.build();
 
     match client {
         Ok(client) => {
             let health_url = format!("{}health", endpoint);
-            match client.get(&health_url).send().await {
+            let extra_headers = config
+                .and_then(|c| c.cloud.header_map().ok())
+                .unwrap_or_default();
+            match client.get(&health_url).headers(extra_headers).send().await {
                 Ok(response) => {
                     if response.status().is_success() {
                         results.push(CheckResult::ok("Connectivity"));
@@ -513,7 +643,34 @@ async fn check_cloud_connectivity(config: Option<&Config>) -> Vec<CheckResult> {
     results
 }
 
-fn check_spool(_config: &Config) -> Vec<CheckResult> {
+async fn check_updates(config: Option<&Config>) -> Vec<CheckResult> {
+    if config.is_some_and(|c| !c.agent.check_for_updates) {
+        return vec![CheckResult::not_configured(
+            "Update check (disabled by agent.check_for_updates)",
+        )];
+    }
+
+    let cloud = config.map(|c| c.cloud.clone()).unwrap_or_default();
+    let result = match update_check::check(&cloud, false).await {
+        Ok(status) if status.update_available() => CheckResult::warning(
+            "Latest release",
+            format!(
+                "update available: v{} ({})",
+                status.latest,
+                status.release_url()
+            ),
+        ),
+        Ok(status) => CheckResult::ok_with_detail(
+            "Latest release",
+            format!("v{} (up to date)", status.latest),
+        ),
+        Err(e) => CheckResult::warning("Latest release", format!("check failed: {:#}", e)),
+    };
+
+    vec![result]
+}
+
+fn check_spool(config: &Config) -> Vec<CheckResult> {
     let mut results = Vec::new();
 
     let spool_dir = config::paths::spool_dir();
@@ -548,6 +705,32 @@ fn check_spool(_config: &Config) -> Vec<CheckResult> {
         }
     }
 
+    // Free space on the data drive
+    let min_free_mb = config.spool.min_free_disk_mb;
+    match crate::disk::free_space_mb(&spool_dir) {
+        Ok(free_mb) if free_mb < min_free_mb => {
+            results.push(CheckResult::warning(
+                "Free disk space",
+                format!(
+                    "{} MB (below {} MB minimum, processing deferred)",
+                    free_mb, min_free_mb
+                ),
+            ));
+        }
+        Ok(free_mb) => {
+            results.push(CheckResult::ok_with_detail(
+                "Free disk space",
+                format!("{} MB", free_mb),
+            ));
+        }
+        Err(e) => {
+            results.push(CheckResult::warning(
+                "Free disk space",
+                format!("unknown: {}", e),
+            ));
+        }
+    }
+
     // Count pending items
     let pending_dir = spool_dir.join("pending");
     if pending_dir.exists() {
@@ -593,24 +776,16 @@ fn check_windows_environment() -> Vec<CheckResult> {
     results.push(CheckResult::ok_with_detail("Windows version", version_info));
 
     // Check if Start Menu shortcut exists (needed for notifications)
-    let shortcut_path = std::env::var("APPDATA")
-        .map(|appdata| {
-            std::path::PathBuf::from(appdata)
-                .join("Microsoft")
-                .join("Windows")
-                .join("Start Menu")
-                .join("Programs")
-                .join("MD QC Agent.lnk")
-        })
-        .ok();
-
-    if let Some(ref path) = shortcut_path {
+    if let Some(ref path) = config::paths::start_menu_shortcut() {
         if path.exists() {
-            results.push(CheckResult::ok("Start Menu shortcut"));
+            results.push(CheckResult::ok_with_detail(
+                "Start Menu shortcut",
+                "present (send test toasts with 'mdqc notify-test')",
+            ));
         } else {
             results.push(CheckResult::warning(
                 "Start Menu shortcut",
-                "missing (notifications may show as 'PowerShell')",
+                "missing (notifications may show as 'PowerShell'; check with 'mdqc notify-test')",
             ));
         }
     }
@@ -1,10 +1,19 @@
 //! Failed files CLI commands.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::io::{self, Write};
+use std::path::PathBuf;
 
+use crate::baseline::BaselineManager;
+use crate::classifier::Classifier;
 use crate::cli::FailedAction;
-use crate::failed_files::FailedFiles;
+use crate::config::{Config, InstrumentMode};
+use crate::consumables;
+use crate::extractor::Extractor;
+use crate::failed_files::{ExtractionDiagnostics, FailedFile, FailedFiles, FailureCategory};
+use crate::history::{ClassificationAudit, ProcessingDecision, RunHistory};
+use crate::spool::Spool;
+use crate::types::RunClassification;
 
 /// Run a failed files command.
 pub async fn run(action: FailedAction) -> Result<()> {
@@ -12,6 +21,7 @@ pub async fn run(action: FailedAction) -> Result<()> {
 
     match action {
         FailedAction::List => list_failed(&failed),
+        FailedAction::Show { path, rerun } => show_failed(&failed, &path, rerun).await,
         FailedAction::Retry { path } => retry_failed(&failed, &path).await,
         FailedAction::Clear { confirm } => clear_failed(&failed, confirm),
     }
@@ -32,6 +42,9 @@ fn list_failed(failed: &FailedFiles) -> Result<()> {
         println!("Path:       {}", file.path.display());
         println!("Instrument: {}", file.instrument_id);
         println!("Reason:     {}", file.reason);
+        if file.category == FailureCategory::Aborted {
+            println!("Category:   aborted acquisition");
+        }
         println!(
             "Failed at:  {}",
             file.failed_at.format("%Y-%m-%d %H:%M:%S UTC")
@@ -39,86 +52,513 @@ fn list_failed(failed: &FailedFiles) -> Result<()> {
         if file.retry_count > 0 {
             println!("Retries:    {}", file.retry_count);
         }
+        if let Some(ref original) = file.quarantined_from {
+            println!("Quarantined from: {}", original.display());
+        }
         println!("{}", "-".repeat(80));
     }
 
-    println!("\nTo retry a file: mdqc failed retry <path>");
+    println!("\nTo see Skyline's output: mdqc failed show <path>");
+    println!("To retry a file: mdqc failed retry <path>");
     println!("To retry all:    mdqc failed retry all");
     println!("To clear list:   mdqc failed clear --confirm");
 
     Ok(())
 }
 
+async fn show_failed(failed: &FailedFiles, path: &str, rerun: bool) -> Result<()> {
+    let file = if path == "latest" {
+        failed.get_all().into_iter().next()
+    } else {
+        failed.get(&PathBuf::from(path))
+    };
+    let Some(file) = file else {
+        if path == "latest" {
+            println!("No failed files.");
+        } else {
+            println!("File not in failed list: {}", path);
+        }
+        return Ok(());
+    };
+
+    println!("Path:       {}", file.path.display());
+    println!("Instrument: {}", file.instrument_id);
+    println!("Reason:     {}", file.reason);
+    println!(
+        "Failed at:  {}",
+        file.failed_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if file.retry_count > 0 {
+        println!("Retries:    {}", file.retry_count);
+    }
+
+    match file.diagnostics {
+        Some(ref diagnostics) => print_diagnostics(diagnostics),
+        None => println!("\nNo Skyline output was recorded for this failure."),
+    }
+
+    if rerun {
+        println!();
+        rerun_extraction(&file).await?;
+    }
+
+    Ok(())
+}
+
+fn print_diagnostics(diagnostics: &ExtractionDiagnostics) {
+    println!("Template:   {}", diagnostics.template);
+    if let Some(ref c) = diagnostics.classification {
+        let well = c
+            .well_position
+            .as_ref()
+            .map_or_else(|| "-".to_string(), |w| w.to_string());
+        println!(
+            "Classified: {} (well {}, {} confidence)",
+            c.control_type, well, c.confidence
+        );
+    }
+    match diagnostics.exit_code {
+        Some(code) => println!("Exit code:  {}", code),
+        None => println!("Exit code:  - (Skyline did not run to completion)"),
+    }
+
+    println!("\nstderr:");
+    print_block(&diagnostics.stderr);
+    println!("\nstdout (tail):");
+    print_block(&diagnostics.stdout_tail);
+}
+
+fn print_block(text: &str) {
+    if text.trim().is_empty() {
+        println!("  (empty)");
+    }
+    for line in text.lines() {
+        println!("  {}", line);
+    }
+}
+
+/// Extract a failed file again in the foreground and report what Skyline did.
+///
+/// Nothing is spooled; use `mdqc failed retry` to reprocess the run for real.
+async fn rerun_extraction(file: &FailedFile) -> Result<()> {
+    let config = Config::load().context("Failed to load configuration")?;
+    let instrument = config
+        .instruments
+        .iter()
+        .find(|i| i.id == file.instrument_id)
+        .ok_or_else(|| anyhow::anyhow!("Instrument {} is not configured", file.instrument_id))?;
+
+    let classification = match file
+        .diagnostics
+        .as_ref()
+        .and_then(|d| d.classification.clone())
+    {
+        Some(c) => c,
+        None => Classifier::new()
+            .with_aliases(&config.classifier.control_aliases)
+            .classify(&file.path, instrument)?,
+    };
+
+    let extractor = Extractor::new(&config.skyline)?;
+    println!("Re-running extraction of {}", file.path.display());
+    println!("Template:   {}", instrument.template);
+    println!("(use --log-level debug to also log the SkylineCmd command line)");
+    println!();
+
+    match extractor
+        .extract(&file.path, instrument, &classification)
+        .await
+    {
+        Ok(result) => {
+            println!(
+                "Extraction succeeded: {}/{} targets in {} ms",
+                result.run_metrics.targets_found,
+                result.run_metrics.targets_expected,
+                result.extraction_time_ms
+            );
+            for warning in &result.import_warnings {
+                println!("  Import warning: {}", warning);
+            }
+            println!(
+                "\nTo reprocess the run: mdqc failed retry {}",
+                file.path.display()
+            );
+        }
+        Err(e) => {
+            println!("Extraction failed: {}", e);
+            print_diagnostics(&ExtractionDiagnostics::new(
+                &e,
+                &classification,
+                &instrument.template,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 async fn retry_failed(failed: &FailedFiles, path: &str) -> Result<()> {
     if path == "all" {
-        let files = failed.get_all();
+        // Aborted acquisitions won't extract; retry those one by one if needed
+        let (aborted, files): (Vec<_>, Vec<_>) = failed
+            .get_all()
+            .into_iter()
+            .partition(|f| f.category == FailureCategory::Aborted);
+        if !aborted.is_empty() {
+            println!(
+                "Skipping {} aborted acquisition(s); retry them by path if needed.",
+                aborted.len()
+            );
+        }
         if files.is_empty() {
             println!("No failed files to retry.");
             return Ok(());
         }
 
+        let pipeline = RetryPipeline::new().await?;
         println!("Retrying {} failed files...", files.len());
+        let mut outcomes = Vec::new();
         for file in files {
             println!("\nRetrying: {}", file.path.display());
-            match retry_single_file(&file.path, &file.instrument_id).await {
-                Ok(()) => {
-                    println!("  Success! File has been queued for reprocessing.");
-                    failed.mark_success(&file.path);
-                }
-                Err(e) => {
-                    println!("  Failed: {}", e);
-                }
-            }
+            let outcome = pipeline.retry(failed, &file).await;
+            print_outcome(&outcome, "  ");
+            outcomes.push((file.path, outcome));
         }
+        print_summary(&outcomes);
     } else {
         let path = std::path::PathBuf::from(path);
 
         // Check if this file is in the failed list
-        let files = failed.get_all();
-        let file_info = files.iter().find(|f| f.path == path);
-
-        if let Some(info) = file_info {
-            println!("Retrying: {}", path.display());
-            match retry_single_file(&path, &info.instrument_id).await {
-                Ok(()) => {
-                    println!("Success! File has been queued for reprocessing.");
-                    failed.mark_success(&path);
-                }
-                Err(e) => {
-                    println!("Failed: {}", e);
-                }
-            }
-        } else {
+        let Some(file) = failed.get(&path) else {
             // File not in failed list, but user might want to force-process it
             println!("File not in failed list: {}", path.display());
             println!("\nTo process a file that's not in the failed list,");
             println!("use: mdqc classify {}", path.display());
-        }
+            return Ok(());
+        };
+
+        let pipeline = RetryPipeline::new().await?;
+        println!("Retrying: {}", path.display());
+        let outcome = pipeline.retry(failed, &file).await;
+        print_outcome(&outcome, "");
     }
 
     Ok(())
 }
 
-async fn retry_single_file(path: &std::path::Path, _instrument_id: &str) -> Result<()> {
-    // For now, we just validate the file exists and queue it
-    // In a full implementation, this would trigger the processing pipeline
+/// A retry whose extraction succeeded.
+struct Retried {
+    /// Targets found and expected; none when the run wasn't extracted
+    targets: Option<(u32, u32)>,
+    /// Spooled (or kept) payloads; none when the run wasn't spooled
+    payloads: Vec<PathBuf>,
+    /// Why the run wasn't queued for upload, if it wasn't
+    not_uploaded: Option<String>,
+}
+
+/// What a retry needs, set up once for `mdqc failed retry all`.
+struct RetryPipeline {
+    config: Config,
+    classifier: Classifier,
+    extractor: Extractor,
+    spool: Spool,
+    history: RunHistory,
+}
+
+impl RetryPipeline {
+    async fn new() -> Result<Self> {
+        let config = Config::load().context("Failed to load configuration")?;
+        let classifier = Classifier::new().with_aliases(&config.classifier.control_aliases);
+        let extractor = Extractor::new(&config.skyline)?;
+        let spool = Spool::new(&config.spool)?
+            .with_baselines(BaselineManager::load())
+            .with_config_hash(config.config_hash())
+            .with_projects(&config.instruments)
+            .with_payload_limit(
+                config.cloud.max_payload_targets,
+                config.cloud.oversized_payloads,
+            );
+        spool
+            .set_agent_id(crate::cli::run::resolve_agent_id(&config))
+            .await;
 
-    if !path.exists() {
-        anyhow::bail!("File no longer exists: {}", path.display());
+        Ok(Self {
+            config,
+            classifier,
+            extractor,
+            spool,
+            history: RunHistory::new(),
+        })
     }
 
-    // Touch the file to make it appear "new" to the watcher
-    // This will cause it to be picked up again
-    let now = std::time::SystemTime::now();
-    if let Err(e) = filetime::set_file_mtime(path, filetime::FileTime::from_system_time(now)) {
-        // If we can't touch the file, inform the user
-        println!("  Note: Could not update file timestamp ({})", e);
-        println!("  The file will be processed on next agent restart.");
-    } else {
-        println!("  File timestamp updated - watcher will pick it up.");
+    /// Classify, extract and spool a failed file in this process, counting
+    /// the retry. The file leaves the failed list only if extraction
+    /// succeeds; otherwise its failure reason is updated. Errors are the
+    /// reason the retry failed.
+    async fn retry(&self, failed: &FailedFiles, file: &FailedFile) -> Result<Retried, String> {
+        let path = &file.path;
+        failed.increment_retry(path);
+
+        let fail = |reason: String, diagnostics: Option<ExtractionDiagnostics>| {
+            failed.record_retry_failure(path, reason.clone(), diagnostics);
+            reason
+        };
+
+        let Some(instrument) = self
+            .config
+            .instruments
+            .iter()
+            .find(|i| i.id == file.instrument_id)
+        else {
+            return Err(fail(
+                format!("Instrument {} is not configured", file.instrument_id),
+                None,
+            ));
+        };
+        if !path.exists() {
+            return Err(fail(
+                format!("File no longer exists: {}", path.display()),
+                None,
+            ));
+        }
+
+        let audit = |classification: Option<&RunClassification>, decision| {
+            ClassificationAudit::new(
+                path.clone(),
+                instrument.id.clone(),
+                classification.cloned(),
+                decision,
+            )
+        };
+
+        let classification = match self.classifier.classify(path, instrument) {
+            Ok(c) => c,
+            Err(e) => {
+                let reason = format!("Classification failed: {}", e);
+                self.history.record_audit(
+                    audit(None, ProcessingDecision::ClassificationFailed)
+                        .with_detail(e.to_string()),
+                );
+                return Err(fail(reason, None));
+            }
+        };
+        if !classification.control_type.is_qc() {
+            self.history.record_audit(audit(
+                Some(&classification),
+                ProcessingDecision::SkippedNonQc,
+            ));
+            return Err(fail(
+                format!(
+                    "Classified as {}, not a QC run",
+                    classification.control_type
+                ),
+                None,
+            ));
+        }
+
+        // Detect-only instruments stop at classification, as in the agent
+        if instrument.mode == InstrumentMode::DetectOnly {
+            self.history
+                .record_audit(audit(Some(&classification), ProcessingDecision::Detected));
+            failed.mark_success(path);
+            return Ok(Retried {
+                targets: None,
+                payloads: Vec::new(),
+                not_uploaded: Some("detect-only instrument, not extracted".to_string()),
+            });
+        }
+
+        let mut result = match self
+            .extractor
+            .extract(path, instrument, &classification)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => {
+                self.history.record_audit(
+                    audit(Some(&classification), ProcessingDecision::ExtractionFailed)
+                        .with_detail(e.to_string()),
+                );
+                let diagnostics =
+                    ExtractionDiagnostics::new(&e, &classification, &instrument.template);
+                return Err(fail(format!("Extraction failed: {}", e), Some(diagnostics)));
+            }
+        };
+        let mut retried = Retried {
+            targets: Some((
+                result.run_metrics.targets_found,
+                result.run_metrics.targets_expected,
+            )),
+            payloads: Vec::new(),
+            not_uploaded: None,
+        };
+
+        // Historical backlog from before the instrument went live
+        if instrument.acquired_before_cutoff(result.acquisition.acquisition_time) {
+            let detail = format!(
+                "acquired {} before process_acquired_after {}",
+                result
+                    .acquisition
+                    .acquisition_time
+                    .unwrap_or_default()
+                    .to_rfc3339(),
+                instrument
+                    .process_acquired_after
+                    .unwrap_or_default()
+                    .to_rfc3339()
+            );
+            self.history.record_audit(
+                audit(
+                    Some(&classification),
+                    ProcessingDecision::AcquiredBeforeCutoff,
+                )
+                .with_run_id(result.run_id)
+                .with_detail(detail.clone()),
+            );
+            failed.mark_success(path);
+            retried.not_uploaded = Some(format!("not spooled, {}", detail));
+            return Ok(retried);
+        }
+
+        result.consumables = consumables::resolve(&instrument.consumables, path);
+
+        // Observe-only instruments and uncertain classifications are kept
+        // locally, as the agent would
+        let min_upload_confidence = instrument.min_upload_confidence(&self.config.cloud);
+        let (decision, keep_local) = if instrument.mode == InstrumentMode::ObserveOnly {
+            (
+                ProcessingDecision::Observed,
+                Some("observe-only instrument".to_string()),
+            )
+        } else if !classification.confidence.meets(min_upload_confidence) {
+            (
+                ProcessingDecision::BelowUploadConfidence,
+                Some(format!(
+                    "confidence {} is below min_upload_confidence {}",
+                    classification.confidence, min_upload_confidence
+                )),
+            )
+        } else {
+            (ProcessingDecision::Processed, None)
+        };
+        let spooled = match keep_local {
+            Some(_) => self
+                .spool
+                .observe(
+                    &result,
+                    &classification,
+                    instrument.vendor,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .map(|path| vec![path]),
+            None => {
+                self.spool
+                    .enqueue(
+                        &result,
+                        &classification,
+                        instrument.vendor,
+                        None,
+                        None,
+                        None,
+                    )
+                    .await
+            }
+        };
+        retried.payloads = match spooled {
+            Ok(payloads) => payloads,
+            Err(e) => {
+                self.history.record_audit(
+                    audit(Some(&classification), ProcessingDecision::SpoolFailed)
+                        .with_run_id(result.run_id)
+                        .with_detail(e.to_string()),
+                );
+                return Err(fail(format!("Failed to spool result: {}", e), None));
+            }
+        };
+
+        // Count it as processed so dedup and template-change detection see
+        // it, as the agent does after a successful spool
+        self.history.record_run(
+            path.clone(),
+            instrument.id.clone(),
+            result.template_hash.clone(),
+        );
+
+        let mut processed = audit(Some(&classification), decision)
+            .with_run_id(result.run_id)
+            .with_metrics(result.run_metrics.clone());
+        if let Some(ref reason) = keep_local {
+            processed = processed.with_detail(reason.clone());
+        }
+        self.history.record_audit(processed);
+        failed.mark_success(path);
+        retried.not_uploaded = keep_local.map(|reason| format!("kept locally, {}", reason));
+        Ok(retried)
     }
+}
 
-    Ok(())
+fn print_outcome(outcome: &Result<Retried, String>, indent: &str) {
+    match outcome {
+        Ok(retried) => {
+            if let Some((found, expected)) = retried.targets {
+                println!("{}Extracted: {}/{} targets", indent, found, expected);
+            }
+            for payload in &retried.payloads {
+                println!("{}Spooled:   {}", indent, payload.display());
+            }
+            if let Some(ref reason) = retried.not_uploaded {
+                println!("{}Not uploaded: {}", indent, reason);
+            }
+        }
+        Err(reason) => println!("{}Failed: {}", indent, reason),
+    }
+}
+
+/// One line per retried file, then the totals.
+fn print_summary(outcomes: &[(PathBuf, Result<Retried, String>)]) {
+    let name = |path: &PathBuf| {
+        path.file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string())
+    };
+    let width = outcomes
+        .iter()
+        .map(|(path, _)| name(path).len())
+        .max()
+        .unwrap_or_default()
+        .max("File".len());
+
+    println!();
+    println!("Summary");
+    println!("-------");
+    println!("{:<width$}  Outcome", "File");
+    for (path, outcome) in outcomes {
+        let text = match outcome {
+            Ok(retried) => {
+                let mut text = "ok".to_string();
+                if let Some((found, expected)) = retried.targets {
+                    text.push_str(&format!(", {}/{} targets", found, expected));
+                }
+                if let Some(ref reason) = retried.not_uploaded {
+                    text.push_str(&format!(" ({})", reason));
+                }
+                text
+            }
+            Err(reason) => format!("failed: {}", reason),
+        };
+        println!("{:<width$}  {}", name(path), text);
+    }
+
+    let succeeded = outcomes.iter().filter(|(_, o)| o.is_ok()).count();
+    println!();
+    println!(
+        "{} succeeded, {} failed",
+        succeeded,
+        outcomes.len() - succeeded
+    );
 }
 
 fn clear_failed(failed: &FailedFiles, confirm: bool) -> Result<()> {
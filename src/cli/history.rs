@@ -0,0 +1,111 @@
+//! History CLI commands.
+
+use anyhow::Result;
+
+use crate::cli::HistoryAction;
+use crate::history::{self, ClassificationAudit, ProcessingDecision};
+use crate::types::{ClassificationConfidence, ClassificationSource};
+
+/// Run a history command.
+pub async fn run(action: HistoryAction) -> Result<()> {
+    match action {
+        HistoryAction::Show { run_id } => show_audit(&run_id),
+    }
+}
+
+fn show_audit(id: &str) -> Result<()> {
+    let Some(audit) = history::find_audit(&history::audit_log_path(), id)? else {
+        println!("No audit record found for '{}'.", id);
+        println!("\nPass a run ID or the raw file name.");
+        return Ok(());
+    };
+
+    print_audit(&audit);
+    Ok(())
+}
+
+fn print_audit(audit: &ClassificationAudit) {
+    println!();
+    println!("Classification Audit");
+    println!("====================");
+    println!("Run ID:     {}", audit.run_id);
+    println!("File:       {}", audit.path.display());
+    println!("Instrument: {}", audit.instrument_id);
+    println!(
+        "Decided at: {}",
+        audit.decided_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    println!();
+
+    println!("Classification");
+    println!("--------------");
+    match audit.classification {
+        Some(ref c) => {
+            println!("Control Type:    {}", c.control_type);
+            println!(
+                "Matched Pattern: {}",
+                c.matched_pattern.as_deref().unwrap_or("(none)")
+            );
+            match c.well_position {
+                Some(ref well) => println!("Well Position:   {}", well),
+                None => println!("Well Position:   (not detected)"),
+            }
+            if let Some(ref plate) = c.plate_id {
+                println!("Plate ID:        {}", plate);
+            }
+            if let Some(index) = c.injection_index {
+                println!("Injection:       {}", index);
+            }
+            if let Some(previous) = c.reinjection_of {
+                println!("Re-injection of: {}", previous);
+            }
+            println!(
+                "Confidence:      {}",
+                match c.confidence {
+                    ClassificationConfidence::High => "HIGH",
+                    ClassificationConfidence::Medium => "MEDIUM",
+                    ClassificationConfidence::Low => "LOW",
+                }
+            );
+            println!(
+                "Source:          {}",
+                match c.source {
+                    ClassificationSource::Filename => "FILENAME",
+                    ClassificationSource::Metadata => "METADATA",
+                    ClassificationSource::Position => "POSITION",
+                    ClassificationSource::Default => "DEFAULT",
+                }
+            );
+        }
+        None => println!("(classification failed)"),
+    }
+    println!();
+
+    println!("Decision");
+    println!("--------");
+    println!(
+        "{}",
+        match audit.decision {
+            ProcessingDecision::Processed => "Processed as QC and spooled for upload",
+            ProcessingDecision::SkippedNonQc => "Skipped (not a QC run)",
+            ProcessingDecision::ClassificationFailed => "Skipped (classification failed)",
+            ProcessingDecision::ExtractionFailed => "Extraction failed",
+            ProcessingDecision::SpoolFailed => "Extracted, but spooling failed",
+            ProcessingDecision::Observed =>
+                "Extracted and kept locally (observe-only, not uploaded)",
+            ProcessingDecision::BelowUploadConfidence =>
+                "Extracted and kept locally (classification confidence below min_upload_confidence, not uploaded)",
+            ProcessingDecision::Detected => "Classified only (detect-only, not extracted)",
+            ProcessingDecision::AcquiredBeforeCutoff =>
+                "Skipped (acquired before process_acquired_after, not spooled)",
+            ProcessingDecision::CarryoverChecked =>
+                "Checked for QC target carryover (SAMPLE run, not spooled)",
+            ProcessingDecision::SkippedDuplicate =>
+                "Skipped (file name already processed, dedup_by_filename)",
+        }
+    );
+    if let Some(ref detail) = audit.detail {
+        println!("Detail: {}", detail);
+    }
+    println!();
+}
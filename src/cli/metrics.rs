@@ -0,0 +1,97 @@
+//! Metrics CLI commands.
+//!
+//! `recompute` re-derives run metrics from a saved Skyline report so metric
+//! changes can be checked without another Skyline extraction. Reports are
+//! removed after parsing, so the report has to be passed in by path; lookup
+//! by run_id or date range and re-spooling would need reports to be retained.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::cli::MetricsAction;
+use crate::config::{Config, RtUnit};
+use crate::extractor::Extractor;
+
+/// Run a metrics command.
+pub async fn run(action: MetricsAction) -> Result<()> {
+    match action {
+        MetricsAction::Recompute {
+            report,
+            instrument,
+            json,
+        } => recompute(Path::new(&report), instrument.as_deref(), json),
+    }
+}
+
+fn recompute(report: &Path, instrument: Option<&str>, json: bool) -> Result<()> {
+    if !report.is_file() {
+        anyhow::bail!("Report not found: {}", report.display());
+    }
+
+    let config = Config::load().context("Failed to load configuration")?;
+    let rt_unit = match instrument {
+        Some(id) => {
+            config
+                .instruments
+                .iter()
+                .find(|i| i.id == id)
+                .with_context(|| format!("No instrument '{}' in the configuration", id))?
+                .rt_unit
+        }
+        None => RtUnit::default(),
+    };
+    let extractor = Extractor::new(&config.skyline)?;
+    let (targets, run_metrics) = extractor.recompute_metrics(report, rt_unit)?;
+
+    if json {
+        let output = serde_json::json!({
+            "report": report.display().to_string(),
+            "recomputed": true,
+            "run_metrics": run_metrics,
+            "target_metrics": targets,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!();
+    println!("Recomputed Metrics");
+    println!("==================");
+    println!("Report: {}", report.display());
+    println!();
+    println!(
+        "Targets: {}/{} ({:.1}%)",
+        run_metrics.targets_found, run_metrics.targets_expected, run_metrics.target_recovery_pct
+    );
+    println!(
+        "Median RT shift: {}",
+        format_optional(run_metrics.median_rt_shift, "min")
+    );
+    println!(
+        "Median mass error: {}",
+        format_optional(run_metrics.median_mass_error_ppm, "ppm")
+    );
+    println!(
+        "Chromatography score: {}",
+        format_optional(run_metrics.chromatography_score, "")
+    );
+    println!(
+        "Total ion current: {}",
+        format_optional(run_metrics.total_ion_current, "")
+    );
+    println!(
+        "Base peak intensity: {}",
+        format_optional(run_metrics.base_peak_intensity, "")
+    );
+    println!();
+
+    Ok(())
+}
+
+fn format_optional(value: Option<f64>, unit: &str) -> String {
+    match value {
+        Some(v) if unit.is_empty() => format!("{:.2}", v),
+        Some(v) => format!("{:.2} {}", v, unit),
+        None => "-".to_string(),
+    }
+}
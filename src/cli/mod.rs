@@ -2,13 +2,28 @@
 
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::types::Vendor;
+
 pub mod baseline;
+pub mod bundle;
 pub mod classify;
 pub mod config;
+pub mod diff;
 pub mod doctor;
 pub mod failed;
+pub mod history;
+pub mod metrics;
+pub mod notify_test;
+pub mod process;
+pub mod report;
 pub mod run;
+pub mod safe;
+pub mod spool;
 pub mod status;
+pub mod telemetry;
+pub mod uninstall;
+pub mod version;
+pub mod watch_debug;
 
 /// MD Local QC Agent - System suitability monitoring for mass spectrometry.
 #[derive(Parser, Debug)]
@@ -23,10 +38,22 @@ pub struct Cli {
     #[arg(long, env = "MDQC_CONFIG")]
     pub config_path: Option<String>,
 
+    /// Config profile; loads `config.<profile>.toml` instead of `config.toml`
+    #[arg(long, env = "MDQC_PROFILE", value_parser = parse_profile)]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+/// Output format for `mdqc report`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum LogLevel {
     Error,
@@ -55,19 +82,104 @@ pub enum Command {
         /// Run in foreground instead of as service
         #[arg(long, short)]
         foreground: bool,
+
+        /// Shut down cleanly after this long in foreground mode (e.g. "30s", "5m")
+        #[arg(long, value_parser = parse_duration)]
+        exit_after: Option<std::time::Duration>,
+    },
+
+    /// Collect logs, redacted config, and diagnostics into a zip for support
+    Bundle {
+        /// Output zip path
+        out: String,
     },
 
     /// Check system health and dependencies
-    Doctor,
+    Doctor {
+        /// Output the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Send a test toast of each notification type and report the result
+    NotifyTest,
 
     /// Preview run classification without processing
     Classify {
         /// Path to raw file or directory
-        path: String,
+        #[arg(required_unless_present = "evaluate")]
+        path: Option<String>,
+
+        /// Score the classifier against a CSV of file names and expected
+        /// control types, listing mismatches
+        #[arg(long, value_name = "GROUND_TRUTH_CSV", conflicts_with = "path")]
+        evaluate: Option<String>,
+
+        /// Instrument whose classification settings to evaluate with
+        /// (defaults to the first configured)
+        #[arg(long, requires = "evaluate")]
+        instrument: Option<String>,
+    },
+
+    /// Extract and spool a folder of runs in a single Skyline call
+    Process {
+        /// Folder of raw files to import with --import-all
+        #[arg(long)]
+        batch: String,
+
+        /// Instrument ID (defaults to the instrument whose watch_path contains the folder)
+        #[arg(long)]
+        instrument: Option<String>,
     },
 
     /// Show agent status and queue
-    Status,
+    Status {
+        /// Also show each instrument's latest QC run and health index
+        #[arg(long)]
+        details: bool,
+    },
+
+    /// Summarize QC runs over a date range from the local history
+    Report {
+        /// First day to include (YYYY-MM-DD; defaults to six days before --to)
+        #[arg(long)]
+        from: Option<chrono::NaiveDate>,
+
+        /// Last day to include (YYYY-MM-DD; defaults to today)
+        #[arg(long)]
+        to: Option<chrono::NaiveDate>,
+
+        /// Only include this instrument
+        #[arg(long)]
+        instrument: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: ReportFormat,
+    },
+
+    /// Compare the metrics of two spooled runs
+    Diff {
+        /// First run (run ID or raw file name)
+        run_a: String,
+
+        /// Second run, compared against the first
+        run_b: String,
+
+        /// Print the diff as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Diagnose why a raw file is or isn't being picked up by the watcher
+    WatchDebug {
+        /// Path to raw file or directory
+        path: String,
+
+        /// Vendor to check as (defaults to the matching instrument, then inference)
+        #[arg(long)]
+        vendor: Option<Vendor>,
+    },
 
     /// Manage baselines
     Baseline {
@@ -87,6 +199,52 @@ pub enum Command {
         action: FailedAction,
     },
 
+    /// Inspect the classification audit trail
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+
+    /// Work with run metrics
+    Metrics {
+        #[command(subcommand)]
+        action: MetricsAction,
+    },
+
+    /// Show diagnostics without starting the agent or loading the config
+    Safe {
+        /// Replace the config file with defaults (the old file is kept as a backup)
+        #[arg(long)]
+        reset_config: bool,
+    },
+
+    /// Manage the local upload spool
+    Spool {
+        #[command(subcommand)]
+        action: SpoolAction,
+    },
+
+    /// Inspect opt-in anonymized telemetry
+    Telemetry {
+        #[command(subcommand)]
+        action: TelemetryAction,
+    },
+
+    /// Remove the service and Start Menu shortcut, and optionally all local data
+    Uninstall {
+        /// Also delete the data directory (config, spool, logs, state)
+        #[arg(long)]
+        purge_data: bool,
+
+        /// Purge even if some payloads have not been uploaded yet
+        #[arg(long)]
+        force: bool,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        confirm: bool,
+    },
+
     /// Run system tray icon (Windows only)
     Tray,
 
@@ -94,7 +252,11 @@ pub enum Command {
     Gui,
 
     /// Show version information
-    Version,
+    Version {
+        /// Also check GitHub releases for a newer version
+        #[arg(long)]
+        check: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -112,6 +274,13 @@ pub enum BaselineAction {
         baseline_id: String,
     },
 
+    /// Fetch baselines from the cloud now, cache the active one and print them
+    Refresh {
+        /// Only refresh this instrument
+        #[arg(long)]
+        instrument: Option<String>,
+    },
+
     /// Reset (archive) current baseline for an instrument
     Reset {
         /// Instrument ID
@@ -130,7 +299,12 @@ pub enum ConfigAction {
     Validate,
 
     /// Show current configuration
-    Show,
+    Show {
+        /// Show the configuration as the agent uses it: defaults filled in,
+        /// per-instrument watcher overrides merged, secrets redacted
+        #[arg(long)]
+        effective: bool,
+    },
 
     /// Show configuration file path
     Path,
@@ -141,6 +315,16 @@ pub enum FailedAction {
     /// List all failed files
     List,
 
+    /// Show what Skyline reported for a failed extraction
+    Show {
+        /// Path to the failed file (or "latest" for the most recent failure)
+        path: String,
+
+        /// Re-run the extraction now, printing Skyline's output
+        #[arg(long)]
+        rerun: bool,
+    },
+
     /// Retry processing a failed file
     Retry {
         /// Path to the file to retry (or "all" to retry all)
@@ -154,3 +338,105 @@ pub enum FailedAction {
         confirm: bool,
     },
 }
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryAction {
+    /// Show how a run was classified and what was done with it
+    Show {
+        /// Run ID or raw file name
+        run_id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MetricsAction {
+    /// Re-derive run metrics from a saved Skyline report with the current code
+    Recompute {
+        /// Path to the Skyline report CSV
+        report: String,
+
+        /// Read the report with this instrument's settings (e.g. rt_unit)
+        #[arg(long)]
+        instrument: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SpoolAction {
+    /// Remove payloads past max_age_days or completed_retention_count now
+    Gc {
+        /// Report what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TelemetryAction {
+    /// Print exactly what would be sent if telemetry is enabled
+    Preview,
+}
+
+/// Parse a duration such as "500ms", "30s", "5m", or "2h" (bare numbers are seconds).
+fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+
+    match unit {
+        "ms" => Ok(std::time::Duration::from_millis(number)),
+        "" | "s" => Ok(std::time::Duration::from_secs(number)),
+        "m" => Ok(std::time::Duration::from_secs(number * 60)),
+        "h" => Ok(std::time::Duration::from_secs(number * 60 * 60)),
+        _ => Err(format!(
+            "invalid duration unit '{}' (expected ms, s, m, or h)",
+            unit
+        )),
+    }
+}
+
+/// Profile names become part of a file name, so keep them simple.
+fn parse_profile(s: &str) -> Result<String, String> {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(s.to_string())
+    } else {
+        Err("profile names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(parse_profile("dev"), Ok("dev".to_string()));
+        assert_eq!(parse_profile("prod_eu-1"), Ok("prod_eu-1".to_string()));
+        assert!(parse_profile("").is_err());
+        assert!(parse_profile("../prod").is_err());
+    }
+}
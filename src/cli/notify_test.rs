@@ -0,0 +1,61 @@
+//! Notify-test command - send test toasts and report what Windows did with them.
+
+use anyhow::{bail, Result};
+
+use crate::config::{self, Config};
+use crate::notifications;
+
+/// Run the notify-test command.
+pub async fn run() -> Result<()> {
+    println!();
+    println!("Notification Test");
+    println!("=================");
+
+    let Some(results) = notifications::send_test_toasts() else {
+        println!("Toast notifications are not supported on this platform (Windows only).");
+        return Ok(());
+    };
+
+    #[cfg(windows)]
+    println!("AppUserModelID: {}", notifications::APP_USER_MODEL_ID);
+
+    match config::paths::start_menu_shortcut() {
+        Some(path) if path.exists() => println!("Start Menu shortcut: {}", path.display()),
+        Some(path) => println!(
+            "Start Menu shortcut: missing ({}); toasts may show as 'PowerShell' or not at all. \
+             Start the tray app once to create it.",
+            path.display()
+        ),
+        None => println!("Start Menu shortcut: unknown (APPDATA is not set)"),
+    }
+
+    match Config::load() {
+        Ok(config) if config.agent.enable_toast_notifications => {
+            println!("enable_toast_notifications: true")
+        }
+        Ok(_) => println!(
+            "enable_toast_notifications: false (the agent won't send these; set it to true in [agent])"
+        ),
+        Err(e) => println!("enable_toast_notifications: unknown (config not loaded: {})", e),
+    }
+
+    println!();
+    let mut failed = 0;
+    for (kind, result) in &results {
+        match result {
+            Ok(()) => println!("  [OK] {} toast accepted", kind),
+            Err(e) => {
+                failed += 1;
+                println!("  [XX] {} toast failed: {}", kind, e);
+            }
+        }
+    }
+    println!();
+
+    if failed > 0 {
+        bail!("{} of {} test toasts failed", failed, results.len());
+    }
+    println!("Windows accepted all test toasts. If none appeared, check Focus Assist and");
+    println!("Settings > System > Notifications for 'MD QC Agent'.");
+    Ok(())
+}
@@ -0,0 +1,374 @@
+//! Process command - extract a folder of runs in one Skyline call.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::baseline::BaselineManager;
+use crate::classifier::Classifier;
+use crate::config::{ComputedMetric, Config, InstrumentMode};
+use crate::consumables::{self, ConsumablesTracker};
+use crate::extractor::Extractor;
+use crate::failed_files::FailedFiles;
+use crate::history::{ClassificationAudit, ProcessingDecision, RunHistory};
+use crate::hooks::{self, HookContext};
+use crate::influx::InfluxExporter;
+use crate::previous_run::PreviousRunTracker;
+use crate::reinjection::ReinjectionTracker;
+use crate::replicates::ReplicateTracker;
+use crate::spool::Spool;
+use crate::types::ComparisonMetrics;
+use crate::watcher;
+
+/// Run the process command in batch mode.
+pub async fn run(batch: &str, instrument_id: Option<String>) -> Result<()> {
+    let folder = Path::new(batch);
+
+    if !folder.is_dir() {
+        anyhow::bail!("Batch path is not a directory: {}", folder.display());
+    }
+
+    let config = Config::load().context("Failed to load configuration")?;
+
+    // Use the named instrument, or the one whose watch_path contains the folder
+    let instrument = match instrument_id {
+        Some(ref id) => config.instruments.iter().find(|i| &i.id == id),
+        None => config
+            .instruments
+            .iter()
+            .find(|i| folder.starts_with(&i.watch_path)),
+    }
+    .cloned()
+    .ok_or_else(|| {
+        anyhow::anyhow!(
+            "No matching instrument for {} (use --instrument <id>)",
+            folder.display()
+        )
+    })?;
+
+    // Collect raw files for this vendor
+    let mut raw_files: Vec<PathBuf> = std::fs::read_dir(folder)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| watcher::is_valid_raw_file(p, instrument.vendor))
+        .collect();
+    raw_files.sort();
+
+    println!();
+    println!("Batch Processing");
+    println!("================");
+    println!("Folder: {}", folder.display());
+    println!("Instrument: {} ({})", instrument.id, instrument.vendor);
+    println!("Raw files: {}", raw_files.len());
+    println!();
+
+    // Classify each file; only QC runs are extracted
+    let classifier = Classifier::new().with_aliases(&config.classifier.control_aliases);
+    let history = RunHistory::new();
+    let mut qc_runs = Vec::new();
+
+    for path in &raw_files {
+        match classifier.classify(path, &instrument) {
+            Ok(c) if c.control_type.is_qc() => qc_runs.push((path.clone(), c)),
+            Ok(c) => {
+                println!("Skipping {} ({})", display_name(path), c.control_type);
+                history.record_audit(ClassificationAudit::new(
+                    path.clone(),
+                    instrument.id.clone(),
+                    Some(c),
+                    ProcessingDecision::SkippedNonQc,
+                ));
+            }
+            Err(e) => {
+                println!(
+                    "Skipping {} (classification failed: {})",
+                    display_name(path),
+                    e
+                );
+                history.record_audit(
+                    ClassificationAudit::new(
+                        path.clone(),
+                        instrument.id.clone(),
+                        None,
+                        ProcessingDecision::ClassificationFailed,
+                    )
+                    .with_detail(e.to_string()),
+                );
+            }
+        }
+    }
+
+    if qc_runs.is_empty() {
+        println!("No QC runs to process.");
+        println!();
+        return Ok(());
+    }
+
+    if instrument.mode == InstrumentMode::DetectOnly {
+        println!();
+        println!("Detect-only instrument, not extracting:");
+        for (path, classification) in qc_runs {
+            println!("  [--] {}: detected", display_name(&path));
+            history.record_audit(ClassificationAudit::new(
+                path,
+                instrument.id.clone(),
+                Some(classification),
+                ProcessingDecision::Detected,
+            ));
+        }
+        println!();
+        return Ok(());
+    }
+    let observe_only = instrument.mode == InstrumentMode::ObserveOnly;
+
+    println!();
+    println!("Extracting {} QC run(s) with Skyline...", qc_runs.len());
+
+    let extractor = Extractor::new(&config.skyline)?;
+    let qc_paths: Vec<PathBuf> = qc_runs.iter().map(|(p, _)| p.clone()).collect();
+    let results = extractor.extract_batch(&qc_paths, &instrument).await?;
+
+    let spool = Spool::new(&config.spool)?
+        .with_baselines(BaselineManager::load())
+        .with_config_hash(config.config_hash())
+        .with_projects(&config.instruments)
+        .with_payload_limit(
+            config.cloud.max_payload_targets,
+            config.cloud.oversized_payloads,
+        );
+    spool
+        .set_agent_id(crate::cli::run::resolve_agent_id(&config))
+        .await;
+    let failed_files = FailedFiles::new();
+    let replicates = ReplicateTracker::new(&config.replicates);
+    let previous_runs = PreviousRunTracker::new(
+        config.comparison.include_previous_run
+            && config.skyline.computes(ComputedMetric::Comparison),
+    );
+    let reinjections = ReinjectionTracker::new(&config.reinjection);
+    let consumable_tracker = ConsumablesTracker::new();
+    let influx = config.influx.as_ref().map(InfluxExporter::new);
+    let min_upload_confidence = instrument.min_upload_confidence(&config.cloud);
+
+    let mut succeeded = 0;
+    let mut failed = 0;
+    let mut skipped = 0;
+
+    for ((path, result), (_, classification)) in results.into_iter().zip(&mut qc_runs) {
+        match result {
+            Ok(result)
+                if instrument.acquired_before_cutoff(result.acquisition.acquisition_time) =>
+            {
+                let detail = format!(
+                    "acquired {} before process_acquired_after {}",
+                    result
+                        .acquisition
+                        .acquisition_time
+                        .unwrap_or_default()
+                        .to_rfc3339(),
+                    instrument
+                        .process_acquired_after
+                        .unwrap_or_default()
+                        .to_rfc3339()
+                );
+                println!("  [--] {}: skipped, {}", display_name(&path), detail);
+                history.record_audit(
+                    ClassificationAudit::new(
+                        path.clone(),
+                        instrument.id.clone(),
+                        Some(classification.clone()),
+                        ProcessingDecision::AcquiredBeforeCutoff,
+                    )
+                    .with_run_id(result.run_id)
+                    .with_detail(detail),
+                );
+                failed_files.mark_success(&path);
+                skipped += 1;
+            }
+            Ok(mut result) => {
+                result.consumables = consumables::resolve(&instrument.consumables, &path);
+                for change in consumable_tracker.record(&instrument.id, &result.consumables) {
+                    println!(
+                        "  [!!] {}: consumable changed ({})",
+                        display_name(&path),
+                        change
+                    );
+                }
+
+                let injected_at = result
+                    .acquisition
+                    .acquisition_time
+                    .unwrap_or_else(chrono::Utc::now);
+                classification.reinjection_of =
+                    reinjections.record(classification, result.run_id, injected_at);
+
+                // Observe-only runs stay on this machine
+                if let Some(influx) = influx.as_ref().filter(|_| !observe_only) {
+                    influx
+                        .export(&result, classification, chrono::Utc::now())
+                        .await;
+                }
+
+                let replicate_metrics = replicates.record(
+                    &instrument.id,
+                    classification.control_type,
+                    result.run_id,
+                    &result.target_metrics,
+                );
+                if let Some(ref metrics) = replicate_metrics {
+                    if replicates.exceeds_threshold(metrics) {
+                        println!(
+                            "  [!!] {}: median area CV {:.1}% over last {} runs exceeds threshold",
+                            display_name(&path),
+                            metrics.median_area_cv_pct.unwrap_or_default(),
+                            metrics.window_size
+                        );
+                    }
+                }
+
+                let comparison_metrics = previous_runs
+                    .record(
+                        &instrument.id,
+                        classification.control_type,
+                        result.run_id,
+                        &result.run_metrics,
+                    )
+                    .map(|vs_previous| ComparisonMetrics {
+                        vs_baseline: None,
+                        vs_previous: Some(vs_previous),
+                    });
+
+                // Observe-only instruments and uncertain classifications are
+                // kept locally, not uploaded
+                let below_upload_confidence =
+                    !classification.confidence.meets(min_upload_confidence);
+                let spooled = if observe_only || below_upload_confidence {
+                    spool
+                        .observe(
+                            &result,
+                            classification,
+                            instrument.vendor,
+                            replicate_metrics,
+                            comparison_metrics,
+                            None,
+                        )
+                        .await
+                        .map(|_| ())
+                } else {
+                    spool
+                        .enqueue(
+                            &result,
+                            classification,
+                            instrument.vendor,
+                            replicate_metrics,
+                            comparison_metrics,
+                            None,
+                        )
+                        .await
+                        .map(|_| ())
+                };
+                if let Err(e) = spooled {
+                    println!("  [XX] {}: failed to spool: {}", display_name(&path), e);
+                    history.record_audit(
+                        ClassificationAudit::new(
+                            path.clone(),
+                            instrument.id.clone(),
+                            Some(classification.clone()),
+                            ProcessingDecision::SpoolFailed,
+                        )
+                        .with_run_id(result.run_id)
+                        .with_detail(e.to_string()),
+                    );
+                    failed_files.record_failure(
+                        path.clone(),
+                        instrument.id.clone(),
+                        format!("Failed to spool result: {}", e),
+                    );
+                    failed += 1;
+                    let context = HookContext::failure(
+                        &path,
+                        &instrument.id,
+                        Some(classification),
+                        &format!("Failed to spool result: {}", e),
+                    );
+                    hooks::run_for(&instrument, &context).await;
+                } else {
+                    println!(
+                        "  [OK] {}: {}/{} targets",
+                        display_name(&path),
+                        result.run_metrics.targets_found,
+                        result.run_metrics.targets_expected
+                    );
+                    let mut audit = ClassificationAudit::new(
+                        path.clone(),
+                        instrument.id.clone(),
+                        Some(classification.clone()),
+                        ProcessingDecision::Processed,
+                    )
+                    .with_run_id(result.run_id)
+                    .with_metrics(result.run_metrics.clone());
+                    if observe_only {
+                        println!("       kept locally, not uploaded: observe-only instrument");
+                        audit.decision = ProcessingDecision::Observed;
+                    } else if below_upload_confidence {
+                        let detail = format!(
+                            "confidence {} is below min_upload_confidence {}",
+                            classification.confidence, min_upload_confidence
+                        );
+                        println!("       kept locally, not uploaded: {}", detail);
+                        audit.decision = ProcessingDecision::BelowUploadConfidence;
+                        audit = audit.with_detail(detail);
+                    }
+                    history.record_audit(audit);
+                    failed_files.mark_success(&path);
+                    succeeded += 1;
+                    let context = HookContext::success(&path, classification, &result);
+                    hooks::run_for(&instrument, &context).await;
+                }
+            }
+            Err(e) => {
+                println!("  [XX] {}: {}", display_name(&path), e);
+                history.record_audit(
+                    ClassificationAudit::new(
+                        path.clone(),
+                        instrument.id.clone(),
+                        Some(classification.clone()),
+                        ProcessingDecision::ExtractionFailed,
+                    )
+                    .with_detail(e.to_string()),
+                );
+                failed_files.record_failure(
+                    path.clone(),
+                    instrument.id.clone(),
+                    format!("Skyline batch extraction failed: {}", e),
+                );
+                failed += 1;
+                let context = HookContext::failure(
+                    &path,
+                    &instrument.id,
+                    Some(classification),
+                    &format!("Skyline batch extraction failed: {}", e),
+                );
+                hooks::run_for(&instrument, &context).await;
+            }
+        }
+    }
+
+    println!();
+    if skipped > 0 {
+        println!(
+            "Done: {} spooled, {} failed, {} skipped (acquired before process_acquired_after)",
+            succeeded, failed, skipped
+        );
+    } else {
+        println!("Done: {} spooled, {} failed", succeeded, failed);
+    }
+    println!();
+
+    Ok(())
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
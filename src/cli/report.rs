@@ -0,0 +1,546 @@
+//! Report command - summarize QC runs over a date range.
+//!
+//! Built from the local audit log, so it works without cloud access. Metrics
+//! are only known for runs extracted since audit records started carrying
+//! them; older runs are counted but have no recovery or mass error.
+
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::cli::ReportFormat;
+use crate::config::{ComparisonConfig, Config};
+use crate::history::{self, ClassificationAudit, ProcessingDecision};
+use crate::replicates::median;
+
+/// Days covered when `--from` is not given.
+const DEFAULT_DAYS: i64 = 7;
+
+/// Summary of QC runs over a date range.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub instrument: Option<String>,
+    pub min_target_recovery_pct: f64,
+    /// One entry per instrument and control type
+    pub groups: Vec<GroupSummary>,
+    /// Runs that failed classification, extraction or spooling, oldest first
+    pub failures: Vec<FailureRow>,
+}
+
+/// Runs of one control type on one instrument.
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    pub instrument_id: String,
+    pub control_type: String,
+    /// QC runs detected
+    pub runs: usize,
+    /// Runs extracted with metrics
+    pub extracted: usize,
+    /// Extracted runs meeting `min_target_recovery_pct`
+    pub passed: usize,
+    pub pass_rate_pct: Option<f64>,
+    pub median_recovery_pct: Option<f64>,
+    pub median_mass_error_ppm: Option<f64>,
+    /// Daily medians, oldest first
+    pub trend: Vec<DailyPoint>,
+}
+
+/// Medians of one day's extracted runs.
+#[derive(Debug, Serialize)]
+pub struct DailyPoint {
+    pub date: NaiveDate,
+    pub runs: usize,
+    pub median_recovery_pct: Option<f64>,
+    pub median_mass_error_ppm: Option<f64>,
+}
+
+/// A run that failed.
+#[derive(Debug, Serialize)]
+pub struct FailureRow {
+    pub decided_at: DateTime<Utc>,
+    pub instrument_id: String,
+    pub file_name: String,
+    pub decision: ProcessingDecision,
+    pub detail: Option<String>,
+}
+
+/// Run the report command.
+pub async fn run(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    instrument: Option<String>,
+    format: ReportFormat,
+) -> Result<()> {
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or(to - chrono::Duration::days(DEFAULT_DAYS - 1));
+    if from > to {
+        anyhow::bail!("--from ({}) is after --to ({})", from, to);
+    }
+
+    // Acceptance thresholds only; fall back to defaults without a config
+    let comparison = Config::load().map(|c| c.comparison).unwrap_or_default();
+    let audits = history::read_audits(&history::audit_log_path())?;
+    let report = build_report(
+        &audits,
+        from,
+        to,
+        instrument.as_deref(),
+        &comparison,
+        |at| at.with_timezone(&Local).date_naive(),
+    );
+
+    match format {
+        ReportFormat::Text => print_text(&report),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        ReportFormat::Csv => write_csv(&report, std::io::stdout())?,
+    }
+
+    Ok(())
+}
+
+/// Summarize the audit records decided between `from` and `to` (inclusive,
+/// as dates given by `date_of`). A file processed more than once counts once,
+/// by its latest record.
+pub fn build_report(
+    audits: &[ClassificationAudit],
+    from: NaiveDate,
+    to: NaiveDate,
+    instrument: Option<&str>,
+    comparison: &ComparisonConfig,
+    date_of: impl Fn(DateTime<Utc>) -> NaiveDate,
+) -> Report {
+    let mut latest: HashMap<&PathBuf, &ClassificationAudit> = HashMap::new();
+    for audit in audits {
+        let date = date_of(audit.decided_at);
+        if date < from || date > to || instrument.is_some_and(|id| audit.instrument_id != id) {
+            continue;
+        }
+        latest.insert(&audit.path, audit);
+    }
+    let mut audits: Vec<_> = latest.into_values().collect();
+    audits.sort_by_key(|a| a.decided_at);
+
+    let mut groups: BTreeMap<(String, String), Vec<&ClassificationAudit>> = BTreeMap::new();
+    let mut failures = Vec::new();
+
+    for audit in audits {
+        match audit.decision {
+            ProcessingDecision::SkippedNonQc
+            | ProcessingDecision::AcquiredBeforeCutoff
+            | ProcessingDecision::CarryoverChecked
+            | ProcessingDecision::SkippedDuplicate => continue,
+            ProcessingDecision::ClassificationFailed
+            | ProcessingDecision::ExtractionFailed
+            | ProcessingDecision::SpoolFailed => failures.push(FailureRow {
+                decided_at: audit.decided_at,
+                instrument_id: audit.instrument_id.clone(),
+                file_name: audit.file_name(),
+                decision: audit.decision,
+                detail: audit.detail.clone(),
+            }),
+            ProcessingDecision::Processed
+            | ProcessingDecision::Observed
+            | ProcessingDecision::BelowUploadConfidence
+            | ProcessingDecision::Detected => {}
+        }
+
+        if let Some(ref classification) = audit.classification {
+            groups
+                .entry((
+                    audit.instrument_id.clone(),
+                    classification.control_type.to_string(),
+                ))
+                .or_default()
+                .push(audit);
+        }
+    }
+
+    let groups = groups
+        .into_iter()
+        .map(|((instrument_id, control_type), runs)| {
+            summarize(instrument_id, control_type, &runs, comparison, &date_of)
+        })
+        .collect();
+
+    Report {
+        from,
+        to,
+        instrument: instrument.map(str::to_string),
+        min_target_recovery_pct: comparison.min_target_recovery_pct,
+        groups,
+        failures,
+    }
+}
+
+fn summarize(
+    instrument_id: String,
+    control_type: String,
+    runs: &[&ClassificationAudit],
+    comparison: &ComparisonConfig,
+    date_of: &impl Fn(DateTime<Utc>) -> NaiveDate,
+) -> GroupSummary {
+    let extracted: Vec<_> = runs
+        .iter()
+        .filter_map(|a| Some((date_of(a.decided_at), a.run_metrics.as_ref()?)))
+        .collect();
+    let passed = extracted
+        .iter()
+        .filter(|(_, m)| m.target_recovery_pct >= comparison.min_target_recovery_pct)
+        .count();
+
+    let mut days: BTreeMap<NaiveDate, Vec<_>> = BTreeMap::new();
+    for (date, metrics) in &extracted {
+        days.entry(*date).or_default().push(*metrics);
+    }
+    let trend = days
+        .into_iter()
+        .map(|(date, metrics)| DailyPoint {
+            date,
+            runs: metrics.len(),
+            median_recovery_pct: median(
+                &mut metrics
+                    .iter()
+                    .map(|m| m.target_recovery_pct)
+                    .collect::<Vec<_>>(),
+            ),
+            median_mass_error_ppm: median(
+                &mut metrics
+                    .iter()
+                    .filter_map(|m| m.median_mass_error_ppm)
+                    .collect::<Vec<_>>(),
+            ),
+        })
+        .collect();
+
+    GroupSummary {
+        instrument_id,
+        control_type,
+        runs: runs.len(),
+        extracted: extracted.len(),
+        passed,
+        pass_rate_pct: (!extracted.is_empty())
+            .then(|| passed as f64 / extracted.len() as f64 * 100.0),
+        median_recovery_pct: median(
+            &mut extracted
+                .iter()
+                .map(|(_, m)| m.target_recovery_pct)
+                .collect::<Vec<_>>(),
+        ),
+        median_mass_error_ppm: median(
+            &mut extracted
+                .iter()
+                .filter_map(|(_, m)| m.median_mass_error_ppm)
+                .collect::<Vec<_>>(),
+        ),
+        trend,
+    }
+}
+
+fn print_text(report: &Report) {
+    println!();
+    let title = format!("QC Summary {} to {}", report.from, report.to);
+    println!("{}", title);
+    println!("{}", "=".repeat(title.len()));
+    if let Some(ref instrument) = report.instrument {
+        println!("Instrument: {}", instrument);
+    }
+    println!(
+        "Pass: target recovery >= {:.0}%",
+        report.min_target_recovery_pct
+    );
+    println!();
+
+    if report.groups.is_empty() {
+        println!("(no QC runs in this range)");
+    }
+    for group in &report.groups {
+        println!("{} {}", group.instrument_id, group.control_type);
+        println!(
+            "  Runs: {} ({} extracted, {} passed{})",
+            group.runs,
+            group.extracted,
+            group.passed,
+            fmt_opt(group.pass_rate_pct, ", ", "% pass rate", 0)
+        );
+        println!(
+            "  Median recovery: {}   Median mass error: {}",
+            fmt_opt(group.median_recovery_pct, "", "%", 1),
+            fmt_opt(group.median_mass_error_ppm, "", " ppm", 2)
+        );
+        for point in &group.trend {
+            println!(
+                "    {}  {:>3} run(s)  recovery {:>7}  mass error {:>10}",
+                point.date,
+                point.runs,
+                fmt_opt(point.median_recovery_pct, "", "%", 1),
+                fmt_opt(point.median_mass_error_ppm, "", " ppm", 2)
+            );
+        }
+        println!();
+    }
+
+    println!("Failures ({})", report.failures.len());
+    println!("------------");
+    for failure in &report.failures {
+        println!(
+            "  {}  {}  {}  {:?}{}",
+            failure.decided_at.format("%Y-%m-%d %H:%M"),
+            failure.instrument_id,
+            failure.file_name,
+            failure.decision,
+            failure
+                .detail
+                .as_deref()
+                .map(|d| format!(": {}", d))
+                .unwrap_or_default()
+        );
+    }
+    println!();
+}
+
+/// One row per instrument and control type.
+fn write_csv(report: &Report, out: impl std::io::Write) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record([
+        "from",
+        "to",
+        "instrument_id",
+        "control_type",
+        "runs",
+        "extracted",
+        "passed",
+        "pass_rate_pct",
+        "median_recovery_pct",
+        "median_mass_error_ppm",
+    ])?;
+
+    let opt = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_default();
+    for group in &report.groups {
+        writer.write_record([
+            report.from.to_string(),
+            report.to.to_string(),
+            group.instrument_id.clone(),
+            group.control_type.clone(),
+            group.runs.to_string(),
+            group.extracted.to_string(),
+            group.passed.to_string(),
+            opt(group.pass_rate_pct),
+            opt(group.median_recovery_pct),
+            opt(group.median_mass_error_ppm),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn fmt_opt(value: Option<f64>, prefix: &str, suffix: &str, decimals: usize) -> String {
+    match value {
+        Some(v) => format!("{}{:.*}{}", prefix, decimals, v, suffix),
+        None if prefix.is_empty() => "-".to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        ClassificationConfidence, ClassificationSource, ControlType, RunClassification, RunMetrics,
+    };
+    use chrono::TimeZone;
+
+    fn audit(
+        name: &str,
+        instrument: &str,
+        control_type: Option<ControlType>,
+        decision: ProcessingDecision,
+        day: u32,
+        metrics: Option<(f64, f64)>,
+    ) -> ClassificationAudit {
+        let classification = control_type.map(|control_type| RunClassification {
+            control_type,
+            well_position: None,
+            instrument_id: instrument.to_string(),
+            plate_id: None,
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Filename,
+            matched_pattern: None,
+            injection_index: None,
+            reinjection_of: None,
+        });
+        let mut audit = ClassificationAudit::new(
+            PathBuf::from(format!("/data/{}", name)),
+            instrument.to_string(),
+            classification,
+            decision,
+        );
+        audit.decided_at = Utc.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap();
+        if let Some((recovery, mass_error)) = metrics {
+            audit = audit.with_metrics(RunMetrics {
+                targets_found: 0,
+                targets_expected: 0,
+                target_recovery_pct: recovery,
+                median_rt_shift: None,
+                median_mass_error_ppm: Some(mass_error),
+                chromatography_score: None,
+                missing_targets: Vec::new(),
+                undetected_targets: Vec::new(),
+                total_ion_current: None,
+                base_peak_intensity: None,
+                health_index: None,
+            });
+        }
+        audit
+    }
+
+    #[test]
+    fn test_report_over_history() {
+        use ProcessingDecision::*;
+
+        let audits = vec![
+            // Before the range
+            audit(
+                "old.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcA),
+                Processed,
+                1,
+                Some((10.0, 9.0)),
+            ),
+            audit(
+                "a1.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcA),
+                Processed,
+                12,
+                Some((95.0, 1.0)),
+            ),
+            audit(
+                "a2.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcA),
+                Processed,
+                12,
+                Some((70.0, 3.0)),
+            ),
+            // Reprocessed later; only the latest record counts
+            audit(
+                "a3.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcA),
+                Processed,
+                13,
+                Some((50.0, 5.0)),
+            ),
+            audit(
+                "a3.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcA),
+                Processed,
+                14,
+                Some((90.0, 2.0)),
+            ),
+            audit(
+                "b1.raw",
+                "TIMSTOF01",
+                Some(ControlType::QcB),
+                ExtractionFailed,
+                13,
+                None,
+            ),
+            audit(
+                "s1.raw",
+                "TIMSTOF01",
+                Some(ControlType::Sample),
+                SkippedNonQc,
+                13,
+                None,
+            ),
+            audit(
+                "bad.raw",
+                "EXPLORIS01",
+                None,
+                ClassificationFailed,
+                14,
+                None,
+            ),
+            audit(
+                "e1.raw",
+                "EXPLORIS01",
+                Some(ControlType::QcA),
+                Processed,
+                14,
+                Some((85.0, -1.0)),
+            ),
+        ];
+        let comparison = ComparisonConfig::default();
+        let from = NaiveDate::from_ymd_opt(2026, 10, 10).unwrap();
+        let to = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+
+        let report = build_report(&audits, from, to, None, &comparison, |at| at.date_naive());
+        let keys: Vec<_> = report
+            .groups
+            .iter()
+            .map(|g| (g.instrument_id.as_str(), g.control_type.as_str()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("EXPLORIS01", "QC_A"),
+                ("TIMSTOF01", "QC_A"),
+                ("TIMSTOF01", "QC_B")
+            ]
+        );
+
+        let qc_a = &report.groups[1];
+        assert_eq!((qc_a.runs, qc_a.extracted, qc_a.passed), (3, 3, 2));
+        assert!((qc_a.pass_rate_pct.unwrap() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(qc_a.median_recovery_pct, Some(90.0));
+        assert_eq!(qc_a.median_mass_error_ppm, Some(2.0));
+        let trend: Vec<_> = qc_a
+            .trend
+            .iter()
+            .map(|p| (p.date.to_string(), p.runs, p.median_recovery_pct))
+            .collect();
+        assert_eq!(
+            trend,
+            [
+                ("2026-10-12".to_string(), 2, Some(82.5)),
+                ("2026-10-14".to_string(), 1, Some(90.0)),
+            ]
+        );
+
+        // A failed run counts towards its group but has no metrics
+        let qc_b = &report.groups[2];
+        assert_eq!((qc_b.runs, qc_b.extracted), (1, 0));
+        assert_eq!(qc_b.pass_rate_pct, None);
+
+        let failed: Vec<_> = report
+            .failures
+            .iter()
+            .map(|f| f.file_name.as_str())
+            .collect();
+        assert_eq!(failed, ["b1.raw", "bad.raw"]);
+
+        // Filtered to one instrument
+        let report = build_report(&audits, from, to, Some("EXPLORIS01"), &comparison, |at| {
+            at.date_naive()
+        });
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.failures.len(), 1);
+
+        let mut csv = Vec::new();
+        write_csv(&report, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "from,to,instrument_id,control_type,runs,extracted,passed,pass_rate_pct,\
+             median_recovery_pct,median_mass_error_ppm\n\
+             2026-10-10,2026-10-16,EXPLORIS01,QC_A,1,1,1,100.00,85.00,-1.00\n"
+        );
+    }
+}
@@ -1,26 +1,57 @@
 //! Run command - main agent execution loop.
 
 use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
 use tokio::signal;
 use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
+use crate::agent_state::{AgentState, PauseReason};
+use crate::baseline::{compare_to_baseline, BaselineManager};
+use crate::carryover::CarryoverTracker;
 use crate::classifier::Classifier;
-use crate::config::Config;
-use crate::extractor::Extractor;
-use crate::failed_files::FailedFiles;
+use crate::config::{
+    paths, ComputedMetric, Config, DeliveryMode, InstrumentConfig, InstrumentMode,
+};
+use crate::consumables::{self, ConsumablesTracker};
+use crate::empty_import::EmptyImportTracker;
+use crate::error::SpoolError;
+use crate::extractor::probe::ColumnProbeCache;
+use crate::extractor::{skyline, Extractor};
+use crate::failed_files::{ExtractionDiagnostics, FailedFiles};
+use crate::heartbeat;
+use crate::history::{ClassificationAudit, ProcessingDecision, RunHistory};
+use crate::hooks::{self, HookContext};
+use crate::influx::InfluxExporter;
+use crate::instance_lock::InstanceLock;
+use crate::notifications::{Notification, NotificationQueue};
+use crate::previous_run::PreviousRunTracker;
+use crate::ready_queue::ReadyQueue;
+use crate::reinjection::ReinjectionTracker;
+use crate::replicates::ReplicateTracker;
+use crate::schedule::DeferredFiles;
 use crate::spool::Spool;
-use crate::types::TrackedFile;
-use crate::uploader::Uploader;
-use crate::watcher::Watcher;
+use crate::telemetry::Telemetry;
+use crate::types::{ComparisonMetrics, FinalizationState, RunClassification, TrackedFile};
+use crate::uploader::{UploadRuntime, Uploader};
+use crate::watcher::watchdog::WatchdogState;
+use crate::watcher::{self, Watcher};
 
 /// Run the agent in foreground mode.
-pub async fn run_foreground() -> Result<()> {
+///
+/// With `exit_after`, shuts down through the same path as Ctrl-C once the
+/// duration elapses (for test harnesses and short-lived probes).
+pub async fn run_foreground(exit_after: Option<std::time::Duration>) -> Result<()> {
     info!("Running agent in foreground mode");
 
     // Load configuration
     let config = Config::load()?;
-    info!(config_path = ?config.path, "Configuration loaded");
+    info!(
+        config_path = ?config.path,
+        profile = paths::profile().unwrap_or("default"),
+        "Configuration loaded"
+    );
 
     // Create shutdown channel
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
@@ -28,15 +59,49 @@ pub async fn run_foreground() -> Result<()> {
     // Spawn shutdown signal handler
     let shutdown_tx_clone = shutdown_tx.clone();
     tokio::spawn(async move {
-        if let Err(e) = signal::ctrl_c().await {
-            error!("Failed to listen for ctrl-c: {}", e);
-        }
+        wait_for_shutdown_signal().await;
         info!("Received shutdown signal");
         let _ = shutdown_tx_clone.send(()).await;
     });
 
+    if let Some(duration) = exit_after {
+        let shutdown_tx = shutdown_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            info!(after = ?duration, "Exit timer elapsed");
+            let _ = shutdown_tx.send(()).await;
+        });
+    }
+
     // Run the main agent loop
-    run_agent(config, &mut shutdown_rx).await
+    run_agent(config, &mut shutdown_rx, AgentState::new()).await
+}
+
+/// Wait for Ctrl-C, or SIGTERM on Unix (e.g. `systemctl stop`).
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal as unix_signal, SignalKind};
+
+        match unix_signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    result = signal::ctrl_c() => {
+                        if let Err(e) = result {
+                            error!("Failed to listen for ctrl-c: {}", e);
+                        }
+                    }
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!(error = %e, "Failed to listen for SIGTERM"),
+        }
+    }
+
+    if let Err(e) = signal::ctrl_c().await {
+        error!("Failed to listen for ctrl-c: {}", e);
+    }
 }
 
 /// Generate a hardware-based agent ID.
@@ -57,6 +122,16 @@ fn generate_agent_id() -> String {
         }
     }
 
+    #[cfg(not(windows))]
+    {
+        // On Linux, use the systemd/dbus machine ID
+        let machine_id = read_machine_id(Path::new("/etc/machine-id"))
+            .or_else(|| read_machine_id(Path::new("/var/lib/dbus/machine-id")));
+        if let Some(id) = machine_id {
+            return format!("mdqc-{}", &id[..8]);
+        }
+    }
+
     // Fallback: use hostname + random suffix
     let hostname = hostname::get()
         .map(|h| h.to_string_lossy().to_string())
@@ -66,55 +141,599 @@ fn generate_agent_id() -> String {
     format!("mdqc-{}-{:08x}", hostname, random)
 }
 
-/// Resolve the agent ID from config or generate one.
-fn resolve_agent_id(config: &Config) -> String {
+/// Read the persisted agent ID, or generate and persist a new one.
+///
+/// The file is created with `create_new`, so if two processes (e.g. the
+/// service and the tray) race on first start, the loser adopts the winner's ID.
+fn load_or_persist_agent_id(path: &Path, generate: impl FnOnce() -> String) -> String {
+    if let Some(id) = read_agent_id(path) {
+        return id;
+    }
+
+    let id = generate();
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(mut file) => {
+            if let Err(e) = file.write_all(id.as_bytes()) {
+                warn!(error = %e, path = %path.display(), "Failed to persist agent ID");
+            }
+            id
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            read_agent_id(path).unwrap_or(id)
+        }
+        Err(e) => {
+            warn!(error = %e, path = %path.display(), "Failed to persist agent ID");
+            id
+        }
+    }
+}
+
+/// Read a machine ID file, accepting only a hex ID of at least 8 characters.
+#[cfg(not(windows))]
+fn read_machine_id(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| s.len() >= 8 && s.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn read_agent_id(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// Resolve the agent ID from config, the persisted ID, or generate one.
+pub(crate) fn resolve_agent_id(config: &Config) -> String {
     if config.agent.agent_id == "auto" {
-        generate_agent_id()
+        load_or_persist_agent_id(&paths::agent_id_file(), generate_agent_id)
     } else {
         config.agent.agent_id.clone()
     }
 }
 
+/// Detect template changes since the last run and requeue recent runs.
+///
+/// Last-seen hashes are tracked for every instrument; runs are only requeued
+/// for instruments with `reprocess_on_template_change` enabled.
+async fn requeue_on_template_change(
+    config: &Config,
+    extractor: &Extractor,
+    history: &RunHistory,
+    file_tx: &mpsc::Sender<TrackedFile>,
+) {
+    for instrument in &config.instruments {
+        let template_hash = match extractor.template_hash(instrument) {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(instrument = %instrument.id, error = %e, "Could not hash template");
+                continue;
+            }
+        };
+
+        let count = if instrument.reprocess_on_template_change {
+            instrument.reprocess_count
+        } else {
+            0
+        };
+
+        let Some((change, runs)) = history.check_template(&instrument.id, &template_hash, count)
+        else {
+            continue;
+        };
+
+        info!(
+            instrument = %instrument.id,
+            old_hash = %change.old_hash,
+            new_hash = %change.new_hash,
+            requeued = runs.len(),
+            "Template changed since last run"
+        );
+
+        for path in runs {
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                warn!(path = ?path, "Run no longer exists, skipping reprocess");
+                continue;
+            };
+
+            let mut file = TrackedFile::new(
+                path.clone(),
+                instrument.vendor,
+                metadata.len(),
+                metadata.modified().ok().map(Into::into),
+            );
+            file.state = FinalizationState::Ready;
+            file.reprocess = true;
+
+            info!(path = ?path, "Requeuing run for reprocessing with new template");
+            if file_tx.send(file).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Fail, or complain loudly, when there are no instruments to watch.
+///
+/// An agent with no instruments otherwise starts cleanly and does nothing,
+/// which is easy to miss.
+fn check_instruments(config: &Config) -> Result<()> {
+    if !config.instruments.is_empty() {
+        return Ok(());
+    }
+
+    if config.agent.fail_on_no_instruments {
+        anyhow::bail!(
+            "No instruments configured in {}; add an [[instruments]] section",
+            config.path.display()
+        );
+    }
+
+    error!(
+        config_path = ?config.path,
+        "No instruments configured; the agent will idle until an [[instruments]] section is added"
+    );
+    if config.agent.enable_toast_notifications {
+        crate::notifications::notify_no_instruments();
+    }
+    Ok(())
+}
+
+/// Save an auto-discovered SkylineCmd path into the config file when
+/// `skyline.persist_discovered_path` is set, so later starts skip discovery.
+fn persist_discovered_skyline(config: &Config, discovered: Option<&Path>) {
+    if !config.skyline.persist_discovered_path || config.skyline.configured_path().is_some() {
+        return;
+    }
+    let Some(discovered) = discovered else {
+        return;
+    };
+
+    let mut updated = config.clone();
+    updated.skyline.path = Some(discovered.display().to_string());
+    match updated.save() {
+        Ok(()) => info!(
+            path = %discovered.display(),
+            config_path = %config.path.display(),
+            "Saved discovered Skyline path to config"
+        ),
+        Err(e) => warn!(error = %e, "Failed to save discovered Skyline path"),
+    }
+}
+
+/// Give a SAMPLE run the instrument's carryover check if it qualifies,
+/// logging and auditing the result; nothing is spooled. Returns false when
+/// the run isn't checked and should be skipped as usual.
+async fn check_sample_carryover(
+    extractor: &Extractor,
+    carryover: &CarryoverTracker,
+    history: &RunHistory,
+    instrument: &InstrumentConfig,
+    file_path: &Path,
+    classification: &RunClassification,
+) -> bool {
+    let Some(check) = instrument.sample_carryover_check.as_ref() else {
+        return false;
+    };
+    let Some(reason) = carryover.qualifies(&instrument.id, check) else {
+        return false;
+    };
+    let targets = carryover.target_list(instrument);
+    if targets.is_empty() {
+        info!(
+            path = ?file_path,
+            instrument = %instrument.id,
+            "No QC targets to check for carryover yet (set expected_targets or wait for a QC run)"
+        );
+        return false;
+    }
+
+    info!(
+        path = ?file_path,
+        reason = %reason,
+        targets = targets.len(),
+        "Checking SAMPLE run for QC target carryover"
+    );
+    let audit = ClassificationAudit::new(
+        file_path.to_path_buf(),
+        instrument.id.clone(),
+        Some(classification.clone()),
+        ProcessingDecision::CarryoverChecked,
+    );
+    let audit = match extractor
+        .extract_targets(file_path, instrument, classification, &targets)
+        .await
+    {
+        Ok(result) => {
+            let report = carryover.measure(&instrument.id, &targets, &result.target_metrics);
+            if report.exceeds(check) {
+                warn!(
+                    path = ?file_path,
+                    carryover_pct = ?report.carryover_pct,
+                    max_carryover_pct = check.max_carryover_pct,
+                    detected = ?report.detected,
+                    "QC target carryover in SAMPLE run exceeds max_carryover_pct"
+                );
+            } else {
+                info!(
+                    path = ?file_path,
+                    carryover_pct = ?report.carryover_pct,
+                    detected = report.detected.len(),
+                    "Carryover check complete"
+                );
+            }
+            audit
+                .with_run_id(result.run_id)
+                .with_metrics(result.run_metrics)
+                .with_detail(report.to_string())
+        }
+        Err(e) => {
+            warn!(path = ?file_path, error = %e, "Carryover check extraction failed");
+            audit.with_detail(format!("Extraction failed: {}", e))
+        }
+    };
+    history.record_audit(audit);
+    true
+}
+
+/// Rescore a run's health index against the instrument's cached active
+/// baseline (see `mdqc baseline`), when the index is enabled and there is
+/// one. The extractor scores it without baseline tolerance.
+async fn score_health_against_baseline(
+    config: &Config,
+    baselines: &BaselineManager,
+    instrument_id: &str,
+    result: &mut crate::types::ExtractionResult,
+) {
+    let Some(weights) = &config.skyline.health_index_weights else {
+        return;
+    };
+    let Some(baseline) = baselines.get_active(instrument_id).await else {
+        return;
+    };
+
+    let comparison = compare_to_baseline(
+        &result.run_metrics,
+        &result.target_metrics,
+        &baseline,
+        &config.comparison,
+    );
+    result.run_metrics.health_index = crate::metrics::health_index(
+        &result.run_metrics,
+        Some(comparison.within_tolerance),
+        weights,
+    );
+}
+
+/// Refresh every instrument's cached baseline from the cloud at startup.
+///
+/// An instrument whose fetch fails keeps its cached copy, flagged stale.
+async fn refresh_baselines(
+    instruments: Vec<InstrumentConfig>,
+    baselines: BaselineManager,
+    uploader: Uploader,
+) {
+    for instrument in &instruments {
+        let refreshed = baselines
+            .refresh_from_cloud(&uploader, &instrument.id, instrument.project_id.as_deref())
+            .await;
+        match refreshed {
+            Ok(_) => match baselines.get_cached(&instrument.id).await {
+                Some(cached) => info!(
+                    instrument = %instrument.id,
+                    baseline_id = %cached.baseline.baseline_id,
+                    "Refreshed active baseline"
+                ),
+                None => debug!(instrument = %instrument.id, "No active baseline on the cloud"),
+            },
+            Err(e) => warn!(instrument = %instrument.id, error = %e, "Failed to refresh baselines"),
+        }
+    }
+}
+
+/// Requeue runs left in the ready queue when the agent last stopped.
+///
+/// Runs processed since they were queued, or deleted since, are dropped.
+/// Restored runs are tracked by their watcher so a scan doesn't queue them
+/// again.
+async fn restore_ready_queue(
+    config: &Config,
+    ready_queue: &ReadyQueue,
+    history: &RunHistory,
+    watchers: &[Watcher],
+    file_tx: &mpsc::Sender<TrackedFile>,
+) {
+    let mut restored = 0;
+
+    for queued in ready_queue.pending() {
+        if history.processed_since(&queued.path, queued.queued_at) {
+            ready_queue.remove(&queued.path);
+            continue;
+        }
+
+        let Some(file) = queued.to_tracked() else {
+            warn!(path = ?queued.path, "Queued run no longer exists, dropping");
+            ready_queue.remove(&queued.path);
+            continue;
+        };
+
+        let watcher = config
+            .instruments
+            .iter()
+            .find(|i| queued.path.starts_with(&i.watch_path))
+            .and_then(|i| watchers.iter().find(|w| w.instrument_id() == i.id));
+        if let Some(watcher) = watcher {
+            watcher.track_restored(&file);
+        }
+
+        if file_tx.send(file).await.is_err() {
+            return;
+        }
+        restored += 1;
+    }
+
+    if restored > 0 {
+        info!(count = restored, "Restored queued runs from before restart");
+    }
+}
+
+/// Check that each instrument's template report maps the core metric columns.
+///
+/// Probes are cached by template hash, so Skyline only runs for new or changed
+/// templates. Gaps are logged here and shown by `mdqc doctor`.
+async fn probe_template_columns(config: Config) {
+    let extractor = match Extractor::new(&config.skyline) {
+        Ok(extractor) => extractor,
+        Err(e) => {
+            warn!(error = %e, "Could not start template column probe");
+            return;
+        }
+    };
+    let mut cache = ColumnProbeCache::load();
+
+    for instrument in &config.instruments {
+        // Unhashable templates were already reported at startup
+        let Ok(template_hash) = extractor.template_hash(instrument) else {
+            continue;
+        };
+
+        let probe = match cache.get(&instrument.id, &template_hash) {
+            Some(probe) => probe.clone(),
+            None => match extractor.probe_columns(instrument).await {
+                Ok(probe) => {
+                    cache
+                        .instruments
+                        .insert(instrument.id.clone(), probe.clone());
+                    if let Err(e) = cache.save() {
+                        warn!(error = %e, "Failed to save template column probe");
+                    }
+                    probe
+                }
+                Err(e) => {
+                    warn!(instrument = %instrument.id, error = %e, "Could not probe template report columns");
+                    continue;
+                }
+            },
+        };
+
+        if !probe.missing.is_empty() {
+            warn!(
+                instrument = %instrument.id,
+                template = %instrument.template,
+                missing = ?probe.missing,
+                "Template report is missing core columns; runs will have empty metrics"
+            );
+        }
+    }
+}
+
+/// Prune run history and the audit log to `retention_days`, at startup and
+/// then daily. Audit records for payloads still in the spool are kept.
+async fn prune_history(history: RunHistory, spool: Spool, retention_days: u32) {
+    if retention_days == 0 {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(24 * 60 * 60));
+    loop {
+        interval.tick().await;
+
+        let history = history.clone();
+        let spool = spool.clone();
+        let pruned = tokio::task::spawn_blocking(move || {
+            history.prune(retention_days, &spool.unsent_run_ids())
+        })
+        .await;
+
+        match pruned {
+            Ok(Ok(report)) if report.total() > 0 => info!(
+                runs = report.runs,
+                events = report.events,
+                audit_records = report.audit_records,
+                retention_days,
+                "Pruned old history"
+            ),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!(error = %e, "Failed to prune history"),
+            Err(e) => warn!(error = %e, "History prune task failed"),
+        }
+    }
+}
+
 /// Main agent processing loop.
-pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) -> Result<()> {
+///
+/// Progress is recorded in `state` for in-process readers such as the tray.
+pub async fn run_agent(
+    config: Config,
+    shutdown_rx: &mut mpsc::Receiver<()>,
+    state: AgentState,
+) -> Result<()> {
+    // Held until the agent returns
+    let _instance_lock = if config.agent.single_instance {
+        Some(InstanceLock::acquire(&paths::data_dir())?)
+    } else {
+        None
+    };
+
+    check_instruments(&config)?;
+
     // Initialize components
-    let spool = Spool::new(&config.spool)?;
-    let failed_files = FailedFiles::new();
     let enable_notifications = config.agent.enable_toast_notifications;
+    let notifier = enable_notifications.then(|| NotificationQueue::spawn(&config.agent));
+    let config_hash = config.config_hash();
+    info!(config_hash = %config_hash, "Effective configuration hash");
+    let baselines = BaselineManager::load().with_retry(&config.cloud);
+    let spool = Spool::new(&config.spool)?
+        .with_baselines(baselines.clone())
+        .with_notifications(enable_notifications)
+        .with_config_hash(config_hash)
+        .with_projects(&config.instruments)
+        .with_payload_limit(
+            config.cloud.max_payload_targets,
+            config.cloud.oversized_payloads,
+        );
+    let failed_files = FailedFiles::new();
 
     // Set agent ID
     let agent_id = resolve_agent_id(&config);
     spool.set_agent_id(agent_id.clone()).await;
     info!(agent_id = %agent_id, "Agent ID configured");
 
-    let uploader = Uploader::new(&config.cloud, spool.clone())?;
+    let uploader = Uploader::new(&config.cloud, spool.clone())?
+        .with_schedule(config.active_schedule.clone())
+        .with_state(state.clone());
     let extractor = Extractor::new(&config.skyline)?;
-    let classifier = Classifier::new();
+    persist_discovered_skyline(&config, skyline::discover_skyline().as_deref());
+    let classifier = Classifier::new().with_aliases(&config.classifier.control_aliases);
+    let telemetry = Telemetry::new(config.agent.share_telemetry);
+    let history = RunHistory::new();
+    let replicates = ReplicateTracker::new(&config.replicates);
+    let previous_runs = PreviousRunTracker::new(
+        config.comparison.include_previous_run
+            && config.skyline.computes(ComputedMetric::Comparison),
+    );
+    let reinjections = ReinjectionTracker::new(&config.reinjection);
+    let empty_imports = EmptyImportTracker::new(&config.empty_import);
+    let consumable_tracker = ConsumablesTracker::new();
+    let carryover = CarryoverTracker::new();
+    let influx = config.influx.as_ref().map(InfluxExporter::new);
 
-    // Create channel for files ready for processing
-    let (file_tx, mut file_rx) = mpsc::channel::<TrackedFile>(100);
+    // Create channel for files ready for processing. Everything sent on it is
+    // recorded in the ready queue before it reaches the processing loop, and
+    // stays there until handled.
+    let (file_tx, mut incoming_rx) = mpsc::channel::<TrackedFile>(100);
+    let (queued_tx, mut file_rx) = mpsc::unbounded_channel::<TrackedFile>();
+    let ready_queue = ReadyQueue::new();
+    let queue_handle = tokio::spawn({
+        let ready_queue = ready_queue.clone();
+        async move {
+            while let Some(file) = incoming_rx.recv().await {
+                ready_queue.push(&file);
+                if queued_tx.send(file).is_err() {
+                    break;
+                }
+            }
+        }
+    });
 
     // Start watcher for each instrument
     let mut watchers = Vec::new();
     for instrument in &config.instruments {
+        if instrument.mode != InstrumentMode::Full {
+            info!(instrument = %instrument.id, mode = %instrument.mode, "Instrument will not upload runs");
+        }
         let watcher = Watcher::new(
             instrument.clone(),
-            config.watcher.clone(),
+            instrument.watcher_config(&config.watcher),
             file_tx.clone(),
             enable_notifications,
         )?;
         watchers.push(watcher);
     }
 
+    // Feed back runs that were queued but not handled before the last stop
+    restore_ready_queue(&config, &ready_queue, &history, &watchers, &file_tx).await;
+
     // Start all watchers
     for watcher in &watchers {
         watcher.start()?;
     }
 
-    // Start uploader background task
-    let uploader_handle = tokio::spawn({
-        let uploader = uploader.clone();
-        async move { uploader.run().await }
+    // Supervise the watcher loops, restarting any that stop ticking
+    let mut watchdog = WatchdogState::new();
+    if let Err(e) = watchdog.save() {
+        warn!(error = %e, "Failed to save watchdog state");
+    }
+    let mut watchdog_check = tokio::time::interval(std::time::Duration::from_secs(15));
+
+    // Requeue recent runs for instruments whose template changed
+    requeue_on_template_change(&config, &extractor, &history, &file_tx).await;
+
+    // Take runs handed in as job files
+    let drop_queue_handle = config.drop_queue.clone().map(|drop_queue| {
+        tokio::spawn(crate::drop_queue::run(
+            drop_queue,
+            config.instruments.clone(),
+            file_tx.clone(),
+        ))
+    });
+
+    // Bring cached baselines up to date; comparisons use the disk copy
+    // until then, or for good if the cloud can't be reached
+    let baseline_handle = match config.cloud.delivery {
+        DeliveryMode::Http => Some(tokio::spawn(refresh_baselines(
+            config.instruments.clone(),
+            baselines.clone(),
+            uploader.clone(),
+        ))),
+        DeliveryMode::LocalDir { .. } => None,
+    };
+
+    // Catch report column gaps once up front rather than on every run
+    let probe_handle = tokio::spawn(probe_template_columns(config.clone()));
+
+    // Keep history and the audit log within the retention window
+    let prune_handle = tokio::spawn(prune_history(
+        history.clone(),
+        spool.clone(),
+        config.agent.history_retention_days,
+    ));
+
+    // Let the cloud know the agent is alive between runs
+    let heartbeat_handle = (config.cloud.heartbeat_interval_minutes > 0).then(|| {
+        tokio::spawn(heartbeat::run(
+            config.cloud.heartbeat_interval_minutes,
+            agent_id.clone(),
+            config.instruments.clone(),
+            history.clone(),
+            spool.clone(),
+        ))
+    });
+
+    // Start uploader, on its own runtime unless cloud.upload_threads = 0
+    let upload_runtime = UploadRuntime::start(uploader, config.cloud.upload_threads)?;
+
+    // Start telemetry reporter (no-op unless opted in)
+    let telemetry_handle = tokio::spawn({
+        let telemetry = telemetry.clone();
+        let cloud = config.cloud.clone();
+        let agent_id = agent_id.clone();
+        let instrument_count = config.instruments.len();
+        async move {
+            telemetry
+                .run_reporter(cloud, agent_id, instrument_count)
+                .await
+        }
     });
 
     info!(
@@ -123,6 +742,21 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
         "Agent started, watching for QC runs"
     );
 
+    // Files held outside active hours or while disk space is low, retried on
+    // each check
+    let mut deferred = DeferredFiles::default();
+    let mut low_disk = false;
+    // Instruments whose watch drive went away mid-run, paused until it returns
+    let mut unplugged = std::collections::HashSet::<String>::new();
+    let min_free_disk_mb = config.spool.min_free_disk_mb;
+    let mut schedule_check = tokio::time::interval(std::time::Duration::from_secs(60));
+    let is_active = || {
+        config
+            .active_schedule
+            .as_ref()
+            .is_none_or(|s| s.is_active_now())
+    };
+
     // Main processing loop
     loop {
         tokio::select! {
@@ -132,36 +766,99 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
                 break;
             }
 
+            // Release deferred files once the active window opens
+            _ = schedule_check.tick(), if !deferred.is_empty() => {
+                let active = is_active();
+                if active && !low_disk {
+                    state.set_paused(None);
+                }
+                let released = deferred.release(active);
+                if !released.is_empty() {
+                    info!(count = released.len(), "Retrying deferred files");
+                }
+                for file in released {
+                    if let Err(e) = file_tx.try_send(file) {
+                        // Channel full; hold it for the next check
+                        deferred.admit(e.into_inner(), false);
+                    }
+                }
+            }
+
+            // Restart watcher loops that have stopped ticking
+            _ = watchdog_check.tick() => {
+                let mut restarted = false;
+                for watcher in &watchers {
+                    if watcher.restart_stalled_loops() > 0 {
+                        watchdog.instruments.insert(watcher.instrument_id().to_string(), watcher.restarts());
+                        restarted = true;
+                    }
+                }
+                if restarted {
+                    if let Err(e) = watchdog.save() {
+                        warn!(error = %e, "Failed to save watchdog state");
+                    }
+                }
+            }
+
             // Process incoming files
             Some(tracked_file) = file_rx.recv() => {
+                let Some(tracked_file) = deferred.admit(tracked_file, is_active()) else {
+                    state.set_paused(Some(PauseReason::OutsideActiveHours));
+                    info!(
+                        deferred = deferred.len(),
+                        "Outside active hours, deferring extraction"
+                    );
+                    continue;
+                };
+
                 let file_path = tracked_file.path.clone();
                 let vendor = tracked_file.vendor;
                 info!(path = ?file_path, vendor = %vendor, "Processing file");
+                let _processing = state.processing(&file_path);
 
                 // Find the instrument config for this file
-                let instrument = config.instruments.iter()
-                    .find(|i| file_path.starts_with(&i.watch_path))
-                    .cloned();
+                let instrument = match &tracked_file.instrument_id {
+                    Some(id) => config.instruments.iter().find(|i| &i.id == id),
+                    None => config.instruments.iter().find(|i| file_path.starts_with(&i.watch_path)),
+                }
+                .cloned();
 
                 let Some(instrument) = instrument else {
                     warn!(path = ?file_path, "No instrument config found for file");
+                    ready_queue.remove(&file_path);
                     continue;
                 };
 
                 // Find the watcher to mark done/failed
                 let watcher = watchers.iter()
-                    .find(|_w| file_path.starts_with(PathBuf::from(&instrument.watch_path)));
+                    .find(|w| w.instrument_id() == instrument.id);
+
+                if unplugged.contains(&instrument.id) {
+                    if !watcher::drive_present(Path::new(&instrument.watch_path)) {
+                        deferred.admit(tracked_file, false);
+                        continue;
+                    }
+                    info!(instrument = %instrument.id, "Watch drive is back, resuming processing");
+                    unplugged.remove(&instrument.id);
+                }
 
                 // Classify the run
-                let classification = match classifier.classify(&file_path, &instrument) {
+                let mut classification = match classifier.classify(&file_path, &instrument) {
                     Ok(c) => c,
                     Err(e) => {
                         warn!(path = ?file_path, error = %e, "Classification failed");
+                        history.record_audit(
+                            ClassificationAudit::new(file_path.clone(), instrument.id.clone(), None, ProcessingDecision::ClassificationFailed)
+                                .with_detail(e.to_string()),
+                        );
                         failed_files.record_failure(
                             file_path.clone(),
                             instrument.id.clone(),
                             format!("Classification failed: {}", e),
                         );
+                        state.record_failed(&file_path);
+                        hooks::spawn_for(&instrument, HookContext::failure(&file_path, &instrument.id, None, &format!("Classification failed: {}", e)));
+                        ready_queue.remove(&file_path);
                         if let Some(w) = watcher {
                             w.mark_failed(&file_path);
                         }
@@ -169,19 +866,58 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
                     }
                 };
 
+                // Fields given by a drop-queue job win over the file name
+                if let Some(overrides) = &tracked_file.overrides {
+                    overrides.apply(&mut classification);
+                }
+
                 // Skip SAMPLE runs unless configured otherwise
                 if !classification.control_type.is_qc() {
+                    if check_sample_carryover(&extractor, &carryover, &history, &instrument, &file_path, &classification).await {
+                        ready_queue.remove(&file_path);
+                        if let Some(w) = watcher {
+                            w.mark_done(&file_path);
+                        }
+                        continue;
+                    }
                     info!(
                         path = ?file_path,
                         control_type = %classification.control_type,
                         "Skipping non-QC run"
                     );
+                    history.record_audit(ClassificationAudit::new(
+                        file_path.clone(),
+                        instrument.id.clone(),
+                        Some(classification),
+                        ProcessingDecision::SkippedNonQc,
+                    ));
+                    ready_queue.remove(&file_path);
                     if let Some(w) = watcher {
                         w.mark_done(&file_path);
                     }
                     continue;
                 }
 
+                // Refuse a file name this instrument has already processed
+                if config.agent.dedup_by_filename && !tracked_file.reprocess {
+                    if let Some(earlier) = history.processed_file_name(&instrument.id, &file_path, config.agent.dedup_window_days) {
+                        info!(
+                            path = ?file_path,
+                            earlier = ?earlier.path,
+                            "Skipping run whose file name was already processed"
+                        );
+                        history.record_audit(
+                            ClassificationAudit::new(file_path.clone(), instrument.id.clone(), Some(classification), ProcessingDecision::SkippedDuplicate)
+                                .with_detail(format!("Already processed as {} at {}", earlier.path.display(), earlier.processed_at.to_rfc3339())),
+                        );
+                        ready_queue.remove(&file_path);
+                        if let Some(w) = watcher {
+                            w.mark_done(&file_path);
+                        }
+                        continue;
+                    }
+                }
+
                 info!(
                     path = ?file_path,
                     control_type = %classification.control_type,
@@ -189,6 +925,45 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
                     "Run classified"
                 );
 
+                if instrument.mode == InstrumentMode::DetectOnly {
+                    info!(path = ?file_path, instrument = %instrument.id, "Detect-only instrument, not extracting");
+                    history.record_audit(ClassificationAudit::new(
+                        file_path.clone(),
+                        instrument.id.clone(),
+                        Some(classification),
+                        ProcessingDecision::Detected,
+                    ));
+                    ready_queue.remove(&file_path);
+                    if let Some(w) = watcher {
+                        w.mark_done(&file_path);
+                    }
+                    continue;
+                }
+                let observe_only = instrument.mode == InstrumentMode::ObserveOnly;
+                // Uncertain classifications are extracted for local review but not uploaded
+                let min_upload_confidence = instrument.min_upload_confidence(&config.cloud);
+                let below_upload_confidence = !classification.confidence.meets(min_upload_confidence);
+                let keep_local = observe_only || below_upload_confidence;
+
+                // Skyline and the spool both write to the data drive
+                if let Err(free_mb) = crate::disk::ensure_free_space(&paths::spool_dir(), min_free_disk_mb) {
+                    warn!(
+                        path = ?file_path,
+                        free_mb,
+                        min_free_disk_mb,
+                        "Low disk space, deferring extraction"
+                    );
+                    if !low_disk && enable_notifications {
+                        crate::notifications::notify_low_disk_space(free_mb, min_free_disk_mb);
+                    }
+                    low_disk = true;
+                    state.set_paused(Some(PauseReason::LowDisk));
+                    deferred.admit(tracked_file, false);
+                    continue;
+                }
+                low_disk = false;
+                state.set_paused(None);
+
                 // Extract metrics
                 let file_name = file_path
                     .file_name()
@@ -197,67 +972,253 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
                     .to_string();
 
                 // Notify processing started
-                if enable_notifications {
-                    crate::notifications::notify_processing_started(&file_name);
+                if let Some(notifier) = &notifier {
+                    notifier.push(Notification::ProcessingStarted { file_name: file_name.clone() });
                 }
 
                 match extractor.extract(&file_path, &instrument, &classification).await {
-                    Ok(result) => {
+                    Ok(mut result) => {
+                        telemetry.record_extraction(true);
                         info!(
                             path = ?file_path,
                             targets_found = result.run_metrics.targets_found,
                             "Extraction complete"
                         );
 
-                        // Show success notification
-                        if enable_notifications {
-                            crate::notifications::notify_extraction_success(
-                                &file_name,
-                                result.run_metrics.targets_found,
-                                result.run_metrics.targets_expected,
+                        // Historical backlog from before the instrument went live
+                        if instrument.acquired_before_cutoff(result.acquisition.acquisition_time) {
+                            let detail = format!(
+                                "acquired {} before process_acquired_after {}",
+                                result.acquisition.acquisition_time.unwrap_or_default().to_rfc3339(),
+                                instrument.process_acquired_after.unwrap_or_default().to_rfc3339()
+                            );
+                            info!(path = ?file_path, "Skipping run: {}", detail);
+                            history.record_audit(
+                                ClassificationAudit::new(file_path.clone(), instrument.id.clone(), Some(classification), ProcessingDecision::AcquiredBeforeCutoff)
+                                    .with_run_id(result.run_id)
+                                    .with_detail(detail),
                             );
+                            ready_queue.remove(&file_path);
+                            if let Some(w) = watcher {
+                                w.mark_done(&file_path);
+                            }
+                            continue;
+                        }
+
+                        // Defer before the trackers below record the run, so
+                        // the extraction retried later isn't counted twice
+                        if let Err(SpoolError::LowDisk(free_mb, _)) = spool.ensure_free_space(keep_local) {
+                            warn!(path = ?file_path, free_mb, min_free_disk_mb, "Low disk space, deferring spool");
+                            if !low_disk && enable_notifications {
+                                crate::notifications::notify_low_disk_space(free_mb, min_free_disk_mb);
+                            }
+                            low_disk = true;
+                            state.set_paused(Some(PauseReason::LowDisk));
+                            deferred.admit(tracked_file, false);
+                            continue;
+                        }
+
+                        result.consumables = consumables::resolve(&instrument.consumables, &file_path);
+                        for change in consumable_tracker.record(&instrument.id, &result.consumables) {
+                            info!(
+                                instrument = %instrument.id,
+                                path = ?file_path,
+                                change = %change,
+                                "Consumable changed since the previous run"
+                            );
+                        }
+
+                        if let Some(median_ms) = empty_imports.check(&instrument.id, result.extraction_time_ms, result.run_metrics.target_recovery_pct) {
+                            result.suspect_empty_import = true;
+                            warn!(
+                                path = ?file_path,
+                                extraction_time_ms = result.extraction_time_ms,
+                                median_extraction_time_ms = median_ms,
+                                targets_found = result.run_metrics.targets_found,
+                                "Extraction was implausibly fast with few targets; Skyline may have imported nothing (check the file association and vendor reader)"
+                            );
+                        }
+
+                        score_health_against_baseline(&config, &baselines, &instrument.id, &mut result).await;
+                        if let Some(health_index) = result.run_metrics.health_index {
+                            info!(path = ?file_path, health_index = format!("{:.0}", health_index), "Instrument health index");
+                        }
+
+                        // Show success notification
+                        if let Some(notifier) = &notifier {
+                            notifier.push(Notification::ExtractionSuccess {
+                                file_name: file_name.clone(),
+                                targets_found: result.run_metrics.targets_found,
+                                targets_expected: result.run_metrics.targets_expected,
+                                health_index: result.run_metrics.health_index,
+                            });
                         }
 
-                        // Spool for upload (pass vendor from instrument config)
-                        if let Err(e) = spool.enqueue(&result, &classification, instrument.vendor).await {
+                        let injected_at = result.acquisition.acquisition_time.unwrap_or_else(chrono::Utc::now);
+                        classification.reinjection_of = reinjections.record(&classification, result.run_id, injected_at);
+                        if let Some(previous) = classification.reinjection_of {
+                            info!(path = ?file_path, reinjection_of = %previous, "Run re-injects a recent run of the same well");
+                        }
+
+                        // Best-effort InfluxDB export, off the processing path.
+                        // Observe-only runs stay on this machine.
+                        if let Some(influx) = influx.as_ref().filter(|_| !observe_only) {
+                            let influx = influx.clone();
+                            let result = result.clone();
+                            let classification = classification.clone();
+                            tokio::spawn(async move {
+                                influx.export(&result, &classification, chrono::Utc::now()).await;
+                            });
+                        }
+
+                        let replicate_metrics = replicates.record(
+                            &instrument.id,
+                            classification.control_type,
+                            result.run_id,
+                            &result.target_metrics,
+                        );
+                        if let Some(ref metrics) = replicate_metrics {
+                            if replicates.exceeds_threshold(metrics) {
+                                warn!(
+                                    path = ?file_path,
+                                    median_area_cv_pct = ?metrics.median_area_cv_pct,
+                                    window_size = metrics.window_size,
+                                    "Replicate peak-area CV exceeds threshold"
+                                );
+                            }
+                        }
+
+                        carryover.record_qc(&instrument.id, &result.target_metrics);
+
+                        let comparison_metrics = previous_runs
+                            .record(&instrument.id, classification.control_type, result.run_id, &result.run_metrics)
+                            .map(|vs_previous| ComparisonMetrics { vs_baseline: None, vs_previous: Some(vs_previous) });
+
+                        // Spool for upload (pass vendor from instrument config), or
+                        // keep the payload locally for observe-only instruments and
+                        // runs below the upload confidence
+                        if below_upload_confidence && !observe_only {
+                            info!(
+                                path = ?file_path,
+                                confidence = %classification.confidence,
+                                min_upload_confidence = %min_upload_confidence,
+                                "Classification confidence below upload threshold, keeping result locally"
+                            );
+                        }
+                        let spooled = if keep_local {
+                            spool.observe(&result, &classification, instrument.vendor, replicate_metrics, comparison_metrics, Some(&tracked_file)).await.map(|_| ())
+                        } else {
+                            spool.enqueue(&result, &classification, instrument.vendor, replicate_metrics, comparison_metrics, Some(&tracked_file)).await.map(|_| ())
+                        };
+                        if let Err(SpoolError::LowDisk(free_mb, _)) = spooled {
+                            // Disk filled between the check above and the write;
+                            // extract again once there's room
+                            warn!(path = ?file_path, free_mb, min_free_disk_mb, "Low disk space, deferring spool");
+                            if !low_disk && enable_notifications {
+                                crate::notifications::notify_low_disk_space(free_mb, min_free_disk_mb);
+                            }
+                            low_disk = true;
+                            state.set_paused(Some(PauseReason::LowDisk));
+                            deferred.admit(tracked_file, false);
+                        } else if let Err(e) = spooled {
                             error!(path = ?file_path, error = %e, "Failed to spool result");
+                            hooks::spawn_for(&instrument, HookContext::failure(&file_path, &instrument.id, Some(&classification), &format!("Failed to spool result: {}", e)));
+                            history.record_audit(
+                                ClassificationAudit::new(file_path.clone(), instrument.id.clone(), Some(classification), ProcessingDecision::SpoolFailed)
+                                    .with_run_id(result.run_id)
+                                    .with_detail(e.to_string()),
+                            );
                             failed_files.record_failure(
                                 file_path.clone(),
                                 instrument.id.clone(),
                                 format!("Failed to spool result: {}", e),
                             );
+                            state.record_failed(&file_path);
+                            ready_queue.remove(&file_path);
                             if let Some(w) = watcher {
                                 w.mark_failed(&file_path);
                             }
                         } else {
                             // Notify queued for upload
-                            if enable_notifications {
-                                crate::notifications::notify_upload_queued(&file_name);
+                            if let Some(notifier) = notifier.as_ref().filter(|_| !keep_local) {
+                                notifier.push(Notification::UploadQueued { file_name: file_name.clone() });
                             }
+                            hooks::spawn_for(&instrument, HookContext::success(&file_path, &classification, &result));
+                            history.record_run(
+                                file_path.clone(),
+                                instrument.id.clone(),
+                                result.template_hash.clone(),
+                            );
+                            let mut audit = ClassificationAudit::new(file_path.clone(), instrument.id.clone(), Some(classification.clone()), ProcessingDecision::Processed)
+                                .with_run_id(result.run_id)
+                                .with_metrics(result.run_metrics.clone());
+                            if observe_only {
+                                audit.decision = ProcessingDecision::Observed;
+                            } else if below_upload_confidence {
+                                audit.decision = ProcessingDecision::BelowUploadConfidence;
+                                audit = audit.with_detail(format!(
+                                    "confidence {} is below min_upload_confidence {}",
+                                    classification.confidence, min_upload_confidence
+                                ));
+                            }
+                            history.record_audit(audit);
+                            state.record_processed();
+                            ready_queue.remove(&file_path);
                             if let Some(w) = watcher {
                                 w.mark_done(&file_path);
                             }
                         }
                     }
+                    Err(e) if !watcher::drive_present(Path::new(&instrument.watch_path)) => {
+                        // Not the run's fault; hold it rather than failing it
+                        warn!(
+                            instrument = %instrument.id,
+                            path = ?file_path,
+                            error = %e,
+                            "Watch drive is no longer present (unplugged?) - pausing this instrument until it returns"
+                        );
+                        unplugged.insert(instrument.id.clone());
+                        deferred.admit(tracked_file, false);
+                    }
                     Err(e) => {
+                        telemetry.record_extraction(false);
                         error!(path = ?file_path, error = %e, "Extraction failed");
+                        let diagnostics = ExtractionDiagnostics::new(&e, &classification, &instrument.template);
+                        hooks::spawn_for(&instrument, HookContext::failure(&file_path, &instrument.id, Some(&classification), &format!("Skyline extraction failed: {}", e)));
+                        history.record_audit(
+                            ClassificationAudit::new(file_path.clone(), instrument.id.clone(), Some(classification), ProcessingDecision::ExtractionFailed)
+                                .with_detail(e.to_string()),
+                        );
 
                         // Show failure notification
-                        if enable_notifications {
-                            crate::notifications::notify_extraction_failure(
-                                &file_name,
-                                &e.to_string(),
-                            );
+                        if let Some(notifier) = &notifier {
+                            notifier.push(Notification::ExtractionFailure {
+                                file_name: file_name.clone(),
+                                error: e.to_string(),
+                            });
                         }
 
-                        failed_files.record_failure(
+                        failed_files.record_extraction_failure(
                             file_path.clone(),
                             instrument.id.clone(),
                             format!("Skyline extraction failed: {}", e),
+                            diagnostics,
                         );
+                        state.record_failed(&file_path);
+                        ready_queue.remove(&file_path);
                         if let Some(w) = watcher {
                             w.mark_failed(&file_path);
                         }
+                        if let Some(ref dir) = instrument.quarantine_dir {
+                            if let Some(dest) = failed_files.quarantine_if_exhausted(&file_path, Path::new(dir), instrument.max_retries) {
+                                warn!(
+                                    path = ?file_path,
+                                    quarantined = ?dest,
+                                    max_retries = instrument.max_retries,
+                                    "File kept failing, moved to quarantine"
+                                );
+                            }
+                        }
                     }
                 }
             }
@@ -267,14 +1228,97 @@ pub async fn run_agent(config: Config, shutdown_rx: &mut mpsc::Receiver<()>) ->
     // Cleanup
     info!("Stopping watchers");
     for watcher in watchers {
-        watcher.stop()?;
+        watcher.stop().await?;
     }
 
     info!("Stopping uploader");
-    uploader_handle.abort();
+    upload_runtime.stop().await;
+    telemetry_handle.abort();
+    telemetry.persist();
+    if let Some(handle) = drop_queue_handle {
+        handle.abort();
+    }
+    probe_handle.abort();
+    if let Some(handle) = baseline_handle {
+        handle.abort();
+    }
+    prune_handle.abort();
+    if let Some(handle) = heartbeat_handle {
+        handle.abort();
+    }
+    queue_handle.abort();
 
     info!("Agent stopped");
     Ok(())
 }
 
-use std::path::PathBuf;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_id_stable_across_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent_id");
+
+        // Fallback generator yields a different id on every call
+        let first = load_or_persist_agent_id(&path, generate_agent_id);
+        // Simulated restart: fresh call against the same data directory
+        let second = load_or_persist_agent_id(&path, generate_agent_id);
+
+        assert_eq!(first, second);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), first);
+    }
+
+    #[test]
+    fn test_no_instruments() {
+        let mut config = Config::default();
+        assert!(config.instruments.is_empty());
+
+        config.agent.enable_toast_notifications = false;
+        assert!(check_instruments(&config).is_ok());
+
+        config.agent.fail_on_no_instruments = true;
+        let err = check_instruments(&config).unwrap_err();
+        assert!(err.to_string().contains("No instruments configured"));
+    }
+
+    #[test]
+    fn test_persist_discovered_skyline() {
+        let dir = tempfile::tempdir().unwrap();
+        let skyline = dir.path().join("SkylineCmd.exe");
+        let mut config = Config {
+            path: dir.path().join("config.toml"),
+            ..Config::default()
+        };
+        config.skyline.path = Some("auto".to_string());
+
+        // Off by default
+        persist_discovered_skyline(&config, Some(&skyline));
+        assert!(!config.path.exists());
+
+        config.skyline.persist_discovered_path = true;
+        persist_discovered_skyline(&config, Some(&skyline));
+        let saved = Config::load_from(&config.path).unwrap();
+        assert_eq!(saved.skyline.configured_path(), Some(skyline));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_read_machine_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("machine-id");
+
+        std::fs::write(&path, "4C4C4544003957108052B4C04F4E3732\n").unwrap();
+        assert_eq!(
+            read_machine_id(&path).as_deref(),
+            Some("4c4c4544003957108052b4c04f4e3732")
+        );
+
+        // systemd writes "uninitialized" before first boot completes
+        std::fs::write(&path, "uninitialized\n").unwrap();
+        assert_eq!(read_machine_id(&path), None);
+
+        assert_eq!(read_machine_id(&dir.path().join("missing")), None);
+    }
+}
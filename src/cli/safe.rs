@@ -0,0 +1,71 @@
+//! Safe mode - diagnostics and recovery without starting the agent.
+//!
+//! Nothing here needs a loadable config, so it works however broken the file
+//! is: it runs the doctor, points at the config file, and can replace the
+//! file with defaults (keeping the old one as a backup).
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::doctor;
+use crate::config::{paths, Config};
+
+/// Run safe mode.
+pub async fn run(reset_config: bool) -> Result<()> {
+    let config_path = paths::config_file();
+
+    println!();
+    println!("Safe Mode");
+    println!("=========");
+    println!("The agent is not started; the configuration is only inspected.");
+
+    if reset_config {
+        println!();
+        match write_default_config(&config_path)? {
+            Some(backup) => println!("Previous config saved as {}", backup.display()),
+            None => println!("No previous config to back up"),
+        }
+        println!("Wrote default configuration to {}", config_path.display());
+    }
+
+    doctor::run(false).await?;
+
+    println!("Recovery");
+    println!("--------");
+    println!("Config file: {}", config_path.display());
+    #[cfg(windows)]
+    println!("Edit settings: mdqc gui");
+    if !reset_config {
+        println!("Reset to defaults (keeps a backup): mdqc safe --reset-config");
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Replace the config file with defaults, returning where the old one went.
+fn write_default_config(config_path: &Path) -> Result<Option<PathBuf>> {
+    let backup = if config_path.exists() {
+        let backup = config_path.with_extension(format!(
+            "toml.bak-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        std::fs::rename(config_path, &backup)
+            .with_context(|| format!("Failed to back up {}", config_path.display()))?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let config = Config {
+        path: config_path.to_path_buf(),
+        ..Config::default()
+    };
+    config.save()?;
+
+    Ok(backup)
+}
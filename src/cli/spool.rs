@@ -0,0 +1,46 @@
+//! Spool CLI commands.
+
+use anyhow::Result;
+
+use crate::cli::SpoolAction;
+use crate::config::Config;
+use crate::spool::Spool;
+
+/// Run a spool command.
+pub async fn run(action: SpoolAction) -> Result<()> {
+    let config = Config::load()?;
+    let spool = Spool::new(&config.spool)?;
+
+    match action {
+        SpoolAction::Gc { dry_run } => gc(&spool, &config, dry_run),
+    }
+}
+
+fn gc(spool: &Spool, config: &Config, dry_run: bool) -> Result<()> {
+    println!(
+        "Retention: {} days, {} completed payloads",
+        config.spool.max_age_days, config.spool.completed_retention_count
+    );
+
+    let report = spool.gc(dry_run)?;
+
+    if report.removed.is_empty() {
+        println!("Nothing to remove.");
+        return Ok(());
+    }
+
+    if dry_run {
+        for path in &report.removed {
+            println!("  {}", path.display());
+        }
+    }
+
+    println!(
+        "{} {} payload(s), {:.1} MB",
+        if dry_run { "Would remove" } else { "Removed" },
+        report.removed.len(),
+        report.bytes as f64 / (1024.0 * 1024.0)
+    );
+
+    Ok(())
+}
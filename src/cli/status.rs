@@ -3,10 +3,12 @@
 use anyhow::Result;
 use chrono::Utc;
 
-use crate::config::{self, Config};
+use crate::config::{self, Config, InstrumentConfig};
+use crate::history::ClassificationAudit;
 
-/// Run the status command.
-pub async fn run() -> Result<()> {
+/// Run the status command. With `details`, also show each instrument's
+/// latest extracted QC run.
+pub async fn run(details: bool) -> Result<()> {
     println!();
     println!("Agent Status");
     println!("============");
@@ -26,6 +28,8 @@ pub async fn run() -> Result<()> {
         println!("Service: N/A (not on Windows)");
     }
 
+    println!("Profile: {}", config::paths::profile().unwrap_or("default"));
+
     // Load config
     let config = match Config::load() {
         Ok(c) => c,
@@ -36,7 +40,51 @@ pub async fn run() -> Result<()> {
     };
 
     println!("Config: loaded");
-    println!("Instruments: {}", config.instruments.len());
+    if config.instruments.is_empty() {
+        println!("Instruments: none configured (agent idles, nothing is watched)");
+    } else {
+        println!("Instruments: {}", config.instruments.len());
+    }
+
+    if let Some(ref schedule) = config.active_schedule {
+        let now = chrono::Local::now().naive_local();
+        if schedule.is_active_at(now) {
+            println!(
+                "Schedule: within active hours ({}-{})",
+                schedule.start, schedule.end
+            );
+        } else {
+            match schedule.next_start_after(now) {
+                Some(next) => println!(
+                    "Schedule: outside active hours, deferring until {}",
+                    next.format("%a %H:%M")
+                ),
+                None => println!("Schedule: outside active hours, deferring"),
+            }
+        }
+    }
+
+    // Show watcher restarts since the agent started
+    if let Ok(Some(watchdog)) = crate::watcher::watchdog::WatchdogState::load() {
+        let restarted: Vec<_> = watchdog
+            .instruments
+            .iter()
+            .filter(|(_, r)| r.total() > 0)
+            .collect();
+        if restarted.is_empty() {
+            println!("Watcher restarts: none");
+        } else {
+            for (instrument, r) in restarted {
+                println!(
+                    "Watcher restarts ({}): {} event, {} scan since {}",
+                    instrument,
+                    r.event,
+                    r.scan,
+                    watchdog.started_at.format("%Y-%m-%d %H:%M UTC")
+                );
+            }
+        }
+    }
 
     // Show spool status
     println!();
@@ -52,6 +100,84 @@ pub async fn run() -> Result<()> {
     println!("Pending: {}", pending_count);
     println!("Uploading: {}", uploading_count);
     println!("Failed: {}", failed_count);
+    println!(
+        "Spool usage: {}% of {} MB",
+        crate::spool::pending_usage_pct(&config.spool),
+        config.spool.max_pending_mb
+    );
+
+    match crate::disk::free_space_mb(&spool_dir) {
+        Ok(free_mb) if free_mb < config.spool.min_free_disk_mb => println!(
+            "Free disk: {} MB (below {} MB minimum, processing deferred)",
+            free_mb, config.spool.min_free_disk_mb
+        ),
+        Ok(free_mb) => println!("Free disk: {} MB", free_mb),
+        Err(e) => println!("Free disk: unknown ({})", e),
+    }
+
+    // Show local history size
+    println!();
+    println!("History");
+    println!("-------");
+
+    let runs = crate::history::HistoryStore::load()
+        .map(|store| store.runs.len())
+        .unwrap_or(0);
+    let history_path = crate::history::HistoryStore::store_path();
+    let audit_path = crate::history::audit_log_path();
+    println!(
+        "Processed runs: {} ({:.1} MB)",
+        runs,
+        file_mb(&history_path)
+    );
+    println!(
+        "Audit log: {} record(s) ({:.1} MB)",
+        count_lines(&audit_path),
+        file_mb(&audit_path)
+    );
+    match config.agent.history_retention_days {
+        0 => println!("Retention: forever"),
+        days => println!("Retention: {} days", days),
+    }
+
+    if details {
+        println!();
+        println!("Latest QC Runs");
+        println!("--------------");
+
+        let audits =
+            crate::history::read_audits(&crate::history::audit_log_path()).unwrap_or_default();
+        if config.instruments.is_empty() {
+            println!("(no instruments configured)");
+        }
+        for (instrument_id, audit) in latest_runs(&audits, &config.instruments) {
+            let Some((audit, metrics)) = audit.and_then(|a| a.run_metrics.as_ref().map(|m| (a, m)))
+            else {
+                println!("{}  (no runs yet)", instrument_id);
+                continue;
+            };
+
+            let health = metrics
+                .health_index
+                .map(|index| format!("health {:.0}/100", index))
+                .unwrap_or_else(|| "health -".to_string());
+            let mut parts = vec![format!("recovery {:.1}%", metrics.target_recovery_pct)];
+            if let Some(ppm) = metrics.median_mass_error_ppm {
+                parts.push(format!("mass error {:.1} ppm", ppm));
+            }
+            if let Some(score) = metrics.chromatography_score {
+                parts.push(format!("chromatography {:.2}", score));
+            }
+            println!(
+                "{}  {}  {}  {}",
+                instrument_id,
+                health,
+                audit.decided_at.format("%Y-%m-%d %H:%M"),
+                audit.file_name()
+            );
+            println!("    {}", parts.join(", "));
+        }
+    }
 
     // Show recent activity
     println!();
@@ -102,6 +228,24 @@ pub async fn run() -> Result<()> {
     Ok(())
 }
 
+/// Each configured instrument's most recent audit record with run metrics,
+/// in config order.
+fn latest_runs<'a>(
+    audits: &'a [ClassificationAudit],
+    instruments: &'a [InstrumentConfig],
+) -> Vec<(&'a str, Option<&'a ClassificationAudit>)> {
+    instruments
+        .iter()
+        .map(|instrument| {
+            let latest = audits
+                .iter()
+                .filter(|a| a.instrument_id == instrument.id && a.run_metrics.is_some())
+                .max_by_key(|a| a.decided_at);
+            (instrument.id.as_str(), latest)
+        })
+        .collect()
+}
+
 fn count_files(dir: &std::path::Path) -> usize {
     if dir.exists() {
         std::fs::read_dir(dir)
@@ -112,6 +256,19 @@ fn count_files(dir: &std::path::Path) -> usize {
     }
 }
 
+fn file_mb(path: &std::path::Path) -> f64 {
+    let bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn count_lines(path: &std::path::Path) -> usize {
+    use std::io::BufRead;
+
+    std::fs::File::open(path)
+        .map(|file| std::io::BufReader::new(file).lines().count())
+        .unwrap_or(0)
+}
+
 #[cfg(windows)]
 enum ServiceStatus {
     Running,
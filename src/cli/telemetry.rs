@@ -0,0 +1,40 @@
+//! Telemetry command - audit the opt-in anonymized telemetry.
+
+use anyhow::Result;
+
+use crate::cli::TelemetryAction;
+use crate::config::Config;
+use crate::telemetry::TelemetryStore;
+
+/// Run the telemetry command.
+pub async fn run(action: TelemetryAction) -> Result<()> {
+    match action {
+        TelemetryAction::Preview => preview().await,
+    }
+}
+
+async fn preview() -> Result<()> {
+    let config = Config::load()?;
+    let store = TelemetryStore::load().unwrap_or_default();
+    let agent_id = crate::cli::run::resolve_agent_id(&config);
+    let rollup = store.rollup(&agent_id, config.instruments.len());
+
+    println!();
+    if config.agent.share_telemetry {
+        println!("Telemetry: ENABLED");
+        println!(
+            "Endpoint:  {}telemetry (sent once per day)",
+            config.cloud.endpoint
+        );
+    } else {
+        println!("Telemetry: disabled (set share_telemetry = true under [agent] to opt in)");
+        println!("Counters are only collected while telemetry is enabled.");
+    }
+    println!();
+    println!("The following payload would be sent:");
+    println!();
+    println!("{}", serde_json::to_string_pretty(&rollup)?);
+    println!();
+
+    Ok(())
+}
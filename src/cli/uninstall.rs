@@ -0,0 +1,236 @@
+//! Uninstall command - remove the service, shortcut, and optionally all data.
+//!
+//! The binary itself is left for the installer (or the user) to remove; it
+//! can't reliably delete itself while running.
+
+use anyhow::Result;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::paths;
+use crate::instance_lock::{InstanceLock, InstanceLockError};
+use crate::service;
+
+/// Run the uninstall command.
+pub async fn run(purge_data: bool, force: bool, confirm: bool) -> Result<()> {
+    println!();
+    println!("Uninstall");
+    println!("=========");
+
+    let service_removed = match service::uninstall_service() {
+        Ok(true) => {
+            println!("[OK] Stopped and removed the MassDynamicsQC service");
+            true
+        }
+        Ok(false) => {
+            println!("[--] MassDynamicsQC service not installed");
+            true
+        }
+        Err(e) => {
+            println!(
+                "[XX] Could not remove the service: {} (run as administrator)",
+                e
+            );
+            false
+        }
+    };
+
+    match paths::start_menu_shortcut().filter(|p| p.exists()) {
+        Some(shortcut) => match std::fs::remove_file(&shortcut) {
+            Ok(()) => println!("[OK] Removed Start Menu shortcut"),
+            Err(e) => println!("[XX] Could not remove {}: {}", shortcut.display(), e),
+        },
+        None => println!("[--] No Start Menu shortcut"),
+    }
+
+    // Every profile's data lives under the base directory
+    let data_dir = paths::base_dir();
+    if !purge_data {
+        println!();
+        println!("Data kept in {}", data_dir.display());
+        println!("Remove it too with: mdqc uninstall --purge-data");
+        println!();
+        return Ok(());
+    }
+
+    // A service that may still be running would write into what we delete
+    if !service_removed {
+        anyhow::bail!("The service could not be removed; data kept. Fix that and run again.");
+    }
+
+    let config_file = paths::config_file();
+    if config_file.exists() && !config_file.starts_with(&data_dir) {
+        println!(
+            "[--] Keeping {} (MDQC_CONFIG, outside the data directory)",
+            config_file.display()
+        );
+    }
+
+    let targets = purge_targets(&data_dir);
+    if targets.is_empty() {
+        println!("[--] No data to remove");
+        println!();
+        return Ok(());
+    }
+
+    // An agent started by hand (or under another profile) isn't stopped
+    // with the service; held until the data is gone
+    let _locks = lock_data_dirs(&data_dir)?;
+
+    let unsent = unsent_payloads(&data_dir);
+    if unsent > 0 && !force {
+        anyhow::bail!(
+            "{} payload(s) in the spool have not been uploaded and would be lost; \
+             let the agent upload them first, or pass --force",
+            unsent
+        );
+    }
+
+    println!();
+    println!("This will permanently delete:");
+    for target in &targets {
+        println!("  {}", target.display());
+    }
+    if unsent > 0 {
+        println!("including {} payload(s) that were never uploaded.", unsent);
+    }
+
+    if !confirm {
+        print!("Delete? [y/N] ");
+        io::stdout().flush()?;
+        if !confirmed(io::stdin().lock())? {
+            println!("Cancelled; data kept.");
+            return Ok(());
+        }
+    }
+
+    for target in &targets {
+        let removed = if target.is_dir() {
+            std::fs::remove_dir_all(target)
+        } else {
+            std::fs::remove_file(target)
+        };
+        match removed {
+            Ok(()) => println!("[OK] Removed {}", target.display()),
+            Err(e) => println!("[XX] Could not remove {}: {}", target.display(), e),
+        }
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Paths `--purge-data` deletes: the data directory (config, spool, logs,
+/// templates, state). A config file kept elsewhere via `MDQC_CONFIG` isn't
+/// the agent's to delete.
+fn purge_targets(data_dir: &Path) -> Vec<PathBuf> {
+    if data_dir.exists() {
+        vec![data_dir.to_path_buf()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Take the single-instance lock of the base data directory and of every
+/// profile under it, failing if a running agent holds any of them.
+fn lock_data_dirs(base_dir: &Path) -> Result<Vec<InstanceLock>> {
+    let profiles = std::fs::read_dir(base_dir.join("profiles"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir());
+    std::iter::once(base_dir.to_path_buf())
+        .chain(profiles)
+        .map(|dir| {
+            InstanceLock::acquire(&dir).map_err(|e| match e {
+                InstanceLockError::HeldBy(pid) => anyhow::anyhow!(
+                    "An agent (PID {}) is still running on {}; data kept. Stop it and run again.",
+                    pid,
+                    dir.display()
+                ),
+                InstanceLockError::Held => anyhow::anyhow!(
+                    "An agent is still running on {}; data kept. Stop it and run again.",
+                    dir.display()
+                ),
+                e => e.into(),
+            })
+        })
+        .collect()
+}
+
+/// Payloads that haven't reached the cloud: pending, mid-upload, or failed,
+/// in the spool of the default profile or any other.
+fn unsent_payloads(base_dir: &Path) -> usize {
+    let profiles = std::fs::read_dir(base_dir.join("profiles"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path());
+    std::iter::once(base_dir.to_path_buf())
+        .chain(profiles)
+        .flat_map(|dir| {
+            let spool_dir = dir.join("spool");
+            ["pending", "uploading", "failed"].map(|state| spool_dir.join(state))
+        })
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .count()
+}
+
+/// Read a y/N answer; anything but "y" declines.
+fn confirmed(mut input: impl BufRead) -> Result<bool> {
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purge_targets_and_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("QC");
+        let spool_dir = data_dir.join("spool");
+
+        // Nothing installed yet
+        assert!(purge_targets(&data_dir).is_empty());
+
+        std::fs::create_dir_all(spool_dir.join("pending")).unwrap();
+        std::fs::create_dir_all(spool_dir.join("completed")).unwrap();
+        std::fs::write(data_dir.join("config.toml"), "").unwrap();
+        assert_eq!(purge_targets(&data_dir), vec![data_dir.clone()]);
+
+        // Uploaded payloads don't block a purge; unsent ones do
+        std::fs::write(spool_dir.join("completed").join("a_payload.json"), "{}").unwrap();
+        assert_eq!(unsent_payloads(&data_dir), 0);
+        std::fs::write(spool_dir.join("pending").join("b_payload.json"), "{}").unwrap();
+        std::fs::write(spool_dir.join("pending").join(".b_payload.json.tmp"), "").unwrap();
+        assert_eq!(unsent_payloads(&data_dir), 1);
+
+        // Other profiles' spools count too
+        let dev_pending = data_dir.join("profiles/dev/spool/pending");
+        std::fs::create_dir_all(&dev_pending).unwrap();
+        std::fs::write(dev_pending.join("c_payload.json"), "{}").unwrap();
+        assert_eq!(unsent_payloads(&data_dir), 2);
+
+        // A running agent under any profile blocks the purge, naming its PID
+        let dev_lock = InstanceLock::acquire(&data_dir.join("profiles/dev")).unwrap();
+        let err = lock_data_dirs(&data_dir).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("PID {}", std::process::id())),
+            "{err}"
+        );
+        drop(dev_lock);
+        assert_eq!(lock_data_dirs(&data_dir).unwrap().len(), 2);
+
+        assert!(confirmed("y\n".as_bytes()).unwrap());
+        assert!(confirmed("Y\n".as_bytes()).unwrap());
+        assert!(!confirmed("\n".as_bytes()).unwrap());
+        assert!(!confirmed("yes please\n".as_bytes()).unwrap());
+        assert!(!confirmed("".as_bytes()).unwrap());
+    }
+}
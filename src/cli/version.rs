@@ -0,0 +1,38 @@
+//! Version command - print the agent version and optionally check for updates.
+
+use anyhow::Result;
+
+use crate::config::{CloudConfig, Config};
+use crate::update_check;
+
+/// Run the version command.
+pub async fn run(check: bool) -> Result<()> {
+    println!("mdqc {}", env!("CARGO_PKG_VERSION"));
+
+    if !check {
+        return Ok(());
+    }
+
+    // Without a config the check still runs, without a proxy
+    let cloud = match Config::load() {
+        Ok(config) if !config.agent.check_for_updates => {
+            println!("Update checks are disabled (agent.check_for_updates = false)");
+            return Ok(());
+        }
+        Ok(config) => config.cloud,
+        Err(_) => CloudConfig::default(),
+    };
+
+    let status = update_check::check(&cloud, true).await?;
+    if status.update_available() {
+        println!(
+            "Update available: v{} ({})",
+            status.latest,
+            status.release_url()
+        );
+    } else {
+        println!("Up to date (latest release: v{})", status.latest);
+    }
+
+    Ok(())
+}
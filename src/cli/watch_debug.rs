@@ -0,0 +1,135 @@
+//! Watch-debug command - one-shot diagnosis of finalization blockers.
+//!
+//! Runs the same checks the watcher's finalization loop uses against a
+//! single path and prints the result, so operators don't need to enable
+//! trace logging and wait for the next scan.
+
+use anyhow::Result;
+use chrono::Utc;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::types::Vendor;
+use crate::watcher;
+
+/// Run the watch-debug command.
+pub async fn run(path: &str, vendor_override: Option<Vendor>) -> Result<()> {
+    let path = Path::new(path);
+
+    if !path.exists() {
+        anyhow::bail!("Path does not exist: {}", path.display());
+    }
+
+    // Prefer the vendor of a configured instrument watching this path
+    let config = Config::load().ok();
+    let instrument = config.as_ref().and_then(|c| {
+        c.instruments
+            .iter()
+            .find(|i| path.starts_with(&i.watch_path))
+            .cloned()
+    });
+
+    let (vendor, vendor_source) = match (vendor_override, &instrument) {
+        (Some(v), _) => (Some(v), "command line".to_string()),
+        (None, Some(inst)) => (Some(inst.vendor), format!("instrument '{}'", inst.id)),
+        (None, None) => (
+            watcher::infer_vendor(path),
+            "inferred from path".to_string(),
+        ),
+    };
+
+    println!();
+    println!("Finalization Diagnosis");
+    println!("======================");
+    println!("Path: {}", path.display());
+
+    let Some(vendor) = vendor else {
+        println!("Vendor: (could not infer - pass --vendor)");
+        println!();
+        return Ok(());
+    };
+
+    println!("Vendor: {} ({})", vendor, vendor_source);
+
+    let is_valid = watcher::is_valid_raw_file(path, vendor);
+    println!(
+        "Valid raw file: {}",
+        if is_valid {
+            "yes"
+        } else {
+            "NO (extension or file/directory type does not match vendor)"
+        }
+    );
+
+    let (size, modified, mut is_complete) = watcher::check_file_state(path, vendor);
+    let sentinel = instrument
+        .as_ref()
+        .and_then(|i| i.completion_sentinel.as_deref());
+    if let Some(pattern) = sentinel {
+        let found = watcher::find_relative_file(path, pattern);
+        match found {
+            Some(ref file) => println!("Completion sentinel: present ({})", file.display()),
+            None => println!("Completion sentinel: MISSING ({})", pattern),
+        }
+        // The sentinel overrides the vendor completeness checks
+        is_complete = found.is_some();
+    }
+
+    println!("Current size: {} bytes", size);
+    match modified {
+        watcher::Mtime::Known(modified) => println!(
+            "Last modified: {} ({}s ago)",
+            modified.format("%Y-%m-%d %H:%M:%S UTC"),
+            (Utc::now() - modified).num_seconds()
+        ),
+        watcher::Mtime::Unsupported => {
+            println!("Last modified: unavailable (watcher will use size-only stability)")
+        }
+        watcher::Mtime::Unread => println!("Last modified: not read (file missing or locked)"),
+    }
+    println!("Complete: {}", if is_complete { "yes" } else { "NO" });
+
+    match watcher::find_lock_file(path, vendor) {
+        Some(lock) => println!("Lock file: PRESENT ({})", lock.display()),
+        None => println!("Lock file: none"),
+    }
+
+    let can_open = watcher::try_exclusive_open(path, vendor);
+    println!(
+        "Exclusive open: {}",
+        if can_open {
+            "succeeded"
+        } else {
+            "FAILED (file in use)"
+        }
+    );
+
+    // Summarize what the finalization loop would do
+    println!();
+    println!("Finalization Decision");
+    println!("---------------------");
+
+    if !is_valid {
+        println!("Would track: NO (not a valid raw file for {})", vendor);
+    } else if !is_complete {
+        println!("Would track: YES, but stays STABILIZING until the file is complete");
+    } else if !can_open {
+        println!("Would track: YES, but stays READY until the file is released");
+    } else {
+        let global = config
+            .as_ref()
+            .map(|c| c.watcher.clone())
+            .unwrap_or_default();
+        let window = instrument
+            .as_ref()
+            .map_or(global.clone(), |i| i.watcher_config(&global))
+            .stability_window(vendor);
+        println!(
+            "Would track: YES, queued once unchanged for {}s stability window",
+            window
+        );
+    }
+
+    println!();
+    Ok(())
+}
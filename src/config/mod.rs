@@ -1,10 +1,12 @@
 //! Configuration management for the MD Local QC Agent.
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-use crate::types::Vendor;
+use crate::types::{ClassificationConfidence, ControlType, RawHashMode, Vendor};
 
 pub mod paths;
 
@@ -35,6 +37,38 @@ pub struct Config {
     #[serde(default)]
     pub spool: SpoolConfig,
 
+    /// Baseline comparison configuration
+    #[serde(default)]
+    pub comparison: ComparisonConfig,
+
+    /// Replicate precision (area CV) configuration
+    #[serde(default)]
+    pub replicates: ReplicateConfig,
+
+    /// Re-injection (repeat QC run) detection
+    #[serde(default)]
+    pub reinjection: ReinjectionConfig,
+
+    /// Suspect empty import (implausibly fast extraction) detection
+    #[serde(default)]
+    pub empty_import: EmptyImportConfig,
+
+    /// Run classification configuration
+    #[serde(default)]
+    pub classifier: ClassifierConfig,
+
+    /// Active hours for extraction and upload (always active if unset)
+    #[serde(default)]
+    pub active_schedule: Option<ActiveSchedule>,
+
+    /// InfluxDB export of run metrics (disabled if unset)
+    #[serde(default)]
+    pub influx: Option<InfluxConfig>,
+
+    /// Directory of job files handing runs to the agent (disabled if unset)
+    #[serde(default)]
+    pub drop_queue: Option<DropQueueConfig>,
+
     /// Configured instruments
     #[serde(default)]
     pub instruments: Vec<InstrumentConfig>,
@@ -42,9 +76,29 @@ pub struct Config {
 
 impl Config {
     /// Load configuration from the default path or environment.
+    ///
+    /// Also applies the process-wide path settings (`extended_length_paths`),
+    /// so every command and the service see them, not just `mdqc run`.
     pub fn load() -> Result<Self> {
-        let config_path = paths::config_file();
-        Self::load_from(&config_path)
+        let config = Self::load_profile(&paths::config_file(), paths::profile())?;
+        paths::set_extended_length_paths(config.agent.extended_length_paths);
+        Ok(config)
+    }
+
+    /// Load the config file of `profile`, which must exist when a profile is
+    /// selected.
+    fn load_profile(config_path: &PathBuf, profile: Option<&str>) -> Result<Self> {
+        if let Some(profile) = profile {
+            if !config_path.exists() {
+                anyhow::bail!(
+                    "Config profile '{}' not found (expected {})",
+                    profile,
+                    config_path.display()
+                );
+            }
+        }
+
+        Self::load_from(config_path)
     }
 
     /// Load configuration from a specific path.
@@ -71,6 +125,55 @@ impl Config {
         Ok(())
     }
 
+    /// Stable hash of the effective configuration, sent with each payload.
+    ///
+    /// Covers every setting including defaults, so equivalent files hash
+    /// equally regardless of layout. The file path and secret values (see
+    /// [`redact_secrets`]) are left out.
+    pub fn config_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut value =
+            toml::Value::try_from(self).unwrap_or_else(|_| toml::Value::Table(toml::Table::new()));
+        redact_secrets(&mut value);
+
+        // serde_json objects are sorted by key, so the encoding is canonical
+        let canonical = serde_json::to_value(&value).unwrap_or_default();
+        let encoded = serde_json::to_vec(&canonical).unwrap_or_default();
+        hex::encode(Sha256::digest(encoded))
+    }
+
+    /// The configuration as the agent uses it, as TOML.
+    ///
+    /// Every default is filled in, each instrument's `watcher_overrides`
+    /// holds its full merged watcher settings, and secrets are masked. The
+    /// output loads as a config that behaves the same (secrets aside).
+    pub fn effective_toml(&self) -> Result<String> {
+        let mut config = self.clone();
+        for instrument in &mut config.instruments {
+            let watcher = instrument.watcher_config(&self.watcher);
+            instrument.watcher_overrides = Some(WatcherOverrides {
+                use_filesystem_events: Some(watcher.use_filesystem_events),
+                scan_interval_seconds: Some(watcher.scan_interval_seconds),
+                stability_window_seconds: Some(watcher.stability_window(instrument.vendor)),
+                stabilization_timeout_seconds: Some(watcher.stabilization_timeout_seconds),
+                lock_free_checks: Some(watcher.lock_free_checks),
+                skip_exclusive_open: watcher.skip_exclusive_open,
+                abort_marker_patterns: Some(watcher.abort_marker_patterns),
+            });
+        }
+
+        let mut value =
+            toml::Value::try_from(&config).context("Failed to serialize configuration")?;
+        redact_secrets(&mut value);
+
+        // Back through the struct, so the output keeps its field order
+        let redacted: Config = value
+            .try_into()
+            .context("Failed to serialize configuration")?;
+        toml::to_string_pretty(&redacted).context("Failed to serialize configuration")
+    }
+
     /// Validate the configuration.
     fn validate(&self) -> Result<()> {
         // Validate instruments
@@ -84,6 +187,88 @@ impl Config {
             if inst.template.is_empty() {
                 anyhow::bail!("Instrument '{}' has empty template", inst.id);
             }
+            if inst.quarantine_dir.as_deref() == Some("") {
+                anyhow::bail!("Instrument '{}' has empty quarantine_dir", inst.id);
+            }
+            if inst.worklist_pattern.as_deref() == Some("") {
+                anyhow::bail!("Instrument '{}' has empty worklist_pattern", inst.id);
+            }
+            if let Some(ref fields) = inst.filename_fields {
+                if fields.delimiter.is_empty() {
+                    anyhow::bail!(
+                        "Instrument '{}' has empty filename_fields.delimiter",
+                        inst.id
+                    );
+                }
+                if fields.control.is_none() && fields.well.is_none() {
+                    anyhow::bail!(
+                        "Instrument '{}' filename_fields needs a control or well index",
+                        inst.id
+                    );
+                }
+            }
+            if inst.expected_targets.iter().any(|t| t.trim().is_empty()) {
+                anyhow::bail!(
+                    "Instrument '{}' has an empty expected_targets entry",
+                    inst.id
+                );
+            }
+            if inst.excluded_targets.iter().any(|t| t.trim().is_empty()) {
+                anyhow::bail!(
+                    "Instrument '{}' has an empty excluded_targets entry",
+                    inst.id
+                );
+            }
+            for command in [&inst.on_success_command, &inst.on_failure_command]
+                .into_iter()
+                .flatten()
+            {
+                crate::hooks::validate_command(command)
+                    .with_context(|| format!("Instrument '{}'", inst.id))?;
+            }
+        }
+
+        // Instruments uploading to the same endpoint must use the same
+        // credentials, or runs land in whichever tenant the token belongs to
+        let mut auth_by_endpoint: HashMap<&str, (&str, Option<&str>)> = HashMap::new();
+        for inst in &self.instruments {
+            let project_id = inst.project_id.as_deref();
+            if project_id.is_some_and(|p| p.trim().is_empty()) {
+                anyhow::bail!("Instrument '{}' has empty project_id", inst.id);
+            }
+            let endpoint = self.cloud.project_endpoint(project_id);
+            let token = self.cloud.project_token(project_id);
+            let (other, other_token) = *auth_by_endpoint
+                .entry(endpoint)
+                .or_insert((inst.id.as_str(), token));
+            if other_token != token {
+                anyhow::bail!(
+                    "Instruments '{}' and '{}' upload to {} with different credentials; \
+                     give their projects the same api_token or separate endpoints",
+                    other,
+                    inst.id,
+                    endpoint
+                );
+            }
+        }
+
+        for alias in self.classifier.control_aliases.keys() {
+            if alias.trim().is_empty() {
+                anyhow::bail!("Empty alias in classifier.control_aliases");
+            }
+        }
+
+        // Validate custom HTTP headers
+        self.cloud.header_map()?;
+
+        if self.cloud.token_url.is_some()
+            && (self.cloud.client_id.is_none() || self.cloud.client_secret.is_none())
+        {
+            anyhow::bail!("cloud.token_url requires cloud.client_id and cloud.client_secret");
+        }
+
+        if let Some(ref schedule) = self.active_schedule {
+            schedule.validate()?;
         }
 
         Ok(())
@@ -99,11 +284,59 @@ impl Default for Config {
             skyline: SkylineConfig::default(),
             watcher: WatcherConfig::default(),
             spool: SpoolConfig::default(),
+            comparison: ComparisonConfig::default(),
+            replicates: ReplicateConfig::default(),
+            reinjection: ReinjectionConfig::default(),
+            empty_import: EmptyImportConfig::default(),
+            classifier: ClassifierConfig::default(),
+            active_schedule: None,
+            influx: None,
+            drop_queue: None,
             instruments: Vec::new(),
         }
     }
 }
 
+/// Placeholder for masked secrets.
+pub const REDACTED: &str = "<redacted>";
+
+/// Where secrets live in a config document, as paths of table keys; `*`
+/// stands for every key of a table.
+const SECRET_KEYS: &[&[&str]] = &[
+    &["cloud", "api_token"],
+    &["cloud", "client_secret"],
+    &["cloud", "certificate_thumbprint"],
+    &["cloud", "extra_headers", "*"],
+    &["cloud", "projects", "*", "api_token"],
+    &["influx", "token"],
+];
+
+/// Mask every secret present in a config document.
+///
+/// The one list of secrets for anything that shows a config outside the
+/// agent: `mdqc config show`, the payload config hash and support bundles.
+pub fn redact_secrets(value: &mut toml::Value) {
+    for path in SECRET_KEYS {
+        redact_path(value, path);
+    }
+}
+
+fn redact_path(value: &mut toml::Value, path: &[&str]) {
+    let Some((key, rest)) = path.split_first() else {
+        *value = toml::Value::String(REDACTED.to_string());
+        return;
+    };
+    let Some(table) = value.as_table_mut() else {
+        return;
+    };
+
+    if *key == "*" {
+        table.iter_mut().for_each(|(_, v)| redact_path(v, rest));
+    } else if let Some(v) = table.get_mut(*key) {
+        redact_path(v, rest);
+    }
+}
+
 /// Agent-level configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
@@ -118,6 +351,55 @@ pub struct AgentConfig {
     /// Enable Windows toast notifications
     #[serde(default = "default_notifications_enabled")]
     pub enable_toast_notifications: bool,
+
+    /// Seconds over which same-type processing toasts are collapsed into one
+    /// summary toast, so a backlog drain doesn't flood the notification
+    /// center (0 = one toast per event)
+    #[serde(default = "default_toast_coalesce_seconds")]
+    pub toast_coalesce_seconds: u64,
+
+    /// Always show a separate toast for each failed extraction instead of
+    /// counting it in the summary
+    #[serde(default)]
+    pub toast_each_failure: bool,
+
+    /// Share anonymized usage statistics (opt-in, see `mdqc telemetry preview`)
+    #[serde(default)]
+    pub share_telemetry: bool,
+
+    /// Exit with an error at startup when no instruments are configured,
+    /// instead of idling
+    #[serde(default)]
+    pub fail_on_no_instruments: bool,
+
+    /// Days of run history and classification audit records to keep
+    /// (0 = keep forever)
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u32,
+
+    /// Refuse to start the agent while another agent holds the lock on the
+    /// data directory
+    #[serde(default = "default_single_instance")]
+    pub single_instance: bool,
+
+    /// Check GitHub releases for a newer agent version (tray, `mdqc doctor`,
+    /// `mdqc version --check`)
+    #[serde(default = "default_check_for_updates")]
+    pub check_for_updates: bool,
+
+    /// Skip a QC run whose file name was already processed on the same
+    /// instrument, wherever the earlier file was
+    #[serde(default)]
+    pub dedup_by_filename: bool,
+
+    /// Days of local history `dedup_by_filename` looks back (0 = all of it)
+    #[serde(default = "default_dedup_window_days")]
+    pub dedup_window_days: u32,
+
+    /// On Windows, give long paths in the watcher, extractor work dir and
+    /// spool the `\\?\` extended-length prefix so they work past `MAX_PATH`
+    #[serde(default = "default_extended_length_paths")]
+    pub extended_length_paths: bool,
 }
 
 fn default_agent_id() -> String {
@@ -132,12 +414,46 @@ fn default_notifications_enabled() -> bool {
     true // Enabled by default for better UX
 }
 
+fn default_toast_coalesce_seconds() -> u64 {
+    10
+}
+
+fn default_check_for_updates() -> bool {
+    true
+}
+
+fn default_history_retention_days() -> u32 {
+    365
+}
+
+fn default_single_instance() -> bool {
+    true
+}
+
+fn default_dedup_window_days() -> u32 {
+    30
+}
+
+fn default_extended_length_paths() -> bool {
+    true
+}
+
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
             agent_id: default_agent_id(),
             log_level: default_log_level(),
             enable_toast_notifications: true, // Enabled by default for better UX
+            toast_coalesce_seconds: default_toast_coalesce_seconds(),
+            toast_each_failure: false,
+            share_telemetry: false,
+            fail_on_no_instruments: false,
+            history_retention_days: default_history_retention_days(),
+            single_instance: default_single_instance(),
+            check_for_updates: default_check_for_updates(),
+            dedup_by_filename: false,
+            dedup_window_days: default_dedup_window_days(),
+            extended_length_paths: default_extended_length_paths(),
         }
     }
 }
@@ -153,28 +469,209 @@ pub struct CloudConfig {
     /// Can be a Personal Access Token from MD or an API key
     pub api_token: Option<String>,
 
-    /// Certificate thumbprint (from Windows cert store) for mTLS
+    /// OAuth2 token endpoint for client-credentials auth; when set, short-lived
+    /// Bearer tokens are fetched and refreshed instead of using `api_token`
+    pub token_url: Option<String>,
+
+    /// OAuth2 client ID (with `token_url`)
+    pub client_id: Option<String>,
+
+    /// OAuth2 client secret (with `token_url`)
+    pub client_secret: Option<String>,
+
+    /// Certificate thumbprint for mTLS (Windows cert store, or
+    /// `<data dir>/certs/<thumbprint>.pem` elsewhere)
     pub certificate_thumbprint: Option<String>,
 
     /// Proxy URL (optional)
     pub proxy: Option<String>,
+
+    /// Extra HTTP headers sent with every cloud request (e.g. API gateway keys)
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
+
+    /// PEM bundle of CA certificates to verify the endpoint against, instead
+    /// of the system trust store (optional)
+    pub ca_bundle_path: Option<String>,
+
+    /// Skip server certificate verification entirely (testing only)
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+
+    /// Worker threads for the uploader's own runtime, so extraction load
+    /// can't delay uploads (0 runs uploads on the shared agent runtime)
+    #[serde(default = "default_upload_threads")]
+    pub upload_threads: usize,
+
+    /// Per-project upload routes, keyed by instrument `project_id`
+    #[serde(default)]
+    pub projects: HashMap<String, ProjectRoute>,
+
+    /// Runs classified with lower confidence are still extracted, but kept
+    /// locally instead of being spooled for upload
+    #[serde(default = "default_min_upload_confidence")]
+    pub min_upload_confidence: ClassificationConfidence,
+
+    /// Most targets sent in one payload, to stay under the ingest request
+    /// size limit (0 = no limit)
+    #[serde(default)]
+    pub max_payload_targets: usize,
+
+    /// What to do with a run over `max_payload_targets`
+    #[serde(default)]
+    pub oversized_payloads: OversizedPayloadHandling,
+
+    /// Spool a heartbeat for `{endpoint}heartbeat` this often, so the cloud
+    /// can tell a quiet instrument from a dead agent (0 = off)
+    #[serde(default)]
+    pub heartbeat_interval_minutes: u64,
+
+    /// How spooled payloads are delivered (HTTP to `endpoint` by default)
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+
+    /// Extra attempts at a failed baseline fetch before falling back to the
+    /// cached copy
+    #[serde(default = "default_baseline_fetch_retries")]
+    pub baseline_fetch_retries: u32,
+
+    /// Wait before the first baseline fetch retry, doubled for each after
+    #[serde(default = "default_baseline_fetch_retry_delay_seconds")]
+    pub baseline_fetch_retry_delay_seconds: u64,
+}
+
+/// How the uploader delivers spooled payloads.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum DeliveryMode {
+    /// POST each payload to the ingest endpoint
+    #[default]
+    Http,
+    /// Write payloads as NDJSON batches into a folder another process
+    /// drains; nothing is sent over the network
+    LocalDir { path: PathBuf },
+}
+
+/// Handling of runs with more targets than `max_payload_targets`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedPayloadHandling {
+    /// Spread the targets over several payloads, numbered as parts
+    #[default]
+    Split,
+    /// Send only the targets with the largest peak areas
+    Truncate,
+}
+
+/// Where a project's runs are uploaded, when not to the default endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectRoute {
+    /// Ingest endpoint for the project (defaults to `cloud.endpoint`)
+    pub endpoint: Option<String>,
+
+    /// Bearer token for the project (defaults to the cloud authentication)
+    pub api_token: Option<String>,
 }
 
 fn default_endpoint() -> String {
     "https://qc-ingest.massdynamics.com/v1/".to_string()
 }
 
+fn default_upload_threads() -> usize {
+    1
+}
+
+fn default_baseline_fetch_retries() -> u32 {
+    3
+}
+
+fn default_baseline_fetch_retry_delay_seconds() -> u64 {
+    5
+}
+
+fn default_min_upload_confidence() -> ClassificationConfidence {
+    ClassificationConfidence::Low
+}
+
 impl Default for CloudConfig {
     fn default() -> Self {
         Self {
             endpoint: default_endpoint(),
             api_token: None,
+            token_url: None,
+            client_id: None,
+            client_secret: None,
             certificate_thumbprint: None,
             proxy: None,
+            extra_headers: HashMap::new(),
+            ca_bundle_path: None,
+            insecure_skip_verify: false,
+            upload_threads: default_upload_threads(),
+            projects: HashMap::new(),
+            min_upload_confidence: default_min_upload_confidence(),
+            max_payload_targets: 0,
+            oversized_payloads: OversizedPayloadHandling::default(),
+            heartbeat_interval_minutes: 0,
+            delivery: DeliveryMode::default(),
+            baseline_fetch_retries: default_baseline_fetch_retries(),
+            baseline_fetch_retry_delay_seconds: default_baseline_fetch_retry_delay_seconds(),
         }
     }
 }
 
+impl CloudConfig {
+    /// Endpoint that uploads for `project_id` go to.
+    pub fn project_endpoint(&self, project_id: Option<&str>) -> &str {
+        project_id
+            .and_then(|id| self.projects.get(id))
+            .and_then(|route| route.endpoint.as_deref())
+            .unwrap_or(&self.endpoint)
+    }
+
+    /// Bearer token of `project_id`, when it doesn't use the cloud authentication.
+    pub fn project_token(&self, project_id: Option<&str>) -> Option<&str> {
+        project_id
+            .and_then(|id| self.projects.get(id))
+            .and_then(|route| route.api_token.as_deref())
+    }
+
+    /// Parse `extra_headers` into a header map, rejecting invalid names or values.
+    pub fn header_map(&self) -> Result<reqwest::header::HeaderMap> {
+        use reqwest::header::{HeaderName, HeaderValue};
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &self.extra_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes()).with_context(|| {
+                format!("Invalid header name in cloud.extra_headers: '{}'", name)
+            })?;
+            let header_value = HeaderValue::from_str(value).with_context(|| {
+                format!("Invalid value for header '{}' in cloud.extra_headers", name)
+            })?;
+            headers.insert(header_name, header_value);
+        }
+
+        Ok(headers)
+    }
+
+    /// Load the certificates in `ca_bundle_path` (empty if not configured).
+    pub fn ca_certificates(&self) -> Result<Vec<reqwest::Certificate>> {
+        let Some(ref path) = self.ca_bundle_path else {
+            return Ok(Vec::new());
+        };
+
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read cloud.ca_bundle_path '{}'", path))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid PEM in cloud.ca_bundle_path '{}'", path))?;
+
+        if certs.is_empty() {
+            anyhow::bail!("No certificates found in cloud.ca_bundle_path '{}'", path);
+        }
+
+        Ok(certs)
+    }
+}
+
 /// Skyline configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SkylineConfig {
@@ -185,15 +682,206 @@ pub struct SkylineConfig {
     #[serde(default = "default_skyline_timeout")]
     pub timeout_seconds: u64,
 
+    /// Kill Skyline early if it prints nothing for this long (0 disables)
+    #[serde(default = "default_skyline_idle_timeout")]
+    pub idle_timeout_seconds: u64,
+
+    /// Import each run once without a report before the full extraction,
+    /// so a file Skyline can't read fails in seconds
+    #[serde(default)]
+    pub preflight_probe: bool,
+
+    /// How long the preflight probe may run before Skyline is killed and
+    /// the run counts as unreadable
+    #[serde(default = "default_preflight_timeout")]
+    pub preflight_timeout_seconds: u64,
+
     /// Process priority
     #[serde(default = "default_process_priority")]
     pub process_priority: String,
+
+    /// How to interpret symmetry-family report columns
+    #[serde(default)]
+    pub symmetry_metric_kind: SymmetryMetricKind,
+
+    /// What to do with report rows that share a target id (e.g. a peptide
+    /// with two peaks)
+    #[serde(default)]
+    pub duplicate_targets: DuplicateTargetHandling,
+
+    /// How to hash raw files for the payload (full, chunked, head_tail,
+    /// size_and_mtime, none)
+    #[serde(default)]
+    pub raw_hash_mode: RawHashMode,
+
+    /// Run-level metrics to compute; those left out stay unset in the
+    /// payload (all by default)
+    #[serde(default = "default_computed_metrics")]
+    pub computed_metrics: Vec<ComputedMetric>,
+
+    /// Weights of the run's 0-100 health index (`[skyline.health_index_weights]`);
+    /// no index is computed without them
+    #[serde(default)]
+    pub health_index_weights: Option<HealthIndexWeights>,
+
+    /// Write an auto-discovered SkylineCmd path back into the config file
+    #[serde(default)]
+    pub persist_discovered_path: bool,
+
+    /// When an absolute template path doesn't exist, look for a template of
+    /// the same file name in the template directory
+    #[serde(default = "default_template_basename_fallback")]
+    pub allow_template_basename_fallback: bool,
+}
+
+fn default_template_basename_fallback() -> bool {
+    true
+}
+
+fn default_computed_metrics() -> Vec<ComputedMetric> {
+    ComputedMetric::ALL.to_vec()
+}
+
+impl SkylineConfig {
+    /// Whether `metric` is in `computed_metrics`.
+    pub fn computes(&self, metric: ComputedMetric) -> bool {
+        self.computed_metrics.contains(&metric)
+    }
+
+    /// The explicitly configured SkylineCmd path; `None` for "auto" or unset.
+    pub fn configured_path(&self) -> Option<PathBuf> {
+        self.path
+            .as_ref()
+            .filter(|p| !p.eq_ignore_ascii_case("auto") && !p.is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+/// Interpretation of peak shape columns in the Skyline report.
+///
+/// Skyline can export either a symmetry value (ideal 1.0) or a USP tailing /
+/// asymmetry factor (ideal ~1.0-1.2, >1 means tailing). Column names alone
+/// are not always enough to tell them apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymmetryMetricKind {
+    /// Decide from the column name ("Tailing Factor"/"Asymmetry" vs "Symmetry")
+    #[default]
+    Auto,
+    /// Treat all symmetry-family columns as symmetry
+    Symmetry,
+    /// Treat all symmetry-family columns as tailing factor
+    TailingFactor,
+}
+
+/// Unit of the time columns in a Skyline report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RtUnit {
+    /// Skyline's default
+    #[default]
+    Minutes,
+    /// Some custom reports export times in seconds
+    Seconds,
+}
+
+impl RtUnit {
+    /// Factor that converts a time in this unit to minutes.
+    pub fn to_minutes(self) -> f64 {
+        match self {
+            RtUnit::Minutes => 1.0,
+            RtUnit::Seconds => 1.0 / 60.0,
+        }
+    }
+}
+
+/// Run-level metric that can be switched off to save time on large panels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputedMetric {
+    /// Targets found/expected and recovery. Always computed, as the payload
+    /// requires them; accepted so an allow-list can name it
+    Recovery,
+    /// Median RT shift
+    RtShift,
+    /// Median mass error
+    MassError,
+    /// Chromatography score from peak width, symmetry and mass accuracy
+    ChromatographyScore,
+    /// Deltas against the previous run (`comparison.include_previous_run`)
+    Comparison,
+}
+
+impl ComputedMetric {
+    pub const ALL: [ComputedMetric; 5] = [
+        ComputedMetric::Recovery,
+        ComputedMetric::RtShift,
+        ComputedMetric::MassError,
+        ComputedMetric::ChromatographyScore,
+        ComputedMetric::Comparison,
+    ];
+}
+
+/// Relative weights of the health index inputs. Weights need not sum to 1;
+/// an input a run lacks is left out and the others share its weight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthIndexWeights {
+    /// Target recovery
+    #[serde(default = "default_health_recovery_weight")]
+    pub recovery: f64,
+    /// Median mass error (0 ppm scores 1, 10 ppm or more scores 0)
+    #[serde(default = "default_health_minor_weight")]
+    pub mass_accuracy: f64,
+    /// Chromatography score
+    #[serde(default = "default_health_minor_weight")]
+    pub chromatography: f64,
+    /// Within tolerance of the instrument's cached active baseline
+    #[serde(default = "default_health_minor_weight")]
+    pub baseline_tolerance: f64,
+}
+
+fn default_health_recovery_weight() -> f64 {
+    0.4
+}
+
+fn default_health_minor_weight() -> f64 {
+    0.2
+}
+
+impl Default for HealthIndexWeights {
+    fn default() -> Self {
+        Self {
+            recovery: default_health_recovery_weight(),
+            mass_accuracy: default_health_minor_weight(),
+            chromatography: default_health_minor_weight(),
+            baseline_tolerance: default_health_minor_weight(),
+        }
+    }
+}
+
+/// Handling of report rows that produce the same target id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateTargetHandling {
+    /// Keep every row; repeats get an occurrence suffix ("X#2", "X#3")
+    #[default]
+    Disambiguate,
+    /// Keep only the row with the largest peak area
+    KeepLargest,
 }
 
 fn default_skyline_timeout() -> u64 {
     300
 }
 
+fn default_skyline_idle_timeout() -> u64 {
+    120
+}
+
+fn default_preflight_timeout() -> u64 {
+    30
+}
+
 fn default_process_priority() -> String {
     "below_normal".to_string()
 }
@@ -203,7 +891,17 @@ impl Default for SkylineConfig {
         Self {
             path: None,
             timeout_seconds: default_skyline_timeout(),
+            idle_timeout_seconds: default_skyline_idle_timeout(),
+            preflight_probe: false,
+            preflight_timeout_seconds: default_preflight_timeout(),
             process_priority: default_process_priority(),
+            symmetry_metric_kind: SymmetryMetricKind::default(),
+            duplicate_targets: DuplicateTargetHandling::default(),
+            raw_hash_mode: RawHashMode::default(),
+            computed_metrics: default_computed_metrics(),
+            health_index_weights: None,
+            persist_discovered_path: false,
+            allow_template_basename_fallback: default_template_basename_fallback(),
         }
     }
 }
@@ -219,13 +917,37 @@ pub struct WatcherConfig {
     #[serde(default = "default_scan_interval")]
     pub scan_interval_seconds: u64,
 
-    /// Stability window before processing in seconds
-    #[serde(default = "default_stability_window")]
-    pub stability_window_seconds: u64,
+    /// Stability window before processing in seconds, for every vendor
+    /// (unset = each vendor's default window)
+    #[serde(default)]
+    pub stability_window_seconds: Option<u64>,
+
+    /// Replacements for the built-in per-vendor stability windows, in
+    /// seconds, used when `stability_window_seconds` is unset
+    #[serde(default)]
+    pub vendor_stability_window_seconds: HashMap<Vendor, u64>,
 
     /// Maximum stabilization wait in seconds
     #[serde(default = "default_stabilization_timeout")]
     pub stabilization_timeout_seconds: u64,
+
+    /// Consecutive checks a vendor lock file must be absent before the run
+    /// counts as complete (guards against journals briefly re-created on commit)
+    #[serde(default = "default_lock_free_checks")]
+    pub lock_free_checks: u32,
+
+    /// Skip the exclusive-open test and finalize on stability and vendor
+    /// checks alone (unset = skip only on network paths, where SMB oplocks
+    /// can make the open fail for complete files)
+    #[serde(default)]
+    pub skip_exclusive_open: Option<bool>,
+
+    /// Files whose presence means the acquisition was aborted; such runs are
+    /// dropped instead of extracted. Globs relative to the run's folder, with
+    /// `{stem}` (run name without extension) and `{name}` (full run name).
+    /// Empty (off) by default
+    #[serde(default)]
+    pub abort_marker_patterns: Vec<String>,
 }
 
 fn default_true() -> bool {
@@ -236,21 +958,84 @@ fn default_scan_interval() -> u64 {
     30
 }
 
-fn default_stability_window() -> u64 {
-    60
+/// Built-in stability window for `vendor`'s runs, in seconds. Directory
+/// formats are written piece by piece over the whole acquisition and can sit
+/// unchanged between writes, so they wait longer than single files.
+pub fn default_stability_window(vendor: Vendor) -> u64 {
+    if vendor.is_directory_format() {
+        180
+    } else {
+        60
+    }
 }
 
 fn default_stabilization_timeout() -> u64 {
     600
 }
 
+fn default_lock_free_checks() -> u32 {
+    3
+}
+
 impl Default for WatcherConfig {
     fn default() -> Self {
         Self {
             use_filesystem_events: true,
             scan_interval_seconds: default_scan_interval(),
-            stability_window_seconds: default_stability_window(),
+            stability_window_seconds: None,
+            vendor_stability_window_seconds: HashMap::new(),
             stabilization_timeout_seconds: default_stabilization_timeout(),
+            lock_free_checks: default_lock_free_checks(),
+            skip_exclusive_open: None,
+            abort_marker_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Per-instrument watcher settings; each field set here replaces the global
+/// `[watcher]` value, unset fields fall back to it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatcherOverrides {
+    pub use_filesystem_events: Option<bool>,
+    pub scan_interval_seconds: Option<u64>,
+    pub stability_window_seconds: Option<u64>,
+    pub stabilization_timeout_seconds: Option<u64>,
+    pub lock_free_checks: Option<u32>,
+    pub skip_exclusive_open: Option<bool>,
+    pub abort_marker_patterns: Option<Vec<String>>,
+}
+
+impl WatcherConfig {
+    /// Stability window for `vendor`'s runs: `stability_window_seconds` when
+    /// set, else the vendor's configured or built-in default.
+    pub fn stability_window(&self, vendor: Vendor) -> u64 {
+        self.stability_window_seconds
+            .or_else(|| self.vendor_stability_window_seconds.get(&vendor).copied())
+            .unwrap_or_else(|| default_stability_window(vendor))
+    }
+
+    /// Apply an instrument's overrides on top of this (global) config.
+    pub fn with_overrides(&self, overrides: &WatcherOverrides) -> WatcherConfig {
+        WatcherConfig {
+            use_filesystem_events: overrides
+                .use_filesystem_events
+                .unwrap_or(self.use_filesystem_events),
+            scan_interval_seconds: overrides
+                .scan_interval_seconds
+                .unwrap_or(self.scan_interval_seconds),
+            stability_window_seconds: overrides
+                .stability_window_seconds
+                .or(self.stability_window_seconds),
+            vendor_stability_window_seconds: self.vendor_stability_window_seconds.clone(),
+            stabilization_timeout_seconds: overrides
+                .stabilization_timeout_seconds
+                .unwrap_or(self.stabilization_timeout_seconds),
+            lock_free_checks: overrides.lock_free_checks.unwrap_or(self.lock_free_checks),
+            skip_exclusive_open: overrides.skip_exclusive_open.or(self.skip_exclusive_open),
+            abort_marker_patterns: overrides
+                .abort_marker_patterns
+                .clone()
+                .unwrap_or_else(|| self.abort_marker_patterns.clone()),
         }
     }
 }
@@ -269,6 +1054,11 @@ pub struct SpoolConfig {
     /// Number of completed items to retain
     #[serde(default = "default_completed_retention")]
     pub completed_retention_count: usize,
+
+    /// Minimum free disk space (MB) on the data drive before extracting or
+    /// spooling; below it, processing is deferred (0 disables the check)
+    #[serde(default = "default_min_free_disk_mb")]
+    pub min_free_disk_mb: u64,
 }
 
 fn default_max_pending_mb() -> u64 {
@@ -283,16 +1073,291 @@ fn default_completed_retention() -> usize {
     10
 }
 
+fn default_min_free_disk_mb() -> u64 {
+    500
+}
+
 impl Default for SpoolConfig {
     fn default() -> Self {
         Self {
             max_pending_mb: default_max_pending_mb(),
             max_age_days: default_max_age_days(),
             completed_retention_count: default_completed_retention(),
+            min_free_disk_mb: default_min_free_disk_mb(),
         }
     }
 }
 
+/// Baseline comparison configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonConfig {
+    /// A target is an outlier when its area ratio to baseline deviates from
+    /// 1.0 by more than this (0.5 = more than 50% up or down)
+    #[serde(default = "default_area_ratio_outlier_threshold")]
+    pub area_ratio_outlier_threshold: f64,
+
+    /// Flag outliers on log2 fold-change instead of the linear area ratio,
+    /// so high- and low-abundance targets are judged alike
+    #[serde(default)]
+    pub comparison_log_space: bool,
+
+    /// With `comparison_log_space`, a target is an outlier when its |log2
+    /// fold-change| vs baseline exceeds this (1.0 = more than 2-fold)
+    #[serde(default = "default_log2_fold_change_outlier_threshold")]
+    pub log2_fold_change_outlier_threshold: f64,
+
+    /// A run is within tolerance only if the standard deviation of its RT
+    /// shifts vs baseline (in minutes) is below this, and it has no outliers
+    #[serde(default = "default_rt_shift_std_tolerance")]
+    pub rt_shift_std_tolerance: f64,
+
+    /// A run passes acceptance when at least this percentage of its targets
+    /// is recovered (pass rates in `mdqc report`)
+    #[serde(default = "default_min_target_recovery_pct")]
+    pub min_target_recovery_pct: f64,
+
+    /// Attach deltas against the previous run of the same control type on
+    /// the instrument (`comparison_metrics.vs_previous`)
+    #[serde(default)]
+    pub include_previous_run: bool,
+
+    /// A run has dropped in sensitivity when its total ion current (or base
+    /// peak intensity, without a TIC) is below this fraction of the
+    /// baseline's (0.5 = less than half)
+    #[serde(default = "default_min_intensity_ratio")]
+    pub min_intensity_ratio: f64,
+}
+
+fn default_area_ratio_outlier_threshold() -> f64 {
+    0.5
+}
+
+fn default_log2_fold_change_outlier_threshold() -> f64 {
+    1.0
+}
+
+fn default_rt_shift_std_tolerance() -> f64 {
+    0.5
+}
+
+fn default_min_target_recovery_pct() -> f64 {
+    80.0
+}
+
+fn default_min_intensity_ratio() -> f64 {
+    0.5
+}
+
+impl Default for ComparisonConfig {
+    fn default() -> Self {
+        Self {
+            area_ratio_outlier_threshold: default_area_ratio_outlier_threshold(),
+            comparison_log_space: false,
+            log2_fold_change_outlier_threshold: default_log2_fold_change_outlier_threshold(),
+            rt_shift_std_tolerance: default_rt_shift_std_tolerance(),
+            min_target_recovery_pct: default_min_target_recovery_pct(),
+            include_previous_run: false,
+            min_intensity_ratio: default_min_intensity_ratio(),
+        }
+    }
+}
+
+/// Run classification configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassifierConfig {
+    /// Site-specific names for control types, e.g. `iRT = "QC_A"`. Matched
+    /// as whole filename tokens, case-insensitively
+    #[serde(default)]
+    pub control_aliases: HashMap<String, ControlType>,
+}
+
+/// Replicate precision configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicateConfig {
+    /// Compute peak-area CV across recent runs of the same control type
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Number of most recent runs (including the current one) in the window
+    #[serde(default = "default_replicate_window")]
+    pub replicate_window: usize,
+
+    /// Warn when the run-level median area CV (%) exceeds this
+    #[serde(default = "default_median_cv_warning_pct")]
+    pub median_cv_warning_pct: f64,
+}
+
+fn default_replicate_window() -> usize {
+    5
+}
+
+fn default_median_cv_warning_pct() -> f64 {
+    20.0
+}
+
+impl Default for ReplicateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            replicate_window: default_replicate_window(),
+            median_cv_warning_pct: default_median_cv_warning_pct(),
+        }
+    }
+}
+
+/// Re-injection detection configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReinjectionConfig {
+    /// Tag runs that repeat the same control type and well on an instrument
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// A run injected within this many minutes of the previous one from the
+    /// same well is a re-injection
+    #[serde(default = "default_reinjection_window_minutes")]
+    pub window_minutes: u64,
+}
+
+fn default_reinjection_window_minutes() -> u64 {
+    120
+}
+
+impl Default for ReinjectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_minutes: default_reinjection_window_minutes(),
+        }
+    }
+}
+
+/// Suspect empty import detection configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmptyImportConfig {
+    /// Flag runs extracted implausibly fast with few targets recovered
+    #[serde(default = "default_empty_import_enabled")]
+    pub enabled: bool,
+
+    /// Number of recent extraction times per instrument in the rolling median
+    #[serde(default = "default_empty_import_window")]
+    pub window: usize,
+
+    /// Runs needed in the window before any run is judged
+    #[serde(default = "default_empty_import_min_history")]
+    pub min_history: usize,
+
+    /// A run faster than this fraction of the median is implausibly fast
+    #[serde(default = "default_empty_import_max_time_fraction")]
+    pub max_time_fraction: f64,
+
+    /// ...and is only suspect if its target recovery (%) is at most this
+    #[serde(default = "default_empty_import_max_recovery_pct")]
+    pub max_recovery_pct: f64,
+}
+
+fn default_empty_import_enabled() -> bool {
+    true
+}
+
+fn default_empty_import_window() -> usize {
+    20
+}
+
+fn default_empty_import_min_history() -> usize {
+    5
+}
+
+fn default_empty_import_max_time_fraction() -> f64 {
+    0.2
+}
+
+fn default_empty_import_max_recovery_pct() -> f64 {
+    20.0
+}
+
+impl Default for EmptyImportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_empty_import_enabled(),
+            window: default_empty_import_window(),
+            min_history: default_empty_import_min_history(),
+            max_time_fraction: default_empty_import_max_time_fraction(),
+            max_recovery_pct: default_empty_import_max_recovery_pct(),
+        }
+    }
+}
+
+/// Active-hours schedule (local time).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveSchedule {
+    /// Days the schedule applies to (e.g. "Mon", "Tue")
+    #[serde(default = "default_active_days")]
+    pub days: Vec<chrono::Weekday>,
+
+    /// Window start, "HH:MM"
+    pub start: String,
+
+    /// Window end, "HH:MM" (before `start` for windows spanning midnight)
+    pub end: String,
+}
+
+fn default_active_days() -> Vec<chrono::Weekday> {
+    use chrono::Weekday::*;
+    vec![Mon, Tue, Wed, Thu, Fri]
+}
+
+/// InfluxDB v2 connection for exporting run metrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfluxConfig {
+    /// Base URL of the InfluxDB server (e.g. "http://influx:8086")
+    pub url: String,
+
+    /// API token with write access to the bucket
+    pub token: String,
+
+    /// Destination bucket
+    pub bucket: String,
+
+    /// Organization owning the bucket
+    pub org: String,
+}
+
+/// Drop-queue directory polled for job files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DropQueueConfig {
+    /// Directory external systems write `*.json` job files into
+    pub path: PathBuf,
+
+    /// Seconds between scans of the directory
+    #[serde(default = "default_drop_queue_scan_seconds")]
+    pub scan_interval_seconds: u64,
+}
+
+fn default_drop_queue_scan_seconds() -> u64 {
+    5
+}
+
+/// Which SAMPLE runs get a carryover check, and when carryover is too high.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleCarryoverConfig {
+    /// Check every Nth SAMPLE run (0 = not by count)
+    #[serde(default)]
+    pub every_nth: u32,
+
+    /// Check the first SAMPLE run after each QC run
+    #[serde(default)]
+    pub after_qc: bool,
+
+    /// Warn when the QC targets' area in the sample exceeds this percentage
+    /// of their area in the instrument's last QC run
+    #[serde(default = "default_max_carryover_pct")]
+    pub max_carryover_pct: f64,
+}
+
+fn default_max_carryover_pct() -> f64 {
+    1.0
+}
+
 /// Instrument configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InstrumentConfig {
@@ -302,6 +1367,44 @@ pub struct InstrumentConfig {
     /// Vendor type
     pub vendor: Vendor,
 
+    /// How far runs are taken: `full` (extract and upload), `observe_only`
+    /// (extract, keep the payload locally) or `detect_only` (classify and log)
+    #[serde(default)]
+    pub mode: InstrumentMode,
+
+    /// Cloud project (tenant) the instrument's runs belong to; sent as
+    /// `X-Project-Id` and routed by `cloud.projects`
+    #[serde(default)]
+    pub project_id: Option<String>,
+
+    /// Overrides `cloud.min_upload_confidence` for this instrument
+    #[serde(default)]
+    pub min_upload_confidence: Option<ClassificationConfidence>,
+
+    /// Skip runs acquired before this time (e.g. a go-live date). A date
+    /// without a time means local midnight.
+    #[serde(default, deserialize_with = "deserialize_cutoff")]
+    pub process_acquired_after: Option<DateTime<Utc>>,
+
+    /// File the acquisition software writes when a run has finished. When
+    /// set, a run counts as complete only once it exists, instead of by the
+    /// vendor heuristics. A glob relative to the run's folder, with `{stem}`
+    /// and `{name}` as for `abort_marker_patterns` (e.g. `{stem}.done`, or
+    /// `{name}/complete.txt` inside a run folder)
+    #[serde(default)]
+    pub completion_sentinel: Option<String>,
+
+    /// Consumables in use (e.g. `column_serial`, `emitter_lot`), attached to
+    /// every run's payload. A `.mdqc_context.json` in the run's folder
+    /// overrides them per key
+    #[serde(default)]
+    pub consumables: HashMap<String, String>,
+
+    /// Check some SAMPLE runs for carryover of the QC targets instead of
+    /// skipping them all (disabled if unset)
+    #[serde(default)]
+    pub sample_carryover_check: Option<SampleCarryoverConfig>,
+
     /// Path to watch for raw files
     pub watch_path: String,
 
@@ -314,9 +1417,659 @@ pub struct InstrumentConfig {
 
     /// Vendor-specific watcher overrides
     #[serde(default)]
-    pub watcher_overrides: Option<WatcherConfig>,
+    pub watcher_overrides: Option<WatcherOverrides>,
+
+    /// Reprocess recent runs on startup when the template has changed
+    #[serde(default)]
+    pub reprocess_on_template_change: bool,
+
+    /// Number of most recent runs to reprocess after a template change
+    #[serde(default = "default_reprocess_count")]
+    pub reprocess_count: usize,
+
+    /// Directory to move files into once they fail more than `max_retries` times
+    #[serde(default)]
+    pub quarantine_dir: Option<String>,
+
+    /// Failed extraction attempts allowed before a file is quarantined
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Glob (relative to `watch_path`) for the autosampler worklist CSV used
+    /// to classify runs, e.g. "*_sequence.csv"
+    #[serde(default)]
+    pub worklist_pattern: Option<String>,
+
+    /// Positional filename layout; when set, fields are read by position
+    /// before falling back to scanning the whole name
+    #[serde(default)]
+    pub filename_fields: Option<FilenameFields>,
+
+    /// Peptide sequences the template should report; when set, recovery is
+    /// measured against this list rather than the report's rows
+    #[serde(default)]
+    pub expected_targets: Vec<String>,
+
+    /// Peptide sequences known to be unreliable; they stay in the per-target
+    /// detail (flagged `excluded`) but don't count towards recovery or the
+    /// chromatography score
+    #[serde(default)]
+    pub excluded_targets: Vec<String>,
+
+    /// Unit of the time columns (retention time, expected RT, RT delta,
+    /// FWHM) in this instrument's Skyline report. Normalised to minutes
+    #[serde(default)]
+    pub rt_unit: RtUnit,
+
+    /// Also pick up zipped runs (`*.d.zip`, `*.raw.zip`); they are unpacked
+    /// under the spool's `work/` directory for extraction
+    #[serde(default)]
+    pub allow_zipped: bool,
+
+    /// Largest unpacked size allowed for a zipped run, in MB
+    #[serde(default = "default_max_unzipped_mb")]
+    pub max_unzipped_mb: u64,
+
+    /// Command run after a run is extracted and spooled, e.g.
+    /// `notify.exe --run {run_id} "{path}"` (see `hooks::PLACEHOLDERS`)
+    #[serde(default)]
+    pub on_success_command: Option<String>,
+
+    /// Command run after a run fails classification, extraction or spooling
+    #[serde(default)]
+    pub on_failure_command: Option<String>,
+
+    /// How long a hook command may run before it is stopped
+    #[serde(default = "default_hook_timeout")]
+    pub hook_timeout_seconds: u64,
+}
+
+/// How far the agent takes an instrument's runs.
+///
+/// Lets a site run the agent alongside an existing QC process during
+/// validation, with nothing sent to the cloud for some instruments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstrumentMode {
+    /// Classify, extract, spool and upload
+    #[default]
+    Full,
+    /// Classify and extract; the payload is kept under `spool/observed/`
+    /// and never uploaded
+    ObserveOnly,
+    /// Classify and record the decision; no extraction
+    DetectOnly,
+}
+
+impl std::fmt::Display for InstrumentMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            InstrumentMode::Full => "full",
+            InstrumentMode::ObserveOnly => "observe_only",
+            InstrumentMode::DetectOnly => "detect_only",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Positional filename layout, e.g. `{date}_{well}_{control}_{instrument}`
+/// is `delimiter = "_"`, `date = 0`, `well = 1`, `control = 2`.
+///
+/// Indices are zero-based fields of the file stem. A split control token
+/// such as `QC_A` counts as one field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilenameFields {
+    /// Field separator
+    #[serde(default = "default_filename_delimiter")]
+    pub delimiter: String,
+
+    /// Field holding the control type (SSC0, QC_A, QC_B, BLANK, SAMPLE)
+    #[serde(default)]
+    pub control: Option<usize>,
+
+    /// Field holding the well position (e.g. A1)
+    #[serde(default)]
+    pub well: Option<usize>,
+
+    /// Field holding the plate ID
+    #[serde(default)]
+    pub plate: Option<usize>,
+
+    /// Field holding the acquisition date, used to confirm the layout matches
+    #[serde(default)]
+    pub date: Option<usize>,
+}
+
+fn default_filename_delimiter() -> String {
+    "_".to_string()
 }
 
 fn default_file_pattern() -> String {
     "*".to_string()
 }
+
+fn default_hook_timeout() -> u64 {
+    60
+}
+
+fn default_max_unzipped_mb() -> u64 {
+    20_480
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_reprocess_count() -> usize {
+    5
+}
+
+impl InstrumentConfig {
+    /// An instrument with every optional setting at its default.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub fn new(id: &str, vendor: Vendor, watch_path: &str, template: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            vendor,
+            mode: InstrumentMode::default(),
+            project_id: None,
+            min_upload_confidence: None,
+            process_acquired_after: None,
+            completion_sentinel: None,
+            consumables: HashMap::new(),
+            sample_carryover_check: None,
+            watch_path: watch_path.to_string(),
+            file_pattern: default_file_pattern(),
+            template: template.to_string(),
+            watcher_overrides: None,
+            reprocess_on_template_change: false,
+            reprocess_count: default_reprocess_count(),
+            quarantine_dir: None,
+            max_retries: default_max_retries(),
+            worklist_pattern: None,
+            filename_fields: None,
+            expected_targets: Vec::new(),
+            excluded_targets: Vec::new(),
+            rt_unit: RtUnit::default(),
+            allow_zipped: false,
+            max_unzipped_mb: default_max_unzipped_mb(),
+            on_success_command: None,
+            on_failure_command: None,
+            hook_timeout_seconds: default_hook_timeout(),
+        }
+    }
+
+    /// Effective watcher settings: the global config with this instrument's
+    /// overrides applied.
+    pub fn watcher_config(&self, global: &WatcherConfig) -> WatcherConfig {
+        match self.watcher_overrides {
+            Some(ref overrides) => global.with_overrides(overrides),
+            None => global.clone(),
+        }
+    }
+
+    /// Lowest classification confidence uploaded for this instrument.
+    pub fn min_upload_confidence(&self, cloud: &CloudConfig) -> ClassificationConfidence {
+        self.min_upload_confidence
+            .unwrap_or(cloud.min_upload_confidence)
+    }
+
+    /// Whether a run acquired at `acquired_at` predates
+    /// `process_acquired_after`. Runs with no acquisition time in the report
+    /// are never skipped.
+    pub fn acquired_before_cutoff(&self, acquired_at: Option<DateTime<Utc>>) -> bool {
+        matches!(
+            (self.process_acquired_after, acquired_at),
+            (Some(cutoff), Some(acquired_at)) if acquired_at < cutoff
+        )
+    }
+}
+
+/// Parse a cutoff given as RFC 3339, a local date and time, or a local date
+/// (midnight).
+fn parse_cutoff(value: &str) -> Option<DateTime<Utc>> {
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Accept a cutoff as a quoted string or a bare TOML date/datetime.
+fn deserialize_cutoff<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Cutoff {
+        Text(String),
+        Toml(toml::value::Datetime),
+    }
+
+    let text = match Option::<Cutoff>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Cutoff::Text(text)) => text,
+        Some(Cutoff::Toml(datetime)) => datetime.to_string(),
+    };
+    parse_cutoff(&text).map(Some).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "invalid date '{}' (use e.g. \"2026-10-01\" or \"2026-10-01T08:00:00Z\")",
+            text
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_hash_ignores_layout_path_and_secrets() {
+        let config: Config = toml::from_str(
+            r#"
+[cloud]
+api_token = "token-a"
+extra_headers = { "X-Gateway-Key" = "key-a", "X-Site" = "lab-1" }
+
+[watcher]
+scan_interval_seconds = 30
+stability_window_seconds = 60
+
+[influx]
+url = "http://influx:8086"
+org = "lab"
+bucket = "qc"
+token = "influx-a"
+"#,
+        )
+        .unwrap();
+
+        // Same settings: reordered, defaults spelled out, other secrets and path
+        let mut equivalent: Config = toml::from_str(
+            r#"
+# Same watcher, written differently
+[watcher]
+stability_window_seconds = 60
+scan_interval_seconds = 30
+
+[spool]
+max_pending_mb = 1000
+
+[influx]
+bucket = "qc"
+org = "lab"
+token = "influx-b"
+url = "http://influx:8086"
+
+[cloud]
+extra_headers = { "X-Site" = "lab-2", "X-Gateway-Key" = "key-b" }
+api_token = "token-b"
+"#,
+        )
+        .unwrap();
+        equivalent.path = PathBuf::from("/elsewhere/config.toml");
+        assert_eq!(config.config_hash(), equivalent.config_hash());
+
+        // A setting that changes behavior changes the hash
+        let mut changed = config.clone();
+        changed.watcher.stability_window_seconds = Some(90);
+        assert_ne!(config.config_hash(), changed.config_hash());
+    }
+
+    #[test]
+    fn test_absent_profile_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging = dir.path().join("config.staging.toml");
+
+        let err = Config::load_profile(&staging, Some("staging")).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Config profile 'staging' not found"));
+
+        std::fs::write(&staging, "").unwrap();
+        assert!(Config::load_profile(&staging, Some("staging")).is_ok());
+    }
+
+    #[test]
+    fn test_watcher_overrides_merge_per_field() {
+        let config: Config = toml::from_str(
+            r#"
+[watcher]
+scan_interval_seconds = 45
+stability_window_seconds = 120
+
+[[instruments]]
+id = "TIMSTOF01"
+vendor = "bruker"
+watch_path = "D:\\Data\\TIMSTOF01"
+template = "qc.sky"
+
+[instruments.watcher_overrides]
+scan_interval_seconds = 10
+
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "D:\\Data\\Exploris"
+template = "qc.sky"
+"#,
+        )
+        .unwrap();
+
+        // The override takes effect; everything else is the global value
+        let effective = config.instruments[0].watcher_config(&config.watcher);
+        assert_eq!(effective.scan_interval_seconds, 10);
+        assert_eq!(effective.stability_window_seconds, Some(120));
+        assert_eq!(
+            effective.stabilization_timeout_seconds,
+            config.watcher.stabilization_timeout_seconds
+        );
+        assert!(effective.use_filesystem_events);
+
+        // No overrides: global as-is
+        let effective = config.instruments[1].watcher_config(&config.watcher);
+        assert_eq!(effective.scan_interval_seconds, 45);
+        assert_eq!(effective.stability_window_seconds, Some(120));
+    }
+
+    #[test]
+    fn test_stability_window_defaults_per_vendor() {
+        let mut config: Config = toml::from_str(
+            r#"
+[[instruments]]
+id = "TIMSTOF01"
+vendor = "bruker"
+watch_path = "D:\\Data\\TIMSTOF01"
+template = "qc.sky"
+
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "D:\\Data\\Exploris"
+template = "qc.sky"
+"#,
+        )
+        .unwrap();
+        let window = |config: &Config, i: usize| {
+            let instrument = &config.instruments[i];
+            instrument
+                .watcher_config(&config.watcher)
+                .stability_window(instrument.vendor)
+        };
+
+        // Nothing configured: the directory format waits longer
+        assert_eq!(window(&config, 0), 180);
+        assert_eq!(window(&config, 1), 60);
+
+        // A vendor's default can be replaced
+        config.watcher =
+            toml::from_str("vendor_stability_window_seconds = { bruker = 300 }").unwrap();
+        assert_eq!(window(&config, 0), 300);
+        assert_eq!(window(&config, 1), 60);
+
+        // An explicit global window applies to every vendor
+        config.watcher.stability_window_seconds = Some(90);
+        assert_eq!(window(&config, 0), 90);
+        assert_eq!(window(&config, 1), 90);
+    }
+
+    #[test]
+    fn test_redact_secrets_masks_every_secret() {
+        let mut value: toml::Value = r#"
+[cloud]
+endpoint = "https://example.com/"
+api_token = "token"
+client_secret = "oauth-secret"
+certificate_thumbprint = "AB12"
+extra_headers = { "X-Gateway-Key" = "key" }
+
+[cloud.projects.acme]
+api_token = "acme-token"
+endpoint = "https://acme.example.com/"
+
+[influx]
+url = "http://influx:8086"
+token = "influx-token"
+"#
+        .parse()
+        .unwrap();
+        redact_secrets(&mut value);
+
+        let cloud = &value["cloud"];
+        for secret in [
+            &cloud["api_token"],
+            &cloud["client_secret"],
+            &cloud["certificate_thumbprint"],
+            &cloud["extra_headers"]["X-Gateway-Key"],
+            &cloud["projects"]["acme"]["api_token"],
+            &value["influx"]["token"],
+        ] {
+            assert_eq!(secret.as_str(), Some(REDACTED));
+        }
+        assert_eq!(cloud["endpoint"].as_str(), Some("https://example.com/"));
+        assert_eq!(
+            cloud["projects"]["acme"]["endpoint"].as_str(),
+            Some("https://acme.example.com/")
+        );
+    }
+
+    #[test]
+    fn test_effective_toml_merges_overrides_and_masks_secrets() {
+        let config: Config = toml::from_str(
+            r#"
+[cloud]
+api_token = "token-a"
+extra_headers = { "X-Gateway-Key" = "key-a" }
+
+[watcher]
+scan_interval_seconds = 45
+
+[[instruments]]
+id = "TIMSTOF01"
+vendor = "bruker"
+watch_path = "D:\\Data\\TIMSTOF01"
+template = "qc.sky"
+
+[instruments.watcher_overrides]
+stability_window_seconds = 300
+"#,
+        )
+        .unwrap();
+
+        let effective = config.effective_toml().unwrap();
+        let value: toml::Value = toml::from_str(&effective).unwrap();
+
+        // Merged watcher: the override plus the global and default values
+        let watcher = &value["instruments"][0]["watcher_overrides"];
+        assert_eq!(watcher["stability_window_seconds"].as_integer(), Some(300));
+        assert_eq!(watcher["scan_interval_seconds"].as_integer(), Some(45));
+        assert_eq!(watcher["lock_free_checks"].as_integer(), Some(3));
+
+        // Defaults that the file never mentions are filled in
+        assert_eq!(value["spool"]["max_pending_mb"].as_integer(), Some(1000));
+
+        assert!(!effective.contains("token-a"));
+        assert!(!effective.contains("key-a"));
+        assert_eq!(value["cloud"]["api_token"].as_str(), Some("<redacted>"));
+        assert!(value["cloud"].get("client_secret").is_none());
+
+        // The output is itself a config with the same effective settings
+        let reloaded: Config = toml::from_str(&effective).unwrap();
+        let merged = reloaded.instruments[0].watcher_config(&reloaded.watcher);
+        assert_eq!(merged.stability_window_seconds, Some(300));
+        assert_eq!(merged.scan_interval_seconds, 45);
+    }
+
+    #[test]
+    fn test_process_acquired_after_cutoff() {
+        use chrono::{Local, TimeZone};
+
+        let config: Config = toml::from_str(
+            r#"
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "/data/exploris01"
+template = "qc.sky"
+process_acquired_after = "2026-10-01T08:00:00Z"
+
+[[instruments]]
+id = "EXPLORIS02"
+vendor = "thermo"
+watch_path = "/data/exploris02"
+template = "qc.sky"
+process_acquired_after = 2026-10-01
+
+[[instruments]]
+id = "EXPLORIS03"
+vendor = "thermo"
+watch_path = "/data/exploris03"
+template = "qc.sky"
+"#,
+        )
+        .unwrap();
+
+        let at = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc));
+        let exact = &config.instruments[0];
+        assert!(exact.acquired_before_cutoff(at("2026-10-01T07:59:59Z")));
+        assert!(!exact.acquired_before_cutoff(at("2026-10-01T08:00:00Z")));
+        // Unknown acquisition time: processed
+        assert!(!exact.acquired_before_cutoff(None));
+
+        // A bare date is local midnight
+        let midnight = Local.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            config.instruments[1].process_acquired_after,
+            Some(midnight.with_timezone(&Utc))
+        );
+
+        assert!(!config.instruments[2].acquired_before_cutoff(at("2000-01-01T00:00:00Z")));
+
+        let invalid = toml::from_str::<Config>(
+            r#"
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "/data/exploris01"
+template = "qc.sky"
+process_acquired_after = "last tuesday"
+"#,
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_min_upload_confidence_per_instrument() {
+        let config: Config = toml::from_str(
+            r#"
+[cloud]
+min_upload_confidence = "MEDIUM"
+
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "/data/exploris01"
+template = "qc.sky"
+
+[[instruments]]
+id = "EXPLORIS02"
+vendor = "thermo"
+min_upload_confidence = "LOW"
+watch_path = "/data/exploris02"
+template = "qc.sky"
+"#,
+        )
+        .unwrap();
+
+        // Low-confidence runs are only uploaded where the instrument allows it
+        let inherited = config.instruments[0].min_upload_confidence(&config.cloud);
+        assert_eq!(inherited, ClassificationConfidence::Medium);
+        assert!(!ClassificationConfidence::Low.meets(inherited));
+        assert!(ClassificationConfidence::Medium.meets(inherited));
+        assert!(ClassificationConfidence::High.meets(inherited));
+
+        let overridden = config.instruments[1].min_upload_confidence(&config.cloud);
+        assert!(ClassificationConfidence::Low.meets(overridden));
+
+        // Everything is uploaded by default
+        assert_eq!(
+            CloudConfig::default().min_upload_confidence,
+            ClassificationConfidence::Low
+        );
+    }
+
+    #[test]
+    fn test_project_routes_need_compatible_auth_per_endpoint() {
+        let config = |projects: &str| -> Config {
+            toml::from_str(&format!(
+                r#"
+[cloud]
+endpoint = "https://qc-ingest.example.com/v1/"
+api_token = "shared"
+{}
+
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+project_id = "acme"
+watch_path = "/data/exploris01"
+template = "qc.sky"
+
+[[instruments]]
+id = "EXPLORIS02"
+vendor = "thermo"
+watch_path = "/data/exploris02"
+template = "qc.sky"
+"#,
+                projects
+            ))
+            .unwrap()
+        };
+
+        // A project without a route shares the default endpoint and token
+        let shared = config("");
+        assert!(shared.validate().is_ok());
+        assert_eq!(
+            shared.cloud.project_endpoint(Some("acme")),
+            "https://qc-ingest.example.com/v1/"
+        );
+        assert_eq!(shared.cloud.project_token(Some("acme")), None);
+
+        // Its own endpoint and token route it to another tenant
+        let routed = config(
+            r#"[cloud.projects.acme]
+endpoint = "https://acme.example.com/v1/"
+api_token = "acme-token""#,
+        );
+        assert!(routed.validate().is_ok());
+        assert_eq!(
+            routed.cloud.project_endpoint(Some("acme")),
+            "https://acme.example.com/v1/"
+        );
+        assert_eq!(routed.cloud.project_token(Some("acme")), Some("acme-token"));
+
+        // A different token on the shared endpoint is rejected
+        let conflicting = config(
+            r#"[cloud.projects.acme]
+api_token = "acme-token""#,
+        );
+        let err = conflicting.validate().unwrap_err().to_string();
+        assert!(err.contains("different credentials"), "{}", err);
+
+        // Project tokens are secrets
+        assert!(!routed.effective_toml().unwrap().contains("acme-token"));
+    }
+}
@@ -2,13 +2,35 @@
 //!
 //! Defines standard locations for configuration, logs, spool, and templates.
 
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-/// Base data directory for the agent.
+/// Config profile selected with `--profile`.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Whether `long_path` may add the extended-length prefix
+/// (`agent.extended_length_paths`).
+static EXTENDED_LENGTH_PATHS: AtomicBool = AtomicBool::new(true);
+
+/// Paths at least this long get the extended-length prefix. Well under
+/// Windows' 260-character `MAX_PATH`, so the vendor files and temp names
+/// joined onto a prefixed folder stay reachable too.
+const LONG_PATH_THRESHOLD: usize = 200;
+
+/// Base directory of the agent install, holding the config files of every
+/// profile.
 ///
 /// On Windows: `C:\ProgramData\MassDynamics\QC`
-/// On other platforms: `~/.local/share/massdynamics/qc` (for development)
-pub fn data_dir() -> PathBuf {
+/// On other platforms: `~/.local/share/massdynamics/qc`
+///
+/// `MDQC_DATA_DIR` overrides the default, e.g. for headless Linux deployments.
+pub fn base_dir() -> PathBuf {
+    if let Ok(path) = std::env::var("MDQC_DATA_DIR") {
+        return PathBuf::from(path);
+    }
+
     #[cfg(windows)]
     {
         PathBuf::from(r"C:\ProgramData\MassDynamics\QC")
@@ -16,7 +38,6 @@ pub fn data_dir() -> PathBuf {
 
     #[cfg(not(windows))]
     {
-        // For development on macOS/Linux
         directories::ProjectDirs::from("com", "MassDynamics", "QC")
             .map(|p| p.data_dir().to_path_buf())
             .unwrap_or_else(|| {
@@ -30,16 +51,53 @@ pub fn data_dir() -> PathBuf {
     }
 }
 
+/// Data directory for the agent's state: spool, history, queues, logs and
+/// the instance lock.
+///
+/// The base directory, or `profiles\<profile>` under it when a profile is
+/// selected, so profiles never share payloads or state and can run side by
+/// side.
+pub fn data_dir() -> PathBuf {
+    profile_data_dir(&base_dir(), profile())
+}
+
+/// Data directory for `profile` under `base`.
+pub fn profile_data_dir(base: &Path, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => base.join("profiles").join(name),
+        None => base.to_path_buf(),
+    }
+}
+
+/// Select a config profile for this process. Only the first call takes effect.
+pub fn set_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+/// The selected config profile, if any.
+pub fn profile() -> Option<&'static str> {
+    PROFILE.get().map(String::as_str)
+}
+
 /// Configuration file path.
 ///
-/// On Windows: `C:\ProgramData\MassDynamics\QC\config.toml`
+/// On Windows: `C:\ProgramData\MassDynamics\QC\config.toml`, or
+/// `config.<profile>.toml` when a profile is selected.
 pub fn config_file() -> PathBuf {
     // Check environment variable first
     if let Ok(path) = std::env::var("MDQC_CONFIG") {
         return PathBuf::from(path);
     }
 
-    data_dir().join("config.toml")
+    profile_config_file(&base_dir(), profile())
+}
+
+/// Config file for `profile` in `dir`.
+pub fn profile_config_file(dir: &Path, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => dir.join(format!("config.{}.toml", name)),
+        None => dir.join("config.toml"),
+    }
 }
 
 /// Log directory.
@@ -82,10 +140,107 @@ pub fn spool_completed_dir() -> PathBuf {
 ///
 /// On Windows: `C:\ProgramData\MassDynamics\QC\templates`
 pub fn template_dir() -> PathBuf {
-    data_dir().join("templates")
+    base_dir().join("templates")
+}
+
+/// Persisted agent ID file.
+///
+/// On Windows: `C:\ProgramData\MassDynamics\QC\agent_id`
+pub fn agent_id_file() -> PathBuf {
+    data_dir().join("agent_id")
+}
+
+/// Start Menu shortcut the tray creates for toast notifications.
+///
+/// On Windows: `%APPDATA%\Microsoft\Windows\Start Menu\Programs\MD QC Agent.lnk`
+pub fn start_menu_shortcut() -> Option<PathBuf> {
+    let appdata = std::env::var_os("APPDATA")?;
+    Some(
+        PathBuf::from(appdata)
+            .join("Microsoft")
+            .join("Windows")
+            .join("Start Menu")
+            .join("Programs")
+            .join("MD QC Agent.lnk"),
+    )
+}
+
+/// Enable or disable the extended-length prefix for this process.
+pub fn set_extended_length_paths(enabled: bool) {
+    EXTENDED_LENGTH_PATHS.store(enabled, Ordering::Relaxed);
+}
+
+/// `path` as passed to filesystem calls.
+///
+/// On Windows, a long absolute path gets the `\\?\` prefix (`\\?\UNC\` for
+/// network shares) so deep acquisition folders don't hit `MAX_PATH` whether
+/// or not long paths are enabled system-wide. Short, relative and already
+/// prefixed paths, and every path on other platforms, are returned as is.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    if !cfg!(windows)
+        || !EXTENDED_LENGTH_PATHS.load(Ordering::Relaxed)
+        || path.as_os_str().len() < LONG_PATH_THRESHOLD
+    {
+        return Cow::Borrowed(path);
+    }
+
+    match path.to_str().and_then(extended_length) {
+        Some(prefixed) => Cow::Owned(PathBuf::from(prefixed)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Extended-length form of an absolute Windows path, or `None` when it
+/// can't have one (relative, drive-relative, already prefixed).
+///
+/// The prefix turns off Windows' path normalization, so separators are
+/// made backslashes and `.` and `..` components are resolved here.
+fn extended_length(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+
+    let normalized = path.replace('/', "\\");
+    let (mut prefixed, rest) = if let Some(unc) = normalized.strip_prefix(r"\\") {
+        let mut parts = unc.splitn(3, '\\');
+        let server = parts.next().filter(|s| !s.is_empty())?;
+        let share = parts.next().filter(|s| !s.is_empty())?;
+        (
+            format!(r"\\?\UNC\{}\{}", server, share),
+            parts.next().unwrap_or_default(),
+        )
+    } else {
+        let bytes = normalized.as_bytes();
+        if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || &bytes[1..3] != b":\\" {
+            return None;
+        }
+        (format!(r"\\?\{}", &normalized[..2]), &normalized[3..])
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+
+    for component in &components {
+        prefixed.push('\\');
+        prefixed.push_str(component);
+    }
+    if components.is_empty() && !normalized.starts_with(r"\\") {
+        // Drive root: `\\?\C:\`
+        prefixed.push('\\');
+    }
+    Some(prefixed)
 }
 
 /// Ensure all required directories exist.
+#[cfg_attr(not(windows), allow(dead_code))]
 pub fn ensure_directories() -> std::io::Result<()> {
     std::fs::create_dir_all(data_dir())?;
     std::fs::create_dir_all(log_dir()?)?;
@@ -109,4 +264,67 @@ mod tests {
         let _ = spool_dir();
         let _ = template_dir();
     }
+
+    #[test]
+    fn test_profile_config_file() {
+        let dir = Path::new("/data");
+        assert_eq!(profile_config_file(dir, None), dir.join("config.toml"));
+        assert_eq!(
+            profile_config_file(dir, Some("dev")),
+            dir.join("config.dev.toml")
+        );
+
+        // Each profile keeps its state apart from the default's
+        assert_eq!(profile_data_dir(dir, None), dir);
+        assert_eq!(
+            profile_data_dir(dir, Some("dev")),
+            dir.join("profiles").join("dev")
+        );
+    }
+
+    #[test]
+    fn test_extended_length_prefix() {
+        let prefixed = |path: &str| extended_length(path);
+
+        // Drive letters, with either separator and `.`/`..` resolved
+        assert_eq!(
+            prefixed(r"D:\Data\2026\QC_A_A1.raw").as_deref(),
+            Some(r"\\?\D:\Data\2026\QC_A_A1.raw")
+        );
+        assert_eq!(
+            prefixed(r"c:/Data/./runs/../2026//QC.d/").as_deref(),
+            Some(r"\\?\c:\Data\2026\QC.d")
+        );
+        assert_eq!(prefixed(r"C:\").as_deref(), Some(r"\\?\C:\"));
+
+        // UNC shares
+        assert_eq!(
+            prefixed(r"\\nas01\instruments\EXPLORIS01\QC_A_A1.raw").as_deref(),
+            Some(r"\\?\UNC\nas01\instruments\EXPLORIS01\QC_A_A1.raw")
+        );
+        assert_eq!(
+            prefixed("//nas01/instruments/..").as_deref(),
+            Some(r"\\?\UNC\nas01\instruments")
+        );
+        assert_eq!(prefixed(r"\\nas01"), None);
+
+        // Already prefixed, device, relative and drive-relative paths
+        assert_eq!(prefixed(r"\\?\D:\Data\QC_A_A1.raw"), None);
+        assert_eq!(prefixed(r"\\?\UNC\nas01\share\QC.raw"), None);
+        assert_eq!(prefixed(r"\\.\pipe\mdqc"), None);
+        assert_eq!(prefixed(r"Data\QC_A_A1.raw"), None);
+        assert_eq!(prefixed("D:QC_A_A1.raw"), None);
+        assert_eq!(prefixed("/mnt/data/QC_A_A1.raw"), None);
+
+        // Short paths are left alone, and nothing changes off Windows
+        let short = Path::new(r"D:\Data\QC_A_A1.raw");
+        assert_eq!(long_path(short), short);
+        let deep = format!(r"D:\{}\QC_A_A1.raw", "Nested\\".repeat(30));
+        assert_eq!(
+            long_path(Path::new(&deep))
+                .to_string_lossy()
+                .starts_with(r"\\?\"),
+            cfg!(windows)
+        );
+    }
 }
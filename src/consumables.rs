@@ -0,0 +1,269 @@
+//! Consumable lots attached to each run.
+//!
+//! QC drift often lines up with a hardware change: a new LC column, emitter
+//! or trap. An instrument's `[instruments.consumables]` table holds static
+//! values (`column_serial = "..."`); a `.mdqc_context.json` file in the run's
+//! folder, e.g. `{"consumables": {"emitter_lot": "E-2231"}}`, overrides them
+//! per key, so acquisition software or a technician can update it on a swap.
+//! The resolved values go into the payload, and a value that differs from
+//! the instrument's previous run is logged as a consumable change.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use crate::config::paths;
+
+/// Sidecar file read from the run's folder.
+pub const CONTEXT_FILE: &str = ".mdqc_context.json";
+
+/// Contents of a `.mdqc_context.json` sidecar.
+#[derive(Debug, Default, Deserialize)]
+struct RunContext {
+    #[serde(default)]
+    consumables: HashMap<String, String>,
+}
+
+/// The consumables of a run: the configured values, overridden by the
+/// sidecar next to `raw_path` when there is one.
+pub fn resolve(configured: &HashMap<String, String>, raw_path: &Path) -> HashMap<String, String> {
+    let mut consumables = configured.clone();
+
+    let Some(sidecar) = raw_path.parent().map(|dir| dir.join(CONTEXT_FILE)) else {
+        return consumables;
+    };
+    let Ok(content) = std::fs::read_to_string(&sidecar) else {
+        return consumables;
+    };
+    match serde_json::from_str::<RunContext>(&content) {
+        Ok(context) => consumables.extend(context.consumables),
+        Err(e) => warn!(
+            path = %sidecar.display(),
+            error = %e,
+            "Ignoring unreadable run context file"
+        ),
+    }
+
+    consumables
+}
+
+/// A consumable whose value differs from the instrument's previous run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumableChange {
+    pub key: String,
+    /// `None` when the consumable wasn't recorded before
+    pub previous: Option<String>,
+    /// `None` when the consumable is no longer recorded
+    pub current: Option<String>,
+}
+
+impl std::fmt::Display for ConsumableChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |v: &Option<String>| v.clone().unwrap_or_else(|| "(none)".to_string());
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            value(&self.previous),
+            value(&self.current)
+        )
+    }
+}
+
+/// Store of the consumables of each instrument's last run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsumablesStore {
+    /// Consumables by instrument ID
+    pub instruments: HashMap<String, HashMap<String, String>>,
+}
+
+impl ConsumablesStore {
+    /// Load the store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("consumables.json")
+    }
+
+    /// Remember a run's consumables and return how they differ from the
+    /// instrument's previous run, sorted by key. Nothing has changed on the
+    /// first run recorded for an instrument.
+    pub fn replace(
+        &mut self,
+        instrument_id: &str,
+        consumables: &HashMap<String, String>,
+    ) -> Vec<ConsumableChange> {
+        let Some(previous) = self.instruments.get(instrument_id) else {
+            if !consumables.is_empty() {
+                self.instruments
+                    .insert(instrument_id.to_string(), consumables.clone());
+            }
+            return Vec::new();
+        };
+
+        let keys: BTreeSet<&String> = previous.keys().chain(consumables.keys()).collect();
+        let changes: Vec<_> = keys
+            .into_iter()
+            .filter(|key| previous.get(*key) != consumables.get(*key))
+            .map(|key| ConsumableChange {
+                key: key.clone(),
+                previous: previous.get(key).cloned(),
+                current: consumables.get(key).cloned(),
+            })
+            .collect();
+
+        if !changes.is_empty() {
+            self.instruments
+                .insert(instrument_id.to_string(), consumables.clone());
+        }
+        changes
+    }
+}
+
+/// Thread-safe consumable change tracker.
+#[derive(Clone)]
+pub struct ConsumablesTracker {
+    inner: Arc<Mutex<ConsumablesStore>>,
+}
+
+impl ConsumablesTracker {
+    /// Create a tracker, loading the last consumables from disk
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ConsumablesStore::load().unwrap_or_default())),
+        }
+    }
+
+    /// Record a run's consumables, returning the ones that changed since the
+    /// instrument's previous run.
+    pub fn record(
+        &self,
+        instrument_id: &str,
+        consumables: &HashMap<String, String>,
+    ) -> Vec<ConsumableChange> {
+        let mut store = self.inner.lock().unwrap();
+        let was_known = store.instruments.contains_key(instrument_id);
+        let changes = store.replace(instrument_id, consumables);
+        if !changes.is_empty() || (!was_known && !consumables.is_empty()) {
+            let _ = store.save();
+        }
+        changes
+    }
+}
+
+impl Default for ConsumablesTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sidecar_overrides_configured_consumables() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("QC_A_A1.raw");
+        let configured = map(&[("column_serial", "COL-1"), ("trap", "T-7")]);
+
+        // No sidecar: the configured values
+        assert_eq!(resolve(&configured, &raw), configured);
+
+        std::fs::write(
+            dir.path().join(CONTEXT_FILE),
+            r#"{"consumables": {"column_serial": "COL-2", "emitter_lot": "E-2231"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            resolve(&configured, &raw),
+            map(&[
+                ("column_serial", "COL-2"),
+                ("trap", "T-7"),
+                ("emitter_lot", "E-2231"),
+            ])
+        );
+
+        // A broken sidecar is ignored
+        std::fs::write(dir.path().join(CONTEXT_FILE), "{not json").unwrap();
+        assert_eq!(resolve(&configured, &raw), configured);
+    }
+
+    #[test]
+    fn test_consumable_changes_between_runs() {
+        let mut store = ConsumablesStore::default();
+        let first = map(&[("column_serial", "COL-1"), ("trap", "T-7")]);
+
+        // The first run has nothing to compare against
+        assert!(store.replace("EXPLORIS01", &first).is_empty());
+        assert!(store.replace("EXPLORIS01", &first).is_empty());
+
+        // Column swapped, emitter lot recorded for the first time
+        let second = map(&[
+            ("column_serial", "COL-2"),
+            ("trap", "T-7"),
+            ("emitter_lot", "E-2231"),
+        ]);
+        assert_eq!(
+            store.replace("EXPLORIS01", &second),
+            [
+                ConsumableChange {
+                    key: "column_serial".to_string(),
+                    previous: Some("COL-1".to_string()),
+                    current: Some("COL-2".to_string()),
+                },
+                ConsumableChange {
+                    key: "emitter_lot".to_string(),
+                    previous: None,
+                    current: Some("E-2231".to_string()),
+                },
+            ]
+        );
+
+        // Dropped from the sidecar
+        let third = map(&[("column_serial", "COL-2"), ("trap", "T-7")]);
+        let changes = store.replace("EXPLORIS01", &third);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, "emitter_lot");
+        assert_eq!(changes[0].current, None);
+
+        // Instruments are tracked separately
+        assert!(store
+            .replace("TIMSTOF01", &map(&[("column_serial", "X")]))
+            .is_empty());
+    }
+}
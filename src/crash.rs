@@ -123,7 +123,7 @@ fn show_crash_dialog(report: &str, crash_file: Option<&str>) {
 }
 
 #[cfg(not(windows))]
-fn show_crash_dialog(report: &str, crash_file: Option<&str>) {
+fn show_crash_dialog(_report: &str, crash_file: Option<&str>) {
     eprintln!("MD QC Agent crashed!");
     if let Some(f) = crash_file {
         eprintln!("Crash report saved to: {}", f);
@@ -134,6 +134,7 @@ fn show_crash_dialog(report: &str, crash_file: Option<&str>) {
     );
 }
 
+#[cfg(windows)]
 fn open_github_issue(report: &str) {
     let version = env!("CARGO_PKG_VERSION");
 
@@ -158,6 +159,7 @@ fn open_github_issue(report: &str) {
         .spawn();
 }
 
+#[cfg(windows)]
 fn build_issue_body(report: &str) -> String {
     // Truncate backtrace to keep URL reasonable (max ~2000 chars for body)
     let truncated_report = if report.len() > 1500 {
@@ -195,6 +197,7 @@ The application crashed unexpectedly.
 }
 
 /// Simple URL encoding module
+#[cfg(windows)]
 mod urlencoding {
     pub fn encode(input: &str) -> String {
         let mut result = String::with_capacity(input.len() * 3);
@@ -0,0 +1,116 @@
+//! Free disk space checks.
+//!
+//! Skyline and the spool both write under the data directory. Before either
+//! runs, the agent checks that the volume has at least `min_free_disk_mb`
+//! free and defers work instead of failing halfway through a write.
+
+use anyhow::Result;
+use std::path::Path;
+use tracing::debug;
+
+/// Free space available to this process on the volume holding `path`, in MB.
+///
+/// `path` need not exist yet; its nearest existing ancestor is checked.
+pub fn free_space_mb(path: &Path) -> Result<u64> {
+    let path = path.ancestors().find(|p| p.exists()).unwrap_or(path);
+    Ok(free_space_bytes(path)? / (1024 * 1024))
+}
+
+/// Check that the volume holding `path` has at least `min_free_mb` free.
+///
+/// Returns the free space in MB when it is below the minimum. If free space
+/// can't be determined the check passes, so a failing API never blocks work.
+pub fn ensure_free_space(path: &Path, min_free_mb: u64) -> Result<(), u64> {
+    if min_free_mb == 0 {
+        return Ok(());
+    }
+
+    let free_mb = match free_space_mb(path) {
+        Ok(free_mb) => Some(free_mb),
+        Err(e) => {
+            debug!(path = %path.display(), error = %e, "Could not determine free disk space");
+            None
+        }
+    };
+
+    check_free_space(free_mb, min_free_mb)
+}
+
+/// Compare a free-space reading against the minimum.
+fn check_free_space(free_mb: Option<u64>, min_free_mb: u64) -> Result<(), u64> {
+    match free_mb {
+        Some(free_mb) if free_mb < min_free_mb => Err(free_mb),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn free_space_bytes(path: &Path) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut free_bytes: u64 = 0;
+
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free_bytes,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(free_bytes)
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // statvfs field widths vary by platform
+fn free_space_bytes(path: &Path) -> Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_free_space() {
+        // Below the minimum: defer, reporting what's free
+        assert_eq!(check_free_space(Some(120), 500), Err(120));
+
+        // At or above the minimum
+        assert_eq!(check_free_space(Some(500), 500), Ok(()));
+        assert_eq!(check_free_space(Some(80_000), 500), Ok(()));
+
+        // Unknown free space never blocks processing
+        assert_eq!(check_free_space(None, 500), Ok(()));
+
+        // A zero minimum disables the guard
+        assert_eq!(ensure_free_space(Path::new("/nonexistent/dir"), 0), Ok(()));
+    }
+
+    #[test]
+    fn test_free_space_of_missing_dir_uses_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not").join("created");
+
+        assert!(free_space_mb(&missing).is_ok());
+    }
+}
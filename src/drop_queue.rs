@@ -0,0 +1,259 @@
+//! Drop-queue of job files.
+//!
+//! Integrations that would rather hand runs to the agent explicitly than rely
+//! on folder watching write a small JSON job into `[drop_queue] path`:
+//!
+//! ```json
+//! {
+//!   "path": "D:\\Exports\\20260127_plate7_A3.raw",
+//!   "instrument": "EXPLORIS01",
+//!   "control_type": "QC_A",
+//!   "well_position": "A3",
+//!   "plate_id": "P-7"
+//! }
+//! ```
+//!
+//! Only `path` and `instrument` are required; the other fields override what
+//! the classifier would read from the file name. The run doesn't have to be
+//! under the instrument's watch path. A valid job queues the run for
+//! processing and is moved to `archive/`; an invalid one is moved to
+//! `rejected/` with the reason logged. Writers should create the job under
+//! another extension and rename it to `.json` once complete.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::{DropQueueConfig, InstrumentConfig};
+use crate::types::{ControlType, FinalizationState, RunOverrides, TrackedFile, WellPosition};
+
+/// Folder accepted jobs are moved to.
+const ARCHIVE_DIR: &str = "archive";
+
+/// Folder invalid jobs are moved to.
+const REJECTED_DIR: &str = "rejected";
+
+/// A job file as written by an integration.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DropJob {
+    path: PathBuf,
+    instrument: String,
+    #[serde(default)]
+    control_type: Option<String>,
+    #[serde(default)]
+    well_position: Option<String>,
+    #[serde(default)]
+    plate_id: Option<String>,
+}
+
+/// Read and validate a job file, returning the run to queue.
+fn read_job(job_path: &Path, instruments: &[InstrumentConfig]) -> Result<TrackedFile, String> {
+    let content = std::fs::read_to_string(job_path).map_err(|e| e.to_string())?;
+    let job: DropJob =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid job file: {}", e))?;
+
+    let instrument = instruments
+        .iter()
+        .find(|i| i.id == job.instrument)
+        .ok_or_else(|| format!("Unknown instrument '{}'", job.instrument))?;
+
+    let extension = job
+        .path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    if !instrument
+        .vendor
+        .extensions()
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(extension))
+    {
+        return Err(format!(
+            "{} is not a {} run",
+            job.path.display(),
+            instrument.vendor
+        ));
+    }
+
+    let metadata = std::fs::metadata(&job.path)
+        .map_err(|e| format!("Cannot read {}: {}", job.path.display(), e))?;
+
+    let control_type = job
+        .control_type
+        .map(|token| {
+            ControlType::from_token(&token)
+                .ok_or_else(|| format!("Unknown control type '{}'", token))
+        })
+        .transpose()?;
+    let well_position = job
+        .well_position
+        .map(|well| {
+            WellPosition::from_str(&well).ok_or_else(|| format!("Invalid well position '{}'", well))
+        })
+        .transpose()?;
+
+    let mut file = TrackedFile::new(
+        job.path,
+        instrument.vendor,
+        metadata.len(),
+        metadata.modified().ok().map(Into::into),
+    );
+    file.state = FinalizationState::Ready;
+    file.stable_since = Some(file.first_seen);
+    file.finalized_at = Some(file.first_seen);
+    file.instrument_id = Some(instrument.id.clone());
+    file.overrides = Some(RunOverrides {
+        control_type,
+        well_position,
+        plate_id: job.plate_id,
+    });
+    Ok(file)
+}
+
+/// Job files waiting in the queue directory, oldest name first.
+fn pending_jobs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut jobs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+        })
+        .collect();
+    jobs.sort();
+    jobs
+}
+
+/// Move a handled job file into `subdir` of the queue directory.
+fn move_job(dir: &Path, job_path: &Path, subdir: &str) {
+    let target_dir = dir.join(subdir);
+    let result = std::fs::create_dir_all(&target_dir).and_then(|_| {
+        let name = job_path.file_name().unwrap_or_default();
+        let mut target = target_dir.join(name);
+        if target.exists() {
+            // Integrations may reuse job names; keep both
+            let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S%3f");
+            target = target_dir.join(format!("{}_{}", stamp, name.to_string_lossy()));
+        }
+        std::fs::rename(job_path, target)
+    });
+
+    if let Err(e) = result {
+        warn!(path = %job_path.display(), error = %e, "Failed to move drop-queue job; removing it");
+        let _ = std::fs::remove_file(job_path);
+    }
+}
+
+/// Poll the queue directory and feed each valid job's run to the
+/// processing loop.
+pub async fn run(
+    config: DropQueueConfig,
+    instruments: Vec<InstrumentConfig>,
+    file_tx: mpsc::Sender<TrackedFile>,
+) {
+    if let Err(e) = std::fs::create_dir_all(&config.path) {
+        warn!(path = %config.path.display(), error = %e, "Failed to create drop-queue directory");
+    }
+    info!(path = %config.path.display(), "Watching drop-queue directory for job files");
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(config.scan_interval_seconds.max(1)));
+    loop {
+        interval.tick().await;
+
+        for job_path in pending_jobs(&config.path) {
+            match read_job(&job_path, &instruments) {
+                Ok(file) => {
+                    info!(
+                        job = %job_path.display(),
+                        path = ?file.path,
+                        instrument = file.instrument_id.as_deref().unwrap_or_default(),
+                        "Queued run from drop-queue job"
+                    );
+                    if file_tx.send(file).await.is_err() {
+                        return;
+                    }
+                    move_job(&config.path, &job_path, ARCHIVE_DIR);
+                }
+                Err(reason) => {
+                    warn!(job = %job_path.display(), reason = %reason, "Rejected drop-queue job");
+                    move_job(&config.path, &job_path, REJECTED_DIR);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Vendor;
+
+    fn instrument(watch_path: &Path) -> InstrumentConfig {
+        InstrumentConfig::new(
+            "EXPLORIS01",
+            Vendor::Thermo,
+            &watch_path.to_string_lossy(),
+            "qc.sky",
+        )
+    }
+
+    #[test]
+    fn test_read_job_validates_and_sets_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let instruments = [instrument(&dir.path().join("watch"))];
+        let raw = dir.path().join("plate7_17.raw");
+        std::fs::write(&raw, b"raw").unwrap();
+        let job_path = dir.path().join("job.json");
+        let read = |job: serde_json::Value| {
+            std::fs::write(&job_path, job.to_string()).unwrap();
+            read_job(&job_path, &instruments)
+        };
+
+        let file = read(serde_json::json!({
+            "path": raw,
+            "instrument": "EXPLORIS01",
+            "control_type": "QC_B",
+            "well_position": "a3",
+        }))
+        .unwrap();
+        assert_eq!(file.vendor, Vendor::Thermo);
+        assert_eq!(file.state, FinalizationState::Ready);
+        assert_eq!(file.instrument_id.as_deref(), Some("EXPLORIS01"));
+        let overrides = file.overrides.unwrap();
+        assert_eq!(overrides.control_type, Some(ControlType::QcB));
+        assert_eq!(overrides.well_position, WellPosition::new('A', 3));
+        assert_eq!(overrides.plate_id, None);
+
+        let rejected = |job: serde_json::Value| read(job).unwrap_err();
+        assert!(
+            rejected(serde_json::json!({"path": raw, "instrument": "TIMSTOF01"}))
+                .contains("Unknown instrument")
+        );
+        assert!(rejected(serde_json::json!({
+            "path": dir.path().join("missing.raw"),
+            "instrument": "EXPLORIS01",
+        }))
+        .contains("Cannot read"));
+        assert!(rejected(serde_json::json!({
+            "path": dir.path().join("run.d"),
+            "instrument": "EXPLORIS01",
+        }))
+        .contains("not a thermo run"));
+        assert!(rejected(serde_json::json!({
+            "path": raw,
+            "instrument": "EXPLORIS01",
+            "well_position": "Z99",
+        }))
+        .contains("Invalid well position"));
+        assert!(rejected(serde_json::json!({"path": raw})).contains("Invalid job file"));
+    }
+}
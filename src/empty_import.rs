@@ -0,0 +1,195 @@
+//! Suspect empty import detection.
+//!
+//! Skyline sometimes "extracts" a run in a fraction of the usual time and
+//! exits 0 having imported nothing (e.g. a wrong file association). Such a
+//! run looks like a genuinely bad QC. Each instrument keeps a rolling window
+//! of its recent extraction times; a run far faster than their median that
+//! also recovered few targets is flagged `suspect_empty_import` instead.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::config::{paths, EmptyImportConfig};
+
+/// Store of recent extraction times per instrument.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtractionTimeStore {
+    /// Recent extraction times in ms (oldest first) by instrument ID
+    pub times: HashMap<String, VecDeque<u64>>,
+}
+
+impl ExtractionTimeStore {
+    /// Load the store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("extraction_times.json")
+    }
+
+    /// Median of the instrument's recent extraction times, once there are at
+    /// least `min_history` of them.
+    pub fn median(&self, instrument_id: &str, min_history: usize) -> Option<u64> {
+        let times = self.times.get(instrument_id)?;
+        if times.is_empty() || times.len() < min_history {
+            return None;
+        }
+
+        let mut sorted: Vec<u64> = times.iter().copied().collect();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            Some((sorted[mid - 1] + sorted[mid]) / 2)
+        } else {
+            Some(sorted[mid])
+        }
+    }
+
+    /// Add an extraction time, keeping the last `window` per instrument.
+    pub fn push(&mut self, instrument_id: &str, extraction_time_ms: u64, window: usize) {
+        let times = self.times.entry(instrument_id.to_string()).or_default();
+        times.push_back(extraction_time_ms);
+        while times.len() > window.max(1) {
+            times.pop_front();
+        }
+    }
+
+    /// Check a run against the instrument's rolling median and remember its
+    /// time. Returns the median when the run looks like an empty import.
+    ///
+    /// Suspect runs are not added to the window, so a string of no-op
+    /// imports can't drag the median down to their level.
+    pub fn check(
+        &mut self,
+        instrument_id: &str,
+        extraction_time_ms: u64,
+        target_recovery_pct: f64,
+        config: &EmptyImportConfig,
+    ) -> Option<u64> {
+        let suspect = self
+            .median(instrument_id, config.min_history)
+            .filter(|&median| {
+                (extraction_time_ms as f64) < median as f64 * config.max_time_fraction
+                    && target_recovery_pct <= config.max_recovery_pct
+            });
+
+        if suspect.is_none() {
+            self.push(instrument_id, extraction_time_ms, config.window);
+        }
+        suspect
+    }
+}
+
+/// Thread-safe extraction time tracker. A disabled tracker is a no-op.
+#[derive(Clone)]
+pub struct EmptyImportTracker {
+    config: EmptyImportConfig,
+    inner: Arc<Mutex<ExtractionTimeStore>>,
+}
+
+impl EmptyImportTracker {
+    /// Create a tracker, loading recent extraction times from disk when enabled
+    pub fn new(config: &EmptyImportConfig) -> Self {
+        let store = if config.enabled {
+            ExtractionTimeStore::load().unwrap_or_default()
+        } else {
+            ExtractionTimeStore::default()
+        };
+
+        Self {
+            config: config.clone(),
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record an extracted run and return the instrument's median extraction
+    /// time if the run looks like an empty import.
+    pub fn check(
+        &self,
+        instrument_id: &str,
+        extraction_time_ms: u64,
+        target_recovery_pct: f64,
+    ) -> Option<u64> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let mut store = self.inner.lock().unwrap();
+        let median = store.check(
+            instrument_id,
+            extraction_time_ms,
+            target_recovery_pct,
+            &self.config,
+        );
+        let _ = store.save();
+
+        median
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_low_recovery_run_is_suspect() {
+        let config = EmptyImportConfig::default();
+        let mut store = ExtractionTimeStore::default();
+
+        // Not enough history yet: nothing can be judged
+        for ms in [40_000, 42_000, 38_000, 45_000] {
+            assert_eq!(store.check("EXPLORIS01", ms, 95.0, &config), None);
+        }
+        // A fast run that recovered its targets is genuine and joins the history
+        assert_eq!(store.check("EXPLORIS01", 2_000, 95.0, &config), None);
+        assert_eq!(store.median("EXPLORIS01", config.min_history), Some(40_000));
+
+        // Far below the median with almost nothing recovered
+        assert_eq!(store.check("EXPLORIS01", 1_500, 0.0, &config), Some(40_000));
+        // ...and it stays out of the window
+        assert_eq!(store.times["EXPLORIS01"].len(), 5);
+
+        // Low recovery at a normal speed is a bad QC, not an empty import
+        assert_eq!(store.check("EXPLORIS01", 39_000, 0.0, &config), None);
+
+        // Other instruments have their own history
+        assert_eq!(store.check("TIMSTOF01", 1_500, 0.0, &config), None);
+    }
+
+    #[test]
+    fn test_window_keeps_most_recent_times() {
+        let mut store = ExtractionTimeStore::default();
+        for ms in 1..=30 {
+            store.push("EXPLORIS01", ms * 1_000, 20);
+        }
+        let times = &store.times["EXPLORIS01"];
+        assert_eq!(times.len(), 20);
+        assert_eq!(times.front(), Some(&11_000));
+    }
+}
@@ -82,22 +82,89 @@ pub enum ExtractionError {
     #[error("Skyline not found at: {0}")]
     SkylineNotFound(String),
 
-    #[error("Skyline execution failed: {0}")]
-    SkylineExecution(String),
+    #[error("Could not run Skyline: {0}")]
+    SkylineLaunch(String),
+
+    #[error(
+        "Skyline exited with code {exit_code}: {}",
+        skyline_error_summary(.stderr, .stdout_tail)
+    )]
+    SkylineExecution {
+        exit_code: i32,
+        stderr: String,
+        /// Last lines of stdout (Skyline often reports errors there)
+        stdout_tail: String,
+    },
 
     #[error("Skyline timeout after {0} seconds")]
     SkylineTimeout(u64),
 
+    /// The preflight probe failed; the full extraction was not attempted
+    #[error("Raw file failed the preflight probe: {0}")]
+    Unreadable(String),
+
+    /// No output for the idle timeout; usually an interactive prompt
+    /// (license, update) that will never be answered
+    #[error("Skyline stalled: no output for {0} seconds (waiting on a prompt?)")]
+    SkylineStalled(u64),
+
     #[error("Template not found: {0}")]
     TemplateNotFound(String),
 
     #[error("Vendor reader not available for: {0}")]
     VendorReaderMissing(String),
 
+    #[error("Could not unpack zipped run {0}")]
+    ZipUnpack(String),
+
+    #[error("Could not stage QC runs for batch import: {0}")]
+    BatchStaging(String),
+
     #[error("Report parse error: {0}")]
     ReportParse(String),
 }
 
+/// One-line summary of a failed Skyline run for logs and failed-file reasons.
+///
+/// Uses the first stderr line, else the last stdout line mentioning an error,
+/// else the last stdout line.
+fn skyline_error_summary(stderr: &str, stdout_tail: &str) -> String {
+    const MAX_LEN: usize = 200;
+
+    let non_empty = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let stdout_lines = non_empty(stdout_tail);
+
+    let mut summary = non_empty(stderr)
+        .into_iter()
+        .next()
+        .or_else(|| {
+            stdout_lines
+                .iter()
+                .rev()
+                .find(|l| l.to_lowercase().contains("error"))
+                .cloned()
+        })
+        .or_else(|| stdout_lines.last().cloned())
+        .unwrap_or_else(|| "no output".to_string());
+
+    if summary.chars().count() > MAX_LEN {
+        summary = format!("{}...", summary.chars().take(MAX_LEN).collect::<String>());
+    }
+
+    let all_output = format!("{}\n{}", stderr, stdout_tail);
+    if all_output.contains("does not exist") && all_output.contains("report") {
+        summary.push_str(" (the template needs a report named 'MD_QC_Report'; see README)");
+    }
+
+    summary
+}
+
 #[derive(Error, Debug)]
 pub enum SpoolError {
     #[error("Spool directory not writable: {0}")]
@@ -106,6 +173,9 @@ pub enum SpoolError {
     #[error("Spool full: {0} MB used of {1} MB limit")]
     Full(u64, u64),
 
+    #[error("Low disk space: {0} MB free, {1} MB required")]
+    LowDisk(u64, u64),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -129,6 +199,12 @@ pub enum UploadError {
 
     #[error("Retry exhausted after {0} attempts")]
     RetryExhausted(u32),
+
+    #[error(
+        "Payload with {0} targets is larger than the server accepts (HTTP 413); \
+         set cloud.max_payload_targets below {0} to split large runs"
+    )]
+    PayloadTooLarge(usize),
 }
 
 #[derive(Error, Debug)]
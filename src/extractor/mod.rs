@@ -3,18 +3,31 @@
 //! Invokes SkylineCmd.exe to extract QC metrics from raw files.
 
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::config::{InstrumentConfig, SkylineConfig};
+use crate::config::{
+    ComputedMetric, DuplicateTargetHandling, InstrumentConfig, RtUnit, SkylineConfig,
+    SymmetryMetricKind,
+};
 use crate::error::ExtractionError;
-use crate::types::{ExtractionResult, RunClassification, RunMetrics, TargetMetrics};
-
+use crate::types::{
+    AcquisitionMetadata, ExtractionResult, RunClassification, RunMetrics, TargetMetrics, Vendor,
+    DUPLICATE_TARGET_SEPARATOR,
+};
+
+pub mod pe;
+pub mod probe;
+mod raw_hash;
 pub mod skyline;
+mod staging;
+mod unzip;
 
 /// Extractor for QC metrics.
 pub struct Extractor {
@@ -24,14 +37,8 @@ pub struct Extractor {
 
 impl Extractor {
     pub fn new(config: &SkylineConfig) -> Result<Self> {
-        // Discover Skyline path
-        // Handle "auto" path - treat it as None to trigger auto-discovery
-        let skyline_path = config
-            .path
-            .as_ref()
-            .filter(|p| !p.eq_ignore_ascii_case("auto") && !p.is_empty())
-            .map(PathBuf::from)
-            .or_else(skyline::discover_skyline);
+        // Use the configured path, or discover one for "auto"
+        let skyline_path = config.configured_path().or_else(skyline::discover_skyline);
 
         if skyline_path.is_none() {
             warn!("Skyline not found during extractor initialization");
@@ -50,6 +57,295 @@ impl Extractor {
         instrument: &InstrumentConfig,
         _classification: &RunClassification,
     ) -> Result<ExtractionResult, ExtractionError> {
+        let skyline_path = self.skyline_path()?;
+        let template_path = self.resolve_template(instrument)?;
+
+        // Calculate template hash
+        let template_hash = skyline::hash_template(&template_path)
+            .map_err(|e| ExtractionError::TemplateNotFound(e.to_string()))?;
+
+        // Create temporary output file for the report
+        let run_id = Uuid::new_v4();
+        let report_path = Self::work_dir()?.join(format!("{}_report.csv", run_id));
+
+        // Skyline can't read zipped runs; import an unpacked copy, removed
+        // again when `unpacked` goes out of scope
+        let unpacked = if crate::watcher::is_zipped_raw_file(raw_path, instrument.vendor) {
+            let (zip_path, work_dir) = (raw_path.to_path_buf(), Self::work_dir()?);
+            let max_unzipped_mb = instrument.max_unzipped_mb;
+            let unpacked = tokio::task::spawn_blocking(move || {
+                unzip::unpack(&zip_path, &work_dir, max_unzipped_mb)
+            })
+            .await
+            .map_err(|e| ExtractionError::ZipUnpack(e.to_string()))??;
+            Some(unpacked)
+        } else {
+            None
+        };
+        let import_path = unpacked.as_ref().map_or(raw_path, |u| u.path());
+
+        if self.config.preflight_probe {
+            self.preflight_probe(skyline_path, &template_path, import_path, instrument.vendor)
+                .await?;
+        }
+
+        info!(
+            raw_file = %raw_path.display(),
+            template = %instrument.template,
+            "Starting Skyline extraction"
+        );
+
+        let (extraction_time_ms, import_warnings) = self
+            .run_skyline(
+                skyline_path,
+                &template_path,
+                Some(format!("--import-file={}", import_path.display())),
+                &report_path,
+            )
+            .await?;
+
+        // Parse the report
+        let ParsedReport {
+            targets: mut target_metrics,
+            acquisition,
+            skipped_rows,
+            total_ion_current,
+            base_peak_intensity,
+        } = self.parse_report(&report_path, instrument.rt_unit)?;
+
+        // Calculate run metrics
+        mark_excluded(&mut target_metrics, &instrument.excluded_targets);
+        let mut run_metrics = self.calculate_run_metrics(
+            &target_metrics,
+            &instrument.expected_targets,
+            &instrument.excluded_targets,
+        );
+        run_metrics.total_ion_current = total_ion_current;
+        run_metrics.base_peak_intensity = base_peak_intensity;
+
+        // Get Skyline version
+        let skyline_version =
+            skyline::get_version(skyline_path).unwrap_or_else(|_| "unknown".to_string());
+
+        // Calculate raw file hash
+        let raw_hash_mode = self.config.raw_hash_mode;
+        let raw_file_hash = raw_hash::hash_raw_file(raw_path, instrument.vendor, raw_hash_mode)
+            .unwrap_or_else(|_| "error".to_string());
+
+        // Clean up work file
+        let _ = std::fs::remove_file(crate::config::paths::long_path(&report_path));
+
+        info!(
+            raw_file = %raw_path.display(),
+            targets_found = run_metrics.targets_found,
+            extraction_time_ms = extraction_time_ms,
+            "Extraction complete"
+        );
+
+        Ok(ExtractionResult {
+            run_id,
+            raw_file_path: raw_path.to_path_buf(),
+            raw_file_name: raw_file_name(raw_path),
+            raw_file_hash,
+            raw_file_hash_mode: raw_hash_mode,
+            extraction_time_ms,
+            backend: "skyline".to_string(),
+            backend_version: skyline_version,
+            template_name: instrument.template.clone(),
+            template_hash,
+            target_metrics,
+            run_metrics,
+            import_warnings,
+            acquisition,
+            skipped_rows,
+            // Judged against the instrument's history by the caller
+            suspect_empty_import: false,
+            // Resolved from the config and run folder by the caller
+            consumables: Default::default(),
+        })
+    }
+
+    /// Extract a run keeping only the peptides in `targets`, with recovery
+    /// measured against that list (carryover checks on SAMPLE runs).
+    pub async fn extract_targets(
+        &self,
+        raw_path: &Path,
+        instrument: &InstrumentConfig,
+        classification: &RunClassification,
+        targets: &[String],
+    ) -> Result<ExtractionResult, ExtractionError> {
+        let mut subset = instrument.clone();
+        subset.expected_targets = targets.to_vec();
+
+        let mut result = self.extract(raw_path, &subset, classification).await?;
+        result.target_metrics.retain(|t| {
+            t.peptide_sequence
+                .as_deref()
+                .is_some_and(|seq| targets.iter().any(|e| e.trim().eq_ignore_ascii_case(seq)))
+        });
+        Ok(result)
+    }
+
+    /// Extract QC metrics for many raw files in one Skyline call.
+    ///
+    /// Links `raw_paths` into a staging folder and imports it with
+    /// `--import-all=<folder>` into a single document, so other runs next to
+    /// them (e.g. samples) are never imported. The combined report is split
+    /// back into per-run results using its file name column; a file with no
+    /// rows in the report yields an error entry.
+    pub async fn extract_batch(
+        &self,
+        raw_paths: &[PathBuf],
+        instrument: &InstrumentConfig,
+    ) -> Result<Vec<(PathBuf, Result<ExtractionResult, ExtractionError>)>, ExtractionError> {
+        let skyline_path = self.skyline_path()?;
+        let template_path = self.resolve_template(instrument)?;
+
+        let template_hash = skyline::hash_template(&template_path)
+            .map_err(|e| ExtractionError::TemplateNotFound(e.to_string()))?;
+
+        let batch_id = Uuid::new_v4();
+        let work_dir = Self::work_dir()?;
+        let report_path = work_dir.join(format!("{}_batch_report.csv", batch_id));
+        let staged = staging::stage(raw_paths, &work_dir.join(format!("{}_batch", batch_id)))?;
+        let folder = staged.path();
+
+        info!(
+            folder = %folder.display(),
+            files = raw_paths.len(),
+            template = %instrument.template,
+            "Starting Skyline batch extraction"
+        );
+
+        let (batch_time_ms, import_warnings) = self
+            .run_skyline(
+                skyline_path,
+                &template_path,
+                Some(format!("--import-all={}", folder.display())),
+                &report_path,
+            )
+            .await?;
+
+        let file = std::fs::File::open(crate::config::paths::long_path(&report_path))
+            .map_err(|e| ExtractionError::ReportParse(e.to_string()))?;
+        let mut split = split_batch_report(file, raw_paths, self.config.symmetry_metric_kind)?;
+        for (_, parsed) in &mut split {
+            resolve_duplicate_targets(&mut parsed.targets, self.config.duplicate_targets);
+            normalize_rt_unit(&mut parsed.targets, instrument.rt_unit);
+        }
+        warn_if_rt_unit_mismatch(
+            split.iter().flat_map(|(_, parsed)| &parsed.targets),
+            instrument.rt_unit,
+        );
+        let _ = std::fs::remove_file(crate::config::paths::long_path(&report_path));
+
+        let skyline_version =
+            skyline::get_version(skyline_path).unwrap_or_else(|_| "unknown".to_string());
+
+        // Skyline doesn't report per-file timings or attribute warnings to a
+        // file; spread the time evenly and attach warnings to every run
+        let extraction_time_ms = batch_time_ms / raw_paths.len().max(1) as u64;
+
+        let results = split
+            .into_iter()
+            .map(|(raw_path, parsed)| {
+                let ParsedReport {
+                    targets: mut target_metrics,
+                    acquisition,
+                    skipped_rows,
+                    total_ion_current,
+                    base_peak_intensity,
+                } = parsed;
+                if target_metrics.is_empty() {
+                    let err = ExtractionError::ReportParse(format!(
+                        "no rows for {} in batch report",
+                        raw_path.display()
+                    ));
+                    return (raw_path, Err(err));
+                }
+
+                mark_excluded(&mut target_metrics, &instrument.excluded_targets);
+                let mut run_metrics = self.calculate_run_metrics(
+                    &target_metrics,
+                    &instrument.expected_targets,
+                    &instrument.excluded_targets,
+                );
+                run_metrics.total_ion_current = total_ion_current;
+                run_metrics.base_peak_intensity = base_peak_intensity;
+                let result = ExtractionResult {
+                    run_id: Uuid::new_v4(),
+                    raw_file_path: raw_path.clone(),
+                    raw_file_name: raw_file_name(&raw_path),
+                    raw_file_hash: raw_hash::hash_raw_file(
+                        &raw_path,
+                        instrument.vendor,
+                        self.config.raw_hash_mode,
+                    )
+                    .unwrap_or_else(|_| "error".to_string()),
+                    raw_file_hash_mode: self.config.raw_hash_mode,
+                    extraction_time_ms,
+                    backend: "skyline".to_string(),
+                    backend_version: skyline_version.clone(),
+                    template_name: instrument.template.clone(),
+                    template_hash: template_hash.clone(),
+                    target_metrics,
+                    run_metrics,
+                    import_warnings: import_warnings.clone(),
+                    acquisition,
+                    skipped_rows,
+                    suspect_empty_import: false,
+                    consumables: Default::default(),
+                };
+                (raw_path, Ok(result))
+            })
+            .collect();
+
+        info!(
+            folder = %folder.display(),
+            batch_time_ms = batch_time_ms,
+            "Batch extraction complete"
+        );
+
+        Ok(results)
+    }
+
+    /// Export the template's report without importing any runs and check
+    /// that its columns map to the core metric fields.
+    pub async fn probe_columns(
+        &self,
+        instrument: &InstrumentConfig,
+    ) -> Result<probe::ColumnProbe, ExtractionError> {
+        let skyline_path = self.skyline_path()?;
+        let template_path = self.resolve_template(instrument)?;
+        let template_hash = skyline::hash_template(&template_path)
+            .map_err(|e| ExtractionError::TemplateNotFound(e.to_string()))?;
+
+        let report_path = Self::work_dir()?.join(format!("{}_probe.csv", Uuid::new_v4()));
+        self.run_skyline(skyline_path, &template_path, None, &report_path)
+            .await?;
+
+        let headers = std::fs::File::open(crate::config::paths::long_path(&report_path))
+            .map_err(|e| ExtractionError::ReportParse(e.to_string()))
+            .and_then(|file| {
+                csv::Reader::from_reader(file)
+                    .headers()
+                    .cloned()
+                    .map_err(|e| {
+                        ExtractionError::ReportParse(format!("Failed to read headers: {}", e))
+                    })
+            });
+        let _ = std::fs::remove_file(crate::config::paths::long_path(&report_path));
+
+        Ok(probe::ColumnProbe {
+            template: instrument.template.clone(),
+            template_hash,
+            missing: probe::missing_core_columns(&headers?, self.config.symmetry_metric_kind),
+            probed_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Get the discovered Skyline path, verifying it still exists.
+    fn skyline_path(&self) -> Result<&PathBuf, ExtractionError> {
         let skyline_path = self
             .skyline_path
             .as_ref()
@@ -61,42 +357,46 @@ impl Extractor {
             ));
         }
 
-        // Get template path - use absolute path if provided, otherwise look in template dir
-        let template_path = {
-            let path = PathBuf::from(&instrument.template);
-            if path.is_absolute() && path.exists() {
-                path
-            } else {
-                // Try relative to template directory
-                let template_dir = crate::config::paths::template_dir();
-                template_dir.join(&instrument.template)
-            }
-        };
+        Ok(skyline_path)
+    }
 
-        if !template_path.exists() {
-            return Err(ExtractionError::TemplateNotFound(
-                template_path.display().to_string(),
-            ));
-        }
+    /// Resolve the template path for an instrument.
+    fn resolve_template(&self, instrument: &InstrumentConfig) -> Result<PathBuf, ExtractionError> {
+        resolve_template_path(
+            &instrument.template,
+            &crate::config::paths::template_dir(),
+            self.config.allow_template_basename_fallback,
+        )
+    }
 
-        // Calculate template hash
-        let template_hash = skyline::hash_template(&template_path)
-            .map_err(|e| ExtractionError::TemplateNotFound(e.to_string()))?;
+    /// Hash of the instrument's current template.
+    pub fn template_hash(&self, instrument: &InstrumentConfig) -> Result<String, ExtractionError> {
+        let template_path = self.resolve_template(instrument)?;
+        skyline::hash_template(&template_path)
+            .map_err(|e| ExtractionError::TemplateNotFound(e.to_string()))
+    }
 
-        // Create temporary output file for the report
-        let run_id = Uuid::new_v4();
+    /// Working directory for Skyline reports.
+    fn work_dir() -> Result<PathBuf, ExtractionError> {
         let work_dir = crate::config::paths::spool_dir().join("work");
-        std::fs::create_dir_all(&work_dir)
-            .map_err(|e| ExtractionError::SkylineExecution(e.to_string()))?;
-
-        let report_path = work_dir.join(format!("{}_report.csv", run_id));
-
-        info!(
-            raw_file = %raw_path.display(),
-            template = %instrument.template,
-            "Starting Skyline extraction"
-        );
+        std::fs::create_dir_all(crate::config::paths::long_path(&work_dir))
+            .map_err(|e| ExtractionError::SkylineLaunch(e.to_string()))?;
+        Ok(work_dir)
+    }
 
+    /// Run SkylineCmd with the given import argument (if any), writing the QC
+    /// report.
+    ///
+    /// Returns the elapsed time in milliseconds and any import warnings
+    /// Skyline printed despite exiting successfully.
+    async fn run_skyline(
+        &self,
+        skyline_path: &Path,
+        template_path: &Path,
+        import_arg: Option<String>,
+        report_path: &Path,
+    ) -> Result<(u64, Vec<String>), ExtractionError> {
+        let work_dir = Self::work_dir()?;
         let start = Instant::now();
 
         // Build Skyline command
@@ -105,13 +405,19 @@ impl Extractor {
         let mut cmd = Command::new(skyline_path);
         cmd.current_dir(&work_dir) // Set working directory to spool/work
             .arg(format!("--in={}", template_path.display()))
-            .arg(format!("--import-file={}", raw_path.display()))
+            .args(import_arg)
             .arg("--report-name=MD_QC_Report")
             .arg("--report-invariant") // Use language-independent column names
-            .arg(format!("--report-file={}", report_path.display()))
+            .arg(format!(
+                "--report-file={}",
+                crate::config::paths::long_path(report_path).display()
+            ))
             .arg("--report-format=csv")
+            // Nothing can answer an interactive prompt; make it fail instead of wait
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         // Set process priority on Windows
         // Note: CREATE_NO_WINDOW (0x08000000) causes "os error 50" with Skyline/ClickOnce apps
@@ -128,19 +434,14 @@ impl Extractor {
 
         debug!(command = ?cmd, "Executing Skyline");
 
-        // Run with timeout
-        let timeout = tokio::time::Duration::from_secs(self.config.timeout_seconds);
-        let result = tokio::time::timeout(timeout, cmd.output()).await;
-
-        let output = match result {
-            Ok(Ok(output)) => output,
-            Ok(Err(e)) => {
-                return Err(ExtractionError::SkylineExecution(e.to_string()));
-            }
-            Err(_) => {
-                return Err(ExtractionError::SkylineTimeout(self.config.timeout_seconds));
-            }
-        };
+        // Run with the hard timeout and the idle watchdog
+        let output = run_with_idle_watchdog(
+            cmd,
+            Duration::from_secs(self.config.timeout_seconds),
+            Some(Duration::from_secs(self.config.idle_timeout_seconds))
+                .filter(|idle| !idle.is_zero()),
+        )
+        .await?;
 
         let extraction_time_ms = start.elapsed().as_millis() as u64;
 
@@ -149,84 +450,108 @@ impl Extractor {
             let stdout = String::from_utf8_lossy(&output.stdout);
             let exit_code = output.status.code().unwrap_or(-1);
 
-            // Skyline often writes errors to stdout, not stderr
-            let mut error_msg = if !stderr.is_empty() {
-                stderr.to_string()
-            } else if !stdout.is_empty() {
-                stdout.to_string()
-            } else {
-                format!("Skyline exited with code {}", exit_code)
-            };
-
-            // Add helpful message if report is missing
-            if error_msg.contains("does not exist") && error_msg.contains("report") {
-                error_msg.push_str(
-                    "\n\nHint: Your Skyline template needs a report named 'MD_QC_Report'. ",
-                );
-                error_msg.push_str("Open the template in Skyline, go to View > Document Grid > Reports > Edit Reports, ");
-                error_msg.push_str("and create a report with columns: Peptide Sequence, Precursor Mz, Retention Time, Total Area, Max Height, Fwhm, Mass Error PPM.");
-            }
-
             error!(
                 stderr = %stderr,
                 stdout = %stdout,
                 exit_code = exit_code,
                 "Skyline extraction failed"
             );
-            return Err(ExtractionError::SkylineExecution(error_msg));
+            return Err(skyline_exit_error(exit_code, &stderr, &stdout));
         }
 
-        // Parse the report
-        let target_metrics = self.parse_report(&report_path)?;
-
-        // Calculate run metrics
-        let run_metrics = self.calculate_run_metrics(&target_metrics);
-
-        // Get Skyline version
-        let skyline_version =
-            skyline::get_version(skyline_path).unwrap_or_else(|_| "unknown".to_string());
-
-        // Calculate raw file hash
-        let raw_file_hash = calculate_file_hash(raw_path).unwrap_or_else(|_| "error".to_string());
+        let import_warnings = parse_import_warnings(&String::from_utf8_lossy(&output.stdout));
+        if !import_warnings.is_empty() {
+            warn!(
+                warnings = ?import_warnings,
+                "Skyline reported import warnings; report may be missing targets"
+            );
+        }
 
-        // Clean up work file
-        let _ = std::fs::remove_file(&report_path);
+        Ok((extraction_time_ms, import_warnings))
+    }
 
-        info!(
-            raw_file = %raw_path.display(),
-            targets_found = run_metrics.targets_found,
-            extraction_time_ms = extraction_time_ms,
-            "Extraction complete"
-        );
+    /// Check that Skyline can open a raw file before the full extraction.
+    ///
+    /// First reads the start of the run's key file, so a file the OS can't
+    /// read fails without starting Skyline at all. Then imports the run into
+    /// the template without saving or exporting a report. Both share
+    /// `preflight_timeout_seconds`; Skyline is killed once it runs out, and
+    /// a probe that doesn't finish in time counts as unreadable.
+    async fn preflight_probe(
+        &self,
+        skyline_path: &Path,
+        template_path: &Path,
+        import_path: &Path,
+        vendor: Vendor,
+    ) -> Result<(), ExtractionError> {
+        let timeout = Duration::from_secs(self.config.preflight_timeout_seconds);
+        let deadline = Instant::now() + timeout;
+
+        let key_file = crate::watcher::key_file(import_path, vendor);
+        let read = tokio::task::spawn_blocking({
+            let key_file = key_file.clone();
+            move || read_first_block(&key_file)
+        });
+        let read_error = match tokio::time::timeout(timeout, read).await {
+            Ok(Ok(Ok(()))) => None,
+            Ok(Ok(Err(e))) => Some(e.to_string()),
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("no data within {} seconds", timeout.as_secs())),
+        };
+        if let Some(error) = read_error {
+            return Err(preflight_failed(
+                import_path,
+                format!("{}: {}", key_file.display(), error),
+            ));
+        }
 
-        Ok(ExtractionResult {
-            run_id,
-            raw_file_path: raw_path.to_path_buf(),
-            raw_file_name: raw_path
-                .file_name()
-                .and_then(|f| f.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
-            raw_file_hash,
-            extraction_time_ms,
-            backend: "skyline".to_string(),
-            backend_version: skyline_version,
-            template_name: instrument.template.clone(),
-            template_hash,
-            target_metrics,
-            run_metrics,
-        })
+        let mut cmd = Command::new(skyline_path);
+        cmd.current_dir(Self::work_dir()?)
+            .arg(format!("--in={}", template_path.display()))
+            .arg(format!("--import-file={}", import_path.display()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        debug!(command = ?cmd, "Executing Skyline preflight probe");
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match run_with_idle_watchdog(cmd, remaining, None).await {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => {
+                let error = skyline_exit_error(
+                    output.status.code().unwrap_or(-1),
+                    &String::from_utf8_lossy(&output.stderr),
+                    &String::from_utf8_lossy(&output.stdout),
+                );
+                Err(preflight_failed(import_path, error.to_string()))
+            }
+            Err(ExtractionError::SkylineTimeout(_)) => Err(preflight_failed(
+                import_path,
+                format!(
+                    "Skyline did not finish importing within {} seconds",
+                    timeout.as_secs()
+                ),
+            )),
+            Err(e) => Err(e),
+        }
     }
 
     /// Parse the Skyline report CSV.
     ///
     /// Uses header-based column detection to be flexible with different report formats.
-    fn parse_report(&self, report_path: &Path) -> Result<Vec<TargetMetrics>, ExtractionError> {
-        let file = std::fs::File::open(report_path)
+    /// Time columns are converted from `rt_unit` to minutes.
+    fn parse_report(
+        &self,
+        report_path: &Path,
+        rt_unit: RtUnit,
+    ) -> Result<ParsedReport, ExtractionError> {
+        let file = std::fs::File::open(crate::config::paths::long_path(report_path))
             .map_err(|e| ExtractionError::ReportParse(e.to_string()))?;
 
-        let mut reader = csv::Reader::from_reader(file);
-        let mut metrics = Vec::new();
+        let mut reader = report_reader(file);
+        let mut parsed = ParsedReport::default();
 
         // Build column index map from headers
         let headers = reader
@@ -234,50 +559,90 @@ impl Extractor {
             .map_err(|e| ExtractionError::ReportParse(format!("Failed to read headers: {}", e)))?
             .clone();
 
-        let col_map = build_column_map(&headers);
+        let col_map = build_column_map(&headers, self.config.symmetry_metric_kind);
         debug!(?col_map, "Parsed report column mapping");
+        warn_if_missing_mz(&col_map);
 
-        for (row_idx, result) in reader.records().enumerate() {
+        for result in reader.records() {
             let record = result.map_err(|e| ExtractionError::ReportParse(e.to_string()))?;
-
-            // Generate target_id from peptide sequence + mz, or use row number
-            let peptide_seq = get_string(&record, col_map.get("peptide_sequence"));
-            let mz = get_float(&record, col_map.get("precursor_mz")).unwrap_or(0.0);
-            let target_id = if let Some(ref seq) = peptide_seq {
-                format!("{}_{:.2}", seq, mz)
-            } else {
-                format!("target_{}", row_idx + 1)
-            };
-
-            let peak_area = get_float(&record, col_map.get("peak_area")).unwrap_or(0.0);
-
-            let target_metrics = TargetMetrics {
-                target_id,
-                peptide_sequence: peptide_seq,
-                precursor_mz: mz,
-                retention_time: get_float(&record, col_map.get("retention_time")).unwrap_or(0.0),
-                rt_expected: get_float(&record, col_map.get("rt_expected")),
-                rt_delta: get_float(&record, col_map.get("rt_delta")),
-                peak_area,
-                peak_height: get_float(&record, col_map.get("peak_height")).unwrap_or(0.0),
-                peak_width_fwhm: get_float(&record, col_map.get("fwhm")),
-                peak_symmetry: get_float(&record, col_map.get("peak_symmetry")),
-                mass_error_ppm: get_float(&record, col_map.get("mass_error_ppm")),
-                isotope_dot_product: get_float(&record, col_map.get("isotope_dot_product")),
-                detected: peak_area > 0.0,
-            };
-
-            metrics.push(target_metrics);
+            if !matches_header_width(&record, &headers) {
+                parsed.skipped_rows += 1;
+                continue;
+            }
+            let row_idx = parsed.targets.len();
+            parsed
+                .targets
+                .push(parse_target_row(&record, &col_map, row_idx));
+            fill_acquisition(&mut parsed.acquisition, &record, &col_map);
+            fill_intensity(&mut parsed, &record, &col_map);
         }
+        resolve_duplicate_targets(&mut parsed.targets, self.config.duplicate_targets);
+        normalize_rt_unit(&mut parsed.targets, rt_unit);
+        warn_if_rt_unit_mismatch(&parsed.targets, rt_unit);
+
+        info!(
+            targets_parsed = parsed.targets.len(),
+            skipped_rows = parsed.skipped_rows,
+            "Parsed Skyline report"
+        );
+        Ok(parsed)
+    }
 
-        info!(targets_parsed = metrics.len(), "Parsed Skyline report");
-        Ok(metrics)
+    /// Re-derive target and run metrics from a saved Skyline report using the
+    /// current parsing and metric code, without re-running Skyline.
+    pub fn recompute_metrics(
+        &self,
+        report_path: &Path,
+        rt_unit: RtUnit,
+    ) -> Result<(Vec<TargetMetrics>, RunMetrics), ExtractionError> {
+        let parsed = self.parse_report(report_path, rt_unit)?;
+        let mut run_metrics = self.calculate_run_metrics(&parsed.targets, &[], &[]);
+        run_metrics.total_ion_current = parsed.total_ion_current;
+        run_metrics.base_peak_intensity = parsed.base_peak_intensity;
+        Ok((parsed.targets, run_metrics))
     }
 
     /// Calculate run-level metrics from target metrics.
-    fn calculate_run_metrics(&self, targets: &[TargetMetrics]) -> RunMetrics {
-        let targets_found = targets.iter().filter(|t| t.detected).count() as u32;
-        let targets_expected = targets.len() as u32;
+    ///
+    /// With `expected_targets`, recovery is measured against that list: each
+    /// expected peptide counts once, and one with no report row is missing
+    /// rather than silently dropped from the denominator. Otherwise every
+    /// report row is a target.
+    ///
+    /// Targets flagged `excluded`, and expected peptides listed in
+    /// `excluded_targets`, are left out of every aggregate.
+    fn calculate_run_metrics(
+        &self,
+        targets: &[TargetMetrics],
+        expected_targets: &[String],
+        excluded_targets: &[String],
+    ) -> RunMetrics {
+        let included: Vec<TargetMetrics> =
+            targets.iter().filter(|t| !t.excluded).cloned().collect();
+        let targets = included.as_slice();
+        let expected_targets: Vec<String> = expected_targets
+            .iter()
+            .filter(|e| !is_excluded(e, excluded_targets))
+            .cloned()
+            .collect();
+        let expected_targets = expected_targets.as_slice();
+
+        let (missing_targets, undetected_targets) = expected_coverage(targets, expected_targets);
+        let (targets_found, targets_expected) = if expected_targets.is_empty() {
+            // A target with several peaks counts once, found if any peak is
+            let mut detected_by_target: HashMap<&str, bool> = HashMap::new();
+            for target in targets {
+                *detected_by_target.entry(target.base_id()).or_default() |= target.detected;
+            }
+            (
+                detected_by_target.values().filter(|d| **d).count() as u32,
+                detected_by_target.len() as u32,
+            )
+        } else {
+            let expected = expected_targets.len();
+            let found = expected - missing_targets.len() - undetected_targets.len();
+            (found as u32, expected as u32)
+        };
 
         let target_recovery_pct = if targets_expected > 0 {
             (targets_found as f64 / targets_expected as f64) * 100.0
@@ -286,82 +651,525 @@ impl Extractor {
         };
 
         // Calculate median RT shift
-        let mut rt_deltas: Vec<f64> = targets.iter().filter_map(|t| t.rt_delta).collect();
-        rt_deltas.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-
-        let median_rt_shift = if !rt_deltas.is_empty() {
-            let mid = rt_deltas.len() / 2;
-            if rt_deltas.len().is_multiple_of(2) {
-                Some((rt_deltas[mid - 1] + rt_deltas[mid]) / 2.0)
-            } else {
-                Some(rt_deltas[mid])
-            }
+        let median_rt_shift = if self.config.computes(ComputedMetric::RtShift) {
+            median(targets.iter().filter_map(|t| t.rt_delta).collect())
         } else {
             None
         };
 
         // Calculate median mass error
-        let mut mass_errors: Vec<f64> = targets.iter().filter_map(|t| t.mass_error_ppm).collect();
-        mass_errors.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-
-        let median_mass_error_ppm = if !mass_errors.is_empty() {
-            let mid = mass_errors.len() / 2;
-            if mass_errors.len().is_multiple_of(2) {
-                Some((mass_errors[mid - 1] + mass_errors[mid]) / 2.0)
-            } else {
-                Some(mass_errors[mid])
-            }
+        let median_mass_error_ppm = if self.config.computes(ComputedMetric::MassError) {
+            median(targets.iter().filter_map(|t| t.mass_error_ppm).collect())
         } else {
             None
         };
 
-        RunMetrics {
+        // Calculate chromatography score
+        let chromatography_score =
+            if self.config.computes(ComputedMetric::ChromatographyScore) && !targets.is_empty() {
+                Some(crate::metrics::calculate_chromatography_score(targets))
+            } else {
+                None
+            };
+
+        let mut run_metrics = RunMetrics {
             targets_found,
             targets_expected,
             target_recovery_pct,
             median_rt_shift,
             median_mass_error_ppm,
-            chromatography_score: None, // Could be calculated from peak metrics
+            chromatography_score,
+            missing_targets,
+            undetected_targets,
+            // Run-level columns, filled in from the report by the caller
+            total_ion_current: None,
+            base_peak_intensity: None,
+            health_index: None,
+        };
+        // Without a baseline here; the agent loop rescores against one
+        if let Some(weights) = &self.config.health_index_weights {
+            run_metrics.health_index = crate::metrics::health_index(&run_metrics, None, weights);
+        }
+        run_metrics
+    }
+}
+
+/// Median of the values, `None` when there are none.
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Whether a peptide sequence is in an instrument's `excluded_targets`.
+/// Sequences match case-insensitively.
+fn is_excluded(sequence: &str, excluded_targets: &[String]) -> bool {
+    excluded_targets
+        .iter()
+        .any(|e| e.trim().eq_ignore_ascii_case(sequence.trim()))
+}
+
+/// Flag targets whose peptide is in `excluded_targets`.
+fn mark_excluded(targets: &mut [TargetMetrics], excluded_targets: &[String]) {
+    for target in targets {
+        target.excluded = target
+            .peptide_sequence
+            .as_deref()
+            .is_some_and(|seq| is_excluded(seq, excluded_targets));
+    }
+}
+
+/// Split expected peptides into those with no report row (missing) and those
+/// whose rows are all undetected. Sequences match case-insensitively.
+fn expected_coverage(
+    targets: &[TargetMetrics],
+    expected_targets: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let mut missing = Vec::new();
+    let mut undetected = Vec::new();
+
+    for expected in expected_targets {
+        let expected = expected.trim();
+        let mut rows = targets
+            .iter()
+            .filter(|t| {
+                t.peptide_sequence
+                    .as_deref()
+                    .is_some_and(|seq| seq.eq_ignore_ascii_case(expected))
+            })
+            .peekable();
+
+        if rows.peek().is_none() {
+            missing.push(expected.to_string());
+        } else if !rows.any(|t| t.detected) {
+            undetected.push(expected.to_string());
+        }
+    }
+
+    (missing, undetected)
+}
+
+/// Make target ids unique within one run's report.
+///
+/// Rows producing an id already seen (e.g. the same peptide and m/z at two
+/// retention times) would otherwise overwrite each other wherever targets
+/// are keyed by id.
+fn resolve_duplicate_targets(targets: &mut Vec<TargetMetrics>, handling: DuplicateTargetHandling) {
+    let mut duplicates = Vec::new();
+
+    match handling {
+        DuplicateTargetHandling::Disambiguate => {
+            let mut occurrences: HashMap<String, usize> = HashMap::new();
+            for target in targets.iter_mut() {
+                let n = occurrences.entry(target.target_id.clone()).or_default();
+                *n += 1;
+                if *n > 1 {
+                    duplicates.push(target.target_id.clone());
+                    target.target_id =
+                        format!("{}{}{}", target.target_id, DUPLICATE_TARGET_SEPARATOR, n);
+                }
+            }
+        }
+        DuplicateTargetHandling::KeepLargest => {
+            let mut largest: HashMap<&str, usize> = HashMap::new();
+            for (idx, target) in targets.iter().enumerate() {
+                let kept = largest.entry(&target.target_id).or_insert(idx);
+                if *kept != idx {
+                    duplicates.push(target.target_id.clone());
+                    if target.peak_area > targets[*kept].peak_area {
+                        *kept = idx;
+                    }
+                }
+            }
+            let keep: HashSet<usize> = largest.into_values().collect();
+            *targets = std::mem::take(targets)
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| keep.contains(idx))
+                .map(|(_, target)| target)
+                .collect();
         }
     }
+
+    if !duplicates.is_empty() {
+        duplicates.sort();
+        duplicates.dedup();
+        warn!(
+            targets = ?duplicates,
+            handling = ?handling,
+            "Report has several rows for the same target"
+        );
+    }
 }
 
-/// Calculate SHA-256 hash of a file or directory.
-fn calculate_file_hash(path: &Path) -> Result<String> {
-    use sha2::{Digest, Sha256};
-
-    if path.is_file() {
-        let mut file = std::fs::File::open(path)?;
-        let mut hasher = Sha256::new();
-        std::io::copy(&mut file, &mut hasher)?;
-        Ok(hex::encode(hasher.finalize()))
-    } else if path.is_dir() {
-        // For directories, hash a consistent representation
-        // (e.g., concatenation of filenames and sizes)
-        let mut hasher = Sha256::new();
-
-        let mut entries: Vec<_> = std::fs::read_dir(path)?.filter_map(|e| e.ok()).collect();
-        entries.sort_by_key(|e| e.path());
-
-        for entry in entries {
-            let name = entry.file_name();
-            hasher.update(name.to_string_lossy().as_bytes());
-
-            if let Ok(meta) = entry.metadata() {
-                hasher.update(meta.len().to_le_bytes());
+/// Resolve a configured template: an existing absolute path as is, anything
+/// else relative to `template_dir`.
+///
+/// With `basename_fallback`, an absolute path that no longer exists (e.g. the
+/// templates were moved but the config wasn't updated) falls back to a
+/// template of the same file name in `template_dir`.
+pub fn resolve_template_path(
+    template: &str,
+    template_dir: &Path,
+    basename_fallback: bool,
+) -> Result<PathBuf, ExtractionError> {
+    let path = PathBuf::from(template);
+    if path.is_absolute() {
+        if path.exists() {
+            return Ok(path);
+        }
+        if basename_fallback {
+            if let Some(name) = path.file_name() {
+                let candidate = template_dir.join(name);
+                if candidate.exists() {
+                    warn!(
+                        configured = %path.display(),
+                        resolved = %candidate.display(),
+                        "Template not found at its configured path; using the template of the same name in the template directory"
+                    );
+                    return Ok(candidate);
+                }
             }
         }
+        return Err(ExtractionError::TemplateNotFound(
+            path.display().to_string(),
+        ));
+    }
 
-        Ok(hex::encode(hasher.finalize()))
-    } else {
-        anyhow::bail!("Path is neither file nor directory: {}", path.display())
+    let template_path = template_dir.join(template);
+    if !template_path.exists() {
+        return Err(ExtractionError::TemplateNotFound(
+            template_path.display().to_string(),
+        ));
     }
+
+    Ok(template_path)
+}
+
+/// File name of a raw file for display and payloads.
+fn raw_file_name(raw_path: &Path) -> String {
+    raw_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// Longest retention time, in minutes, that a real LC gradient produces.
+/// Anything beyond it suggests a report exporting seconds.
+const MAX_PLAUSIBLE_RT_MINUTES: f64 = 300.0;
+
+/// Convert a report's time columns (retention time, expected RT, RT delta,
+/// FWHM) from `rt_unit` to minutes.
+fn normalize_rt_unit(targets: &mut [TargetMetrics], rt_unit: RtUnit) {
+    if rt_unit == RtUnit::Minutes {
+        return;
+    }
+
+    let factor = rt_unit.to_minutes();
+    for target in targets {
+        target.retention_time *= factor;
+        for value in [
+            &mut target.rt_expected,
+            &mut target.rt_delta,
+            &mut target.peak_width_fwhm,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *value *= factor;
+        }
+    }
+}
+
+/// Warn when normalised retention times are longer than any gradient, which
+/// usually means the report is in seconds but `rt_unit` says minutes.
+fn warn_if_rt_unit_mismatch<'a>(
+    targets: impl IntoIterator<Item = &'a TargetMetrics>,
+    rt_unit: RtUnit,
+) {
+    let max_rt = targets
+        .into_iter()
+        .filter(|t| t.detected)
+        .map(|t| t.retention_time)
+        .fold(0.0, f64::max);
+
+    if max_rt > MAX_PLAUSIBLE_RT_MINUTES {
+        warn!(
+            max_rt_minutes = max_rt,
+            rt_unit = ?rt_unit,
+            "Retention times exceed {} minutes; if the report exports seconds, set rt_unit = \"seconds\" for this instrument",
+            MAX_PLAUSIBLE_RT_MINUTES
+        );
+    }
+}
+
+/// Build target metrics from a single report row.
+fn parse_target_row(
+    record: &csv::StringRecord,
+    col_map: &std::collections::HashMap<&'static str, usize>,
+    row_idx: usize,
+) -> TargetMetrics {
+    // Generate target_id from peptide sequence + mz. Without a usable m/z,
+    // fall back to peptide + row number so charge states don't collide on a
+    // bogus `_0.00` suffix.
+    let peptide_seq = get_string(record, col_map.get("peptide_sequence"));
+    let mz = get_float(record, col_map.get("precursor_mz"));
+    let target_id = match (&peptide_seq, mz) {
+        (Some(seq), Some(mz)) => format!("{}_{:.2}", seq, mz),
+        (Some(seq), None) => format!("{}_row{}", seq, row_idx + 1),
+        (None, _) => format!("target_{}", row_idx + 1),
+    };
+
+    let peak_area = get_float(record, col_map.get("peak_area")).unwrap_or(0.0);
+
+    TargetMetrics {
+        target_id,
+        peptide_sequence: peptide_seq,
+        precursor_mz: mz.unwrap_or(0.0),
+        retention_time: get_float(record, col_map.get("retention_time")).unwrap_or(0.0),
+        rt_expected: get_float(record, col_map.get("rt_expected")),
+        rt_delta: get_float(record, col_map.get("rt_delta")),
+        peak_area,
+        peak_height: get_float(record, col_map.get("peak_height")).unwrap_or(0.0),
+        peak_width_fwhm: get_float(record, col_map.get("fwhm")),
+        peak_symmetry: get_float(record, col_map.get("peak_symmetry")),
+        tailing_factor: get_float(record, col_map.get("tailing_factor")),
+        mass_error_ppm: get_float(record, col_map.get("mass_error_ppm")),
+        isotope_dot_product: get_float(record, col_map.get("isotope_dot_product")),
+        detected: peak_area > 0.0,
+        // Set per instrument by `mark_excluded`
+        excluded: false,
+    }
+}
+
+/// Split a combined batch report into per-run target metrics.
+///
+/// Rows are matched to `raw_paths` by the report's file name (or replicate
+/// name) column, comparing either the full file name or its stem
+/// case-insensitively. The result preserves the order of `raw_paths`; files
+/// with no matching rows get an empty list, and rows for files not in
+/// `raw_paths` are dropped. Malformed rows are skipped and counted against
+/// the file they name, if any.
+fn split_batch_report<R: std::io::Read>(
+    report: R,
+    raw_paths: &[PathBuf],
+    symmetry_kind: SymmetryMetricKind,
+) -> Result<Vec<(PathBuf, ParsedReport)>, ExtractionError> {
+    let mut reader = report_reader(report);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| ExtractionError::ReportParse(format!("Failed to read headers: {}", e)))?
+        .clone();
+
+    let col_map = build_column_map(&headers, symmetry_kind);
+    warn_if_missing_mz(&col_map);
+    let file_col = col_map.get("file_name").copied().ok_or_else(|| {
+        ExtractionError::ReportParse(
+            "batch report has no file name or replicate column to split on".to_string(),
+        )
+    })?;
+
+    let mut split: Vec<(PathBuf, ParsedReport)> = raw_paths
+        .iter()
+        .map(|p| (p.clone(), ParsedReport::default()))
+        .collect();
+
+    for result in reader.records() {
+        let record = result.map_err(|e| ExtractionError::ReportParse(e.to_string()))?;
+        let Some(file_value) = record.get(file_col).map(|v| v.trim().to_lowercase()) else {
+            continue;
+        };
+
+        let matched = raw_paths.iter().position(|p| {
+            let name = p.file_name().map(|n| n.to_string_lossy().to_lowercase());
+            let stem = p.file_stem().map(|n| n.to_string_lossy().to_lowercase());
+            name.as_deref() == Some(file_value.as_str())
+                || stem.as_deref() == Some(file_value.as_str())
+        });
+
+        let well_formed = matches_header_width(&record, &headers);
+        if let Some(idx) = matched {
+            let parsed = &mut split[idx].1;
+            if !well_formed {
+                parsed.skipped_rows += 1;
+                continue;
+            }
+            // Row index within the file so target_N ids match single-file extraction
+            let row_idx = parsed.targets.len();
+            parsed
+                .targets
+                .push(parse_target_row(&record, &col_map, row_idx));
+            fill_acquisition(&mut parsed.acquisition, &record, &col_map);
+            fill_intensity(parsed, &record, &col_map);
+        }
+    }
+
+    Ok(split)
+}
+
+/// Log a failed preflight probe and turn it into the run's error.
+fn preflight_failed(import_path: &Path, error: String) -> ExtractionError {
+    warn!(
+        raw_file = %import_path.display(),
+        error = %error,
+        "Preflight probe failed, skipping extraction"
+    );
+    ExtractionError::Unreadable(error)
+}
+
+/// Bytes the preflight probe reads from the start of a raw file.
+const PREFLIGHT_READ_BYTES: usize = 64 * 1024;
+
+/// Open `path` and read its first block. An empty file counts as unreadable.
+fn read_first_block(path: &Path) -> std::io::Result<()> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(crate::config::paths::long_path(path))?;
+    let mut block = vec![0; PREFLIGHT_READ_BYTES];
+    if file.read(&mut block)? == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "file is empty",
+        ));
+    }
+    Ok(())
+}
+
+/// Run a command to completion, killing it after `timeout` or once stdout
+/// has been silent for `idle_timeout`.
+///
+/// Stdout is read as it is produced so a process stuck on a prompt is caught
+/// early instead of holding the pipeline for the full timeout.
+async fn run_with_idle_watchdog(
+    mut cmd: Command,
+    timeout: Duration,
+    idle_timeout: Option<Duration>,
+) -> Result<std::process::Output, ExtractionError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ExtractionError::SkylineLaunch(e.to_string()))?;
+    let mut stdout_pipe = child.stdout.take();
+    let stderr_task = child.stderr.take().map(|mut stderr| {
+        tokio::spawn(async move {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf).await;
+            buf
+        })
+    });
+
+    let mut stdout = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    while let Some(pipe) = stdout_pipe.as_mut() {
+        let wait_until = match idle_timeout {
+            Some(idle) => deadline.min(tokio::time::Instant::now() + idle),
+            None => deadline,
+        };
+
+        match tokio::time::timeout_at(wait_until, pipe.read(&mut chunk)).await {
+            Ok(Ok(0)) | Ok(Err(_)) => stdout_pipe = None,
+            Ok(Ok(n)) => stdout.extend_from_slice(&chunk[..n]),
+            Err(_) => {
+                let _ = child.kill().await;
+                return Err(if tokio::time::Instant::now() >= deadline {
+                    ExtractionError::SkylineTimeout(timeout.as_secs())
+                } else {
+                    let idle = idle_timeout.unwrap_or_default().as_secs();
+                    warn!(
+                        idle_seconds = idle,
+                        "Skyline produced no output, killing it"
+                    );
+                    ExtractionError::SkylineStalled(idle)
+                });
+            }
+        }
+    }
+
+    let status = match tokio::time::timeout_at(deadline, child.wait()).await {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => return Err(ExtractionError::SkylineLaunch(e.to_string())),
+        Err(_) => {
+            let _ = child.kill().await;
+            return Err(ExtractionError::SkylineTimeout(timeout.as_secs()));
+        }
+    };
+
+    let stderr = match stderr_task {
+        Some(task) => task.await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    Ok(std::process::Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Lines of Skyline stdout kept on an execution error.
+const STDOUT_TAIL_LINES: usize = 20;
+
+/// Build the error for a Skyline run that exited unsuccessfully.
+///
+/// Skyline often writes errors to stdout rather than stderr, so the last
+/// `STDOUT_TAIL_LINES` lines of stdout are kept alongside stderr.
+fn skyline_exit_error(exit_code: i32, stderr: &str, stdout: &str) -> ExtractionError {
+    let lines: Vec<&str> = stdout.trim_end().lines().collect();
+    let stdout_tail = lines[lines.len().saturating_sub(STDOUT_TAIL_LINES)..].join("\n");
+
+    ExtractionError::SkylineExecution {
+        exit_code,
+        stderr: stderr.trim().to_string(),
+        stdout_tail,
+    }
+}
+
+/// Phrases (lowercase) in Skyline output that indicate a partial import.
+const IMPORT_WARNING_PHRASES: &[&str] = &[
+    "warning:",
+    "failed to import",
+    "could not be imported",
+    "were not imported",
+    "no chromatograms",
+    "no results found",
+];
+
+/// Collect distinct Skyline output lines that contain a known warning phrase.
+fn parse_import_warnings(stdout: &str) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+
+    for line in stdout.lines().map(str::trim) {
+        let lower = line.to_lowercase();
+        if IMPORT_WARNING_PHRASES.iter().any(|p| lower.contains(p))
+            && !warnings.iter().any(|w| w == line)
+        {
+            warnings.push(line.to_string());
+        }
+    }
+
+    warnings
 }
 
 /// Build a mapping from our field names to CSV column indices.
 ///
-/// Handles various Skyline column name variations.
-fn build_column_map(headers: &csv::StringRecord) -> std::collections::HashMap<&'static str, usize> {
+/// Handles various Skyline column name variations. Symmetry-family columns are
+/// routed to `peak_symmetry` or `tailing_factor` according to `symmetry_kind`.
+fn build_column_map(
+    headers: &csv::StringRecord,
+    symmetry_kind: SymmetryMetricKind,
+) -> std::collections::HashMap<&'static str, usize> {
     let mut map = std::collections::HashMap::new();
 
     for (idx, header) in headers.iter().enumerate() {
@@ -376,6 +1184,11 @@ fn build_column_map(headers: &csv::StringRecord) -> std::collections::HashMap<&'
             }
             "moleculename" | "molecule" | "compoundname" => Some("peptide_sequence"),
 
+            // Source file (used to split batch reports). Replicate names are
+            // often set by hand, so they only stand in for a missing file column
+            "filename" | "resultfilename" | "file" => Some("file_name"),
+            "replicatename" | "replicate" => Some("replicate_name"),
+
             // Precursor m/z
             "mz" | "precursormz" | "precursormass" | "mass" => Some("precursor_mz"),
 
@@ -390,7 +1203,16 @@ fn build_column_map(headers: &csv::StringRecord) -> std::collections::HashMap<&'
             "totalarea" | "area" | "peakarea" | "sumarea" => Some("peak_area"),
             "maxheight" | "height" | "peakheight" | "maxintensity" => Some("peak_height"),
             "fwhm" | "maxfwhm" | "peakwidth" | "width" => Some("fwhm"),
-            "peaksymmetry" | "symmetry" => Some("peak_symmetry"),
+            "peaksymmetry" | "symmetry" => match symmetry_kind {
+                SymmetryMetricKind::TailingFactor => Some("tailing_factor"),
+                _ => Some("peak_symmetry"),
+            },
+            "tailingfactor" | "tailing" | "usptailing" | "asymmetryfactor" | "asymmetry" => {
+                match symmetry_kind {
+                    SymmetryMetricKind::Symmetry => Some("peak_symmetry"),
+                    _ => Some("tailing_factor"),
+                }
+            }
 
             // Mass accuracy
             "masserrorppm" | "averagemasserrorppm" | "ppm" | "deltamass" => Some("mass_error_ppm"),
@@ -398,6 +1220,20 @@ fn build_column_map(headers: &csv::StringRecord) -> std::collections::HashMap<&'
             // Quality scores
             "isotopedotproduct" | "idotp" | "dotproduct" => Some("isotope_dot_product"),
 
+            // Acquisition metadata (same value on every row of a run)
+            "acquiredtime" | "acquisitiontime" => Some("acquired_time"),
+            "instrumentserialnumber" | "serialnumber" => Some("instrument_serial"),
+            "instrumentmodel" => Some("instrument_model"),
+            "acquisitionmethod" | "instrumentmethod" => Some("method_name"),
+
+            // Run-level intensity (same value on every row of a run)
+            "totalioncurrent" | "tic" | "ticarea" | "totalionchromatogramarea" => {
+                Some("total_ion_current")
+            }
+            "basepeakintensity" | "bpi" | "basepeakarea" | "basepeak" => {
+                Some("base_peak_intensity")
+            }
+
             _ => None,
         };
 
@@ -406,9 +1242,119 @@ fn build_column_map(headers: &csv::StringRecord) -> std::collections::HashMap<&'
         }
     }
 
+    if let Some(idx) = map.remove("replicate_name") {
+        map.entry("file_name").or_insert(idx);
+    }
+
     map
 }
 
+/// Warn when a report has no precursor m/z column at all.
+///
+/// Target ids then fall back to peptide + row number, which stays unique
+/// within a run but is not stable if the template's row order changes.
+fn warn_if_missing_mz(col_map: &std::collections::HashMap<&'static str, usize>) {
+    if !col_map.contains_key("precursor_mz") {
+        warn!("Report has no precursor m/z column; target ids will use peptide + row number");
+    }
+}
+
+/// Fill acquisition fields not yet set from a report row.
+fn fill_acquisition(
+    acquisition: &mut AcquisitionMetadata,
+    record: &csv::StringRecord,
+    col_map: &std::collections::HashMap<&'static str, usize>,
+) {
+    if acquisition.acquisition_time.is_none() {
+        acquisition.acquisition_time =
+            get_string(record, col_map.get("acquired_time")).and_then(|s| parse_acquired_time(&s));
+    }
+    if acquisition.instrument_serial.is_none() {
+        acquisition.instrument_serial = get_string(record, col_map.get("instrument_serial"));
+    }
+    if acquisition.instrument_model.is_none() {
+        acquisition.instrument_model = get_string(record, col_map.get("instrument_model"));
+    }
+    if acquisition.method_name.is_none() {
+        acquisition.method_name = get_string(record, col_map.get("method_name"));
+    }
+}
+
+/// Fill run-level intensities not yet set from a report row.
+fn fill_intensity(
+    parsed: &mut ParsedReport,
+    record: &csv::StringRecord,
+    col_map: &std::collections::HashMap<&'static str, usize>,
+) {
+    if parsed.total_ion_current.is_none() {
+        parsed.total_ion_current = get_float(record, col_map.get("total_ion_current"));
+    }
+    if parsed.base_peak_intensity.is_none() {
+        parsed.base_peak_intensity = get_float(record, col_map.get("base_peak_intensity"));
+    }
+}
+
+/// Parse an acquired time as exported by Skyline.
+///
+/// Accepts RFC 3339 or the common invariant/US formats; times without an
+/// offset are taken as local to the acquisition PC.
+fn parse_acquired_time(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+
+    const FORMATS: &[&str] = &[
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%m/%d/%Y %I:%M:%S %p",
+        "%m/%d/%Y %H:%M:%S",
+    ];
+
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Rows parsed from a Skyline report.
+#[derive(Debug, Default)]
+struct ParsedReport {
+    targets: Vec<TargetMetrics>,
+    acquisition: AcquisitionMetadata,
+    /// Rows skipped because their field count didn't match the header
+    skipped_rows: u32,
+    total_ion_current: Option<f64>,
+    base_peak_intensity: Option<f64>,
+}
+
+/// CSV reader for Skyline reports. Rows of the wrong width are let through
+/// so they can be skipped one at a time instead of failing the whole report.
+fn report_reader<R: std::io::Read>(report: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new().flexible(true).from_reader(report)
+}
+
+/// Whether a row has exactly one field per header. With a field missing or
+/// an unquoted comma (e.g. in a modified sequence), every column after it
+/// would be read from the wrong position, so such rows are logged and
+/// skipped.
+fn matches_header_width(record: &csv::StringRecord, headers: &csv::StringRecord) -> bool {
+    if record.len() == headers.len() {
+        return true;
+    }
+
+    warn!(
+        line = record.position().map(|p| p.line()),
+        fields = record.len(),
+        expected = headers.len(),
+        "Skipping malformed report row"
+    );
+    false
+}
+
 /// Get a string value from a CSV record by column index.
 fn get_string(record: &csv::StringRecord, col: Option<&usize>) -> Option<String> {
     col.and_then(|&idx| record.get(idx))
@@ -421,3 +1367,609 @@ fn get_float(record: &csv::StringRecord, col: Option<&usize>) -> Option<f64> {
     col.and_then(|&idx| record.get(idx))
         .and_then(|s| s.parse().ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seconds_report_is_normalized_to_minutes() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Total Area,Retention Time,Predicted Retention Time,RT Delta,Max Fwhm
+PEPTIDEA,500.25,1000,600,594,6,12
+PEPTIDEB,600.30,2000,1200,1209,-9,15
+PEPTIDEC,700.35,3000,1800,1797,3,9
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let (targets, run_metrics) = extractor
+            .recompute_metrics(&report_path, RtUnit::Seconds)
+            .unwrap();
+
+        assert!((targets[0].retention_time - 10.0).abs() < 1e-9);
+        assert!((targets[0].rt_expected.unwrap() - 9.9).abs() < 1e-9);
+        assert!((targets[0].rt_delta.unwrap() - 0.1).abs() < 1e-9);
+        assert!((targets[0].peak_width_fwhm.unwrap() - 0.2).abs() < 1e-9);
+        assert!((targets[2].retention_time - 30.0).abs() < 1e-9);
+        // Median of 0.1, -0.15 and 0.05 minutes, not of 6, -9 and 3 seconds
+        assert!((run_metrics.median_rt_shift.unwrap() - 0.05).abs() < 1e-9);
+
+        // The same report read as minutes is left as exported
+        let (targets, run_metrics) = extractor
+            .recompute_metrics(&report_path, RtUnit::Minutes)
+            .unwrap();
+        assert_eq!(targets[0].retention_time, 600.0);
+        assert_eq!(run_metrics.median_rt_shift, Some(3.0));
+    }
+
+    #[test]
+    fn test_recompute_metrics_from_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Total Area,Retention Time,Mass Error PPM
+PEPTIDEA,500.25,1000,10.0,1.5
+PEPTIDEB,600.30,2000,20.0,-0.5
+PEPTIDEC,700.35,0,,
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let (targets, run_metrics) = extractor
+            .recompute_metrics(&report_path, RtUnit::Minutes)
+            .unwrap();
+
+        assert_eq!(targets.len(), 3);
+        assert_eq!(run_metrics.targets_expected, 3);
+        assert_eq!(run_metrics.targets_found, 2);
+        assert!((run_metrics.target_recovery_pct - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(run_metrics.median_mass_error_ppm, Some(0.5));
+    }
+
+    #[test]
+    fn test_run_intensity_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Total Area,Tic Area,Base Peak Intensity
+PEPTIDEA,500.25,1000,3.2E+10,1.5E+09
+PEPTIDEB,600.30,2000,3.2E+10,1.5E+09
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let (targets, run_metrics) = extractor
+            .recompute_metrics(&report_path, RtUnit::Minutes)
+            .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(run_metrics.total_ion_current, Some(3.2e10));
+        assert_eq!(run_metrics.base_peak_intensity, Some(1.5e9));
+
+        // Reports without the columns leave them unset
+        std::fs::write(
+            &report_path,
+            "Peptide Sequence,Precursor Mz,Total Area\nPEPTIDEA,500.25,1000\n",
+        )
+        .unwrap();
+        let (_, run_metrics) = extractor
+            .recompute_metrics(&report_path, RtUnit::Minutes)
+            .unwrap();
+        assert_eq!(run_metrics.total_ion_current, None);
+        assert_eq!(run_metrics.base_peak_intensity, None);
+    }
+
+    #[test]
+    fn test_recovery_against_expected_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Total Area
+PEPTIDEA,500.25,1000
+PEPTIDEA,750.12,0
+PEPTIDEB,600.30,0
+UNEXPECTED,650.00,500
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let targets = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap()
+            .targets;
+
+        let expected = vec![
+            "PEPTIDEA".to_string(),
+            "peptideb".to_string(),
+            "PEPTIDEC".to_string(),
+        ];
+        let run_metrics = extractor.calculate_run_metrics(&targets, &expected, &[]);
+        assert_eq!(run_metrics.targets_expected, 3);
+        assert_eq!(run_metrics.targets_found, 1);
+        assert_eq!(run_metrics.missing_targets, vec!["PEPTIDEC".to_string()]);
+        assert_eq!(run_metrics.undetected_targets, vec!["peptideb".to_string()]);
+
+        // Without a list, every row counts and nothing is reported missing
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &[]);
+        assert_eq!(run_metrics.targets_expected, 4);
+        assert_eq!(run_metrics.targets_found, 2);
+        assert!(run_metrics.missing_targets.is_empty());
+    }
+
+    #[test]
+    fn test_disabled_metrics_are_left_unset() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Retention Time,RT Delta,Total Area,Fwhm,Mass Error PPM
+PEPTIDEA,500.25,12.5,0.05,1000,0.15,1.5
+PEPTIDEB,600.30,15.1,0.10,0,0.20,2.5
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let targets = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap()
+            .targets;
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &[]);
+        assert!(run_metrics.chromatography_score.is_some());
+        assert!(run_metrics.median_mass_error_ppm.is_some());
+
+        let config = SkylineConfig {
+            computed_metrics: vec![ComputedMetric::Recovery, ComputedMetric::MassError],
+            ..SkylineConfig::default()
+        };
+        let extractor = Extractor::new(&config).unwrap();
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &[]);
+        assert_eq!(run_metrics.chromatography_score, None);
+        assert_eq!(run_metrics.median_rt_shift, None);
+        assert_eq!(run_metrics.median_mass_error_ppm, Some(2.0));
+        assert_eq!(run_metrics.targets_found, 1);
+        assert_eq!(run_metrics.targets_expected, 2);
+        assert_eq!(run_metrics.target_recovery_pct, 50.0);
+    }
+
+    #[test]
+    fn test_moved_absolute_template_falls_back_to_basename() {
+        let dir = tempfile::tempdir().unwrap();
+        let template_dir = dir.path().join("templates");
+        std::fs::create_dir_all(&template_dir).unwrap();
+        std::fs::write(template_dir.join("qc.sky"), "<srm_settings/>").unwrap();
+
+        // The configured absolute path points where the template used to be
+        let moved = dir.path().join("old_location").join("qc.sky");
+        let moved = moved.to_str().unwrap();
+
+        let resolved = resolve_template_path(moved, &template_dir, true).unwrap();
+        assert_eq!(resolved, template_dir.join("qc.sky"));
+
+        // Without the fallback the broken path is an error naming that path
+        match resolve_template_path(moved, &template_dir, false) {
+            Err(ExtractionError::TemplateNotFound(path)) => assert_eq!(path, moved),
+            other => panic!("expected TemplateNotFound, got {:?}", other),
+        }
+
+        // Relative names and existing absolute paths are unchanged
+        let relative = resolve_template_path("qc.sky", &template_dir, false).unwrap();
+        assert_eq!(relative, template_dir.join("qc.sky"));
+        let absolute = template_dir.join("qc.sky");
+        let resolved = resolve_template_path(absolute.to_str().unwrap(), &template_dir, false);
+        assert_eq!(resolved.unwrap(), absolute);
+        assert!(resolve_template_path("other.sky", &template_dir, true).is_err());
+    }
+
+    #[test]
+    fn test_excluded_targets_are_left_out_of_run_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Retention Time,Total Area,Mass Error PPM
+PEPTIDEA,500.25,12.3,1000,1.0
+PEPTIDEB,600.30,15.0,2000,2.0
+MISFIRE,650.00,0,0,40.0
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let mut targets = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap()
+            .targets;
+
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &[]);
+        assert_eq!(run_metrics.targets_found, 2);
+        assert_eq!(run_metrics.targets_expected, 3);
+
+        // Excluding the zero-area peptide lifts recovery from 2/3 to 2/2
+        let excluded = vec!["misfire".to_string()];
+        mark_excluded(&mut targets, &excluded);
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &excluded);
+        assert_eq!(run_metrics.targets_found, 2);
+        assert_eq!(run_metrics.targets_expected, 2);
+        assert_eq!(run_metrics.target_recovery_pct, 100.0);
+        assert_eq!(run_metrics.median_mass_error_ppm, Some(1.5));
+
+        // It stays in the per-target detail, flagged
+        assert_eq!(targets.len(), 3);
+        assert!(targets[2].excluded);
+        assert!(!targets[0].excluded);
+
+        // An excluded peptide in expected_targets doesn't count as missing
+        let expected = vec!["PEPTIDEA".to_string(), "MISFIRE".to_string()];
+        let run_metrics = extractor.calculate_run_metrics(&targets, &expected, &excluded);
+        assert_eq!(run_metrics.targets_expected, 1);
+        assert!(run_metrics.undetected_targets.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_target_ids_are_kept_distinct() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Retention Time,Total Area
+PEPTIDEA,500.25,12.3,1000
+PEPTIDEA,500.25,14.1,3000
+PEPTIDEB,600.30,15.0,0
+",
+        )
+        .unwrap();
+
+        // Both peaks survive with distinct ids
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let targets = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap()
+            .targets;
+        let ids: Vec<&str> = targets.iter().map(|t| t.target_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["PEPTIDEA_500.25", "PEPTIDEA_500.25#2", "PEPTIDEB_600.30"]
+        );
+        assert_eq!(targets[1].base_id(), "PEPTIDEA_500.25");
+        assert_eq!(targets[1].retention_time, 14.1);
+
+        // The peptide still counts as one target
+        let run_metrics = extractor.calculate_run_metrics(&targets, &[], &[]);
+        assert_eq!(run_metrics.targets_expected, 2);
+        assert_eq!(run_metrics.targets_found, 1);
+
+        // Or keep only the larger peak
+        let config = SkylineConfig {
+            duplicate_targets: DuplicateTargetHandling::KeepLargest,
+            ..SkylineConfig::default()
+        };
+        let extractor = Extractor::new(&config).unwrap();
+        let targets = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap()
+            .targets;
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].target_id, "PEPTIDEA_500.25");
+        assert_eq!(targets[0].peak_area, 3000.0);
+    }
+
+    #[test]
+    fn test_malformed_rows_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let report_path = dir.path().join("report.csv");
+        std::fs::write(
+            &report_path,
+            "\
+Peptide Sequence,Precursor Mz,Retention Time,Total Area
+PEPTIDEA,500.25,12.5,1000
+PEPT[+57,0]IDEB,600.30,15.1,2000
+\"PEPT[+57,0]IDEC\",650.00,18.2,3000
+PEPTIDED,700.10,20.0
+",
+        )
+        .unwrap();
+
+        let extractor = Extractor::new(&SkylineConfig::default()).unwrap();
+        let parsed = extractor
+            .parse_report(&report_path, RtUnit::Minutes)
+            .unwrap();
+
+        // The unquoted comma and the short row are dropped, not read shifted
+        assert_eq!(parsed.skipped_rows, 2);
+        assert_eq!(parsed.targets.len(), 2);
+        assert_eq!(
+            parsed.targets[1].peptide_sequence.as_deref(),
+            Some("PEPT[+57,0]IDEC")
+        );
+        assert_eq!(parsed.targets[1].retention_time, 18.2);
+        assert_eq!(parsed.targets[1].peak_area, 3000.0);
+
+        let report = "\
+File Name,Peptide Sequence,Precursor Mz,Total Area
+QC_A1.raw,PEPTIDEA,500.25,1000
+QC_A1.raw,PEPTIDEB,600.30
+QC_A2.raw,PEPTIDEA,500.25,2000,extra
+";
+        let raw_paths = vec![PathBuf::from("/data/QC_A1.raw")];
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+        assert_eq!(split[0].1.targets.len(), 1);
+        assert_eq!(split[0].1.skipped_rows, 1);
+    }
+
+    #[test]
+    fn test_column_map_symmetry_kinds() {
+        let headers = csv::StringRecord::from(vec!["Peptide Sequence", "Tailing Factor"]);
+        let map = build_column_map(&headers, SymmetryMetricKind::Auto);
+        assert_eq!(map.get("tailing_factor"), Some(&1));
+        assert_eq!(map.get("peak_symmetry"), None);
+
+        let headers = csv::StringRecord::from(vec!["Peptide Sequence", "Symmetry"]);
+        let map = build_column_map(&headers, SymmetryMetricKind::Auto);
+        assert_eq!(map.get("peak_symmetry"), Some(&1));
+
+        let map = build_column_map(&headers, SymmetryMetricKind::TailingFactor);
+        assert_eq!(map.get("tailing_factor"), Some(&1));
+        assert_eq!(map.get("peak_symmetry"), None);
+    }
+
+    #[test]
+    fn test_split_batch_report() {
+        let report = "\
+File Name,Peptide Sequence,Precursor Mz,Total Area
+QC_A1.raw,PEPTIDEA,500.25,1000
+QC_A1.raw,PEPTIDEB,600.30,0
+QC_A3.raw,PEPTIDEA,500.25,2000
+SAMPLE_B5.raw,PEPTIDEA,500.25,3000
+";
+        let raw_paths = vec![
+            PathBuf::from("/data/QC_A1.raw"),
+            PathBuf::from("/data/QC_A3.raw"),
+            PathBuf::from("/data/QC_A4.raw"),
+        ];
+
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+
+        assert_eq!(split.len(), 3);
+
+        assert_eq!(split[0].0, raw_paths[0]);
+        assert_eq!(split[0].1.targets.len(), 2);
+        assert_eq!(split[0].1.targets[0].target_id, "PEPTIDEA_500.25");
+        assert!(split[0].1.targets[0].detected);
+        assert!(!split[0].1.targets[1].detected);
+
+        assert_eq!(split[1].1.targets.len(), 1);
+        assert_eq!(split[1].1.targets[0].peak_area, 2000.0);
+
+        // QC_A4 produced no rows; SAMPLE_B5 was not requested
+        assert_eq!(split[2].0, raw_paths[2]);
+        assert!(split[2].1.targets.is_empty());
+    }
+
+    #[test]
+    fn test_missing_mz_column_gives_distinct_target_ids() {
+        let report = "\
+File Name,Peptide Sequence,Total Area
+QC_A1.raw,PEPTIDEA,1000
+QC_A1.raw,PEPTIDEA,2000
+";
+        let raw_paths = vec![PathBuf::from("/data/QC_A1.raw")];
+
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+        let targets = &split[0].1.targets;
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].target_id, "PEPTIDEA_row1");
+        assert_eq!(targets[1].target_id, "PEPTIDEA_row2");
+        assert!(!targets[0].target_id.contains("0.00"));
+    }
+
+    #[test]
+    fn test_parse_import_warnings() {
+        let stdout = "\
+Opening file...
+Importing QC_A1.raw
+Warning: Failed to import some transitions for PEPTIDEB
+Warning: Failed to import some transitions for PEPTIDEB
+Exporting report MD_QC_Report...
+";
+        assert_eq!(
+            parse_import_warnings(stdout),
+            vec!["Warning: Failed to import some transitions for PEPTIDEB"]
+        );
+
+        assert!(parse_import_warnings("Opening file...\nExporting report...\n").is_empty());
+    }
+
+    #[test]
+    fn test_split_batch_report_matches_replicate_name() {
+        let report =
+            "Replicate Name,Peptide Sequence,Precursor Mz,Total Area\nqc_a1,PEPTIDEA,500.25,1000\n";
+        let raw_paths = vec![PathBuf::from("/data/QC_A1.raw")];
+
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+        assert_eq!(split[0].1.targets.len(), 1);
+    }
+
+    #[test]
+    fn test_split_batch_report_prefers_file_name_over_replicate() {
+        // Replicates named by hand don't match the raw files
+        let report = "\
+File Name,Peptide Sequence,Precursor Mz,Total Area,Replicate
+QC_A1.raw,PEPTIDEA,500.25,1000,HeLa check 1
+QC_A3.raw,PEPTIDEA,500.25,2000,HeLa check 2
+";
+        let raw_paths = vec![
+            PathBuf::from("/data/QC_A1.raw"),
+            PathBuf::from("/data/QC_A3.raw"),
+        ];
+
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+        assert_eq!(split[0].1.targets.len(), 1);
+        assert_eq!(split[1].1.targets.len(), 1);
+        assert_eq!(split[1].1.targets[0].peak_area, 2000.0);
+    }
+
+    #[test]
+    fn test_acquisition_metadata_columns() {
+        let report = "\
+File Name,Peptide Sequence,Precursor Mz,Total Area,Acquired Time,Instrument Serial Number,Instrument Model,Acquisition Method
+qc_a1.raw,PEPTIDEA,500.25,1000,2026-10-16T09:30:00Z,Exploris240-1234,Orbitrap Exploris 240,C:\\Methods\\QC_30min.meth
+qc_a1.raw,PEPTIDEB,600.30,2000,2026-10-16T09:30:00Z,Exploris240-1234,Orbitrap Exploris 240,C:\\Methods\\QC_30min.meth
+qc_a2.raw,PEPTIDEA,500.25,1500,,,,
+";
+        let raw_paths = vec![
+            PathBuf::from("/data/qc_a1.raw"),
+            PathBuf::from("/data/qc_a2.raw"),
+        ];
+
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+
+        let acquisition = &split[0].1.acquisition;
+        assert_eq!(
+            acquisition.acquisition_time,
+            Some(
+                chrono::DateTime::parse_from_rfc3339("2026-10-16T09:30:00Z")
+                    .unwrap()
+                    .with_timezone(&chrono::Utc)
+            )
+        );
+        assert_eq!(
+            acquisition.instrument_serial.as_deref(),
+            Some("Exploris240-1234")
+        );
+        assert_eq!(
+            acquisition.instrument_model.as_deref(),
+            Some("Orbitrap Exploris 240")
+        );
+        assert_eq!(
+            acquisition.method_name.as_deref(),
+            Some("C:\\Methods\\QC_30min.meth")
+        );
+
+        // Empty cells leave the fields unset
+        assert_eq!(split[1].1.acquisition, AcquisitionMetadata::default());
+
+        // Reports without the columns parse as before
+        let report =
+            "File Name,Peptide Sequence,Precursor Mz,Total Area\nqc_a1.raw,PEPTIDEA,500.25,1000\n";
+        let split =
+            split_batch_report(report.as_bytes(), &raw_paths, SymmetryMetricKind::Auto).unwrap();
+        assert_eq!(split[0].1.targets.len(), 1);
+        assert_eq!(split[0].1.acquisition, AcquisitionMetadata::default());
+
+        assert!(parse_acquired_time("10/16/2026 9:30:00 AM").is_some());
+        assert!(parse_acquired_time("not a time").is_none());
+    }
+
+    #[test]
+    fn test_skyline_exit_code_preserved() {
+        let stdout: String = (1..=50)
+            .map(|i| format!("Importing chunk {}\n", i))
+            .chain(["Error: The file C:\\data\\run.raw could not be opened.\n".to_string()])
+            .collect();
+
+        let err: anyhow::Error = skyline_exit_error(2, "", &stdout).into();
+        let Some(ExtractionError::SkylineExecution {
+            exit_code,
+            stderr,
+            stdout_tail,
+        }) = err.downcast_ref::<ExtractionError>()
+        else {
+            panic!("expected SkylineExecution, got {:?}", err);
+        };
+
+        assert_eq!(*exit_code, 2);
+        assert!(stderr.is_empty());
+        assert_eq!(stdout_tail.lines().count(), STDOUT_TAIL_LINES);
+        assert!(stdout_tail.ends_with("could not be opened."));
+
+        // The message stays one concise line
+        assert_eq!(
+            err.to_string(),
+            "Skyline exited with code 2: Error: The file C:\\data\\run.raw could not be opened."
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_idle_watchdog_kills_silent_process() {
+        let silent = |script: &str| {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", script])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true);
+            cmd
+        };
+
+        // Stuck on a prompt: no output, long before the hard timeout
+        let started = Instant::now();
+        let result = run_with_idle_watchdog(
+            silent("sleep 30"),
+            Duration::from_secs(30),
+            Some(Duration::from_secs(1)),
+        )
+        .await;
+        assert!(matches!(result, Err(ExtractionError::SkylineStalled(1))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Steady progress output keeps it alive past the idle timeout
+        let output = run_with_idle_watchdog(
+            silent("for i in 1 2 3 4; do echo \"Importing $i\"; sleep 0.5; done"),
+            Duration::from_secs(30),
+            Some(Duration::from_secs(1)),
+        )
+        .await
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 4);
+
+        // The hard timeout still applies
+        let result = run_with_idle_watchdog(
+            silent("while true; do echo tick; sleep 0.2; done"),
+            Duration::from_secs(1),
+            Some(Duration::from_secs(1)),
+        )
+        .await;
+        assert!(matches!(result, Err(ExtractionError::SkylineTimeout(1))));
+    }
+
+    #[test]
+    fn test_preflight_read_needs_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("run.raw");
+        std::fs::write(&raw, b"fake raw data").unwrap();
+        let empty = dir.path().join("empty.raw");
+        std::fs::write(&empty, b"").unwrap();
+
+        assert!(read_first_block(&raw).is_ok());
+        assert!(read_first_block(&empty).is_err());
+        assert!(read_first_block(&dir.path().join("missing.raw")).is_err());
+        assert!(read_first_block(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Architecture of Windows executables, read from the PE header.
+//!
+//! A 32-bit agent driving a 64-bit Skyline (or the reverse) shows up as an
+//! opaque launch failure, so `mdqc doctor` compares the agent's architecture
+//! with SkylineCmd's. SkylineCmd is a .NET assembly: an x86 machine type with
+//! an IL-only CLR header and no 32-bit flags is AnyCPU and runs natively on
+//! either.
+
+use std::path::Path;
+
+const MACHINE_I386: u16 = 0x014c;
+const MACHINE_AMD64: u16 = 0x8664;
+const MACHINE_ARM64: u16 = 0xaa64;
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// Data directory entry of the CLR (.NET) header.
+const CLR_DIRECTORY: usize = 14;
+
+const COMIMAGE_FLAGS_ILONLY: u32 = 0x1;
+const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x2;
+const COMIMAGE_FLAGS_32BITPREFERRED: u32 = 0x20000;
+
+/// CPU architecture of an executable or of the running agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    X86,
+    X64,
+    Arm64,
+    /// .NET assembly that runs as the host OS's native architecture
+    AnyCpu,
+    /// Machine type this check doesn't know
+    Other(u16),
+}
+
+impl Architecture {
+    /// Architecture the agent was built for.
+    pub fn of_agent() -> Self {
+        match std::env::consts::ARCH {
+            "x86" => Architecture::X86,
+            "x86_64" => Architecture::X64,
+            "aarch64" => Architecture::Arm64,
+            _ => Architecture::Other(0),
+        }
+    }
+
+    /// Whether an agent of this architecture can drive an executable of
+    /// `other`'s.
+    pub fn matches(self, other: Architecture) -> bool {
+        self == other || other == Architecture::AnyCpu
+    }
+}
+
+impl std::fmt::Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Architecture::X86 => write!(f, "x86 (32-bit)"),
+            Architecture::X64 => write!(f, "x64 (64-bit)"),
+            Architecture::Arm64 => write!(f, "ARM64"),
+            Architecture::AnyCpu => write!(f, "AnyCPU (.NET)"),
+            Architecture::Other(machine) => write!(f, "unknown (machine 0x{:04x})", machine),
+        }
+    }
+}
+
+/// Read an executable's architecture from its PE header.
+pub fn read_architecture(path: &Path) -> Result<Architecture, String> {
+    let image = std::fs::read(path).map_err(|e| e.to_string())?;
+    parse_architecture(&image)
+}
+
+fn parse_architecture(image: &[u8]) -> Result<Architecture, String> {
+    let u16_at = |offset: usize| {
+        image
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or("truncated PE header")
+    };
+    let u32_at = |offset: usize| {
+        image
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or("truncated PE header")
+    };
+
+    if image.get(..2) != Some(b"MZ") {
+        return Err("not a Windows executable".to_string());
+    }
+    let pe = u32_at(0x3c)? as usize;
+    if image.get(pe..pe + 4) != Some(b"PE\0\0") {
+        return Err("missing PE signature".to_string());
+    }
+
+    let machine = u16_at(pe + 4)?;
+    match machine {
+        MACHINE_AMD64 => return Ok(Architecture::X64),
+        MACHINE_ARM64 => return Ok(Architecture::Arm64),
+        MACHINE_I386 => {}
+        other => return Ok(Architecture::Other(other)),
+    }
+
+    // x86 machine type: a native 32-bit image unless it's an AnyCPU assembly
+    let section_count = u16_at(pe + 6)? as usize;
+    let optional_header = pe + 24;
+    let optional_size = u16_at(pe + 20)? as usize;
+    let (directory_count, directories) = match u16_at(optional_header)? {
+        PE32_MAGIC => (u32_at(optional_header + 92)?, optional_header + 96),
+        PE32_PLUS_MAGIC => (u32_at(optional_header + 108)?, optional_header + 112),
+        magic => return Err(format!("unknown optional header magic 0x{:x}", magic)),
+    };
+    if directory_count as usize <= CLR_DIRECTORY {
+        return Ok(Architecture::X86);
+    }
+    let clr_rva = u32_at(directories + CLR_DIRECTORY * 8)?;
+    if clr_rva == 0 {
+        return Ok(Architecture::X86);
+    }
+
+    // Map the CLR header's RVA to a file offset through the section table
+    let sections = optional_header + optional_size;
+    let clr_offset = (0..section_count)
+        .map(|i| sections + i * 40)
+        .find_map(|section| {
+            let virtual_size = u32_at(section + 8).ok()?;
+            let virtual_address = u32_at(section + 12).ok()?;
+            let raw_offset = u32_at(section + 20).ok()?;
+            (virtual_address..virtual_address.saturating_add(virtual_size))
+                .contains(&clr_rva)
+                .then(|| (clr_rva - virtual_address + raw_offset) as usize)
+        })
+        .ok_or("CLR header outside every section")?;
+
+    let flags = u32_at(clr_offset + 16)?;
+    let any_cpu = flags & COMIMAGE_FLAGS_ILONLY != 0
+        && flags & (COMIMAGE_FLAGS_32BITREQUIRED | COMIMAGE_FLAGS_32BITPREFERRED) == 0;
+    Ok(if any_cpu {
+        Architecture::AnyCpu
+    } else {
+        Architecture::X86
+    })
+}
+
+/// VC++ runtime DLLs vendor readers loaded by Skyline need for `arch`.
+fn required_runtime_dlls(arch: Architecture) -> &'static [&'static str] {
+    match arch {
+        Architecture::X86 => &["vcruntime140.dll", "msvcp140.dll"],
+        _ => &["vcruntime140.dll", "vcruntime140_1.dll", "msvcp140.dll"],
+    }
+}
+
+/// Required runtime DLLs for `arch` that aren't in `system_dir`.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn missing_runtime_dlls(system_dir: &Path, arch: Architecture) -> Vec<&'static str> {
+    required_runtime_dlls(arch)
+        .iter()
+        .copied()
+        .filter(|dll| !system_dir.join(dll).exists())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_read_architecture_from_pe_header() {
+        assert_eq!(
+            read_architecture(&fixture("pe_x64.exe")),
+            Ok(Architecture::X64)
+        );
+        assert_eq!(
+            read_architecture(&fixture("pe_x86.exe")),
+            Ok(Architecture::X86)
+        );
+        // IL-only .NET assembly: AnyCPU, unless flagged 32-bit required
+        assert_eq!(
+            read_architecture(&fixture("pe_anycpu.exe")),
+            Ok(Architecture::AnyCpu)
+        );
+        assert_eq!(
+            read_architecture(&fixture("pe_x86_net.exe")),
+            Ok(Architecture::X86)
+        );
+
+        assert!(read_architecture(&fixture("payload_a.json")).is_err());
+        let truncated = &std::fs::read(fixture("pe_x64.exe")).unwrap()[..0x42];
+        assert!(parse_architecture(truncated).is_err());
+
+        assert!(Architecture::X64.matches(Architecture::AnyCpu));
+        assert!(!Architecture::X86.matches(Architecture::X64));
+    }
+}
@@ -0,0 +1,135 @@
+//! One-time check that a template's report has the columns metrics need.
+//!
+//! A template whose `MD_QC_Report` lacks core columns still extracts "fine",
+//! producing runs with all-empty metrics. At startup the agent exports the
+//! report once per template without importing anything and checks its
+//! headers. Results are cached by template hash, so a template is only probed
+//! again after it changes.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::build_column_map;
+use crate::config::{paths, SymmetryMetricKind};
+
+/// Fields every run needs; without them metrics are empty.
+pub const CORE_COLUMNS: [&str; 3] = ["peptide_sequence", "retention_time", "peak_area"];
+
+/// Core fields the report headers don't map to.
+pub fn missing_core_columns(
+    headers: &csv::StringRecord,
+    symmetry_kind: SymmetryMetricKind,
+) -> Vec<String> {
+    let col_map = build_column_map(headers, symmetry_kind);
+    CORE_COLUMNS
+        .iter()
+        .filter(|field| !col_map.contains_key(*field))
+        .map(|field| field.to_string())
+        .collect()
+}
+
+/// Result of probing one instrument's template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnProbe {
+    pub template: String,
+    pub template_hash: String,
+    pub missing: Vec<String>,
+    pub probed_at: DateTime<Utc>,
+}
+
+impl ColumnProbe {
+    /// Warning to surface for an instrument, if the report has gaps.
+    pub fn warning(&self, instrument_id: &str) -> Option<String> {
+        if self.missing.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "{} ({}): report is missing {}; runs will have empty metrics",
+            instrument_id,
+            self.template,
+            self.missing.join(", ")
+        ))
+    }
+}
+
+/// Probe results by instrument id, persisted across restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnProbeCache {
+    #[serde(default)]
+    pub instruments: BTreeMap<String, ColumnProbe>,
+}
+
+impl ColumnProbeCache {
+    /// Load the cache, starting empty if it is missing or unreadable.
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::store_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the cache to disk.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::store_path();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The cached probe for an instrument, if it was taken against this
+    /// template version.
+    pub fn get(&self, instrument_id: &str, template_hash: &str) -> Option<&ColumnProbe> {
+        self.instruments
+            .get(instrument_id)
+            .filter(|probe| probe.template_hash == template_hash)
+    }
+
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("column_probe.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_without_area_warns() {
+        let headers = csv::StringRecord::from(vec![
+            "Peptide Sequence",
+            "Precursor Mz",
+            "Retention Time",
+            "Max Height",
+        ]);
+        let missing = missing_core_columns(&headers, SymmetryMetricKind::Auto);
+        assert_eq!(missing, vec!["peak_area".to_string()]);
+
+        let probe = ColumnProbe {
+            template: "qc.sky".to_string(),
+            template_hash: "abc".to_string(),
+            missing,
+            probed_at: Utc::now(),
+        };
+        let warning = probe.warning("EXPLORIS01").unwrap();
+        assert!(warning.contains("EXPLORIS01"));
+        assert!(warning.contains("peak_area"));
+
+        let headers =
+            csv::StringRecord::from(vec!["Peptide Sequence", "Retention Time", "Total Area"]);
+        assert!(missing_core_columns(&headers, SymmetryMetricKind::Auto).is_empty());
+
+        // A changed template invalidates the cached probe
+        let mut cache = ColumnProbeCache::default();
+        cache.instruments.insert("EXPLORIS01".to_string(), probe);
+        assert!(cache.get("EXPLORIS01", "abc").is_some());
+        assert!(cache.get("EXPLORIS01", "def").is_none());
+    }
+}
@@ -0,0 +1,309 @@
+//! Raw file hashing.
+//!
+//! The payload carries a hash of the raw file so the cloud can recognize the
+//! same acquisition uploaded twice. `RawHashMode` trades hash strength for
+//! speed on multi-GB files.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::{Instant, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::types::{RawHashMode, Vendor};
+use crate::watcher::key_file;
+
+/// Bytes hashed from each end of the file in `HeadTail` mode.
+const HEAD_TAIL_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Chunk size in `Chunked` mode. Part of the digest definition; changing it
+/// changes every chunked hash.
+const CHUNK_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Hash a raw file according to `mode`.
+///
+/// In every mode but `Full`, directory formats hash their key internal file
+/// (e.g. `analysis.tdf` in a Bruker .d). `None` returns an empty string.
+pub fn hash_raw_file(path: &Path, vendor: Vendor, mode: RawHashMode) -> Result<String> {
+    let key_file = key_file(path, vendor);
+    let start = Instant::now();
+
+    let hash = match mode {
+        RawHashMode::Full => full_hash(path),
+        RawHashMode::Chunked => chunked_hash(&key_file),
+        RawHashMode::HeadTail => head_tail_hash(&key_file),
+        RawHashMode::SizeAndMtime => size_and_mtime_hash(&key_file),
+        RawHashMode::None => return Ok(String::new()),
+    }?;
+
+    debug!(
+        path = %path.display(),
+        mode = ?mode,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "Raw file hashed"
+    );
+    Ok(hash)
+}
+
+/// SHA-256 of a file, or of the entry names and sizes of a directory.
+fn full_hash(path: &Path) -> Result<String> {
+    if path.is_file() {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    } else if path.is_dir() {
+        // For directories, hash a consistent representation
+        // (e.g., concatenation of filenames and sizes)
+        let mut hasher = Sha256::new();
+
+        let mut entries: Vec<_> = std::fs::read_dir(path)?.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|e| e.path());
+
+        for entry in entries {
+            let name = entry.file_name();
+            hasher.update(name.to_string_lossy().as_bytes());
+
+            if let Ok(meta) = entry.metadata() {
+                hasher.update(meta.len().to_le_bytes());
+            }
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    } else {
+        anyhow::bail!("Path is neither file nor directory: {}", path.display())
+    }
+}
+
+/// Chunked SHA-256 of a file using a thread per available core.
+fn chunked_hash(path: &Path) -> Result<String> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    chunked_hash_with(path, CHUNK_BYTES, workers)
+}
+
+/// SHA-256 of the file size followed by the SHA-256 of each `chunk_bytes`
+/// chunk, in file order.
+///
+/// Runs of consecutive chunks are hashed on up to `workers` threads, each
+/// reading through its own file handle. The digest does not depend on
+/// `workers`.
+fn chunked_hash_with(path: &Path, chunk_bytes: u64, workers: usize) -> Result<String> {
+    let len = std::fs::metadata(path)?.len();
+    let chunks = len.div_ceil(chunk_bytes) as usize;
+    let per_worker = chunks.div_ceil(workers.max(1)).max(1);
+
+    let mut digests = vec![Vec::new(); chunks];
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = digests
+            .chunks_mut(per_worker)
+            .enumerate()
+            .map(|(worker, slots)| {
+                let first_chunk = (worker * per_worker) as u64;
+                scope.spawn(move || -> Result<()> {
+                    let mut file = std::fs::File::open(path)?;
+                    file.seek(SeekFrom::Start(first_chunk * chunk_bytes))?;
+                    for slot in slots {
+                        let mut hasher = Sha256::new();
+                        std::io::copy(&mut (&mut file).take(chunk_bytes), &mut hasher)?;
+                        *slot = hasher.finalize().to_vec();
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("hash worker panicked"))
+    })?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(len.to_le_bytes());
+    for digest in digests {
+        hasher.update(digest);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// SHA-256 of the file size and its first and last `HEAD_TAIL_BYTES`.
+fn head_tail_hash(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(len.to_le_bytes());
+
+    if len <= 2 * HEAD_TAIL_BYTES {
+        std::io::copy(&mut file, &mut hasher)?;
+    } else {
+        std::io::copy(&mut (&mut file).take(HEAD_TAIL_BYTES), &mut hasher)?;
+        file.seek(SeekFrom::End(-(HEAD_TAIL_BYTES as i64)))?;
+        std::io::copy(&mut file, &mut hasher)?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// SHA-256 of the file size and modification time.
+fn size_and_mtime_hash(path: &Path) -> Result<String> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(metadata.len().to_le_bytes());
+    hasher.update(mtime.as_secs().to_le_bytes());
+    hasher.update(mtime.subsec_nanos().to_le_bytes());
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(path: &Path, mode: RawHashMode) -> String {
+        hash_raw_file(path, Vendor::Thermo, mode).unwrap()
+    }
+
+    #[test]
+    fn test_full_hash_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.raw");
+        let b = dir.path().join("b.raw");
+        std::fs::write(&a, b"same bytes").unwrap();
+        std::fs::write(&b, b"same bytes").unwrap();
+
+        assert_eq!(hash(&a, RawHashMode::Full), hash(&a, RawHashMode::Full));
+        assert_eq!(hash(&a, RawHashMode::Full), hash(&b, RawHashMode::Full));
+
+        std::fs::write(&b, b"other bytes").unwrap();
+        assert_ne!(hash(&a, RawHashMode::Full), hash(&b, RawHashMode::Full));
+    }
+
+    #[test]
+    fn test_parallel_chunked_hash_matches_serial() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.raw");
+        // Ten full chunks and a partial one
+        let chunk_bytes = 4096;
+        let mut data: Vec<u8> = (0..10 * chunk_bytes + 123)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let serial = chunked_hash_with(&path, chunk_bytes as u64, 1).unwrap();
+        for workers in [2, 3, 4, 11, 64] {
+            assert_eq!(
+                chunked_hash_with(&path, chunk_bytes as u64, workers).unwrap(),
+                serial,
+                "{} workers",
+                workers
+            );
+        }
+
+        // Every chunk counts, including the partial last one
+        *data.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_ne!(
+            chunked_hash_with(&path, chunk_bytes as u64, 4).unwrap(),
+            serial
+        );
+
+        // Empty files hash too
+        let empty = dir.path().join("empty.raw");
+        std::fs::write(&empty, b"").unwrap();
+        assert_eq!(
+            chunked_hash_with(&empty, chunk_bytes as u64, 4).unwrap(),
+            chunked_hash_with(&empty, chunk_bytes as u64, 1).unwrap()
+        );
+        assert_eq!(
+            hash(&empty, RawHashMode::Chunked),
+            chunked_hash_with(&empty, CHUNK_BYTES, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_head_tail_hash_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.raw");
+        let mut data = vec![7u8; (2 * HEAD_TAIL_BYTES + 1024) as usize];
+        std::fs::write(&path, &data).unwrap();
+        let original = hash(&path, RawHashMode::HeadTail);
+        assert_eq!(hash(&path, RawHashMode::HeadTail), original);
+
+        // The middle is not read
+        let middle = data.len() / 2;
+        data[middle] = 0;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(hash(&path, RawHashMode::HeadTail), original);
+
+        // The tail is
+        *data.last_mut().unwrap() = 0;
+        std::fs::write(&path, &data).unwrap();
+        assert_ne!(hash(&path, RawHashMode::HeadTail), original);
+
+        // Small files are hashed whole
+        let small = dir.path().join("small.raw");
+        std::fs::write(&small, b"abc").unwrap();
+        assert_eq!(
+            hash(&small, RawHashMode::HeadTail),
+            hash(&small, RawHashMode::HeadTail)
+        );
+        assert_ne!(hash(&small, RawHashMode::HeadTail), original);
+    }
+
+    #[test]
+    fn test_size_and_mtime_hash_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.raw");
+        std::fs::write(&path, b"data").unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_769_500_000, 0);
+        filetime::set_file_mtime(&path, mtime).unwrap();
+
+        let original = hash(&path, RawHashMode::SizeAndMtime);
+        assert_eq!(hash(&path, RawHashMode::SizeAndMtime), original);
+
+        // Same size and mtime, different content: indistinguishable
+        std::fs::write(&path, b"DATA").unwrap();
+        filetime::set_file_mtime(&path, mtime).unwrap();
+        assert_eq!(hash(&path, RawHashMode::SizeAndMtime), original);
+
+        // A new mtime changes the hash
+        filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1_769_500_001, 0))
+            .unwrap();
+        assert_ne!(hash(&path, RawHashMode::SizeAndMtime), original);
+    }
+
+    #[test]
+    fn test_none_and_directory_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = dir.path().join("run.d");
+        std::fs::create_dir(&run).unwrap();
+        std::fs::write(run.join("analysis.tdf"), b"bruker metadata").unwrap();
+        std::fs::write(run.join("analysis.tdf_bin"), b"bruker frames").unwrap();
+
+        assert_eq!(
+            hash_raw_file(&run, Vendor::Bruker, RawHashMode::None).unwrap(),
+            ""
+        );
+
+        // Other modes hash the key internal file
+        for mode in [
+            RawHashMode::Chunked,
+            RawHashMode::HeadTail,
+            RawHashMode::SizeAndMtime,
+        ] {
+            assert_eq!(
+                hash_raw_file(&run, Vendor::Bruker, mode).unwrap(),
+                hash(&run.join("analysis.tdf"), mode)
+            );
+        }
+
+        // Full keeps hashing the directory listing
+        assert_eq!(
+            hash_raw_file(&run, Vendor::Bruker, RawHashMode::Full).unwrap(),
+            hash_raw_file(&run, Vendor::Bruker, RawHashMode::Full).unwrap()
+        );
+    }
+}
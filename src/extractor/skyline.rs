@@ -4,9 +4,57 @@ use anyhow::Result;
 use sha2::{Digest, Sha256};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::debug;
 
-/// Discover SkylineCmd.exe location.
+/// Process-wide discovery result, shared by the extractor, doctor and tray.
+static DISCOVERY: DiscoveryCache = DiscoveryCache::new();
+
+/// Remembers a discovered SkylineCmd path so the registry, filesystem and
+/// PATH are only probed once. A miss isn't cached, so a later install is
+/// still found; a cached path that disappears is probed for again.
+struct DiscoveryCache {
+    path: Mutex<Option<PathBuf>>,
+}
+
+impl DiscoveryCache {
+    const fn new() -> Self {
+        Self {
+            path: Mutex::new(None),
+        }
+    }
+
+    fn get_or_probe(&self, probe: impl FnOnce() -> Option<PathBuf>) -> Option<PathBuf> {
+        let mut cached = self.path.lock().unwrap();
+
+        if let Some(ref path) = *cached {
+            if path.exists() {
+                return Some(path.clone());
+            }
+            debug!(path = %path.display(), "Cached SkylineCmd path is gone, rediscovering");
+        }
+
+        *cached = probe();
+        cached.clone()
+    }
+
+    fn invalidate(&self) {
+        *self.path.lock().unwrap() = None;
+    }
+}
+
+/// Discover SkylineCmd.exe location, probing only on the first call.
 pub fn discover_skyline() -> Option<PathBuf> {
+    DISCOVERY.get_or_probe(probe_skyline)
+}
+
+/// Forget the cached SkylineCmd path so the next lookup probes again.
+pub fn invalidate_discovery() {
+    DISCOVERY.invalidate();
+}
+
+/// Probe the registry, common install locations and PATH for SkylineCmd.exe.
+fn probe_skyline() -> Option<PathBuf> {
     // 1. Check registry (Windows)
     #[cfg(windows)]
     {
@@ -194,4 +242,30 @@ mod tests {
         let hash = hash_template(temp.path()).unwrap();
         assert_eq!(hash.len(), 64); // SHA-256 produces 64 hex chars
     }
+
+    #[test]
+    fn test_discovery_probes_once() {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        let cache = DiscoveryCache::new();
+        let probes = std::cell::Cell::new(0);
+        let probe = || {
+            probes.set(probes.get() + 1);
+            Some(temp.path().to_path_buf())
+        };
+
+        assert_eq!(cache.get_or_probe(probe), Some(temp.path().to_path_buf()));
+        assert_eq!(cache.get_or_probe(probe), Some(temp.path().to_path_buf()));
+        assert_eq!(cache.get_or_probe(probe), Some(temp.path().to_path_buf()));
+        assert_eq!(probes.get(), 1);
+
+        cache.invalidate();
+        cache.get_or_probe(probe);
+        assert_eq!(probes.get(), 2);
+
+        // Not found isn't cached
+        let cache = DiscoveryCache::new();
+        assert_eq!(cache.get_or_probe(|| None), None);
+        cache.get_or_probe(probe);
+        assert_eq!(probes.get(), 3);
+    }
 }
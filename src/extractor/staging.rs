@@ -0,0 +1,123 @@
+//! Staging folder for batch extraction.
+//!
+//! `--import-all` imports everything in a folder, and an acquisition folder
+//! is mostly SAMPLE runs. The QC runs of a batch are linked into a fresh
+//! folder under the work directory instead, so Skyline only sees those.
+//! Files are hard-linked where possible (same volume) and symlinked
+//! otherwise; directory formats (.d, Waters .raw) are symlinked. Nothing is
+//! copied.
+
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::config::paths;
+use crate::error::ExtractionError;
+
+/// Links to a batch's QC runs; removed on drop, leaving the runs untouched.
+#[derive(Debug)]
+pub struct StagedBatch {
+    dir: PathBuf,
+}
+
+impl StagedBatch {
+    /// The folder to hand to `--import-all`.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for StagedBatch {
+    fn drop(&mut self) {
+        // remove_dir_all removes symlinks without following them
+        if let Err(e) = std::fs::remove_dir_all(paths::long_path(&self.dir)) {
+            warn!(dir = %self.dir.display(), error = %e, "Failed to remove batch staging folder");
+        }
+    }
+}
+
+/// Link each of `raw_paths` into `dir`, which must not exist yet, under its
+/// own file name.
+pub fn stage(raw_paths: &[PathBuf], dir: &Path) -> Result<StagedBatch, ExtractionError> {
+    let fail = |path: &Path, e: std::io::Error| {
+        ExtractionError::BatchStaging(format!("{}: {}", path.display(), e))
+    };
+
+    std::fs::create_dir(paths::long_path(dir)).map_err(|e| fail(dir, e))?;
+    let staged = StagedBatch {
+        dir: dir.to_path_buf(),
+    };
+
+    for raw_path in raw_paths {
+        let Some(name) = raw_path.file_name() else {
+            continue;
+        };
+        let link = dir.join(name);
+        link_run(raw_path, &link).map_err(|e| fail(raw_path, e))?;
+    }
+
+    Ok(staged)
+}
+
+/// Hard-link a file run, falling back to a symlink across volumes; symlink a
+/// directory run.
+fn link_run(raw_path: &Path, link: &Path) -> std::io::Result<()> {
+    let target = paths::long_path(raw_path);
+    let link = paths::long_path(link);
+
+    if !target.is_dir() && std::fs::hard_link(&target, &link).is_ok() {
+        return Ok(());
+    }
+    symlink(&target, &link)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_listed_runs_are_staged_and_removed_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let acquisition = dir.path().join("acquisition");
+        std::fs::create_dir(&acquisition).unwrap();
+        let qc_file = acquisition.join("QC_A1.raw");
+        std::fs::write(&qc_file, b"qc").unwrap();
+        let qc_dir = acquisition.join("QC_A2.d");
+        std::fs::create_dir(&qc_dir).unwrap();
+        std::fs::write(qc_dir.join("analysis.tdf"), b"qc").unwrap();
+        std::fs::write(acquisition.join("SAMPLE_B1.raw"), b"sample").unwrap();
+
+        let staging_dir = dir.path().join("batch");
+        let staged = stage(&[qc_file.clone(), qc_dir.clone()], &staging_dir).unwrap();
+
+        let mut names: Vec<_> = std::fs::read_dir(staged.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["QC_A1.raw", "QC_A2.d"]);
+        assert_eq!(
+            std::fs::read(staging_dir.join("QC_A2.d").join("analysis.tdf")).unwrap(),
+            b"qc"
+        );
+
+        // Dropping the staging folder leaves the runs in place
+        drop(staged);
+        assert!(!staging_dir.exists());
+        assert!(qc_file.exists());
+        assert!(qc_dir.join("analysis.tdf").exists());
+    }
+}
@@ -0,0 +1,204 @@
+//! Unpacking zipped runs for extraction.
+//!
+//! Skyline can't import a run from inside a zip, so `X.d.zip` is unpacked
+//! into a fresh directory under the work folder and the unpacked `X.d` is
+//! imported instead. The directory is removed when the [`UnpackedRun`] is
+//! dropped, whether or not extraction succeeded.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::config::paths;
+use crate::error::ExtractionError;
+use crate::watcher::zipped_run_name;
+
+const MB: u64 = 1024 * 1024;
+
+/// A run unpacked from a zip; deleted on drop.
+#[derive(Debug)]
+pub struct UnpackedRun {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl UnpackedRun {
+    /// The unpacked run, to hand to Skyline.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnpackedRun {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(paths::long_path(&self.dir)) {
+            warn!(dir = %self.dir.display(), error = %e, "Failed to remove unpacked run");
+        }
+    }
+}
+
+/// Unpack `zip_path` into a new directory under `work_dir`.
+///
+/// Fails without unpacking anything if the run would take more than
+/// `max_unzipped_mb`, or more than the free space on the work volume. The
+/// limit is also enforced while writing, in case the zip's sizes lie.
+pub fn unpack(
+    zip_path: &Path,
+    work_dir: &Path,
+    max_unzipped_mb: u64,
+) -> Result<UnpackedRun, ExtractionError> {
+    let fail =
+        |reason: String| ExtractionError::ZipUnpack(format!("{}: {}", zip_path.display(), reason));
+
+    let run_name = zipped_run_name(zip_path).ok_or_else(|| fail("not a zipped run".into()))?;
+    let file = std::fs::File::open(paths::long_path(zip_path)).map_err(|e| fail(e.to_string()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| fail(e.to_string()))?;
+
+    let mut declared: u64 = 0;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).map_err(|e| fail(e.to_string()))?;
+        declared = declared.saturating_add(entry.size());
+    }
+    let limit = max_unzipped_mb.saturating_mul(MB);
+    if declared > limit {
+        return Err(fail(format!(
+            "unpacks to {} MB, over max_unzipped_mb ({} MB)",
+            declared / MB,
+            max_unzipped_mb
+        )));
+    }
+    if let Err(free_mb) = crate::disk::ensure_free_space(work_dir, declared / MB + 1) {
+        return Err(fail(format!(
+            "unpacks to {} MB, only {} MB free",
+            declared / MB,
+            free_mb
+        )));
+    }
+
+    // Created first so a failure part-way through is cleaned up too
+    let mut unpacked = UnpackedRun {
+        dir: work_dir.join(format!("{}_unzipped", Uuid::new_v4())),
+        path: PathBuf::new(),
+    };
+    let root = unpacked.dir.join(run_name);
+    std::fs::create_dir_all(paths::long_path(&root)).map_err(|e| fail(e.to_string()))?;
+
+    let mut remaining = limit;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| fail(e.to_string()))?;
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            return Err(fail(format!("unsafe entry name '{}'", entry.name())));
+        };
+        let dest = root.join(relative);
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(paths::long_path(&dest)).map_err(|e| fail(e.to_string()))?;
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(paths::long_path(parent)).map_err(|e| fail(e.to_string()))?;
+        }
+
+        let mut out =
+            std::fs::File::create(paths::long_path(&dest)).map_err(|e| fail(e.to_string()))?;
+        let written = std::io::copy(&mut (&mut entry).take(remaining + 1), &mut out)
+            .map_err(|e| fail(e.to_string()))?;
+        if written > remaining {
+            return Err(fail(format!(
+                "unpacks to more than max_unzipped_mb ({} MB)",
+                max_unzipped_mb
+            )));
+        }
+        remaining -= written;
+        out.flush().map_err(|e| fail(e.to_string()))?;
+    }
+
+    unpacked.path = run_root(&root, run_name);
+    debug!(
+        zip = %zip_path.display(),
+        unpacked = %unpacked.path.display(),
+        "Unpacked zipped run"
+    );
+    Ok(unpacked)
+}
+
+/// The run inside the unpacked directory. Zips usually wrap the run itself
+/// (`X.d.zip` holding `X.d/...`); others hold its contents directly.
+fn run_root(root: &Path, run_name: &str) -> PathBuf {
+    let extension = Path::new(run_name).extension();
+    let entries: Vec<PathBuf> = std::fs::read_dir(paths::long_path(root))
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+
+    match entries.as_slice() {
+        [only]
+            if only.extension().map(|e| e.to_ascii_lowercase())
+                == extension.map(|e| e.to_ascii_lowercase()) =>
+        {
+            only.clone()
+        }
+        _ => root.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::write::FileOptions;
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_unpack_zipped_bruker_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work");
+        std::fs::create_dir_all(&work).unwrap();
+
+        // The .d folder itself was zipped
+        let wrapped = dir.path().join("QC_A_A1.d.zip");
+        write_zip(
+            &wrapped,
+            &[
+                ("QC_A_A1.d/analysis.tdf", b"tdf"),
+                ("QC_A_A1.d/analysis.tdf_bin", b"frames"),
+            ],
+        );
+        let unpacked = unpack(&wrapped, &work, 1).unwrap();
+        assert_eq!(unpacked.path().file_name().unwrap(), "QC_A_A1.d");
+        assert!(unpacked.path().join("analysis.tdf").is_file());
+        assert!(unpacked.path().starts_with(&work));
+
+        let unpacked_dir = unpacked.dir.clone();
+        drop(unpacked);
+        assert!(!unpacked_dir.exists());
+
+        // Only the folder's contents were zipped
+        let flat = dir.path().join("QC_B_A3.d.zip");
+        write_zip(
+            &flat,
+            &[("analysis.tdf", b"tdf"), ("analysis.tdf_bin", b"frames")],
+        );
+        let unpacked = unpack(&flat, &work, 1).unwrap();
+        assert_eq!(unpacked.path().file_name().unwrap(), "QC_B_A3.d");
+        assert!(unpacked.path().join("analysis.tdf_bin").is_file());
+        drop(unpacked);
+
+        // Over the size limit: nothing is unpacked
+        let big = dir.path().join("QC_A_A2.d.zip");
+        write_zip(
+            &big,
+            &[("QC_A_A2.d/analysis.tdf_bin", &vec![0u8; 2 * MB as usize])],
+        );
+        let err = unpack(&big, &work, 1).unwrap_err();
+        assert!(err.to_string().contains("max_unzipped_mb"), "{}", err);
+        assert_eq!(std::fs::read_dir(&work).unwrap().count(), 0);
+    }
+}
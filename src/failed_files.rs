@@ -1,7 +1,8 @@
 //! Failed files tracking and management.
 //!
 //! Tracks files that failed to process (timeout, errors, etc.) and allows
-//! users to view and retry them.
+//! users to view and retry them. Files that keep failing can be moved to a
+//! quarantine directory so the watcher stops picking them up.
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
@@ -9,12 +10,26 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use tracing::warn;
 
 use crate::config::paths;
+use crate::error::ExtractionError;
+use crate::types::RunClassification;
 
 /// Maximum number of failed files to keep in history
 const MAX_FAILED_FILES: usize = 100;
 
+/// Why a file ended up on the failed list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    /// Classification, stabilization, extraction or spooling failed
+    #[default]
+    Processing,
+    /// The acquisition was aborted; an abort marker was found next to the run
+    Aborted,
+}
+
 /// A file that failed to process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FailedFile {
@@ -24,10 +39,59 @@ pub struct FailedFile {
     pub instrument_id: String,
     /// Reason for failure
     pub reason: String,
+    /// Kind of failure
+    #[serde(default)]
+    pub category: FailureCategory,
     /// When the failure occurred
     pub failed_at: DateTime<Utc>,
     /// Number of retry attempts
     pub retry_count: u32,
+    /// Original path, if the file was moved to quarantine
+    #[serde(default)]
+    pub quarantined_from: Option<PathBuf>,
+    /// What Skyline reported, for failed extractions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<ExtractionDiagnostics>,
+}
+
+/// Skyline output and inputs kept for a failed extraction, so the failure can
+/// be diagnosed without digging through the logs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtractionDiagnostics {
+    /// SkylineCmd exit code, if it ran to completion
+    pub exit_code: Option<i32>,
+    pub stderr: String,
+    /// Last lines of stdout
+    pub stdout_tail: String,
+    /// How the run was classified before extraction
+    pub classification: Option<RunClassification>,
+    /// Template the run was extracted with
+    pub template: String,
+}
+
+impl ExtractionDiagnostics {
+    pub fn new(
+        error: &ExtractionError,
+        classification: &RunClassification,
+        template: &str,
+    ) -> Self {
+        let (exit_code, stderr, stdout_tail) = match error {
+            ExtractionError::SkylineExecution {
+                exit_code,
+                stderr,
+                stdout_tail,
+            } => (Some(*exit_code), stderr.clone(), stdout_tail.clone()),
+            _ => (None, String::new(), String::new()),
+        };
+
+        Self {
+            exit_code,
+            stderr,
+            stdout_tail,
+            classification: Some(classification.clone()),
+            template: template.to_string(),
+        }
+    }
 }
 
 /// Store for tracking failed files
@@ -69,14 +133,25 @@ impl FailedFilesStore {
         paths::data_dir().join("failed_files.json")
     }
 
-    /// Add a failed file
-    pub fn add(&mut self, path: PathBuf, instrument_id: String, reason: String) {
+    /// Add a failed file, counting a repeat failure as a retry
+    pub fn add(
+        &mut self,
+        path: PathBuf,
+        instrument_id: String,
+        reason: String,
+        category: FailureCategory,
+        diagnostics: Option<ExtractionDiagnostics>,
+    ) {
+        let retry_count = self.files.get(&path).map_or(0, |f| f.retry_count + 1);
         let failed = FailedFile {
             path: path.clone(),
             instrument_id,
             reason,
+            category,
             failed_at: Utc::now(),
-            retry_count: 0,
+            retry_count,
+            quarantined_from: None,
+            diagnostics,
         };
 
         self.files.insert(path, failed);
@@ -95,7 +170,6 @@ impl FailedFilesStore {
     }
 
     /// Increment retry count for a file
-    #[allow(dead_code)]
     pub fn increment_retry(&mut self, path: &Path) {
         if let Some(file) = self.files.get_mut(path) {
             file.retry_count += 1;
@@ -103,10 +177,20 @@ impl FailedFilesStore {
         }
     }
 
+    /// Re-key a failed file after it was moved to quarantine
+    pub fn move_to(&mut self, from: &Path, to: PathBuf) {
+        if let Some(mut file) = self.files.remove(from) {
+            file.path = to.clone();
+            file.quarantined_from = Some(from.to_path_buf());
+            self.files.insert(to, file);
+            let _ = self.save();
+        }
+    }
+
     /// Get all failed files, sorted by most recent first
     pub fn get_all(&self) -> Vec<&FailedFile> {
         let mut files: Vec<_> = self.files.values().collect();
-        files.sort_by(|a, b| b.failed_at.cmp(&a.failed_at));
+        files.sort_by_key(|f| std::cmp::Reverse(f.failed_at));
         files
     }
 
@@ -129,7 +213,7 @@ impl FailedFilesStore {
 
         // Get paths sorted by oldest first
         let mut entries: Vec<_> = self.files.iter().collect();
-        entries.sort_by(|a, b| a.1.failed_at.cmp(&b.1.failed_at));
+        entries.sort_by_key(|(_, f)| f.failed_at);
 
         // Collect paths to remove
         let to_remove_count = self.files.len() - MAX_FAILED_FILES;
@@ -164,7 +248,91 @@ impl FailedFiles {
     /// Record a file failure
     pub fn record_failure(&self, path: PathBuf, instrument_id: String, reason: String) {
         let mut store = self.inner.lock().unwrap();
-        store.add(path, instrument_id, reason);
+        store.add(
+            path,
+            instrument_id,
+            reason,
+            FailureCategory::Processing,
+            None,
+        );
+    }
+
+    /// Record a failed extraction along with what Skyline reported
+    pub fn record_extraction_failure(
+        &self,
+        path: PathBuf,
+        instrument_id: String,
+        reason: String,
+        diagnostics: ExtractionDiagnostics,
+    ) {
+        let mut store = self.inner.lock().unwrap();
+        store.add(
+            path,
+            instrument_id,
+            reason,
+            FailureCategory::Processing,
+            Some(diagnostics),
+        );
+    }
+
+    /// Record a run whose acquisition was aborted
+    pub fn record_aborted(&self, path: PathBuf, instrument_id: String, reason: String) {
+        let mut store = self.inner.lock().unwrap();
+        store.add(path, instrument_id, reason, FailureCategory::Aborted, None);
+    }
+
+    /// Move a file that has failed more than `max_retries` times into
+    /// `quarantine_dir`, recording its new path.
+    ///
+    /// Returns the quarantined path, or `None` if the file is under the limit
+    /// or could not be moved.
+    pub fn quarantine_if_exhausted(
+        &self,
+        path: &Path,
+        quarantine_dir: &Path,
+        max_retries: u32,
+    ) -> Option<PathBuf> {
+        let mut store = self.inner.lock().unwrap();
+        let retries = store.files.get(path)?.retry_count;
+        if retries < max_retries {
+            return None;
+        }
+
+        match move_to_quarantine(path, quarantine_dir) {
+            Ok(dest) => {
+                store.move_to(path, dest.clone());
+                Some(dest)
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to quarantine file");
+                None
+            }
+        }
+    }
+
+    /// Count a retry of a failed file
+    pub fn increment_retry(&self, path: &Path) {
+        let mut store = self.inner.lock().unwrap();
+        store.increment_retry(path);
+    }
+
+    /// Record why a retry failed, without counting another retry
+    /// (`increment_retry` already did). Diagnostics are replaced when given.
+    pub fn record_retry_failure(
+        &self,
+        path: &Path,
+        reason: String,
+        diagnostics: Option<ExtractionDiagnostics>,
+    ) {
+        let mut store = self.inner.lock().unwrap();
+        if let Some(file) = store.files.get_mut(path) {
+            file.reason = reason;
+            file.failed_at = Utc::now();
+            if diagnostics.is_some() {
+                file.diagnostics = diagnostics;
+            }
+            let _ = store.save();
+        }
     }
 
     /// Remove a file from failures (after successful processing)
@@ -181,6 +349,12 @@ impl FailedFiles {
         store.files.get(path).cloned()
     }
 
+    /// Get a failed file by path
+    pub fn get(&self, path: &Path) -> Option<FailedFile> {
+        let store = self.inner.lock().unwrap();
+        store.files.get(path).cloned()
+    }
+
     /// Get all failed files
     pub fn get_all(&self) -> Vec<FailedFile> {
         let store = self.inner.lock().unwrap();
@@ -205,3 +379,49 @@ impl Default for FailedFiles {
         Self::new()
     }
 }
+
+/// Move a raw file or run directory into `quarantine_dir`.
+///
+/// Keeps the original name, adding a timestamp if it is already taken. Falls
+/// back to copy-and-delete when the directory is on another volume.
+fn move_to_quarantine(path: &Path, quarantine_dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(quarantine_dir)?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("no file name: {}", path.display()))?;
+    let mut dest = quarantine_dir.join(file_name);
+    if dest.exists() {
+        let stem = path.file_stem().unwrap_or(file_name).to_string_lossy();
+        let suffix = Utc::now().format("%Y%m%d%H%M%S");
+        dest = quarantine_dir.join(match path.extension() {
+            Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+            None => format!("{}_{}", stem, suffix),
+        });
+    }
+
+    if std::fs::rename(path, &dest).is_err() {
+        copy_recursive(path, &dest)?;
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    Ok(dest)
+}
+
+/// Copy a file, or a directory and its contents.
+fn copy_recursive(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
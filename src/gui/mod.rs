@@ -68,7 +68,7 @@ impl ConfigEditor {
         let mut skyline_path = String::new();
         let mut skyline_timeout_secs: u64 = 300;
         let mut scan_interval_secs: u64 = 30;
-        let mut stability_window_secs: u64 = 60;
+        let mut stability_window_secs: u64 = 0;
         let mut instruments = Vec::new();
 
         // Try to load existing config
@@ -80,7 +80,7 @@ impl ConfigEditor {
                 skyline_path = cfg.skyline.path.clone().unwrap_or_default();
                 skyline_timeout_secs = cfg.skyline.timeout_seconds;
                 scan_interval_secs = cfg.watcher.scan_interval_seconds;
-                stability_window_secs = cfg.watcher.stability_window_seconds;
+                stability_window_secs = cfg.watcher.stability_window_seconds.unwrap_or(0);
                 instruments = cfg
                     .instruments
                     .iter()
@@ -141,19 +141,28 @@ impl ConfigEditor {
 
         // Watcher settings
         config.watcher.scan_interval_seconds = self.scan_interval_secs;
-        config.watcher.stability_window_seconds = self.stability_window_secs;
+        config.watcher.stability_window_seconds =
+            (self.stability_window_secs > 0).then_some(self.stability_window_secs);
 
-        // Instruments
+        // Instruments: keep each existing instrument's settings the editor
+        // doesn't show (mode, project, hooks, ...)
+        let existing = std::mem::take(&mut config.instruments);
         config.instruments = self
             .instruments
             .iter()
-            .map(|i| InstrumentConfig {
-                id: i.id.clone(),
-                vendor: i.vendor,
-                watch_path: i.watch_path.clone(),
-                file_pattern: i.file_pattern.clone(),
-                template: i.template.clone(),
-                watcher_overrides: None,
+            .map(|i| {
+                let mut instrument = existing
+                    .iter()
+                    .find(|e| e.id == i.id)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        InstrumentConfig::new(&i.id, i.vendor, &i.watch_path, &i.template)
+                    });
+                instrument.vendor = i.vendor;
+                instrument.watch_path = i.watch_path.clone();
+                instrument.file_pattern = i.file_pattern.clone();
+                instrument.template = i.template.clone();
+                instrument
             })
             .collect();
 
@@ -199,8 +208,8 @@ impl eframe::App for ConfigEditor {
                             ui.end_row();
 
                             ui.label("Stability window (seconds):")
-                                .on_hover_text("Wait for file to stop changing before processing");
-                            ui.add(egui::DragValue::new(&mut self.stability_window_secs).range(10..=600));
+                                .on_hover_text("Wait for file to stop changing before processing (0 = per-vendor default)");
+                            ui.add(egui::DragValue::new(&mut self.stability_window_secs).range(0..=600));
                             ui.end_row();
                         });
                 });
@@ -0,0 +1,204 @@
+//! Agent heartbeats.
+//!
+//! An instrument that acquires no QC for days looks the same, cloud-side, as
+//! an agent that died. With `cloud.heartbeat_interval_minutes` set, the agent
+//! periodically spools a small heartbeat (agent ID, version, instruments with
+//! their last processed run, spool counts) that the uploader POSTs to
+//! `{endpoint}heartbeat`. Heartbeats go through the spool like QC payloads so
+//! they survive outages and restarts; only the newest unsent one is kept.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::{InstrumentConfig, InstrumentMode};
+use crate::history::RunHistory;
+use crate::spool::{Spool, SpoolCounts};
+use crate::types::Vendor;
+
+/// Schema version of the heartbeat payload.
+const HEARTBEAT_SCHEMA_VERSION: &str = "1.0";
+
+/// How often the timer is checked. Wall-clock based, so a PC waking from
+/// sleep sends its overdue heartbeat within this.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Heartbeat sent to `{endpoint}heartbeat`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub schema_version: String,
+    pub heartbeat_id: Uuid,
+    pub agent_id: String,
+    pub agent_version: String,
+    pub generated_at: DateTime<Utc>,
+    pub instruments: Vec<InstrumentHeartbeat>,
+    pub spool: SpoolCounts,
+}
+
+/// A configured instrument and its most recent processed run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstrumentHeartbeat {
+    pub instrument_id: String,
+    pub vendor: Vendor,
+    pub mode: InstrumentMode,
+    /// When the instrument's last run was processed, if any is on record
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+impl Heartbeat {
+    pub fn new(
+        agent_id: &str,
+        instruments: &[InstrumentConfig],
+        last_runs: &HashMap<String, DateTime<Utc>>,
+        spool: SpoolCounts,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            schema_version: HEARTBEAT_SCHEMA_VERSION.to_string(),
+            heartbeat_id: Uuid::new_v4(),
+            agent_id: agent_id.to_string(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            generated_at: now,
+            instruments: instruments
+                .iter()
+                .map(|i| InstrumentHeartbeat {
+                    instrument_id: i.id.clone(),
+                    vendor: i.vendor,
+                    mode: i.mode,
+                    last_run_at: last_runs.get(&i.id).copied(),
+                })
+                .collect(),
+            spool,
+        }
+    }
+}
+
+/// Decides when the next heartbeat is due. The first one is due at once.
+#[derive(Debug)]
+pub struct HeartbeatTimer {
+    interval: Duration,
+    next_due: Option<DateTime<Utc>>,
+}
+
+impl HeartbeatTimer {
+    pub fn new(interval_minutes: u64) -> Self {
+        Self {
+            interval: Duration::minutes(interval_minutes as i64),
+            next_due: None,
+        }
+    }
+
+    /// Whether a heartbeat is due at `now`; if so, the next one is scheduled
+    /// an interval later.
+    pub fn due(&mut self, now: DateTime<Utc>) -> bool {
+        if self.next_due.is_some_and(|due| now < due) {
+            return false;
+        }
+        self.next_due = Some(now + self.interval);
+        true
+    }
+}
+
+/// Spool a heartbeat every `interval_minutes` until the task is aborted.
+pub async fn run(
+    interval_minutes: u64,
+    agent_id: String,
+    instruments: Vec<InstrumentConfig>,
+    history: RunHistory,
+    spool: Spool,
+) {
+    info!(interval_minutes, "Heartbeats enabled");
+
+    let mut timer = HeartbeatTimer::new(interval_minutes);
+    let mut check = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        check.tick().await;
+
+        let now = Utc::now();
+        if !timer.due(now) {
+            continue;
+        }
+
+        let heartbeat = Heartbeat::new(
+            &agent_id,
+            &instruments,
+            &history.last_run_per_instrument(),
+            spool.counts(),
+            now,
+        );
+        match spool.enqueue_heartbeat(&heartbeat) {
+            Ok(path) => debug!(path = %path.display(), "Heartbeat spooled"),
+            Err(e) => warn!(error = %e, "Failed to spool heartbeat"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_generated_when_interval_elapses() {
+        let start = Utc::now();
+        let mut timer = HeartbeatTimer::new(15);
+
+        // One at startup, then not again until 15 minutes have passed
+        assert!(timer.due(start));
+        assert!(!timer.due(start + Duration::minutes(5)));
+        assert!(!timer.due(start + Duration::minutes(14)));
+        let now = start + Duration::minutes(15);
+        assert!(timer.due(now));
+        assert!(!timer.due(now + Duration::minutes(1)));
+
+        let config: crate::config::Config = toml::from_str(
+            r#"
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "/data/exploris"
+template = "qc.sky"
+
+[[instruments]]
+id = "TIMSTOF01"
+vendor = "bruker"
+watch_path = "/data/timstof"
+template = "qc.sky"
+mode = "observe_only"
+"#,
+        )
+        .unwrap();
+        let last_run = start - Duration::days(3);
+        let last_runs = HashMap::from([("EXPLORIS01".to_string(), last_run)]);
+        let spool = SpoolCounts {
+            pending: 2,
+            failed: 1,
+        };
+
+        let heartbeat = Heartbeat::new("agent-1", &config.instruments, &last_runs, spool, now);
+        let json = serde_json::to_value(&heartbeat).unwrap();
+
+        assert_eq!(json["schema_version"], "1.0");
+        assert_eq!(json["agent_id"], "agent-1");
+        assert_eq!(json["agent_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["generated_at"], serde_json::to_value(now).unwrap());
+        assert!(json["heartbeat_id"].is_string());
+        assert_eq!(json["spool"]["pending"], 2);
+        assert_eq!(json["spool"]["failed"], 1);
+
+        let instruments = json["instruments"].as_array().unwrap();
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(instruments[0]["instrument_id"], "EXPLORIS01");
+        assert_eq!(instruments[0]["vendor"], "thermo");
+        assert_eq!(instruments[0]["mode"], "full");
+        assert_eq!(
+            instruments[0]["last_run_at"],
+            serde_json::to_value(last_run).unwrap()
+        );
+        // No run on record yet
+        assert_eq!(instruments[1]["mode"], "observe_only");
+        assert!(instruments[1]["last_run_at"].is_null());
+    }
+}
@@ -0,0 +1,742 @@
+//! Local history of processed runs and agent events.
+//!
+//! Keeps a bounded index of successfully processed runs per instrument and
+//! the last-seen template hash for each instrument, so the agent can detect
+//! template changes across restarts and reprocess recent runs.
+//!
+//! Separately, every classification and the resulting processing decision is
+//! appended to an audit log (`audit.jsonl`, one JSON record per line) so labs
+//! can later show why a file was or wasn't processed as QC.
+//!
+//! Both are pruned to `agent.history_retention_days`; audit records for
+//! payloads that haven't been uploaded yet are always kept.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::paths;
+use crate::types::{RunClassification, RunMetrics};
+
+/// Maximum number of processed runs to keep in history
+const MAX_RUNS: usize = 500;
+
+/// Maximum number of events to keep in history
+const MAX_EVENTS: usize = 100;
+
+/// A successfully processed run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessedRun {
+    /// Path to the raw file
+    pub path: PathBuf,
+    /// Instrument ID
+    pub instrument_id: String,
+    /// Hash of the template used for extraction
+    pub template_hash: String,
+    /// When the run was processed
+    pub processed_at: DateTime<Utc>,
+}
+
+/// A notable agent event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    /// An instrument's template changed since it was last seen
+    TemplateChanged {
+        instrument_id: String,
+        old_hash: String,
+        new_hash: String,
+        /// Number of runs requeued for reprocessing
+        requeued: usize,
+        at: DateTime<Utc>,
+    },
+}
+
+/// What the agent did with a detected run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingDecision {
+    /// Extracted and spooled for upload as QC
+    Processed,
+    /// Classified as a non-QC run and skipped
+    SkippedNonQc,
+    /// The file name could not be classified
+    ClassificationFailed,
+    /// Skyline extraction failed
+    ExtractionFailed,
+    /// Extracted, but the result could not be spooled
+    SpoolFailed,
+    /// Extracted on an observe-only instrument; kept locally, not uploaded
+    Observed,
+    /// Extracted, but classified below `min_upload_confidence`; kept
+    /// locally, not uploaded
+    BelowUploadConfidence,
+    /// Classified on a detect-only instrument; not extracted
+    Detected,
+    /// Extracted, but acquired before the instrument's
+    /// `process_acquired_after` cutoff; not spooled
+    AcquiredBeforeCutoff,
+    /// SAMPLE run checked for carryover of the QC targets; not spooled
+    CarryoverChecked,
+    /// A run with the same file name was already processed on the
+    /// instrument (`agent.dedup_by_filename`); not extracted
+    SkippedDuplicate,
+}
+
+/// Durable record of how a run was classified and what was done with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationAudit {
+    /// Extraction run ID (matches the uploaded payload), or a fresh ID for
+    /// runs that were never extracted
+    pub run_id: Uuid,
+    /// Path to the raw file
+    pub path: PathBuf,
+    /// Instrument ID
+    pub instrument_id: String,
+    /// Classification inputs and result (absent if classification failed)
+    pub classification: Option<RunClassification>,
+    /// Processing decision
+    pub decision: ProcessingDecision,
+    /// Error or other detail behind the decision
+    pub detail: Option<String>,
+    /// Run-level metrics of an extracted run (used by `mdqc report`)
+    #[serde(default)]
+    pub run_metrics: Option<RunMetrics>,
+    /// When the decision was made
+    pub decided_at: DateTime<Utc>,
+}
+
+impl ClassificationAudit {
+    pub fn new(
+        path: PathBuf,
+        instrument_id: String,
+        classification: Option<RunClassification>,
+        decision: ProcessingDecision,
+    ) -> Self {
+        Self {
+            run_id: Uuid::new_v4(),
+            path,
+            instrument_id,
+            classification,
+            decision,
+            detail: None,
+            run_metrics: None,
+            decided_at: Utc::now(),
+        }
+    }
+
+    /// Use the extraction's run ID
+    pub fn with_run_id(mut self, run_id: Uuid) -> Self {
+        self.run_id = run_id;
+        self
+    }
+
+    /// Attach the extracted run's metrics
+    pub fn with_metrics(mut self, run_metrics: RunMetrics) -> Self {
+        self.run_metrics = Some(run_metrics);
+        self
+    }
+
+    /// Attach the reason behind the decision
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// File name of the run
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+}
+
+/// Path to the classification audit log
+pub fn audit_log_path() -> PathBuf {
+    paths::data_dir().join("audit.jsonl")
+}
+
+/// Append an audit record to the log at `path`
+pub fn append_audit(path: &Path, audit: &ClassificationAudit) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(audit)?)?;
+    Ok(())
+}
+
+/// All readable audit records in the log at `path`, oldest first
+pub fn read_audits(path: &Path) -> Result<Vec<ClassificationAudit>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut audits = Vec::new();
+
+    for line in std::io::BufReader::new(file).lines() {
+        // Skip lines torn by a crash mid-write
+        if let Ok(audit) = serde_json::from_str(&line?) {
+            audits.push(audit);
+        }
+    }
+
+    Ok(audits)
+}
+
+/// Find the most recent audit record for a run ID or raw file name
+pub fn find_audit(path: &Path, id: &str) -> Result<Option<ClassificationAudit>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut found = None;
+
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        // Skip lines torn by a crash mid-write
+        let Ok(audit) = serde_json::from_str::<ClassificationAudit>(&line) else {
+            continue;
+        };
+        if audit.run_id.to_string() == id || audit.file_name() == id {
+            found = Some(audit);
+        }
+    }
+
+    Ok(found)
+}
+
+/// Rewrite the audit log at `path` without records decided before `cutoff`,
+/// except those whose run ID is in `keep`. Torn lines are dropped too.
+///
+/// The log is written to a temp file and renamed over the original, so the
+/// space taken by pruned records is given back. Returns how many records
+/// were removed.
+pub fn prune_audit(path: &Path, cutoff: DateTime<Utc>, keep: &HashSet<Uuid>) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut kept = Vec::new();
+    let mut removed = 0;
+
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str::<ClassificationAudit>(&line) {
+            Ok(audit) if audit.decided_at >= cutoff || keep.contains(&audit.run_id) => {
+                kept.push(line)
+            }
+            _ => removed += 1,
+        }
+    }
+
+    if removed == 0 {
+        return Ok(0);
+    }
+
+    let temp_path = path.with_extension("jsonl.tmp");
+    {
+        let mut temp = std::io::BufWriter::new(std::fs::File::create(&temp_path)?);
+        for line in &kept {
+            writeln!(temp, "{}", line)?;
+        }
+        temp.into_inner()?.sync_all()?;
+    }
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(removed)
+}
+
+/// Records removed by [`RunHistory::prune`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub runs: usize,
+    pub events: usize,
+    pub audit_records: usize,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> usize {
+        self.runs + self.events + self.audit_records
+    }
+}
+
+/// A detected template change for an instrument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateChange {
+    pub old_hash: String,
+    pub new_hash: String,
+}
+
+/// Store for run history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryStore {
+    /// Processed runs, oldest first
+    #[serde(default)]
+    pub runs: Vec<ProcessedRun>,
+    /// Last-seen template hash per instrument ID
+    #[serde(default)]
+    pub template_hashes: HashMap<String, String>,
+    /// Recent events, oldest first
+    #[serde(default)]
+    pub events: Vec<HistoryEvent>,
+}
+
+impl HistoryStore {
+    /// Load the history store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    pub fn store_path() -> PathBuf {
+        paths::data_dir().join("history.json")
+    }
+
+    /// Record a processed run, replacing any earlier entry for the same path
+    pub fn add_run(&mut self, path: PathBuf, instrument_id: String, template_hash: String) {
+        self.runs.retain(|r| r.path != path);
+        self.runs.push(ProcessedRun {
+            path,
+            instrument_id,
+            template_hash,
+            processed_at: Utc::now(),
+        });
+
+        if self.runs.len() > MAX_RUNS {
+            let excess = self.runs.len() - MAX_RUNS;
+            self.runs.drain(..excess);
+        }
+    }
+
+    /// Compare an instrument's current template hash with the last-seen one.
+    ///
+    /// Always stores the current hash. Returns a change only if a previous
+    /// hash was recorded and differs; the first sighting is not a change.
+    pub fn detect_template_change(
+        &mut self,
+        instrument_id: &str,
+        current_hash: &str,
+    ) -> Option<TemplateChange> {
+        let previous = self
+            .template_hashes
+            .insert(instrument_id.to_string(), current_hash.to_string());
+
+        match previous {
+            Some(old_hash) if old_hash != current_hash => Some(TemplateChange {
+                old_hash,
+                new_hash: current_hash.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The `count` most recently processed runs for an instrument, newest first
+    pub fn recent_runs(&self, instrument_id: &str, count: usize) -> Vec<PathBuf> {
+        let mut runs: Vec<_> = self
+            .runs
+            .iter()
+            .filter(|r| r.instrument_id == instrument_id)
+            .collect();
+        runs.sort_by_key(|r| std::cmp::Reverse(r.processed_at));
+        runs.into_iter()
+            .take(count)
+            .map(|r| r.path.clone())
+            .collect()
+    }
+
+    /// Whether a run at `path` was processed after `since`
+    pub fn processed_since(&self, path: &Path, since: DateTime<Utc>) -> bool {
+        self.runs
+            .iter()
+            .any(|r| r.path == path && r.processed_at > since)
+    }
+
+    /// The latest run on an instrument processed after `since` whose file
+    /// name matches `path`'s, wherever it was
+    pub fn processed_file_name(
+        &self,
+        instrument_id: &str,
+        path: &Path,
+        since: DateTime<Utc>,
+    ) -> Option<&ProcessedRun> {
+        let file_name = path.file_name()?;
+        self.runs
+            .iter()
+            .filter(|r| r.instrument_id == instrument_id && r.processed_at > since)
+            .filter(|r| r.path.file_name() == Some(file_name))
+            .max_by_key(|r| r.processed_at)
+    }
+
+    /// Record an event
+    pub fn add_event(&mut self, event: HistoryEvent) {
+        self.events.push(event);
+
+        if self.events.len() > MAX_EVENTS {
+            let excess = self.events.len() - MAX_EVENTS;
+            self.events.drain(..excess);
+        }
+    }
+
+    /// Drop runs processed and events recorded before `cutoff`. Returns how
+    /// many runs and events were removed.
+    pub fn prune(&mut self, cutoff: DateTime<Utc>) -> (usize, usize) {
+        let (runs, events) = (self.runs.len(), self.events.len());
+        self.runs.retain(|r| r.processed_at >= cutoff);
+        self.events.retain(|e| e.at() >= cutoff);
+        (runs - self.runs.len(), events - self.events.len())
+    }
+}
+
+impl HistoryEvent {
+    /// When the event happened
+    pub fn at(&self) -> DateTime<Utc> {
+        match self {
+            HistoryEvent::TemplateChanged { at, .. } => *at,
+        }
+    }
+}
+
+/// Thread-safe wrapper for the history store
+#[derive(Clone)]
+pub struct RunHistory {
+    inner: Arc<Mutex<HistoryStore>>,
+}
+
+impl RunHistory {
+    /// Create a new history tracker, loading from disk
+    pub fn new() -> Self {
+        let store = HistoryStore::load().unwrap_or_default();
+        Self {
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record a successfully processed run
+    pub fn record_run(&self, path: PathBuf, instrument_id: String, template_hash: String) {
+        let mut store = self.inner.lock().unwrap();
+        store.add_run(path, instrument_id, template_hash);
+        let _ = store.save();
+    }
+
+    /// Whether a run at `path` was processed after `since`
+    pub fn processed_since(&self, path: &Path, since: DateTime<Utc>) -> bool {
+        self.inner.lock().unwrap().processed_since(path, since)
+    }
+
+    /// The latest run on an instrument with the same file name as `path`,
+    /// processed within the last `window_days` (0 = any time)
+    pub fn processed_file_name(
+        &self,
+        instrument_id: &str,
+        path: &Path,
+        window_days: u32,
+    ) -> Option<ProcessedRun> {
+        let since = if window_days == 0 {
+            DateTime::<Utc>::MIN_UTC
+        } else {
+            Utc::now() - chrono::Duration::days(window_days as i64)
+        };
+        self.inner
+            .lock()
+            .unwrap()
+            .processed_file_name(instrument_id, path, since)
+            .cloned()
+    }
+
+    /// When each instrument's most recent run was processed
+    pub fn last_run_per_instrument(&self) -> HashMap<String, DateTime<Utc>> {
+        let store = self.inner.lock().unwrap();
+        let mut last_runs = HashMap::new();
+        for run in &store.runs {
+            let last = last_runs
+                .entry(run.instrument_id.clone())
+                .or_insert(run.processed_at);
+            *last = (*last).max(run.processed_at);
+        }
+        last_runs
+    }
+
+    /// Append a classification audit record, logging (not returning) failures
+    pub fn record_audit(&self, audit: ClassificationAudit) {
+        let _guard = self.inner.lock().unwrap();
+        if let Err(e) = append_audit(&audit_log_path(), &audit) {
+            warn!(error = %e, path = %audit.path.display(), "Failed to write classification audit");
+        }
+    }
+
+    /// Remove history and audit records older than `retention_days`, keeping
+    /// audit records for the run IDs in `unsent`.
+    pub fn prune(&self, retention_days: u32, unsent: &HashSet<Uuid>) -> Result<PruneReport> {
+        let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+        let mut store = self.inner.lock().unwrap();
+
+        let (runs, events) = store.prune(cutoff);
+        if runs + events > 0 {
+            store.save()?;
+        }
+        let audit_records = prune_audit(&audit_log_path(), cutoff, unsent)?;
+
+        Ok(PruneReport {
+            runs,
+            events,
+            audit_records,
+        })
+    }
+
+    /// Check for a template change and select runs to reprocess.
+    ///
+    /// Returns the detected change (if any) and up to `reprocess_count` of the
+    /// most recent runs for the instrument. The change is recorded as an event.
+    pub fn check_template(
+        &self,
+        instrument_id: &str,
+        current_hash: &str,
+        reprocess_count: usize,
+    ) -> Option<(TemplateChange, Vec<PathBuf>)> {
+        let mut store = self.inner.lock().unwrap();
+
+        let change = store.detect_template_change(instrument_id, current_hash);
+        let result = change.map(|change| {
+            let runs = store.recent_runs(instrument_id, reprocess_count);
+            store.add_event(HistoryEvent::TemplateChanged {
+                instrument_id: instrument_id.to_string(),
+                old_hash: change.old_hash.clone(),
+                new_hash: change.new_hash.clone(),
+                requeued: runs.len(),
+                at: Utc::now(),
+            });
+            (change, runs)
+        });
+
+        let _ = store.save();
+        result
+    }
+}
+
+impl Default for RunHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_change_detection() {
+        let mut store = HistoryStore::default();
+
+        // First sighting records the hash but is not a change
+        assert!(store.detect_template_change("TIMSTOF01", "aaa").is_none());
+        assert!(store.detect_template_change("TIMSTOF01", "aaa").is_none());
+
+        let change = store.detect_template_change("TIMSTOF01", "bbb").unwrap();
+        assert_eq!(change.old_hash, "aaa");
+        assert_eq!(change.new_hash, "bbb");
+        assert_eq!(store.template_hashes["TIMSTOF01"], "bbb");
+
+        // Other instruments are tracked independently
+        assert!(store.detect_template_change("EXPLORIS01", "bbb").is_none());
+    }
+
+    #[test]
+    fn test_audit_round_trip() {
+        use crate::types::{ClassificationConfidence, ClassificationSource, ControlType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+
+        let classification = RunClassification {
+            control_type: ControlType::QcA,
+            well_position: crate::types::WellPosition::new('B', 3),
+            instrument_id: "TIMSTOF01".to_string(),
+            plate_id: Some("plate1".to_string()),
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Filename,
+            matched_pattern: Some("QC_A".to_string()),
+            injection_index: None,
+            reinjection_of: None,
+        };
+        let processed = ClassificationAudit::new(
+            PathBuf::from("/data/TIMSTOF01_QC_A_B3_plate1.d"),
+            "TIMSTOF01".to_string(),
+            Some(classification),
+            ProcessingDecision::Processed,
+        );
+        let skipped = ClassificationAudit::new(
+            PathBuf::from("/data/sample_01.d"),
+            "TIMSTOF01".to_string(),
+            None,
+            ProcessingDecision::ClassificationFailed,
+        )
+        .with_detail("bad name");
+
+        append_audit(&log, &processed).unwrap();
+        append_audit(&log, &skipped).unwrap();
+
+        let found = find_audit(&log, &processed.run_id.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.run_id, processed.run_id);
+        assert_eq!(found.path, processed.path);
+        assert_eq!(found.decision, ProcessingDecision::Processed);
+        assert_eq!(found.decided_at, processed.decided_at);
+        let c = found.classification.unwrap();
+        assert_eq!(c.control_type, ControlType::QcA);
+        assert_eq!(c.well_position.unwrap().to_string(), "B3");
+        assert_eq!(c.plate_id.as_deref(), Some("plate1"));
+        assert_eq!(c.confidence, ClassificationConfidence::High);
+        assert_eq!(c.source, ClassificationSource::Filename);
+        assert_eq!(c.matched_pattern.as_deref(), Some("QC_A"));
+
+        let found = find_audit(&log, "sample_01.d").unwrap().unwrap();
+        assert_eq!(found.decision, ProcessingDecision::ClassificationFailed);
+        assert_eq!(found.detail.as_deref(), Some("bad name"));
+
+        assert!(find_audit(&log, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_recent_runs_selection() {
+        let mut store = HistoryStore::default();
+        let start = Utc::now();
+
+        for i in 0..5 {
+            store.runs.push(ProcessedRun {
+                path: PathBuf::from(format!("run{}.d", i)),
+                instrument_id: "TIMSTOF01".to_string(),
+                template_hash: "aaa".to_string(),
+                processed_at: start - chrono::Duration::minutes(20 - i),
+            });
+        }
+        store.runs.push(ProcessedRun {
+            path: PathBuf::from("other.raw"),
+            instrument_id: "EXPLORIS01".to_string(),
+            template_hash: "aaa".to_string(),
+            processed_at: start - chrono::Duration::minutes(10),
+        });
+
+        let recent = store.recent_runs("TIMSTOF01", 3);
+        assert_eq!(
+            recent,
+            vec![
+                PathBuf::from("run4.d"),
+                PathBuf::from("run3.d"),
+                PathBuf::from("run2.d"),
+            ]
+        );
+
+        // Re-recording a run moves it rather than duplicating it
+        store.add_run(
+            PathBuf::from("run0.d"),
+            "TIMSTOF01".to_string(),
+            "bbb".to_string(),
+        );
+        assert_eq!(
+            store.recent_runs("TIMSTOF01", 1),
+            vec![PathBuf::from("run0.d")]
+        );
+        assert_eq!(store.runs.len(), 6);
+    }
+
+    #[test]
+    fn test_prune_removes_records_older_than_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        let now = Utc::now();
+        let cutoff = now - chrono::Duration::days(30);
+
+        let audit = |name: &str, age_days: i64| {
+            let mut audit = ClassificationAudit::new(
+                PathBuf::from(format!("/data/{}.d", name)),
+                "TIMSTOF01".to_string(),
+                None,
+                ProcessingDecision::Processed,
+            );
+            audit.decided_at = now - chrono::Duration::days(age_days);
+            audit
+        };
+        let old = audit("old", 40);
+        let recent = audit("recent", 5);
+        let old_unsent = audit("old_unsent", 90);
+        for a in [&old, &recent, &old_unsent] {
+            append_audit(&log, a).unwrap();
+        }
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(b"{\"run_id\":\"torn")
+            .unwrap();
+
+        let keep = HashSet::from([old_unsent.run_id]);
+        assert_eq!(prune_audit(&log, cutoff, &keep).unwrap(), 2);
+        assert!(find_audit(&log, &old.run_id.to_string()).unwrap().is_none());
+        assert!(find_audit(&log, "recent.d").unwrap().is_some());
+        // Still waiting to upload, so kept despite its age
+        assert!(find_audit(&log, "old_unsent.d").unwrap().is_some());
+        assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 2);
+        assert!(!log.with_extension("jsonl.tmp").exists());
+
+        // Nothing left to prune leaves the log alone
+        assert_eq!(prune_audit(&log, cutoff, &keep).unwrap(), 0);
+
+        let mut store = HistoryStore::default();
+        for (name, age_days) in [("old.d", 40), ("recent.d", 5)] {
+            store.runs.push(ProcessedRun {
+                path: PathBuf::from(name),
+                instrument_id: "TIMSTOF01".to_string(),
+                template_hash: "aaa".to_string(),
+                processed_at: now - chrono::Duration::days(age_days),
+            });
+            store.add_event(HistoryEvent::TemplateChanged {
+                instrument_id: "TIMSTOF01".to_string(),
+                old_hash: "aaa".to_string(),
+                new_hash: "bbb".to_string(),
+                requeued: 0,
+                at: now - chrono::Duration::days(age_days),
+            });
+        }
+        assert_eq!(store.prune(cutoff), (1, 1));
+        assert_eq!(store.runs.len(), 1);
+        assert_eq!(store.runs[0].path, PathBuf::from("recent.d"));
+        assert_eq!(store.events.len(), 1);
+    }
+}
@@ -0,0 +1,389 @@
+//! Site hook commands run after a run is processed.
+//!
+//! An instrument's `on_success_command` / `on_failure_command` is a command
+//! line with placeholders such as `{path}` or `{run_id}`, e.g.
+//! `C:\QC\hooks\notify.exe --run {run_id} --file "{path}"`.
+//!
+//! The command line is checked when the config is loaded. It is split into
+//! arguments before placeholders are filled into them, and the program is
+//! started directly, never through a shell, so values taken from a file name
+//! can't add arguments or run anything the config doesn't name. Sites that
+//! want shell features call the shell explicitly (`cmd /C ...`).
+//!
+//! Hooks are best-effort: failures and timeouts are logged and never change
+//! how the run itself was handled.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::InstrumentConfig;
+use crate::types::{ExtractionResult, RunClassification};
+
+/// Placeholders accepted in hook commands.
+pub const PLACEHOLDERS: &[&str] = &[
+    "path",
+    "file_name",
+    "instrument",
+    "control_type",
+    "run_id",
+    "recovery_pct",
+    "status",
+    "error",
+];
+
+/// Longest output logged per stream.
+const MAX_LOGGED_OUTPUT: usize = 2000;
+
+/// What a hook is told about the run.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub path: PathBuf,
+    pub instrument_id: String,
+    pub control_type: Option<String>,
+    pub run_id: Option<Uuid>,
+    pub recovery_pct: Option<f64>,
+    /// Failure reason; `None` for a successful run
+    pub error: Option<String>,
+}
+
+impl HookContext {
+    /// Context for a run that was extracted and spooled.
+    pub fn success(
+        path: &Path,
+        classification: &RunClassification,
+        result: &ExtractionResult,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            instrument_id: classification.instrument_id.clone(),
+            control_type: Some(classification.control_type.to_string()),
+            run_id: Some(result.run_id),
+            recovery_pct: Some(result.run_metrics.target_recovery_pct),
+            error: None,
+        }
+    }
+
+    /// Context for a run that failed.
+    pub fn failure(
+        path: &Path,
+        instrument_id: &str,
+        classification: Option<&RunClassification>,
+        error: &str,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            instrument_id: instrument_id.to_string(),
+            control_type: classification.map(|c| c.control_type.to_string()),
+            run_id: None,
+            recovery_pct: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// Value of a placeholder; empty when the run has none.
+    fn value(&self, name: &str) -> String {
+        match name {
+            "path" => self.path.display().to_string(),
+            "file_name" => self
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            "instrument" => self.instrument_id.clone(),
+            "control_type" => self.control_type.clone().unwrap_or_default(),
+            "run_id" => self.run_id.map(|id| id.to_string()).unwrap_or_default(),
+            "recovery_pct" => self
+                .recovery_pct
+                .map(|pct| format!("{:.1}", pct))
+                .unwrap_or_default(),
+            "status" => if self.error.is_some() {
+                "failure"
+            } else {
+                "success"
+            }
+            .to_string(),
+            "error" => self.error.clone().unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Split a command line into arguments on whitespace. Double quotes group
+/// text containing spaces; backslashes are literal (Windows paths).
+fn split_args(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_quotes {
+        anyhow::bail!("unclosed quote in hook command '{}'", command);
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+/// Fill placeholders into one argument. Unknown placeholders are an error.
+fn substitute(arg: &str, value: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = arg;
+
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            anyhow::bail!("unclosed '{{' in hook argument '{}'", arg);
+        };
+        let name = &rest[open + 1..open + close];
+        let Some(value) = value(name) else {
+            anyhow::bail!(
+                "unknown placeholder '{{{}}}' in hook command (expected one of: {})",
+                name,
+                PLACEHOLDERS.join(", ")
+            );
+        };
+
+        out.push_str(&rest[..open]);
+        out.push_str(&value);
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// Check a hook command: a program plus arguments, with placeholders only in
+/// the arguments so the program is always the one the config names.
+pub fn validate_command(command: &str) -> Result<()> {
+    let args = split_args(command)?;
+    let Some(program) = args.first() else {
+        anyhow::bail!("hook command is empty");
+    };
+    if program.contains('{') {
+        anyhow::bail!(
+            "hook command '{}' must name its program directly, without placeholders",
+            command
+        );
+    }
+
+    let known = |name: &str| PLACEHOLDERS.contains(&name).then(String::new);
+    for arg in &args[1..] {
+        substitute(arg, known)?;
+    }
+
+    Ok(())
+}
+
+/// The program and arguments to run for a context.
+fn render(command: &str, context: &HookContext) -> Result<Vec<String>> {
+    let value = |name: &str| PLACEHOLDERS.contains(&name).then(|| context.value(name));
+
+    split_args(command)?
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            if i == 0 {
+                Ok(arg.clone())
+            } else {
+                substitute(arg, value)
+            }
+        })
+        .collect()
+}
+
+/// Run a hook to completion or until `timeout`, logging what it printed.
+pub async fn run(command: &str, context: &HookContext, timeout: Duration) {
+    let args = match render(command, context) {
+        Ok(args) if !args.is_empty() => args,
+        Ok(_) => return,
+        Err(e) => {
+            warn!(error = %e, "Invalid hook command, not running it");
+            return;
+        }
+    };
+
+    let mut cmd = tokio::process::Command::new(&args[0]);
+    cmd.args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    #[cfg(windows)]
+    {
+        // CREATE_NO_WINDOW; hooks run unattended under the service
+        cmd.creation_flags(0x08000000);
+    }
+
+    let path = context.path.display().to_string();
+    match tokio::time::timeout(timeout, cmd.output()).await {
+        Err(_) => warn!(
+            hook = %args[0],
+            path = %path,
+            timeout_secs = timeout.as_secs(),
+            "Hook timed out and was stopped"
+        ),
+        Ok(Err(e)) => warn!(hook = %args[0], path = %path, error = %e, "Could not run hook"),
+        Ok(Ok(output)) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let (stdout, stderr) = (truncate(&stdout), truncate(&stderr));
+            if output.status.success() {
+                info!(hook = %args[0], path = %path, stdout = %stdout, stderr = %stderr, "Hook finished");
+            } else {
+                warn!(
+                    hook = %args[0],
+                    path = %path,
+                    status = %output.status,
+                    stdout = %stdout,
+                    stderr = %stderr,
+                    "Hook failed"
+                );
+            }
+        }
+    }
+}
+
+/// Run the instrument's success or failure hook for a run, if it has one.
+pub async fn run_for(instrument: &InstrumentConfig, context: &HookContext) {
+    let command = if context.error.is_some() {
+        &instrument.on_failure_command
+    } else {
+        &instrument.on_success_command
+    };
+
+    if let Some(command) = command {
+        let timeout = Duration::from_secs(instrument.hook_timeout_seconds);
+        run(command, context, timeout).await;
+    }
+}
+
+/// Like [`run_for`], in the background so the processing loop doesn't wait.
+pub fn spawn_for(instrument: &InstrumentConfig, context: HookContext) {
+    if instrument.on_success_command.is_none() && instrument.on_failure_command.is_none() {
+        return;
+    }
+
+    let instrument = instrument.clone();
+    tokio::spawn(async move { run_for(&instrument, &context).await });
+}
+
+fn truncate(output: &str) -> &str {
+    let output = output.trim();
+    match output.char_indices().nth(MAX_LOGGED_OUTPUT) {
+        Some((end, _)) => &output[..end],
+        None => output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn context() -> HookContext {
+        HookContext {
+            path: PathBuf::from("/data/TIMSTOF01/QC A_A1.d"),
+            instrument_id: "TIMSTOF01".to_string(),
+            control_type: Some("QC_A".to_string()),
+            run_id: Some(Uuid::parse_str("6f1c2a9e-0b7d-4c1e-9a3f-2d5e8b7c4a10").unwrap()),
+            recovery_pct: Some(87.5),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_command() {
+        assert!(validate_command(r#""C:\QC hooks\notify.exe" --run {run_id} "{path}""#).is_ok());
+        assert!(validate_command("").is_err());
+        assert!(validate_command("notify.exe {unknown}").is_err());
+        assert!(validate_command("notify.exe \"{path}").is_err());
+        // The program can't come from the run
+        assert!(validate_command("{path} --now").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hook_receives_substituted_arguments() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("hook.sh");
+        let out = dir.path().join("args.txt");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nfor arg in \"$@\"; do echo \"$arg\"; done > '{}'\necho done\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let command = format!(
+            "{} {{path}} --run={{run_id}} {{control_type}} {{recovery_pct}} {{status}} \"{{file_name}}; rm -rf /\"",
+            script.display()
+        );
+        validate_command(&command).unwrap();
+        run(&command, &context(), Duration::from_secs(10)).await;
+
+        let args = std::fs::read_to_string(&out).unwrap();
+        let args: Vec<&str> = args.lines().collect();
+        assert_eq!(
+            args,
+            [
+                "/data/TIMSTOF01/QC A_A1.d",
+                "--run=6f1c2a9e-0b7d-4c1e-9a3f-2d5e8b7c4a10",
+                "QC_A",
+                "87.5",
+                "success",
+                "QC A_A1.d; rm -rf /",
+            ]
+        );
+
+        // A failure leaves run placeholders empty
+        let failure = HookContext::failure(
+            Path::new("/data/QC_B.raw"),
+            "EXPLORIS01",
+            None,
+            "Skyline timeout",
+        );
+        run(
+            &format!("{} {{status}} {{error}} {{run_id}}", script.display()),
+            &failure,
+            Duration::from_secs(10),
+        )
+        .await;
+        let args = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(args, "failure\nSkyline timeout\n\n");
+
+        // A hung hook is stopped at the timeout
+        let started = std::time::Instant::now();
+        run("sleep 30", &context(), Duration::from_millis(200)).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
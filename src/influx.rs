@@ -0,0 +1,284 @@
+//! Export of run metrics to InfluxDB.
+//!
+//! When an `[influx]` section is configured, each successful extraction is
+//! written to an InfluxDB v2 `/api/v2/write` endpoint as line protocol: one
+//! `mdqc_run` point with the run-level metrics and one `mdqc_target` point per
+//! target, tagged by instrument and control type.
+//!
+//! The export is independent of the cloud upload and best-effort: failures
+//! are logged and never affect the processing pipeline.
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::config::InfluxConfig;
+use crate::types::{ExtractionResult, RunClassification};
+
+/// Measurement for run-level metrics.
+const RUN_MEASUREMENT: &str = "mdqc_run";
+
+/// Measurement for per-target metrics.
+const TARGET_MEASUREMENT: &str = "mdqc_target";
+
+/// Writes run metrics to InfluxDB.
+#[derive(Clone)]
+pub struct InfluxExporter {
+    config: InfluxConfig,
+    client: reqwest::Client,
+}
+
+impl InfluxExporter {
+    pub fn new(config: &InfluxConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            config: config.clone(),
+            client,
+        }
+    }
+
+    /// Write a run's metrics, logging (not returning) any failure.
+    pub async fn export(
+        &self,
+        result: &ExtractionResult,
+        classification: &RunClassification,
+        timestamp: DateTime<Utc>,
+    ) {
+        let body = line_protocol(result, classification, timestamp);
+        let url = format!("{}/api/v2/write", self.config.url.trim_end_matches('/'));
+
+        let response = self
+            .client
+            .post(&url)
+            .query(&[
+                ("org", self.config.org.as_str()),
+                ("bucket", self.config.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .header("Authorization", format!("Token {}", self.config.token))
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body)
+            .send()
+            .await;
+
+        match response {
+            Ok(r) if r.status().is_success() => {
+                debug!(run_id = %result.run_id, "Exported run metrics to InfluxDB");
+            }
+            Ok(r) => {
+                let status = r.status().as_u16();
+                let body = r.text().await.unwrap_or_default();
+                warn!(run_id = %result.run_id, status, body = %body, "InfluxDB write rejected");
+            }
+            Err(e) => {
+                warn!(run_id = %result.run_id, error = %e, "InfluxDB write failed");
+            }
+        }
+    }
+}
+
+/// Format a run as InfluxDB line protocol, one point per line.
+pub fn line_protocol(
+    result: &ExtractionResult,
+    classification: &RunClassification,
+    timestamp: DateTime<Utc>,
+) -> String {
+    let timestamp_ns = timestamp.timestamp_nanos_opt().unwrap_or_default();
+    let tags = format!(
+        "instrument_id={},control_type={}",
+        escape_tag(&classification.instrument_id),
+        escape_tag(&classification.control_type.to_string())
+    );
+
+    let metrics = &result.run_metrics;
+    let mut run_fields = vec![
+        format!("targets_found={}i", metrics.targets_found),
+        format!("targets_expected={}i", metrics.targets_expected),
+        format!("target_recovery_pct={}", metrics.target_recovery_pct),
+    ];
+    push_optional(&mut run_fields, "median_rt_shift", metrics.median_rt_shift);
+    push_optional(
+        &mut run_fields,
+        "median_mass_error_ppm",
+        metrics.median_mass_error_ppm,
+    );
+    push_optional(
+        &mut run_fields,
+        "chromatography_score",
+        metrics.chromatography_score,
+    );
+    push_optional(
+        &mut run_fields,
+        "total_ion_current",
+        metrics.total_ion_current,
+    );
+    push_optional(
+        &mut run_fields,
+        "base_peak_intensity",
+        metrics.base_peak_intensity,
+    );
+    run_fields.push(format!(
+        "raw_file_name=\"{}\"",
+        escape_string(&result.raw_file_name)
+    ));
+
+    let mut lines = vec![format!(
+        "{},{} {} {}",
+        RUN_MEASUREMENT,
+        tags,
+        run_fields.join(","),
+        timestamp_ns
+    )];
+
+    for target in &result.target_metrics {
+        let mut fields = vec![
+            format!("peak_area={}", target.peak_area),
+            format!("retention_time={}", target.retention_time),
+            format!("detected={}", target.detected),
+        ];
+        push_optional(&mut fields, "rt_delta", target.rt_delta);
+        push_optional(&mut fields, "mass_error_ppm", target.mass_error_ppm);
+        push_optional(&mut fields, "peak_width_fwhm", target.peak_width_fwhm);
+
+        lines.push(format!(
+            "{},{},target_id={} {} {}",
+            TARGET_MEASUREMENT,
+            tags,
+            escape_tag(&target.target_id),
+            fields.join(","),
+            timestamp_ns
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Add a float field if present and finite (line protocol has no NaN/inf).
+fn push_optional(fields: &mut Vec<String>, name: &str, value: Option<f64>) {
+    if let Some(v) = value.filter(|v| v.is_finite()) {
+        fields.push(format!("{}={}", name, v));
+    }
+}
+
+/// Escape a tag value (commas, equals signs, and spaces).
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Escape a string field value (double quotes and backslashes).
+fn escape_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        ClassificationConfidence, ClassificationSource, ControlType, RunMetrics, TargetMetrics,
+    };
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn target(id: &str, peak_area: f64, rt_delta: Option<f64>) -> TargetMetrics {
+        TargetMetrics {
+            target_id: id.to_string(),
+            peptide_sequence: None,
+            precursor_mz: 500.0,
+            retention_time: 12.5,
+            rt_expected: None,
+            rt_delta,
+            peak_area,
+            peak_height: 0.0,
+            peak_width_fwhm: None,
+            peak_symmetry: None,
+            tailing_factor: None,
+            mass_error_ppm: Some(-1.5),
+            isotope_dot_product: None,
+            detected: peak_area > 0.0,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn test_line_protocol_well_formed() {
+        let result = ExtractionResult {
+            run_id: Uuid::new_v4(),
+            raw_file_path: PathBuf::from("/data/QC A \"1\".raw"),
+            raw_file_name: "QC A \"1\".raw".to_string(),
+            raw_file_hash: "abc".to_string(),
+            raw_file_hash_mode: Default::default(),
+            extraction_time_ms: 1000,
+            backend: "skyline".to_string(),
+            backend_version: "24.1".to_string(),
+            template_name: "qc.sky".to_string(),
+            template_hash: "def".to_string(),
+            target_metrics: vec![
+                target("PEPTIDEA_500.25", 1000.0, Some(0.1)),
+                target("PEP,B=1", 0.0, None),
+            ],
+            run_metrics: RunMetrics {
+                targets_found: 1,
+                targets_expected: 2,
+                target_recovery_pct: 50.0,
+                median_rt_shift: Some(0.1),
+                median_mass_error_ppm: None,
+                chromatography_score: Some(f64::NAN),
+                missing_targets: Vec::new(),
+                undetected_targets: Vec::new(),
+                total_ion_current: None,
+                base_peak_intensity: None,
+                health_index: None,
+            },
+            import_warnings: Vec::new(),
+            acquisition: Default::default(),
+            skipped_rows: 0,
+            suspect_empty_import: false,
+            consumables: Default::default(),
+        };
+        let classification = RunClassification {
+            control_type: ControlType::QcA,
+            instrument_id: "TIMS TOF01".to_string(),
+            well_position: None,
+            plate_id: None,
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Filename,
+            matched_pattern: None,
+            injection_index: None,
+            reinjection_of: None,
+        };
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let output = line_protocol(&result, &classification, timestamp);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "mdqc_run,instrument_id=TIMS\\ TOF01,control_type=QC_A \
+             targets_found=1i,targets_expected=2i,target_recovery_pct=50,median_rt_shift=0.1,\
+             raw_file_name=\"QC A \\\"1\\\".raw\" 1700000000000000000"
+        );
+        assert_eq!(
+            lines[1],
+            "mdqc_target,instrument_id=TIMS\\ TOF01,control_type=QC_A,target_id=PEPTIDEA_500.25 \
+             peak_area=1000,retention_time=12.5,detected=true,rt_delta=0.1,mass_error_ppm=-1.5 \
+             1700000000000000000"
+        );
+        assert!(lines[2].contains(",target_id=PEP\\,B\\=1 "));
+
+        // Every line is `measurement,tags fields timestamp`
+        for line in lines {
+            let timestamp = line.rsplit(' ').next().unwrap();
+            assert_eq!(timestamp, "1700000000000000000");
+            assert!(!line.contains("NaN"));
+        }
+    }
+}
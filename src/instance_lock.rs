@@ -0,0 +1,94 @@
+//! Single-instance lock for the agent.
+//!
+//! Two agents watching the same folders double-process every run and spool
+//! duplicate payloads. The agent takes an exclusive advisory lock on
+//! `agent.lock` in the data directory (flock on Unix, LockFileEx on Windows)
+//! for as long as it runs; the OS releases it if the process dies. The
+//! holder's PID goes in `agent.pid` next to it, since Windows locks block
+//! other processes from reading the locked file itself.
+
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const LOCK_FILE: &str = "agent.lock";
+const PID_FILE: &str = "agent.pid";
+
+#[derive(Error, Debug)]
+pub enum InstanceLockError {
+    #[error("another agent is already running (PID {0}); stop it before starting a new one")]
+    HeldBy(u32),
+
+    #[error("another agent is already running; stop it before starting a new one")]
+    Held,
+
+    #[error("failed to open lock file {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+}
+
+/// Held for the lifetime of the agent; the lock is released on drop.
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+    pid_path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Take the lock in `dir`, failing if another process holds it.
+    pub fn acquire(dir: &Path) -> Result<Self, InstanceLockError> {
+        let lock_path = dir.join(LOCK_FILE);
+        let pid_path = dir.join(PID_FILE);
+
+        std::fs::create_dir_all(dir).map_err(|e| InstanceLockError::Io(lock_path.clone(), e))?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| InstanceLockError::Io(lock_path.clone(), e))?;
+
+        if file.try_lock_exclusive().is_err() {
+            return Err(read_pid(&pid_path)
+                .map(InstanceLockError::HeldBy)
+                .unwrap_or(InstanceLockError::Held));
+        }
+
+        // Best effort; the lock is what matters
+        let _ = std::fs::write(&pid_path, std::process::id().to_string());
+
+        Ok(Self { file, pid_path })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.pid_path);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance_fails_to_acquire_lock() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let lock = InstanceLock::acquire(dir.path()).unwrap();
+        match InstanceLock::acquire(dir.path()) {
+            Err(InstanceLockError::HeldBy(pid)) => assert_eq!(pid, std::process::id()),
+            other => panic!("expected the lock to be held, got {:?}", other),
+        }
+
+        // Released on drop
+        drop(lock);
+        assert!(!dir.path().join(PID_FILE).exists());
+        InstanceLock::acquire(dir.path()).unwrap();
+    }
+}
@@ -7,22 +7,40 @@ use anyhow::Result;
 use clap::Parser;
 use tracing::info;
 
+mod agent_state;
 mod baseline;
+mod carryover;
 mod classifier;
 mod cli;
 mod config;
+mod consumables;
 mod crash;
+mod disk;
+mod drop_queue;
+mod empty_import;
 mod error;
 mod extractor;
 mod failed_files;
 #[cfg(windows)]
 mod gui;
+mod heartbeat;
+mod history;
+mod hooks;
+mod influx;
+mod instance_lock;
 mod metrics;
 mod notifications;
+mod previous_run;
+mod ready_queue;
+mod reinjection;
+mod replicates;
+mod schedule;
 mod service;
 mod spool;
+mod telemetry;
 mod tray;
 mod types;
+mod update_check;
 mod uploader;
 mod watcher;
 
@@ -44,7 +62,7 @@ fn show_startup_error(message: &str) {
 
     let title = "MD QC Agent - Startup Error";
     let full_message = format!(
-        "Failed to start MD QC Agent:\n\n{}\n\nPlease run 'mdqc doctor' for diagnostics.",
+        "Failed to start MD QC Agent:\n\n{}\n\nPlease run 'mdqc safe' for diagnostics and recovery.",
         message
     );
 
@@ -79,6 +97,10 @@ async fn real_main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    if let Some(ref profile) = cli.profile {
+        config::paths::set_profile(profile);
+    }
+
     // Hide console window for tray and GUI commands (they don't need it)
     #[cfg(windows)]
     if matches!(cli.command, Command::Tray | Command::Gui) {
@@ -101,27 +123,62 @@ async fn real_main() -> Result<()> {
     );
 
     match cli.command {
-        Command::Run { foreground } => {
+        Command::Run {
+            foreground,
+            exit_after,
+        } => {
             if foreground {
-                cli::run::run_foreground().await
+                cli::run::run_foreground(exit_after).await
             } else {
                 #[cfg(windows)]
                 {
+                    if exit_after.is_some() {
+                        tracing::warn!("--exit-after only applies with --foreground; ignoring");
+                    }
                     service::run_as_service()
                 }
                 #[cfg(not(windows))]
                 {
                     // On non-Windows, just run in foreground
-                    cli::run::run_foreground().await
+                    cli::run::run_foreground(exit_after).await
                 }
             }
         }
-        Command::Doctor => cli::doctor::run().await,
-        Command::Classify { path } => cli::classify::run(&path).await,
-        Command::Status => cli::status::run().await,
+        Command::Doctor { json } => cli::doctor::run(json).await,
+        Command::NotifyTest => cli::notify_test::run().await,
+        Command::Bundle { out } => cli::bundle::run(&out).await,
+        Command::Classify {
+            path,
+            evaluate,
+            instrument,
+        } => match (path, evaluate) {
+            (_, Some(ground_truth)) => cli::classify::evaluate(&ground_truth, instrument).await,
+            (Some(path), None) => cli::classify::run(&path).await,
+            (None, None) => unreachable!("clap requires a path or --evaluate"),
+        },
+        Command::Process { batch, instrument } => cli::process::run(&batch, instrument).await,
+        Command::Status { details } => cli::status::run(details).await,
+        Command::Report {
+            from,
+            to,
+            instrument,
+            format,
+        } => cli::report::run(from, to, instrument, format).await,
+        Command::Diff { run_a, run_b, json } => cli::diff::run(&run_a, &run_b, json).await,
+        Command::WatchDebug { path, vendor } => cli::watch_debug::run(&path, vendor).await,
         Command::Baseline { action } => cli::baseline::run(action).await,
         Command::Config { action } => cli::config::run(action).await,
         Command::Failed { action } => cli::failed::run(action).await,
+        Command::History { action } => cli::history::run(action).await,
+        Command::Metrics { action } => cli::metrics::run(action).await,
+        Command::Safe { reset_config } => cli::safe::run(reset_config).await,
+        Command::Spool { action } => cli::spool::run(action).await,
+        Command::Telemetry { action } => cli::telemetry::run(action).await,
+        Command::Uninstall {
+            purge_data,
+            force,
+            confirm,
+        } => cli::uninstall::run(purge_data, force, confirm).await,
         Command::Tray => tray::run_tray().await,
         Command::Gui => {
             #[cfg(windows)]
@@ -133,10 +190,7 @@ async fn real_main() -> Result<()> {
                 anyhow::bail!("GUI is only supported on Windows")
             }
         }
-        Command::Version => {
-            println!("mdqc {}", env!("CARGO_PKG_VERSION"));
-            Ok(())
-        }
+        Command::Version { check } => cli::version::run(check).await,
     }
 }
 
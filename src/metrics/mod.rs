@@ -4,7 +4,15 @@
 
 #![allow(dead_code)]
 
-use crate::types::TargetMetrics;
+use crate::config::HealthIndexWeights;
+use crate::types::{RunMetrics, TargetMetrics};
+
+/// Ideal range for USP tailing / asymmetry factor (1.0 = perfectly symmetric).
+const IDEAL_TAILING_FACTOR: (f64, f64) = (1.0, 1.2);
+
+/// Median mass error (ppm) at which the health index's mass accuracy input
+/// reaches 0, as in the chromatography score.
+const HEALTH_MAX_MASS_ERROR_PPM: f64 = 10.0;
 
 /// Calculate a chromatography quality score from target metrics.
 ///
@@ -13,7 +21,15 @@ use crate::types::TargetMetrics;
 /// - Peak width consistency
 /// - Peak symmetry
 /// - Mass accuracy
+///
+/// Targets flagged `excluded` don't contribute.
 pub fn calculate_chromatography_score(targets: &[TargetMetrics]) -> f64 {
+    if targets.iter().any(|t| t.excluded) {
+        let included: Vec<TargetMetrics> =
+            targets.iter().filter(|t| !t.excluded).cloned().collect();
+        return calculate_chromatography_score(&included);
+    }
+
     if targets.is_empty() {
         return 0.0;
     }
@@ -48,11 +64,25 @@ pub fn calculate_chromatography_score(targets: &[TargetMetrics]) -> f64 {
     // Peak symmetry component (0-1)
     let symmetry_values: Vec<f64> = targets.iter().filter_map(|t| t.peak_symmetry).collect();
 
+    let tailing_values: Vec<f64> = targets.iter().filter_map(|t| t.tailing_factor).collect();
+
     if !symmetry_values.is_empty() {
         // Ideal symmetry is 1.0; score decreases as symmetry deviates
         let mean_symmetry = symmetry_values.iter().sum::<f64>() / symmetry_values.len() as f64;
         let symmetry_score = (1.0 - (mean_symmetry - 1.0).abs()).clamp(0.0, 1.0);
         scores.push(symmetry_score);
+    } else if !tailing_values.is_empty() {
+        // Tailing/asymmetry factor: anything in the ideal band scores 1,
+        // score decreases with distance outside it
+        let mean_tailing = tailing_values.iter().sum::<f64>() / tailing_values.len() as f64;
+        let deviation = if mean_tailing < IDEAL_TAILING_FACTOR.0 {
+            IDEAL_TAILING_FACTOR.0 - mean_tailing
+        } else if mean_tailing > IDEAL_TAILING_FACTOR.1 {
+            mean_tailing - IDEAL_TAILING_FACTOR.1
+        } else {
+            0.0
+        };
+        scores.push((1.0 - deviation).clamp(0.0, 1.0));
     }
 
     // Mass accuracy component (0-1)
@@ -77,6 +107,50 @@ pub fn calculate_chromatography_score(targets: &[TargetMetrics]) -> f64 {
     }
 }
 
+/// Calculate a run's 0-100 health index.
+///
+/// Each input is scored 0-1:
+/// - Recovery: target recovery / 100
+/// - Mass accuracy: 1 - |median mass error| / 10 ppm
+/// - Chromatography: the chromatography score
+/// - Baseline tolerance: 1 within tolerance of the baseline, 0 outside
+///
+/// The index is the weighted mean of the scores the run has, times 100, so
+/// a missing input (no baseline, metric not computed) doesn't count against
+/// the run. `None` when no input with a positive weight is available.
+pub fn health_index(
+    run: &RunMetrics,
+    within_baseline_tolerance: Option<bool>,
+    weights: &HealthIndexWeights,
+) -> Option<f64> {
+    let inputs = [
+        (
+            weights.recovery,
+            (run.targets_expected > 0).then(|| run.target_recovery_pct / 100.0),
+        ),
+        (
+            weights.mass_accuracy,
+            run.median_mass_error_ppm
+                .map(|ppm| 1.0 - ppm.abs() / HEALTH_MAX_MASS_ERROR_PPM),
+        ),
+        (weights.chromatography, run.chromatography_score),
+        (
+            weights.baseline_tolerance,
+            within_baseline_tolerance.map(|within| if within { 1.0 } else { 0.0 }),
+        ),
+    ];
+
+    let (weighted, total_weight) = inputs
+        .iter()
+        .filter(|(weight, _)| *weight > 0.0)
+        .filter_map(|(weight, score)| score.map(|s| (weight * s.clamp(0.0, 1.0), *weight)))
+        .fold((0.0, 0.0), |(sum, total), (score, weight)| {
+            (sum + score, total + weight)
+        });
+
+    (total_weight > 0.0).then(|| weighted / total_weight * 100.0)
+}
+
 /// Identify outlier targets based on deviation from expected values.
 pub fn identify_outliers(
     targets: &[TargetMetrics],
@@ -174,4 +248,116 @@ mod tests {
     fn test_chromatography_score_empty() {
         assert_eq!(calculate_chromatography_score(&[]), 0.0);
     }
+
+    fn make_target(peak_symmetry: Option<f64>, tailing_factor: Option<f64>) -> TargetMetrics {
+        TargetMetrics {
+            target_id: "PEPTIDE_500.00".to_string(),
+            peptide_sequence: Some("PEPTIDE".to_string()),
+            precursor_mz: 500.0,
+            retention_time: 10.0,
+            rt_expected: None,
+            rt_delta: None,
+            peak_area: 1000.0,
+            peak_height: 100.0,
+            peak_width_fwhm: None,
+            peak_symmetry,
+            tailing_factor,
+            mass_error_ppm: None,
+            isotope_dot_product: None,
+            detected: true,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn test_chromatography_score_symmetry_vs_tailing() {
+        // Symmetry of 1.0 is ideal: detection 1.0 + symmetry 1.0
+        let score = calculate_chromatography_score(&[make_target(Some(1.0), None)]);
+        assert!((score - 1.0).abs() < 1e-9);
+
+        // Tailing factor of 1.15 is inside the ideal band and scores perfectly,
+        // where the old symmetry logic would have penalized it
+        let score = calculate_chromatography_score(&[make_target(None, Some(1.15))]);
+        assert!((score - 1.0).abs() < 1e-9);
+
+        // Tailing factor of 1.7 is 0.5 above the band: (1.0 + 0.5) / 2
+        let score = calculate_chromatography_score(&[make_target(None, Some(1.7))]);
+        assert!((score - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_health_index_drops_with_each_input() {
+        let healthy = RunMetrics {
+            targets_found: 10,
+            targets_expected: 10,
+            target_recovery_pct: 100.0,
+            median_rt_shift: None,
+            median_mass_error_ppm: Some(0.0),
+            chromatography_score: Some(1.0),
+            missing_targets: Vec::new(),
+            undetected_targets: Vec::new(),
+            total_ion_current: None,
+            base_peak_intensity: None,
+            health_index: None,
+        };
+        let weights = HealthIndexWeights::default();
+        let index =
+            |run: &RunMetrics, within: Option<bool>| health_index(run, within, &weights).unwrap();
+        let assert_close = |actual: f64, expected: f64| {
+            assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+        };
+        assert_close(index(&healthy, Some(true)), 100.0);
+
+        // Half the targets recovered costs half the recovery weight (0.4)
+        let mut run = healthy.clone();
+        run.target_recovery_pct = 50.0;
+        assert_close(index(&run, Some(true)), 80.0);
+
+        // 5 ppm either way is half the mass accuracy score
+        let mut run = healthy.clone();
+        run.median_mass_error_ppm = Some(-5.0);
+        assert_close(index(&run, Some(true)), 90.0);
+        run.median_mass_error_ppm = Some(25.0);
+        assert_close(index(&run, Some(true)), 80.0);
+
+        let mut run = healthy.clone();
+        run.chromatography_score = Some(0.5);
+        assert_close(index(&run, Some(true)), 90.0);
+
+        assert_close(index(&healthy, Some(false)), 80.0);
+
+        // Missing inputs share their weight out
+        assert_close(index(&healthy, None), 100.0);
+        let mut run = healthy.clone();
+        run.target_recovery_pct = 50.0;
+        assert_close(index(&run, None), 75.0);
+
+        let recovery_only = HealthIndexWeights {
+            recovery: 1.0,
+            mass_accuracy: 0.0,
+            chromatography: 0.0,
+            baseline_tolerance: 0.0,
+        };
+        assert_close(
+            health_index(&run, Some(false), &recovery_only).unwrap(),
+            50.0,
+        );
+        run.targets_expected = 0;
+        assert_eq!(health_index(&run, None, &recovery_only), None);
+    }
+
+    #[test]
+    fn test_chromatography_score_ignores_excluded_targets() {
+        let mut misfire = make_target(Some(1.0), None);
+        misfire.detected = false;
+        let good = make_target(Some(1.0), None);
+
+        // An undetected target halves the detection component
+        let score = calculate_chromatography_score(&[good.clone(), misfire.clone()]);
+        assert!((score - 0.75).abs() < 1e-9);
+
+        misfire.excluded = true;
+        let score = calculate_chromatography_score(&[good, misfire]);
+        assert!((score - 1.0).abs() < 1e-9);
+    }
 }
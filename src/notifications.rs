@@ -1,8 +1,199 @@
 //! Toast notifications for Windows.
 //!
 //! Provides lightweight, non-intrusive notifications for QC processing events.
+//! The agent loop sends its processing toasts through a [`NotificationQueue`],
+//! which collapses bursts of the same kind (e.g. while draining a backlog)
+//! into one summary toast.
 
-use tracing::{debug, warn};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+#[cfg(windows)]
+use tracing::warn;
+
+use crate::config::AgentConfig;
+
+/// Notifications that can wait in the queue before being shown.
+const QUEUE_CAPACITY: usize = 64;
+
+/// A processing notification from the agent loop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    ProcessingStarted {
+        file_name: String,
+    },
+    ExtractionSuccess {
+        file_name: String,
+        targets_found: u32,
+        targets_expected: u32,
+        /// 0-100, when the health index is enabled
+        health_index: Option<f64>,
+    },
+    ExtractionFailure {
+        file_name: String,
+        error: String,
+    },
+    UploadQueued {
+        file_name: String,
+    },
+    /// Several processing-started notifications within one window
+    ProcessingStartedBatch {
+        count: usize,
+    },
+    /// Several extraction outcomes within one window
+    RunsProcessed {
+        succeeded: usize,
+        failed: usize,
+    },
+    /// Several upload-queued notifications within one window
+    UploadsQueued {
+        count: usize,
+    },
+}
+
+impl Notification {
+    /// Show the notification as a toast.
+    fn show(&self) {
+        match self {
+            Notification::ProcessingStarted { file_name } => notify_processing_started(file_name),
+            Notification::ExtractionSuccess {
+                file_name,
+                targets_found,
+                targets_expected,
+                health_index,
+            } => notify_extraction_success(
+                file_name,
+                *targets_found,
+                *targets_expected,
+                *health_index,
+            ),
+            Notification::ExtractionFailure { file_name, error } => {
+                notify_extraction_failure(file_name, error)
+            }
+            Notification::UploadQueued { file_name } => notify_upload_queued(file_name),
+            Notification::ProcessingStartedBatch { count } => {
+                notify_processing_started_batch(*count)
+            }
+            Notification::RunsProcessed { succeeded, failed } => {
+                notify_runs_processed(*succeeded, *failed)
+            }
+            Notification::UploadsQueued { count } => notify_uploads_queued(*count),
+        }
+    }
+}
+
+/// Collapse the notifications of one window: a kind with a single
+/// notification keeps it, several become one summary. Failures count
+/// towards the run summary unless `each_failure` keeps them separate.
+fn coalesce(batch: Vec<Notification>, each_failure: bool) -> Vec<Notification> {
+    let mut started = Vec::new();
+    let mut outcomes = Vec::new();
+    let mut separate = Vec::new();
+    let mut queued = Vec::new();
+    for notification in batch {
+        match notification {
+            Notification::ProcessingStarted { .. } => started.push(notification),
+            Notification::ExtractionFailure { .. } if each_failure => separate.push(notification),
+            Notification::ExtractionSuccess { .. } | Notification::ExtractionFailure { .. } => {
+                outcomes.push(notification)
+            }
+            Notification::UploadQueued { .. } => queued.push(notification),
+            summary => separate.push(summary),
+        }
+    }
+
+    let mut shown = Vec::new();
+    if started.len() > 1 {
+        shown.push(Notification::ProcessingStartedBatch {
+            count: started.len(),
+        });
+    } else {
+        shown.extend(started);
+    }
+    if outcomes.len() > 1 {
+        let failed = outcomes
+            .iter()
+            .filter(|n| matches!(n, Notification::ExtractionFailure { .. }))
+            .count();
+        shown.push(Notification::RunsProcessed {
+            succeeded: outcomes.len() - failed,
+            failed,
+        });
+    } else {
+        shown.extend(outcomes);
+    }
+    shown.extend(separate);
+    if queued.len() > 1 {
+        shown.push(Notification::UploadsQueued {
+            count: queued.len(),
+        });
+    } else {
+        shown.extend(queued);
+    }
+    shown
+}
+
+/// Bounded queue of processing notifications, shown by a background task.
+///
+/// The first notification opens a window of `toast_coalesce_seconds`; what
+/// arrives before it closes is shown together, collapsed per kind. When the
+/// queue is full, notifications are dropped rather than holding up the
+/// agent loop.
+#[derive(Clone)]
+pub struct NotificationQueue {
+    tx: mpsc::Sender<Notification>,
+}
+
+impl NotificationQueue {
+    /// Start the queue's background task.
+    pub fn spawn(config: &AgentConfig) -> Self {
+        Self::spawn_with(
+            Duration::from_secs(config.toast_coalesce_seconds),
+            config.toast_each_failure,
+            |notification| notification.show(),
+        )
+    }
+
+    fn spawn_with<F>(window: Duration, each_failure: bool, show: F) -> Self
+    where
+        F: Fn(Notification) + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        tokio::spawn(async move {
+            while let Some(first) = rx.recv().await {
+                if window.is_zero() {
+                    show(first);
+                    continue;
+                }
+
+                let mut batch = vec![first];
+                let deadline = tokio::time::Instant::now() + window;
+                while let Ok(Some(notification)) =
+                    tokio::time::timeout_at(deadline, rx.recv()).await
+                {
+                    batch.push(notification);
+                }
+
+                if batch.len() > 1 {
+                    debug!(notifications = batch.len(), "Coalescing notifications");
+                }
+                for notification in coalesce(batch, each_failure) {
+                    show(notification);
+                }
+            }
+        });
+
+        Self { tx }
+    }
+
+    /// Queue a notification, dropping it if the queue is full.
+    pub fn push(&self, notification: Notification) {
+        if let Err(e) = self.tx.try_send(notification) {
+            debug!(error = %e, "Notification queue full; dropping notification");
+        }
+    }
+}
 
 /// App User Model ID for notifications.
 /// This must match the ID set on the Start Menu shortcut created by ensure_start_menu_shortcut().
@@ -12,6 +203,14 @@ pub const APP_USER_MODEL_ID: &str = "MassDynamics.QCAgent";
 /// Helper to show a toast notification with consistent styling.
 #[cfg(windows)]
 fn show_toast(title: &str, body: &str, silent: bool) {
+    if let Err(e) = try_show_toast(title, body, silent) {
+        warn!(error = %e, "Failed to show toast notification");
+    }
+}
+
+/// Show a toast, returning the toast API's error if it refused.
+#[cfg(windows)]
+fn try_show_toast(title: &str, body: &str, silent: bool) -> Result<(), String> {
     use winrt_notification::{Duration, Sound, Toast};
 
     let mut toast = Toast::new(APP_USER_MODEL_ID);
@@ -21,8 +220,48 @@ fn show_toast(title: &str, body: &str, silent: bool) {
         toast = toast.sound(Some(Sound::Default));
     }
 
-    if let Err(e) = toast.show() {
-        warn!(error = %e, "Failed to show toast notification");
+    toast.show().map_err(|e| e.to_string())
+}
+
+/// Fire one detected, success and failure toast for `mdqc notify-test`.
+///
+/// Returns each notification type with the toast API's result, or `None`
+/// where toasts aren't supported.
+pub fn send_test_toasts() -> Option<Vec<(&'static str, Result<(), String>)>> {
+    #[cfg(windows)]
+    {
+        let file_name = "mdqc_notify_test.raw";
+        Some(vec![
+            (
+                "detected",
+                try_show_toast(
+                    "QC File Detected",
+                    &format!("{}\nTest notification from 'mdqc notify-test'", file_name),
+                    true,
+                ),
+            ),
+            (
+                "success",
+                try_show_toast(
+                    "QC Extraction Complete",
+                    &format!("{}\nTest notification from 'mdqc notify-test'", file_name),
+                    false,
+                ),
+            ),
+            (
+                "failure",
+                try_show_toast(
+                    "QC Extraction Failed",
+                    &format!("{}\nTest notification from 'mdqc notify-test'", file_name),
+                    false,
+                ),
+            ),
+        ])
+    }
+
+    #[cfg(not(windows))]
+    {
+        None
     }
 }
 
@@ -63,26 +302,38 @@ pub fn notify_processing_started(file_name: &str) {
     }
 }
 
-/// Notify when extraction completes successfully.
-pub fn notify_extraction_success(file_name: &str, targets_found: u32, targets_expected: u32) {
+/// Notify when extraction completes successfully. The health index, when
+/// there is one, leads the body.
+pub fn notify_extraction_success(
+    file_name: &str,
+    targets_found: u32,
+    targets_expected: u32,
+    health_index: Option<f64>,
+) {
     debug!(
         file = file_name,
-        targets_found, targets_expected, "Extraction success notification"
+        targets_found, targets_expected, health_index, "Extraction success notification"
     );
 
     #[cfg(windows)]
     {
         let title = "QC Extraction Complete";
-        let body = format!(
-            "{}\n{}/{} targets detected",
-            file_name, targets_found, targets_expected
-        );
+        let body = match health_index {
+            Some(index) => format!(
+                "Health index {:.0}/100\n{}\n{}/{} targets detected",
+                index, file_name, targets_found, targets_expected
+            ),
+            None => format!(
+                "{}\n{}/{} targets detected",
+                file_name, targets_found, targets_expected
+            ),
+        };
         show_toast(title, &body, false); // Play sound for completion
     }
 
     #[cfg(not(windows))]
     {
-        let _ = (file_name, targets_found, targets_expected);
+        let _ = (file_name, targets_found, targets_expected, health_index);
     }
 }
 
@@ -126,6 +377,71 @@ pub fn notify_upload_queued(file_name: &str) {
     }
 }
 
+/// Notify when several runs started processing within one window.
+fn notify_processing_started_batch(count: usize) {
+    debug!(count, "Processing started summary notification");
+
+    #[cfg(windows)]
+    {
+        let title = "Processing QC Files";
+        let body = format!("{} QC files\nExtracting with Skyline...", count);
+        show_toast(title, &body, true); // Silent
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = count;
+    }
+}
+
+/// Notify when several extractions finished within one window.
+fn notify_runs_processed(succeeded: usize, failed: usize) {
+    debug!(succeeded, failed, "Runs processed summary notification");
+
+    #[cfg(windows)]
+    {
+        let title = if failed > 0 {
+            "QC Extractions Complete - Some Failed"
+        } else {
+            "QC Extractions Complete"
+        };
+        show_toast(title, &runs_processed_body(succeeded, failed), false); // Play sound for completion
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = runs_processed_body(succeeded, failed);
+    }
+}
+
+/// "5 QC runs processed, 1 failed"
+fn runs_processed_body(succeeded: usize, failed: usize) -> String {
+    let total = succeeded + failed;
+    let runs = if total == 1 { "run" } else { "runs" };
+    if failed > 0 {
+        format!("{} QC {} processed, {} failed", total, runs, failed)
+    } else {
+        format!("{} QC {} processed", total, runs)
+    }
+}
+
+/// Notify when several results were queued for upload within one window.
+fn notify_uploads_queued(count: usize) {
+    debug!(count, "Uploads queued summary notification");
+
+    #[cfg(windows)]
+    {
+        let title = "QC Results Queued";
+        let body = format!("{} results\nReady for upload", count);
+        show_toast(title, &body, true); // Silent
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = count;
+    }
+}
+
 /// Notify when results are successfully uploaded.
 #[allow(dead_code)] // Will be used when upload destination is configured
 pub fn notify_upload_success(file_name: &str) {
@@ -166,3 +482,145 @@ pub fn notify_upload_failure(file_name: &str, error: &str) {
         let _ = (file_name, error);
     }
 }
+
+/// Notify when processing is deferred because the data drive is nearly full.
+pub fn notify_low_disk_space(free_mb: u64, min_free_mb: u64) {
+    debug!(free_mb, min_free_mb, "Low disk space notification");
+
+    #[cfg(windows)]
+    {
+        let title = "QC Processing Paused - Low Disk Space";
+        let body = format!(
+            "{} MB free, {} MB required.\nFree up space on the data drive to resume.",
+            free_mb, min_free_mb
+        );
+        show_toast(title, &body, false); // Play sound, needs attention
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (free_mb, min_free_mb);
+    }
+}
+
+/// Notify when the agent starts with no instruments to watch.
+pub fn notify_no_instruments() {
+    debug!("No instruments notification");
+
+    #[cfg(windows)]
+    {
+        let title = "QC Agent Not Watching Any Instruments";
+        let body =
+            "No instruments are configured.\nAdd an [[instruments]] section to the config file.";
+        show_toast(title, body, false); // Play sound, needs attention
+    }
+}
+
+/// Notify when the pending spool nears its size limit.
+pub fn notify_spool_near_full(used_mb: u64, max_mb: u64) {
+    debug!(used_mb, max_mb, "Spool near full notification");
+
+    #[cfg(windows)]
+    {
+        let title = "QC Upload Queue Nearly Full";
+        let body = format!(
+            "{} of {} MB used. Results aren't uploading;\ncheck the network connection.",
+            used_mb, max_mb
+        );
+        show_toast(title, &body, false); // Play sound, needs attention
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (used_mb, max_mb);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn success(file_name: &str) -> Notification {
+        Notification::ExtractionSuccess {
+            file_name: file_name.to_string(),
+            targets_found: 24,
+            targets_expected: 25,
+            health_index: None,
+        }
+    }
+
+    fn failure(file_name: &str) -> Notification {
+        Notification::ExtractionFailure {
+            file_name: file_name.to_string(),
+            error: "Skyline exited with code 1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_successes_are_coalesced() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&shown);
+        let queue = NotificationQueue::spawn_with(Duration::from_millis(200), false, move |n| {
+            sink.lock().unwrap().push(n)
+        });
+
+        for i in 0..5 {
+            queue.push(success(&format!("QC_A_{}.raw", i)));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(
+            *shown.lock().unwrap(),
+            [Notification::RunsProcessed {
+                succeeded: 5,
+                failed: 0
+            }]
+        );
+
+        // A lone event after the window keeps its own toast
+        queue.push(success("QC_B_1.raw"));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(shown.lock().unwrap()[1], success("QC_B_1.raw"));
+    }
+
+    #[test]
+    fn test_coalesce_per_kind() {
+        let batch = vec![
+            Notification::ProcessingStarted {
+                file_name: "a.raw".to_string(),
+            },
+            success("a.raw"),
+            success("b.raw"),
+            failure("c.raw"),
+            success("d.raw"),
+            Notification::UploadQueued {
+                file_name: "a.raw".to_string(),
+            },
+        ];
+
+        let shown = coalesce(batch.clone(), false);
+        assert_eq!(
+            shown,
+            [
+                batch[0].clone(),
+                Notification::RunsProcessed {
+                    succeeded: 3,
+                    failed: 1
+                },
+                batch[5].clone(),
+            ]
+        );
+        assert_eq!(runs_processed_body(3, 1), "4 QC runs processed, 1 failed");
+
+        // Failures kept separate when configured
+        let shown = coalesce(batch, true);
+        assert_eq!(
+            shown[1],
+            Notification::RunsProcessed {
+                succeeded: 3,
+                failed: 0
+            }
+        );
+        assert_eq!(shown[2], failure("c.raw"));
+    }
+}
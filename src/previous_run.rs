@@ -0,0 +1,217 @@
+//! Run-over-run comparison.
+//!
+//! Remembers the last run per instrument and control type so that each new
+//! QC run can report how far it moved from the one before it. A baseline
+//! smooths over sudden step changes (a source cleaning, a column swap);
+//! consecutive-run deltas make them obvious.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::paths;
+use crate::types::{ControlType, PreviousRunComparison, RunMetrics};
+
+/// The run-level metrics of the last run seen for a control type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviousRun {
+    pub run_id: Uuid,
+    pub recorded_at: DateTime<Utc>,
+    pub target_recovery_pct: f64,
+    pub median_rt_shift: Option<f64>,
+    pub median_mass_error_ppm: Option<f64>,
+}
+
+impl PreviousRun {
+    pub fn new(run_id: Uuid, metrics: &RunMetrics) -> Self {
+        Self {
+            run_id,
+            recorded_at: Utc::now(),
+            target_recovery_pct: metrics.target_recovery_pct,
+            median_rt_shift: metrics.median_rt_shift,
+            median_mass_error_ppm: metrics.median_mass_error_ppm,
+        }
+    }
+
+    /// Deltas of `current` relative to this run (current minus previous).
+    pub fn compare(&self, current: &PreviousRun) -> PreviousRunComparison {
+        let delta = |cur: Option<f64>, prev: Option<f64>| Some(cur? - prev?);
+
+        PreviousRunComparison {
+            previous_run_id: self.run_id,
+            previous_recorded_at: self.recorded_at,
+            target_recovery_pct_delta: current.target_recovery_pct - self.target_recovery_pct,
+            median_rt_shift_delta: delta(current.median_rt_shift, self.median_rt_shift),
+            median_mass_error_ppm_delta: delta(
+                current.median_mass_error_ppm,
+                self.median_mass_error_ppm,
+            ),
+        }
+    }
+}
+
+/// Store of the last run per instrument and control type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreviousRunStore {
+    /// Last run keyed by "<instrument_id>/<control_type>"
+    pub runs: HashMap<String, PreviousRun>,
+}
+
+impl PreviousRunStore {
+    /// Load the store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("previous_runs.json")
+    }
+
+    /// Make `run` the previous run for its control type and compare it to
+    /// the one it replaces.
+    pub fn replace(
+        &mut self,
+        instrument_id: &str,
+        control_type: ControlType,
+        run: PreviousRun,
+    ) -> Option<PreviousRunComparison> {
+        let key = format!("{}/{:?}", instrument_id, control_type);
+        let previous = self.runs.insert(key, run.clone())?;
+        Some(previous.compare(&run))
+    }
+}
+
+/// Thread-safe run-over-run tracker. A disabled tracker is a no-op.
+#[derive(Clone)]
+pub struct PreviousRunTracker {
+    enabled: bool,
+    inner: Arc<Mutex<PreviousRunStore>>,
+}
+
+impl PreviousRunTracker {
+    /// Create a tracker, loading the previous runs from disk when enabled
+    pub fn new(enabled: bool) -> Self {
+        let store = if enabled {
+            PreviousRunStore::load().unwrap_or_default()
+        } else {
+            PreviousRunStore::default()
+        };
+
+        Self {
+            enabled,
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record a completed run and compare it to the previous run of the same
+    /// control type on the instrument. `None` for the first run.
+    pub fn record(
+        &self,
+        instrument_id: &str,
+        control_type: ControlType,
+        run_id: Uuid,
+        metrics: &RunMetrics,
+    ) -> Option<PreviousRunComparison> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut store = self.inner.lock().unwrap();
+        let comparison = store.replace(
+            instrument_id,
+            control_type,
+            PreviousRun::new(run_id, metrics),
+        );
+        let _ = store.save();
+
+        comparison
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(recovery: f64, rt_shift: Option<f64>, mass_error: Option<f64>) -> RunMetrics {
+        RunMetrics {
+            targets_found: 0,
+            targets_expected: 0,
+            target_recovery_pct: recovery,
+            median_rt_shift: rt_shift,
+            median_mass_error_ppm: mass_error,
+            chromatography_score: None,
+            missing_targets: Vec::new(),
+            undetected_targets: Vec::new(),
+            total_ion_current: None,
+            base_peak_intensity: None,
+            health_index: None,
+        }
+    }
+
+    #[test]
+    fn test_sequential_runs_produce_run_over_run_deltas() {
+        let mut store = PreviousRunStore::default();
+        let first = Uuid::new_v4();
+
+        // The first run has nothing to compare against
+        let comparison = store.replace(
+            "EXPLORIS01",
+            ControlType::QcA,
+            PreviousRun::new(first, &metrics(96.0, Some(0.05), Some(1.5))),
+        );
+        assert!(comparison.is_none());
+
+        // A different control type keeps its own previous run
+        let other = store.replace(
+            "EXPLORIS01",
+            ControlType::QcB,
+            PreviousRun::new(Uuid::new_v4(), &metrics(50.0, None, None)),
+        );
+        assert!(other.is_none());
+
+        let second = Uuid::new_v4();
+        let comparison = store
+            .replace(
+                "EXPLORIS01",
+                ControlType::QcA,
+                PreviousRun::new(second, &metrics(88.0, Some(0.25), None)),
+            )
+            .unwrap();
+        assert_eq!(comparison.previous_run_id, first);
+        assert!((comparison.target_recovery_pct_delta + 8.0).abs() < 1e-9);
+        assert!((comparison.median_rt_shift_delta.unwrap() - 0.2).abs() < 1e-9);
+        // No delta when either run lacks the metric
+        assert!(comparison.median_mass_error_ppm_delta.is_none());
+
+        // The reference survives a save/load round trip
+        let store: PreviousRunStore =
+            serde_json::from_str(&serde_json::to_string(&store).unwrap()).unwrap();
+        assert_eq!(store.runs["EXPLORIS01/QcA"].run_id, second);
+    }
+}
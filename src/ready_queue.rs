@@ -0,0 +1,186 @@
+//! Persisted queue of finalized files awaiting extraction.
+//!
+//! Once the watcher hands a file to the processing loop it only exists in the
+//! channel (or the deferral set) until its result is spooled. The queue keeps
+//! each file on disk from the moment it is queued until it has been handled,
+//! so a restart mid-backlog feeds the unfinished files back in.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use crate::config::paths;
+use crate::types::{FinalizationState, RunOverrides, TrackedFile, Vendor};
+
+/// A finalized file waiting to be processed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedFile {
+    pub path: PathBuf,
+    pub vendor: Vendor,
+    /// When the file was first queued
+    pub queued_at: DateTime<Utc>,
+    /// When the watcher first saw the file
+    #[serde(default)]
+    pub detected_at: Option<DateTime<Utc>>,
+    /// Instrument named by a drop-queue job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrument_id: Option<String>,
+    /// Classification overrides from a drop-queue job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<RunOverrides>,
+    /// Requeued for reprocessing after a template change
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reprocess: bool,
+}
+
+impl QueuedFile {
+    /// Tracked file ready for the processing loop, or `None` if the file is gone.
+    pub fn to_tracked(&self) -> Option<TrackedFile> {
+        let metadata = std::fs::metadata(&self.path).ok()?;
+        let mut file = TrackedFile::new(
+            self.path.clone(),
+            self.vendor,
+            metadata.len(),
+            metadata.modified().ok().map(Into::into),
+        );
+        file.state = FinalizationState::Ready;
+        file.stable_since = Some(Utc::now());
+        file.first_seen = self.detected_at.unwrap_or(self.queued_at);
+        file.finalized_at = Some(self.queued_at);
+        file.instrument_id = self.instrument_id.clone();
+        file.overrides = self.overrides.clone();
+        file.reprocess = self.reprocess;
+        Some(file)
+    }
+}
+
+/// On-disk form of the queue
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ReadyQueueStore {
+    #[serde(default)]
+    files: Vec<QueuedFile>,
+}
+
+/// Thread-safe, write-through ready queue
+#[derive(Clone)]
+pub struct ReadyQueue {
+    store_path: PathBuf,
+    inner: Arc<Mutex<ReadyQueueStore>>,
+}
+
+impl ReadyQueue {
+    /// Open the queue in the data directory.
+    pub fn new() -> Self {
+        Self::at(paths::data_dir().join("ready_queue.json"))
+    }
+
+    /// Open the queue stored at `store_path`.
+    fn at(store_path: PathBuf) -> Self {
+        let store = std::fs::read_to_string(&store_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            store_path,
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record a file as queued; a file already in the queue keeps its entry.
+    pub fn push(&self, file: &TrackedFile) {
+        let mut store = self.inner.lock().unwrap();
+        if store.files.iter().any(|f| f.path == file.path) {
+            return;
+        }
+
+        store.files.push(QueuedFile {
+            path: file.path.clone(),
+            vendor: file.vendor,
+            queued_at: file.finalized_at.unwrap_or_else(Utc::now),
+            detected_at: Some(file.first_seen),
+            instrument_id: file.instrument_id.clone(),
+            overrides: file.overrides.clone(),
+            reprocess: file.reprocess,
+        });
+        self.save(&store);
+    }
+
+    /// Drop a file once it has been handled.
+    pub fn remove(&self, path: &Path) {
+        let mut store = self.inner.lock().unwrap();
+        let before = store.files.len();
+        store.files.retain(|f| f.path != path);
+        if store.files.len() != before {
+            self.save(&store);
+        }
+    }
+
+    /// Files still waiting, oldest first.
+    pub fn pending(&self) -> Vec<QueuedFile> {
+        self.inner.lock().unwrap().files.clone()
+    }
+
+    fn save(&self, store: &ReadyQueueStore) {
+        if let Err(e) = write_store(&self.store_path, store) {
+            warn!(error = %e, path = %self.store_path.display(), "Failed to save ready queue");
+        }
+    }
+}
+
+impl Default for ReadyQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_store(path: &Path, store: &ReadyQueueStore) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, serde_json::to_string_pretty(store)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("ready_queue.json");
+        let raw_a = dir.path().join("QC_A_01.raw");
+        let raw_b = dir.path().join("QC_B_01.raw");
+        std::fs::write(&raw_a, b"raw").unwrap();
+
+        let queue = ReadyQueue::at(store_path.clone());
+        let tracked = |path: &Path| TrackedFile::new(path.to_path_buf(), Vendor::Thermo, 3, None);
+        queue.push(&tracked(&raw_a));
+        queue.push(&tracked(&raw_b));
+        queue.push(&tracked(&raw_a));
+        assert_eq!(queue.pending().len(), 2);
+
+        // Simulated restart: reopen from disk
+        let restored = ReadyQueue::at(store_path.clone()).pending();
+        assert_eq!(restored, queue.pending());
+
+        let file = restored[0].to_tracked().unwrap();
+        assert_eq!(file.path, raw_a);
+        assert_eq!(file.state, FinalizationState::Ready);
+        // Detection time survives the restart for the payload's timing
+        assert_eq!(Some(file.first_seen), restored[0].detected_at);
+        assert_eq!(file.finalized_at, Some(restored[0].queued_at));
+        // Deleted while the agent was down
+        assert!(restored[1].to_tracked().is_none());
+
+        queue.remove(&raw_a);
+        let reopened = ReadyQueue::at(store_path);
+        assert_eq!(reopened.pending().len(), 1);
+        assert_eq!(reopened.pending()[0].path, raw_b);
+    }
+}
@@ -0,0 +1,196 @@
+//! Re-injection detection.
+//!
+//! When a QC run fails, operators often inject the same well again. Left
+//! untagged, trend analysis counts both the failed attempt and the repeat.
+//! A run is taken as a re-injection when the previous run of the same
+//! control type from the same well on the instrument was injected less than
+//! `window_minutes` before it; the payload then references that attempt.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::{paths, ReinjectionConfig};
+use crate::types::RunClassification;
+
+/// The last run injected from a well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentInjection {
+    pub run_id: Uuid,
+    /// Acquisition time, or when the run was processed if unknown
+    pub injected_at: DateTime<Utc>,
+}
+
+/// Store of the last injection per instrument, control type and well.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReinjectionStore {
+    /// Last injection keyed by "<instrument_id>/<control_type>/<well>"
+    pub injections: HashMap<String, RecentInjection>,
+}
+
+impl ReinjectionStore {
+    /// Load the store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("recent_injections.json")
+    }
+
+    /// Remember `injection` for the classification's well and return the
+    /// run it repeats, if the previous injection was within `window`.
+    ///
+    /// Runs without a well position are never matched. Entries older than
+    /// the window are dropped so the store stays small.
+    pub fn record(
+        &mut self,
+        classification: &RunClassification,
+        injection: RecentInjection,
+        window: Duration,
+    ) -> Option<Uuid> {
+        let well = classification.well_position.as_ref()?;
+        let key = format!(
+            "{}/{:?}/{}",
+            classification.instrument_id, classification.control_type, well
+        );
+
+        let now = injection.injected_at;
+        self.injections
+            .retain(|_, recent| (now - recent.injected_at).abs() <= window);
+        let previous = self.injections.insert(key, injection)?;
+        Some(previous.run_id)
+    }
+}
+
+/// Thread-safe re-injection tracker. A disabled tracker is a no-op.
+#[derive(Clone)]
+pub struct ReinjectionTracker {
+    config: ReinjectionConfig,
+    inner: Arc<Mutex<ReinjectionStore>>,
+}
+
+impl ReinjectionTracker {
+    /// Create a tracker, loading recent injections from disk when enabled
+    pub fn new(config: &ReinjectionConfig) -> Self {
+        let store = if config.enabled {
+            ReinjectionStore::load().unwrap_or_default()
+        } else {
+            ReinjectionStore::default()
+        };
+
+        Self {
+            config: config.clone(),
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record an extracted run and return the run ID of the attempt it
+    /// repeats, if it is a re-injection.
+    pub fn record(
+        &self,
+        classification: &RunClassification,
+        run_id: Uuid,
+        injected_at: DateTime<Utc>,
+    ) -> Option<Uuid> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let window = Duration::minutes(self.config.window_minutes as i64);
+        let mut store = self.inner.lock().unwrap();
+        let previous = store.record(
+            classification,
+            RecentInjection {
+                run_id,
+                injected_at,
+            },
+            window,
+        );
+        let _ = store.save();
+
+        previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ClassificationConfidence, ClassificationSource, ControlType, WellPosition};
+
+    fn classification(control_type: ControlType, well: &str) -> RunClassification {
+        RunClassification {
+            control_type,
+            well_position: WellPosition::from_str(well),
+            instrument_id: "EXPLORIS01".to_string(),
+            plate_id: None,
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Filename,
+            matched_pattern: None,
+            injection_index: None,
+            reinjection_of: None,
+        }
+    }
+
+    #[test]
+    fn test_repeat_injection_of_same_well_is_flagged() {
+        let mut store = ReinjectionStore::default();
+        let window = Duration::minutes(120);
+        let start = Utc::now();
+        let injection = |minutes: i64| RecentInjection {
+            run_id: Uuid::new_v4(),
+            injected_at: start + Duration::minutes(minutes),
+        };
+
+        let first = injection(0);
+        let first_id = first.run_id;
+        let qc_a = classification(ControlType::QcA, "A1");
+        assert_eq!(store.record(&qc_a, first, window), None);
+
+        // Another well or control type is not a repeat
+        let qc_b = classification(ControlType::QcB, "A1");
+        assert_eq!(store.record(&qc_b, injection(10), window), None);
+        let other_well = classification(ControlType::QcA, "A2");
+        assert_eq!(store.record(&other_well, injection(15), window), None);
+
+        // The same well again within the window is
+        let repeat = injection(40);
+        let repeat_id = repeat.run_id;
+        assert_eq!(store.record(&qc_a, repeat, window), Some(first_id));
+
+        // A day later it's a fresh QC, not a repeat
+        assert_eq!(store.record(&qc_a, injection(24 * 60), window), None);
+        assert_ne!(store.injections["EXPLORIS01/QcA/A1"].run_id, repeat_id);
+
+        // Without a well there is nothing to match
+        let mut no_well = classification(ControlType::QcA, "A1");
+        no_well.well_position = None;
+        assert_eq!(store.record(&no_well, injection(24 * 60 + 1), window), None);
+    }
+}
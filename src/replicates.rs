@@ -0,0 +1,266 @@
+//! Replicate precision tracking.
+//!
+//! Keeps a rolling window of recent runs per instrument and control type so
+//! that each new QC run can report the injection-to-injection peak-area CV
+//! per target, and a run-level median CV, without waiting for the cloud.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::{paths, ReplicateConfig};
+use crate::types::{ControlType, ReplicateMetrics, TargetCv, TargetMetrics};
+
+/// Peak areas of one run, keyed by target ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicateRun {
+    pub run_id: Uuid,
+    pub areas: HashMap<String, f64>,
+}
+
+impl ReplicateRun {
+    /// Collect areas of detected targets.
+    pub fn from_targets(run_id: Uuid, targets: &[TargetMetrics]) -> Self {
+        let areas = targets
+            .iter()
+            .filter(|t| t.detected && t.peak_area > 0.0)
+            .map(|t| (t.target_id.clone(), t.peak_area))
+            .collect();
+        Self { run_id, areas }
+    }
+}
+
+/// Store of rolling replicate windows.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicateStore {
+    /// Recent runs keyed by "<instrument_id>/<control_type>", oldest first
+    pub windows: HashMap<String, VecDeque<ReplicateRun>>,
+}
+
+impl ReplicateStore {
+    /// Load the replicate store from disk
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        let store: Self = serde_json::from_str(&content)?;
+        Ok(store)
+    }
+
+    /// Save the store to disk
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&store_path, content)?;
+        Ok(())
+    }
+
+    /// Get the path to the store file
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("replicates.json")
+    }
+
+    /// Add a run to its window (trimmed to `window`) and return the window.
+    pub fn push(
+        &mut self,
+        instrument_id: &str,
+        control_type: ControlType,
+        run: ReplicateRun,
+        window: usize,
+    ) -> Vec<ReplicateRun> {
+        let key = format!("{}/{:?}", instrument_id, control_type);
+        let runs = self.windows.entry(key).or_default();
+
+        runs.push_back(run);
+        while runs.len() > window {
+            runs.pop_front();
+        }
+
+        runs.iter().cloned().collect()
+    }
+}
+
+/// Compute per-target area CV (%) and the median across targets.
+///
+/// Only targets seen in at least two runs of the window get a CV. Returns
+/// `None` if fewer than two runs are available.
+pub fn compute_replicate_cv(runs: &[ReplicateRun]) -> Option<ReplicateMetrics> {
+    if runs.len() < 2 {
+        return None;
+    }
+
+    let mut areas_by_target: HashMap<&str, Vec<f64>> = HashMap::new();
+    for run in runs {
+        for (target, area) in &run.areas {
+            areas_by_target.entry(target).or_default().push(*area);
+        }
+    }
+
+    let mut target_area_cv: Vec<TargetCv> = areas_by_target
+        .into_iter()
+        .filter(|(_, areas)| areas.len() >= 2)
+        .filter_map(|(target, areas)| {
+            cv_pct(&areas).map(|cv| TargetCv {
+                target_id: target.to_string(),
+                area_cv_pct: cv,
+                n: areas.len(),
+            })
+        })
+        .collect();
+    target_area_cv.sort_by(|a, b| a.target_id.cmp(&b.target_id));
+
+    let mut cvs: Vec<f64> = target_area_cv.iter().map(|t| t.area_cv_pct).collect();
+
+    Some(ReplicateMetrics {
+        window_size: runs.len(),
+        median_area_cv_pct: median(&mut cvs),
+        target_area_cv,
+    })
+}
+
+/// Sample coefficient of variation in percent.
+fn cv_pct(values: &[f64]) -> Option<f64> {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if mean <= 0.0 {
+        return None;
+    }
+
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some(variance.sqrt() / mean * 100.0)
+}
+
+pub(crate) fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Thread-safe replicate tracker. A disabled tracker is a no-op.
+#[derive(Clone)]
+pub struct ReplicateTracker {
+    config: ReplicateConfig,
+    inner: Arc<Mutex<ReplicateStore>>,
+}
+
+impl ReplicateTracker {
+    /// Create a tracker, loading windows from disk when enabled
+    pub fn new(config: &ReplicateConfig) -> Self {
+        let store = if config.enabled {
+            ReplicateStore::load().unwrap_or_default()
+        } else {
+            ReplicateStore::default()
+        };
+
+        Self {
+            config: config.clone(),
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record a completed run and compute CV over its replicate window.
+    pub fn record(
+        &self,
+        instrument_id: &str,
+        control_type: ControlType,
+        run_id: Uuid,
+        targets: &[TargetMetrics],
+    ) -> Option<ReplicateMetrics> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let mut store = self.inner.lock().unwrap();
+        let window = store.push(
+            instrument_id,
+            control_type,
+            ReplicateRun::from_targets(run_id, targets),
+            self.config.replicate_window,
+        );
+        let _ = store.save();
+
+        compute_replicate_cv(&window)
+    }
+
+    /// Whether the run-level median CV exceeds the warning threshold.
+    pub fn exceeds_threshold(&self, metrics: &ReplicateMetrics) -> bool {
+        metrics
+            .median_area_cv_pct
+            .is_some_and(|cv| cv > self.config.median_cv_warning_pct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(areas: &[(&str, f64)]) -> ReplicateRun {
+        ReplicateRun {
+            run_id: Uuid::new_v4(),
+            areas: areas.iter().map(|(t, a)| (t.to_string(), *a)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_cv_over_three_replicates() {
+        let runs = vec![
+            run(&[("PEP1", 90.0), ("PEP2", 1000.0), ("PEP3", 50.0)]),
+            run(&[("PEP1", 100.0), ("PEP2", 1000.0)]),
+            run(&[("PEP1", 110.0), ("PEP2", 1000.0)]),
+        ];
+
+        let metrics = compute_replicate_cv(&runs).unwrap();
+        assert_eq!(metrics.window_size, 3);
+
+        // PEP3 was only seen once, so it has no CV
+        assert_eq!(metrics.target_area_cv.len(), 2);
+
+        let pep1 = &metrics.target_area_cv[0];
+        assert_eq!(pep1.target_id, "PEP1");
+        assert_eq!(pep1.n, 3);
+        // mean 100, sample sd 10 -> 10%
+        assert!((pep1.area_cv_pct - 10.0).abs() < 1e-9);
+
+        let pep2 = &metrics.target_area_cv[1];
+        assert!(pep2.area_cv_pct.abs() < 1e-9);
+
+        // Median of [0, 10]
+        assert!((metrics.median_area_cv_pct.unwrap() - 5.0).abs() < 1e-9);
+
+        // A single run has no replicates
+        assert!(compute_replicate_cv(&runs[..1]).is_none());
+    }
+
+    #[test]
+    fn test_window_is_rolling_per_control_type() {
+        let mut store = ReplicateStore::default();
+
+        for _ in 0..4 {
+            store.push("EXPLORIS01", ControlType::QcA, run(&[("PEP1", 1.0)]), 3);
+        }
+        let window = store.push("EXPLORIS01", ControlType::QcB, run(&[("PEP1", 1.0)]), 3);
+
+        assert_eq!(window.len(), 1);
+        assert_eq!(store.windows["EXPLORIS01/QcA"].len(), 3);
+    }
+}
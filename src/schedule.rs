@@ -0,0 +1,193 @@
+//! Active-hours schedule for extraction and upload.
+//!
+//! When `[active_schedule]` is configured, the agent keeps detecting files
+//! around the clock but defers extraction and upload until the next active
+//! window, e.g. to avoid overnight alert noise.
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime};
+
+use crate::config::ActiveSchedule;
+use crate::types::TrackedFile;
+
+/// Format for `start` and `end` times.
+const TIME_FORMAT: &str = "%H:%M";
+
+impl ActiveSchedule {
+    /// Check that `start` and `end` parse as `HH:MM` and at least one day is set.
+    pub fn validate(&self) -> Result<()> {
+        self.times()?;
+        if self.days.is_empty() {
+            anyhow::bail!("active_schedule.days must list at least one day");
+        }
+        Ok(())
+    }
+
+    fn times(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let start = NaiveTime::parse_from_str(&self.start, TIME_FORMAT)
+            .with_context(|| format!("Invalid active_schedule.start '{}'", self.start))?;
+        let end = NaiveTime::parse_from_str(&self.end, TIME_FORMAT)
+            .with_context(|| format!("Invalid active_schedule.end '{}'", self.end))?;
+        Ok((start, end))
+    }
+
+    /// Whether the given local time falls inside an active window.
+    ///
+    /// A window whose end is before its start spans midnight and belongs to
+    /// the day it starts on. Invalid times are treated as always active.
+    pub fn is_active_at(&self, local: NaiveDateTime) -> bool {
+        let Ok((start, end)) = self.times() else {
+            return true;
+        };
+
+        let day = local.weekday();
+        let time = local.time();
+
+        if start <= end {
+            self.days.contains(&day) && time >= start && time < end
+        } else {
+            (self.days.contains(&day) && time >= start)
+                || (self.days.contains(&day.pred()) && time < end)
+        }
+    }
+
+    /// Whether the current local time falls inside an active window.
+    pub fn is_active_now(&self) -> bool {
+        self.is_active_at(Local::now().naive_local())
+    }
+
+    /// Start of the next active window after the given local time.
+    pub fn next_start_after(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (start, _) = self.times().ok()?;
+
+        (0..=7)
+            .map(|offset| (local.date() + Duration::days(offset)).and_time(start))
+            .find(|candidate| *candidate > local && self.days.contains(&candidate.weekday()))
+    }
+}
+
+/// Files held back while outside the active schedule.
+#[derive(Debug, Default)]
+pub struct DeferredFiles {
+    files: Vec<TrackedFile>,
+}
+
+impl DeferredFiles {
+    /// Pass a file through if active, otherwise hold it until the window opens.
+    pub fn admit(&mut self, file: TrackedFile, active: bool) -> Option<TrackedFile> {
+        if active {
+            Some(file)
+        } else {
+            if !self.files.iter().any(|f| f.path == file.path) {
+                self.files.push(file);
+            }
+            None
+        }
+    }
+
+    /// Release all held files once active.
+    pub fn release(&mut self, active: bool) -> Vec<TrackedFile> {
+        if active {
+            std::mem::take(&mut self.files)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Number of held files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether no files are held.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Vendor;
+    use chrono::{NaiveDate, Weekday};
+    use std::path::PathBuf;
+
+    fn business_hours() -> ActiveSchedule {
+        ActiveSchedule {
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            start: "08:00".to_string(),
+            end: "18:00".to_string(),
+        }
+    }
+
+    fn at(date: (i32, u32, u32), time: (u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(time.0, time.1, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let schedule = business_hours();
+        assert!(schedule.validate().is_ok());
+
+        // 2024-01-08 is a Monday
+        assert!(schedule.is_active_at(at((2024, 1, 8), (8, 0))));
+        assert!(!schedule.is_active_at(at((2024, 1, 8), (18, 0))));
+        assert!(!schedule.is_active_at(at((2024, 1, 13), (10, 0))));
+
+        assert_eq!(
+            schedule.next_start_after(at((2024, 1, 12), (19, 0))),
+            Some(at((2024, 1, 15), (8, 0)))
+        );
+
+        // Overnight window belongs to the day it starts on
+        let overnight = ActiveSchedule {
+            days: vec![Weekday::Fri],
+            start: "22:00".to_string(),
+            end: "06:00".to_string(),
+        };
+        assert!(overnight.is_active_at(at((2024, 1, 13), (5, 0))));
+        assert!(!overnight.is_active_at(at((2024, 1, 8), (5, 0))));
+
+        let invalid = ActiveSchedule {
+            start: "8am".to_string(),
+            ..business_hours()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_file_deferred_until_window_opens() {
+        let schedule = business_hours();
+        let mut deferred = DeferredFiles::default();
+        let file = TrackedFile::new(PathBuf::from("run.raw"), Vendor::Thermo, 1024, None);
+
+        // Detected Saturday morning: held, not extracted
+        let saturday = at((2024, 1, 13), (10, 0));
+        assert!(deferred
+            .admit(file, schedule.is_active_at(saturday))
+            .is_none());
+        assert_eq!(deferred.len(), 1);
+
+        // Still held just before the window opens
+        let before_open = at((2024, 1, 15), (7, 59));
+        assert!(deferred
+            .release(schedule.is_active_at(before_open))
+            .is_empty());
+
+        // Released once the window opens
+        let open = at((2024, 1, 15), (8, 0));
+        let released = deferred.release(schedule.is_active_at(open));
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].path, PathBuf::from("run.raw"));
+        assert!(deferred.is_empty());
+    }
+}
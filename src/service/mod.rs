@@ -6,9 +6,16 @@
 mod windows_service;
 
 #[cfg(windows)]
-pub use windows_service::run_as_service;
+pub use windows_service::{run_as_service, uninstall_service};
 
 #[cfg(not(windows))]
+#[allow(dead_code)]
 pub fn run_as_service() -> anyhow::Result<()> {
     anyhow::bail!("Windows service is only available on Windows")
 }
+
+/// There is no agent-managed service outside Windows; nothing to remove.
+#[cfg(not(windows))]
+pub fn uninstall_service() -> anyhow::Result<bool> {
+    Ok(false)
+}
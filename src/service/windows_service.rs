@@ -15,6 +15,7 @@ use windows_service::{
     service_dispatcher,
 };
 
+use crate::agent_state::AgentState;
 use crate::cli::run::run_agent;
 use crate::config::Config;
 
@@ -111,7 +112,8 @@ fn run_service(_arguments: Vec<OsString>) -> anyhow::Result<()> {
     info!("Service started");
 
     // Run the agent
-    let result = runtime.block_on(async { run_agent(config, &mut shutdown_rx).await });
+    let result =
+        runtime.block_on(async { run_agent(config, &mut shutdown_rx, AgentState::new()).await });
 
     // Report that we're stopping
     status_handle.set_service_status(ServiceStatus {
@@ -149,3 +151,46 @@ fn run_service(_arguments: Vec<OsString>) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Win32 error for a service name that isn't installed.
+#[cfg(windows)]
+const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+/// Stop and delete the agent's service.
+///
+/// Returns `false` if the service isn't installed.
+#[cfg(windows)]
+pub fn uninstall_service() -> anyhow::Result<bool> {
+    use windows_service::service::ServiceAccess;
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let access = ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE;
+    let service = match manager.open_service(SERVICE_NAME, access) {
+        Ok(service) => service,
+        Err(windows_service::Error::Winapi(e))
+            if e.raw_os_error() == Some(ERROR_SERVICE_DOES_NOT_EXIST) =>
+        {
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        info!("Stopping service");
+        service.stop()?;
+
+        // Give the agent time to finish the file in hand
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        while service.query_status()?.current_state != ServiceState::Stopped {
+            if std::time::Instant::now() > deadline {
+                anyhow::bail!("Service {} did not stop within 30 seconds", SERVICE_NAME);
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    service.delete()?;
+    info!("Service deleted");
+    Ok(true)
+}
@@ -7,53 +7,134 @@
 
 use anyhow::Result;
 use chrono::{Duration, Utc};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::config::{paths, SpoolConfig};
+use crate::baseline::BaselineManager;
+use crate::config::{paths, InstrumentConfig, OversizedPayloadHandling, SpoolConfig};
 use crate::error::SpoolError;
+use crate::heartbeat::Heartbeat;
 use crate::types::{
-    ExtractionInfo, ExtractionResult, QcPayload, RunClassification, RunInfo, Vendor,
+    ComparisonMetrics, ExtractionInfo, ExtractionResult, PayloadPart, ProcessingTiming, QcPayload,
+    ReplicateMetrics, RunClassification, RunInfo, TrackedFile, Vendor,
 };
 
+/// Pending spool usage (percent of `max_pending_mb`) that triggers an early
+/// warning, well before enqueues start failing.
+const SOFT_LIMIT_PCT: u64 = 80;
+
 /// Spool manager for pending uploads.
 #[derive(Clone)]
 pub struct Spool {
     config: SpoolConfig,
+    soft_limit: Arc<SoftLimit>,
+    enable_notifications: bool,
+    config_hash: Option<String>,
+    /// Project ID by instrument ID
+    projects: HashMap<String, String>,
+    /// Most targets per payload (0 = no limit) and what to do over it
+    max_payload_targets: usize,
+    oversized_payloads: OversizedPayloadHandling,
     pending_dir: PathBuf,
     uploading_dir: PathBuf,
     failed_dir: PathBuf,
     completed_dir: PathBuf,
+    observed_dir: PathBuf,
     agent_id: Arc<Mutex<String>>,
+    /// Cached baselines the payload's baseline context is read from
+    baselines: Option<BaselineManager>,
 }
 
 impl Spool {
     /// Create a new spool manager.
     pub fn new(config: &SpoolConfig) -> Result<Self> {
-        let pending_dir = paths::spool_pending_dir();
-        let uploading_dir = paths::spool_uploading_dir();
-        let failed_dir = paths::spool_failed_dir();
-        let completed_dir = paths::spool_completed_dir();
+        Self::in_dir(config, &paths::spool_dir())
+    }
+
+    /// Create a spool manager rooted at `root`.
+    pub(crate) fn in_dir(config: &SpoolConfig, root: &Path) -> Result<Self> {
+        let pending_dir = root.join("pending");
+        let uploading_dir = root.join("uploading");
+        let failed_dir = root.join("failed");
+        let completed_dir = root.join("completed");
+        let observed_dir = root.join("observed");
 
         // Ensure directories exist
-        std::fs::create_dir_all(&pending_dir)?;
-        std::fs::create_dir_all(&uploading_dir)?;
-        std::fs::create_dir_all(&failed_dir)?;
-        std::fs::create_dir_all(&completed_dir)?;
+        // Each filesystem call goes through `long_path` on the full path: a
+        // short root can still make a long payload path
+        for dir in [
+            &pending_dir,
+            &uploading_dir,
+            &failed_dir,
+            &completed_dir,
+            &observed_dir,
+        ] {
+            std::fs::create_dir_all(paths::long_path(dir))?;
+        }
 
         Ok(Self {
             config: config.clone(),
+            soft_limit: Arc::new(SoftLimit::default()),
+            enable_notifications: false,
+            config_hash: None,
+            projects: HashMap::new(),
+            max_payload_targets: 0,
+            oversized_payloads: OversizedPayloadHandling::default(),
             pending_dir,
             uploading_dir,
             failed_dir,
             completed_dir,
+            observed_dir,
             agent_id: Arc::new(Mutex::new("unregistered".to_string())),
+            baselines: None,
         })
     }
 
+    /// Show a toast when the spool nears its size limit.
+    pub fn with_notifications(mut self, enable: bool) -> Self {
+        self.enable_notifications = enable;
+        self
+    }
+
+    /// Stamp payloads with the hash of the agent configuration.
+    pub fn with_config_hash(mut self, config_hash: String) -> Self {
+        self.config_hash = Some(config_hash);
+        self
+    }
+
+    /// Tag payloads with the `project_id` of the instrument they come from.
+    pub fn with_projects(mut self, instruments: &[InstrumentConfig]) -> Self {
+        self.projects = instruments
+            .iter()
+            .filter_map(|i| Some((i.id.clone(), i.project_id.clone()?)))
+            .collect();
+        self
+    }
+
+    /// Describe each run's instrument's cached baseline in its payload.
+    pub fn with_baselines(mut self, baselines: BaselineManager) -> Self {
+        self.baselines = Some(baselines);
+        self
+    }
+
+    /// Split or truncate payloads with more than `max_targets` targets
+    /// (0 = no limit).
+    pub fn with_payload_limit(
+        mut self,
+        max_targets: usize,
+        handling: OversizedPayloadHandling,
+    ) -> Self {
+        self.max_payload_targets = max_targets;
+        self.oversized_payloads = handling;
+        self
+    }
+
     /// Set the agent ID (call after initialization/enrollment).
     pub async fn set_agent_id(&self, agent_id: String) {
         *self.agent_id.lock().await = agent_id;
@@ -71,27 +152,126 @@ impl Spool {
         format!("{}-{}-{:08x}", agent_id, timestamp, random)
     }
 
-    /// Enqueue an extraction result for upload.
+    /// Check there's room for a payload in `observed/` (`observe`) or
+    /// `pending/` (`enqueue`), so callers can defer a run before recording
+    /// it anywhere else.
+    pub fn ensure_free_space(&self, observe: bool) -> Result<(), SpoolError> {
+        let dir = if observe {
+            &self.observed_dir
+        } else {
+            &self.pending_dir
+        };
+        crate::disk::ensure_free_space(dir, self.config.min_free_disk_mb)
+            .map_err(|free_mb| SpoolError::LowDisk(free_mb, self.config.min_free_disk_mb))
+    }
+
+    /// Enqueue an extraction result for upload. Returns the spooled payload
+    /// paths, more than one when the payload was split into parts.
     pub async fn enqueue(
         &self,
         result: &ExtractionResult,
         classification: &RunClassification,
         vendor: Vendor,
-    ) -> Result<(), SpoolError> {
+        replicate_metrics: Option<ReplicateMetrics>,
+        comparison_metrics: Option<ComparisonMetrics>,
+        tracked_file: Option<&TrackedFile>,
+    ) -> Result<Vec<PathBuf>, SpoolError> {
         // Check spool size limits
         self.check_limits()?;
 
+        // Refuse to write a partial payload onto a nearly full disk
+        self.ensure_free_space(false)?;
+
         // Cleanup old payloads
         self.cleanup_old_payloads()?;
 
+        let payload = self
+            .build_payload(
+                result,
+                classification,
+                vendor,
+                replicate_metrics,
+                comparison_metrics,
+                tracked_file,
+            )
+            .await;
+
+        let mut paths = Vec::new();
+        for payload in fit_payload(payload, self.max_payload_targets, self.oversized_payloads) {
+            let final_path = write_payload(&self.pending_dir, &payload)?;
+
+            info!(
+                run_id = %result.run_id,
+                correlation_id = %payload.correlation_id,
+                part = ?payload.part.map(|p| p.index),
+                path = %final_path.display(),
+                "Payload spooled"
+            );
+            paths.push(final_path);
+        }
+
+        Ok(paths)
+    }
+
+    /// Keep the payload for an observe-only instrument under `observed/`,
+    /// which the uploader never reads. Returns the payload's path.
+    pub async fn observe(
+        &self,
+        result: &ExtractionResult,
+        classification: &RunClassification,
+        vendor: Vendor,
+        replicate_metrics: Option<ReplicateMetrics>,
+        comparison_metrics: Option<ComparisonMetrics>,
+        tracked_file: Option<&TrackedFile>,
+    ) -> Result<PathBuf, SpoolError> {
+        self.ensure_free_space(true)?;
+
+        let payload = self
+            .build_payload(
+                result,
+                classification,
+                vendor,
+                replicate_metrics,
+                comparison_metrics,
+                tracked_file,
+            )
+            .await;
+        let path = write_payload(&self.observed_dir, &payload)?;
+
+        info!(
+            run_id = %result.run_id,
+            path = %path.display(),
+            "Payload kept locally (observe-only, not uploaded)"
+        );
+
+        Ok(path)
+    }
+
+    /// Build the payload for an extraction result.
+    async fn build_payload(
+        &self,
+        result: &ExtractionResult,
+        classification: &RunClassification,
+        vendor: Vendor,
+        replicate_metrics: Option<ReplicateMetrics>,
+        comparison_metrics: Option<ComparisonMetrics>,
+        tracked_file: Option<&TrackedFile>,
+    ) -> QcPayload {
         // Get agent ID
         let agent_id = self.agent_id.lock().await.clone();
 
         // Generate correlation ID
         let correlation_id = self.generate_correlation_id(&agent_id);
 
-        // Build payload
-        let payload = QcPayload {
+        let baseline_context = match &self.baselines {
+            Some(baselines) => baselines
+                .get_cached(&classification.instrument_id)
+                .await
+                .map(|cached| cached.context()),
+            None => None,
+        };
+
+        QcPayload {
             schema_version: "1.0".to_string(),
             payload_id: Uuid::new_v4(),
             correlation_id: correlation_id.clone(),
@@ -103,12 +283,22 @@ impl Spool {
                 run_id: result.run_id,
                 raw_file_name: result.raw_file_name.clone(),
                 raw_file_hash: result.raw_file_hash.clone(),
-                acquisition_time: None, // Could be extracted from raw file
+                raw_file_hash_mode: result.raw_file_hash_mode,
+                acquisition_time: result.acquisition.acquisition_time,
+                instrument_serial: result.acquisition.instrument_serial.clone(),
+                instrument_model: result.acquisition.instrument_model.clone(),
+                method_name: result.acquisition.method_name.clone(),
                 instrument_id: classification.instrument_id.clone(),
+                project_id: self.projects.get(&classification.instrument_id).cloned(),
                 vendor, // Use the actual vendor from instrument config
                 control_type: classification.control_type,
                 well_position: classification.well_position.as_ref().map(|w| w.to_string()),
                 plate_id: classification.plate_id.clone(),
+                injection_index: classification.injection_index,
+                is_reinjection: classification.reinjection_of.is_some(),
+                reinjection_of: classification.reinjection_of,
+                suspect_empty_import: result.suspect_empty_import,
+                consumables: result.consumables.clone(),
                 classification_confidence: classification.confidence,
                 classification_source: classification.source,
             },
@@ -120,36 +310,26 @@ impl Spool {
                 template_hash: result.template_hash.clone(),
                 extraction_time_ms: result.extraction_time_ms,
                 status: "SUCCESS".to_string(),
+                import_warnings: result.import_warnings.clone(),
+                skipped_report_rows: result.skipped_rows,
             },
 
-            baseline_context: None, // TODO: fetch from baseline manager
+            baseline_context,
             target_metrics: result.target_metrics.clone(),
             run_metrics: result.run_metrics.clone(),
-            comparison_metrics: None, // TODO: compute if baseline exists
-        };
-
-        // Serialize to JSON
-        let json = serde_json::to_string_pretty(&payload)?;
-
-        // Write to pending directory
-        let filename = format!("{}_payload.json", result.run_id);
-        let temp_path = self.pending_dir.join(format!(".{}.tmp", filename));
-        let final_path = self.pending_dir.join(&filename);
-
-        // Write to temp file first, then rename (atomic on most filesystems)
-        std::fs::write(&temp_path, &json).map_err(|e| SpoolError::FileOperation(e.to_string()))?;
-
-        std::fs::rename(&temp_path, &final_path)
-            .map_err(|e| SpoolError::FileOperation(e.to_string()))?;
-
-        info!(
-            run_id = %result.run_id,
-            correlation_id = %correlation_id,
-            path = %final_path.display(),
-            "Payload spooled"
-        );
-
-        Ok(())
+            comparison_metrics,
+            replicate_metrics,
+            config_hash: self.config_hash.clone(),
+            timing: Some(ProcessingTiming {
+                detected_at: tracked_file.map(|f| f.first_seen),
+                finalized_at: tracked_file.and_then(|f| f.finalized_at),
+                extraction_time_ms: result.extraction_time_ms,
+                spooled_at: Utc::now(),
+            }),
+            part: None,
+            truncated: false,
+            omitted_targets: 0,
+        }
     }
 
     /// Check spool size limits.
@@ -161,6 +341,18 @@ impl Spool {
             return Err(SpoolError::Full(size_mb, self.config.max_pending_mb));
         }
 
+        if self.soft_limit.crossed(size_mb, self.config.max_pending_mb) {
+            warn!(
+                used_mb = size_mb,
+                max_pending_mb = self.config.max_pending_mb,
+                "Spool is over {}% full; uploads may be failing, check connectivity",
+                SOFT_LIMIT_PCT
+            );
+            if self.enable_notifications {
+                crate::notifications::notify_spool_near_full(size_mb, self.config.max_pending_mb);
+            }
+        }
+
         Ok(())
     }
 
@@ -181,37 +373,25 @@ impl Spool {
     /// Remove files older than cutoff from a directory.
     fn cleanup_old_in_dir(
         &self,
-        dir: &PathBuf,
+        dir: &Path,
         cutoff: chrono::DateTime<Utc>,
     ) -> Result<(), SpoolError> {
-        if !dir.exists() {
+        if !paths::long_path(dir).exists() {
             return Ok(());
         }
 
-        let entries =
-            std::fs::read_dir(dir).map_err(|e| SpoolError::FileOperation(e.to_string()))?;
-
-        for entry in entries.flatten() {
-            let path = entry.path();
-
-            if let Ok(metadata) = entry.metadata() {
-                if let Ok(modified) = metadata.modified() {
-                    let modified: chrono::DateTime<Utc> = modified.into();
-                    if modified < cutoff {
-                        warn!(
-                            path = %path.display(),
-                            age_days = (Utc::now() - modified).num_days(),
-                            "Removing stale payload (max_age_days exceeded)"
-                        );
-                        if let Err(e) = std::fs::remove_file(&path) {
-                            error!(
-                                path = %path.display(),
-                                error = %e,
-                                "Failed to remove stale payload"
-                            );
-                        }
-                    }
-                }
+        for (path, modified) in expired_in_dir(dir, cutoff) {
+            warn!(
+                path = %path.display(),
+                age_days = (Utc::now() - modified).num_days(),
+                "Removing stale payload (max_age_days exceeded)"
+            );
+            if let Err(e) = std::fs::remove_file(paths::long_path(&path)) {
+                error!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to remove stale payload"
+                );
             }
         }
 
@@ -220,7 +400,7 @@ impl Spool {
 
     /// Get all pending payloads.
     pub fn get_pending(&self) -> Result<Vec<PathBuf>> {
-        let mut entries: Vec<_> = std::fs::read_dir(&self.pending_dir)?
+        let mut entries: Vec<_> = std::fs::read_dir(paths::long_path(&self.pending_dir))?
             .filter_map(|e| e.ok())
             .filter(|e| {
                 e.path()
@@ -233,35 +413,141 @@ impl Spool {
 
         // Sort by modification time (oldest first)
         entries.sort_by(|a, b| {
-            let a_time = a.metadata().and_then(|m| m.modified()).ok();
-            let b_time = b.metadata().and_then(|m| m.modified()).ok();
+            let modified = |p: &Path| {
+                std::fs::metadata(paths::long_path(p))
+                    .and_then(|m| m.modified())
+                    .ok()
+            };
+            let (a_time, b_time) = (modified(a), modified(b));
             a_time.cmp(&b_time)
         });
 
         Ok(entries)
     }
 
+    /// A spooled payload by run ID, in whichever state it is in.
+    ///
+    /// A run split over several payloads is put back together from the parts
+    /// still in the spool.
+    pub fn find_payload(&self, run_id: &Uuid) -> Result<Option<QcPayload>> {
+        let filename = format!("{}_payload.json", run_id);
+        let part_prefix = format!("{}_part", run_id);
+        let dirs = [
+            &self.pending_dir,
+            &self.uploading_dir,
+            &self.failed_dir,
+            &self.completed_dir,
+        ];
+
+        for dir in dirs {
+            let path = dir.join(&filename);
+            let path = paths::long_path(&path);
+            if path.exists() {
+                let content = std::fs::read_to_string(&path)?;
+                return Ok(Some(serde_json::from_str(&content)?));
+            }
+        }
+
+        let mut parts: Vec<QcPayload> = Vec::new();
+        for dir in dirs {
+            let Ok(entries) = std::fs::read_dir(paths::long_path(dir)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with(&part_prefix) && name.ends_with("_payload.json") {
+                    let content = std::fs::read_to_string(paths::long_path(&entry.path()))?;
+                    parts.push(serde_json::from_str(&content)?);
+                }
+            }
+        }
+        parts.sort_by_key(|p| p.part.map(|part| part.index));
+
+        let mut parts = parts.into_iter();
+        let Some(mut payload) = parts.next() else {
+            return Ok(None);
+        };
+        for part in parts {
+            payload.target_metrics.extend(part.target_metrics);
+        }
+        payload.part = None;
+        Ok(Some(payload))
+    }
+
+    /// Spool a heartbeat for upload, replacing any older one not yet sent:
+    /// only the latest state of the agent is of interest.
+    pub fn enqueue_heartbeat(&self, heartbeat: &Heartbeat) -> Result<PathBuf, SpoolError> {
+        for dir in [&self.pending_dir, &self.failed_dir] {
+            let Ok(entries) = std::fs::read_dir(paths::long_path(dir)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if is_heartbeat(&entry.path()) {
+                    debug!(path = %entry.path().display(), "Superseded unsent heartbeat");
+                    let _ = std::fs::remove_file(paths::long_path(&entry.path()));
+                }
+            }
+        }
+
+        let filename = format!("{}_heartbeat.json", heartbeat.heartbeat_id);
+        write_json(&self.pending_dir, &filename, heartbeat)
+    }
+
+    /// QC payloads waiting for upload and given up on. Heartbeats are not
+    /// counted.
+    pub fn counts(&self) -> SpoolCounts {
+        let count = |dir: &Path| {
+            std::fs::read_dir(paths::long_path(dir)).map_or(0, |entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.file_name().to_string_lossy().ends_with("_payload.json"))
+                    .count()
+            })
+        };
+        SpoolCounts {
+            pending: count(&self.pending_dir) + count(&self.uploading_dir),
+            failed: count(&self.failed_dir),
+        }
+    }
+
+    /// Run IDs of payloads not yet uploaded: pending, uploading, or failed.
+    pub fn unsent_run_ids(&self) -> HashSet<Uuid> {
+        [&self.pending_dir, &self.uploading_dir, &self.failed_dir]
+            .into_iter()
+            .filter_map(|dir| std::fs::read_dir(paths::long_path(dir)).ok())
+            .flat_map(|entries| entries.filter_map(|e| e.ok()))
+            .filter_map(|e| {
+                let name = e.file_name();
+                let stem = name.to_str()?.strip_suffix("_payload.json")?;
+                // Parts of a split run are "{run_id}_part{n}of{count}"
+                let id = stem.split('_').next()?;
+                Uuid::parse_str(id).ok()
+            })
+            .collect()
+    }
+
     /// Move a payload to the uploading directory.
-    pub fn mark_uploading(&self, path: &PathBuf) -> Result<PathBuf> {
+    pub fn mark_uploading(&self, path: &Path) -> Result<PathBuf> {
         let filename = path
             .file_name()
             .ok_or_else(|| anyhow::anyhow!("Invalid path"))?;
         let new_path = self.uploading_dir.join(filename);
 
-        std::fs::rename(path, &new_path)?;
+        std::fs::rename(paths::long_path(path), paths::long_path(&new_path))?;
         debug!(path = %new_path.display(), "Payload marked as uploading");
 
         Ok(new_path)
     }
 
     /// Move a payload to the completed directory.
-    pub fn mark_completed(&self, path: &PathBuf) -> Result<()> {
+    pub fn mark_completed(&self, path: &Path) -> Result<()> {
         let filename = path
             .file_name()
             .ok_or_else(|| anyhow::anyhow!("Invalid path"))?;
         let new_path = self.completed_dir.join(filename);
 
-        std::fs::rename(path, &new_path)?;
+        std::fs::rename(paths::long_path(path), paths::long_path(&new_path))?;
         info!(path = %new_path.display(), "Payload uploaded successfully");
 
         // Cleanup old completed files
@@ -270,27 +556,35 @@ impl Spool {
         Ok(())
     }
 
+    /// Delete a delivered heartbeat. Heartbeats are not kept under
+    /// `completed/`, where they would crowd out QC payloads.
+    pub fn remove_delivered(&self, path: &Path) -> Result<()> {
+        std::fs::remove_file(paths::long_path(path))?;
+        debug!(path = %path.display(), "Heartbeat delivered");
+        Ok(())
+    }
+
     /// Move a payload to the failed directory.
-    pub fn mark_failed(&self, path: &PathBuf) -> Result<()> {
+    pub fn mark_failed(&self, path: &Path) -> Result<()> {
         let filename = path
             .file_name()
             .ok_or_else(|| anyhow::anyhow!("Invalid path"))?;
         let new_path = self.failed_dir.join(filename);
 
-        std::fs::rename(path, &new_path)?;
+        std::fs::rename(paths::long_path(path), paths::long_path(&new_path))?;
         warn!(path = %new_path.display(), "Payload marked as failed");
 
         Ok(())
     }
 
     /// Move a payload back to pending (for retry).
-    pub fn mark_pending(&self, path: &PathBuf) -> Result<PathBuf> {
+    pub fn mark_pending(&self, path: &Path) -> Result<PathBuf> {
         let filename = path
             .file_name()
             .ok_or_else(|| anyhow::anyhow!("Invalid path"))?;
         let new_path = self.pending_dir.join(filename);
 
-        std::fs::rename(path, &new_path)?;
+        std::fs::rename(paths::long_path(path), paths::long_path(&new_path))?;
         debug!(path = %new_path.display(), "Payload returned to pending");
 
         Ok(new_path)
@@ -298,27 +592,16 @@ impl Spool {
 
     /// Cleanup old completed files.
     fn cleanup_completed(&self) -> Result<()> {
-        let mut entries: Vec<_> = std::fs::read_dir(&self.completed_dir)?
-            .filter_map(|e| e.ok())
-            .collect();
-
-        if entries.len() <= self.config.completed_retention_count {
-            return Ok(());
-        }
-
-        // Sort by modification time (oldest first)
-        entries.sort_by(|a, b| {
-            let a_time = a.metadata().and_then(|m| m.modified()).ok();
-            let b_time = b.metadata().and_then(|m| m.modified()).ok();
-            a_time.cmp(&b_time)
-        });
+        let entries = files_oldest_first(&self.completed_dir)?;
+        let to_remove = entries
+            .len()
+            .saturating_sub(self.config.completed_retention_count);
 
         // Remove oldest entries
-        let to_remove = entries.len() - self.config.completed_retention_count;
-        for entry in entries.into_iter().take(to_remove) {
-            if let Err(e) = std::fs::remove_file(entry.path()) {
+        for path in entries.into_iter().take(to_remove) {
+            if let Err(e) = std::fs::remove_file(paths::long_path(&path)) {
                 warn!(
-                    path = %entry.path().display(),
+                    path = %path.display(),
                     error = %e,
                     "Failed to cleanup completed payload"
                 );
@@ -328,9 +611,52 @@ impl Spool {
         Ok(())
     }
 
+    /// Apply retention now instead of waiting for the next enqueue or upload.
+    ///
+    /// Removes pending, failed and completed payloads older than
+    /// `max_age_days`, then trims completed payloads to
+    /// `completed_retention_count`. With `dry_run`, nothing is deleted and the
+    /// report lists what would be.
+    pub fn gc(&self, dry_run: bool) -> Result<GcReport> {
+        let cutoff = Utc::now() - Duration::days(self.config.max_age_days as i64);
+
+        let mut doomed: Vec<PathBuf> = [&self.pending_dir, &self.failed_dir, &self.completed_dir]
+            .into_iter()
+            .flat_map(|dir| expired_in_dir(dir, cutoff))
+            .map(|(path, _)| path)
+            .collect();
+
+        let completed: Vec<PathBuf> = files_oldest_first(&self.completed_dir)?
+            .into_iter()
+            .filter(|path| !doomed.contains(path))
+            .collect();
+        let over_count = completed
+            .len()
+            .saturating_sub(self.config.completed_retention_count);
+        doomed.extend(completed.into_iter().take(over_count));
+
+        let mut report = GcReport::default();
+        for path in doomed {
+            let bytes = std::fs::metadata(paths::long_path(&path))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            if !dry_run {
+                if let Err(e) = std::fs::remove_file(paths::long_path(&path)) {
+                    warn!(path = %path.display(), error = %e, "Failed to remove payload");
+                    continue;
+                }
+                debug!(path = %path.display(), "Removed payload");
+            }
+            report.bytes += bytes;
+            report.removed.push(path);
+        }
+
+        Ok(report)
+    }
+
     /// Recovery: move any uploading files back to pending on startup.
     pub fn recover(&self) -> Result<()> {
-        let entries: Vec<_> = std::fs::read_dir(&self.uploading_dir)?
+        let entries: Vec<_> = std::fs::read_dir(paths::long_path(&self.uploading_dir))?
             .filter_map(|e| e.ok())
             .collect();
 
@@ -349,9 +675,189 @@ impl Spool {
     }
 }
 
+/// Whether a spooled file is a heartbeat rather than a QC payload.
+pub fn is_heartbeat(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with("_heartbeat.json"))
+}
+
+/// Write a payload into `dir` as `{run_id}_payload.json`.
+fn write_payload(dir: &Path, payload: &QcPayload) -> Result<PathBuf, SpoolError> {
+    let filename = match payload.part {
+        Some(part) => format!(
+            "{}_part{}of{}_payload.json",
+            payload.run.run_id, part.index, part.count
+        ),
+        None => format!("{}_payload.json", payload.run.run_id),
+    };
+    write_json(dir, &filename, payload)
+}
+
+/// Write `value` into `dir` as `filename`, via a temp file and rename so
+/// readers never see a partial file.
+fn write_json(dir: &Path, filename: &str, value: &impl Serialize) -> Result<PathBuf, SpoolError> {
+    let json = serde_json::to_string_pretty(value)?;
+
+    let temp_path = dir.join(format!(".{}.tmp", filename));
+    let final_path = dir.join(filename);
+
+    // Write to temp file first, then rename (atomic on most filesystems)
+    std::fs::write(paths::long_path(&temp_path), &json)
+        .map_err(|e| SpoolError::FileOperation(e.to_string()))?;
+
+    std::fs::rename(paths::long_path(&temp_path), paths::long_path(&final_path))
+        .map_err(|e| SpoolError::FileOperation(e.to_string()))?;
+
+    Ok(final_path)
+}
+
+/// Keep a payload within `max_targets` targets (0 = no limit).
+///
+/// Splitting spreads the targets over numbered parts that share the run,
+/// run metrics and correlation ID; truncating keeps the targets with the
+/// largest peak areas, in report order, and records how many were dropped.
+fn fit_payload(
+    payload: QcPayload,
+    max_targets: usize,
+    handling: OversizedPayloadHandling,
+) -> Vec<QcPayload> {
+    let total = payload.target_metrics.len();
+    if max_targets == 0 || total <= max_targets {
+        return vec![payload];
+    }
+
+    match handling {
+        OversizedPayloadHandling::Split => {
+            let count = total.div_ceil(max_targets) as u32;
+            info!(
+                run_id = %payload.run.run_id,
+                targets = total,
+                max_payload_targets = max_targets,
+                parts = count,
+                "Splitting payload over several uploads"
+            );
+            payload
+                .target_metrics
+                .chunks(max_targets)
+                .enumerate()
+                .map(|(i, targets)| QcPayload {
+                    payload_id: Uuid::new_v4(),
+                    target_metrics: targets.to_vec(),
+                    part: Some(PayloadPart {
+                        index: i as u32 + 1,
+                        count,
+                    }),
+                    ..payload.clone()
+                })
+                .collect()
+        }
+        OversizedPayloadHandling::Truncate => {
+            warn!(
+                run_id = %payload.run.run_id,
+                targets = total,
+                max_payload_targets = max_targets,
+                "Truncating payload to the targets with the largest peak areas"
+            );
+            let mut by_area: Vec<usize> = (0..total).collect();
+            by_area.sort_by(|&a, &b| {
+                let area = |i: usize| payload.target_metrics[i].peak_area;
+                area(b).total_cmp(&area(a))
+            });
+            let keep: HashSet<usize> = by_area.into_iter().take(max_targets).collect();
+
+            let mut payload = payload;
+            payload.target_metrics = std::mem::take(&mut payload.target_metrics)
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| keep.contains(i))
+                .map(|(_, target)| target)
+                .collect();
+            payload.truncated = true;
+            payload.omitted_targets = (total - max_targets) as u32;
+            vec![payload]
+        }
+    }
+}
+
+/// Number of QC payloads in the spool, by state.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SpoolCounts {
+    /// Waiting for (or in the middle of) upload
+    pub pending: usize,
+    /// Given up on after retries
+    pub failed: usize,
+}
+
+/// Payloads removed (or, in a dry run, due for removal) by [`Spool::gc`].
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub removed: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+/// Tracks whether the soft-limit warning has been shown.
+#[derive(Default)]
+struct SoftLimit {
+    warned: AtomicBool,
+}
+
+impl SoftLimit {
+    /// True once when usage first reaches the soft limit; re-arms after
+    /// usage drops back below it.
+    fn crossed(&self, used_mb: u64, max_mb: u64) -> bool {
+        let over = used_mb * 100 >= max_mb * SOFT_LIMIT_PCT;
+        if over {
+            !self.warned.swap(true, Ordering::Relaxed)
+        } else {
+            self.warned.store(false, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+/// Pending spool usage as a percentage of `max_pending_mb`.
+pub fn pending_usage_pct(config: &SpoolConfig) -> u64 {
+    let used_mb = calculate_dir_size(&paths::spool_pending_dir()) / (1024 * 1024);
+    used_mb * 100 / config.max_pending_mb.max(1)
+}
+
+/// Files in `dir` last modified before `cutoff`, with their modification time.
+fn expired_in_dir(
+    dir: &Path,
+    cutoff: chrono::DateTime<Utc>,
+) -> Vec<(PathBuf, chrono::DateTime<Utc>)> {
+    let Ok(entries) = std::fs::read_dir(paths::long_path(dir)) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let modified: chrono::DateTime<Utc> = entry.metadata().ok()?.modified().ok()?.into();
+            (modified < cutoff).then(|| (entry.path(), modified))
+        })
+        .collect()
+}
+
+/// Files in `dir`, sorted by modification time (oldest first).
+fn files_oldest_first(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut entries: Vec<_> = std::fs::read_dir(paths::long_path(dir))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+
+    entries.sort_by_key(|path| {
+        std::fs::metadata(paths::long_path(path))
+            .and_then(|m| m.modified())
+            .ok()
+    });
+
+    Ok(entries)
+}
+
 /// Calculate total size of a directory in bytes.
-fn calculate_dir_size(path: &PathBuf) -> u64 {
-    std::fs::read_dir(path)
+fn calculate_dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(paths::long_path(path))
         .map(|entries| {
             entries
                 .filter_map(|e| e.ok())
@@ -361,3 +867,370 @@ fn calculate_dir_size(path: &PathBuf) -> u64 {
         })
         .unwrap_or(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_limit_warns_once() {
+        let soft_limit = SoftLimit::default();
+
+        // Below 80% of 1000 MB: no warning
+        assert!(!soft_limit.crossed(500, 1000));
+        assert!(!soft_limit.crossed(799, 1000));
+
+        // Crossing warns exactly once while usage stays high
+        let warnings = [800, 850, 900, 990]
+            .into_iter()
+            .filter(|&used| soft_limit.crossed(used, 1000))
+            .count();
+        assert_eq!(warnings, 1);
+
+        // Draining below the threshold re-arms the warning
+        assert!(!soft_limit.crossed(200, 1000));
+        assert!(soft_limit.crossed(820, 1000));
+    }
+
+    fn extraction_result(path: &Path) -> ExtractionResult {
+        use crate::types::RunMetrics;
+
+        ExtractionResult {
+            run_id: Uuid::new_v4(),
+            raw_file_path: path.to_path_buf(),
+            raw_file_name: "QC_A_01.raw".to_string(),
+            raw_file_hash: "abc".to_string(),
+            raw_file_hash_mode: Default::default(),
+            extraction_time_ms: 42_000,
+            backend: "skyline".to_string(),
+            backend_version: "24.1".to_string(),
+            template_name: "qc.sky".to_string(),
+            template_hash: "def".to_string(),
+            target_metrics: Vec::new(),
+            run_metrics: RunMetrics {
+                targets_found: 0,
+                targets_expected: 0,
+                target_recovery_pct: 0.0,
+                median_rt_shift: None,
+                median_mass_error_ppm: None,
+                chromatography_score: None,
+                missing_targets: Vec::new(),
+                undetected_targets: Vec::new(),
+                total_ion_current: None,
+                base_peak_intensity: None,
+                health_index: None,
+            },
+            import_warnings: Vec::new(),
+            acquisition: Default::default(),
+            skipped_rows: 0,
+            suspect_empty_import: false,
+            consumables: Default::default(),
+        }
+    }
+
+    fn classification(instrument_id: &str) -> RunClassification {
+        use crate::types::{ClassificationConfidence, ClassificationSource, ControlType};
+
+        RunClassification {
+            control_type: ControlType::QcA,
+            instrument_id: instrument_id.to_string(),
+            well_position: None,
+            plate_id: None,
+            confidence: ClassificationConfidence::High,
+            source: ClassificationSource::Filename,
+            matched_pattern: None,
+            injection_index: None,
+            reinjection_of: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_payload_timing_is_monotonic() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path()).unwrap();
+
+        let mut tracked = TrackedFile::new(PathBuf::from("QC_A_01.raw"), Vendor::Thermo, 10, None);
+        tracked.first_seen = Utc::now() - Duration::minutes(5);
+        tracked.finalized_at = Some(Utc::now() - Duration::minutes(1));
+
+        let result = extraction_result(&tracked.path);
+        let classification = classification("EXPLORIS01");
+
+        spool
+            .enqueue(
+                &result,
+                &classification,
+                Vendor::Thermo,
+                None,
+                None,
+                Some(&tracked),
+            )
+            .await
+            .unwrap();
+
+        let payload = spool.find_payload(&result.run_id).unwrap().unwrap();
+        let timing = payload.timing.unwrap();
+        let detected_at = timing.detected_at.unwrap();
+        let finalized_at = timing.finalized_at.unwrap();
+        assert_eq!(detected_at, tracked.first_seen);
+        assert_eq!(timing.extraction_time_ms, 42_000);
+        assert!(detected_at <= finalized_at);
+        assert!(finalized_at <= timing.spooled_at);
+        assert!(timing.spooled_at <= Utc::now());
+    }
+
+    #[tokio::test]
+    async fn test_payload_carries_instrument_project_id() {
+        let instruments: Vec<InstrumentConfig> = [
+            r#"
+            id = "EXPLORIS01"
+            vendor = "thermo"
+            project_id = "acme"
+            watch_path = "/data/exploris01"
+            template = "qc.sky"
+            "#,
+            r#"
+            id = "EXPLORIS02"
+            vendor = "thermo"
+            watch_path = "/data/exploris02"
+            template = "qc.sky"
+            "#,
+        ]
+        .iter()
+        .map(|toml| toml::from_str(toml).unwrap())
+        .collect();
+
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path())
+            .unwrap()
+            .with_projects(&instruments);
+
+        let mut project_ids = Vec::new();
+        for instrument_id in ["EXPLORIS01", "EXPLORIS02"] {
+            let result = extraction_result(Path::new("QC_A_01.raw"));
+            spool
+                .enqueue(
+                    &result,
+                    &classification(instrument_id),
+                    Vendor::Thermo,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            let payload = spool.find_payload(&result.run_id).unwrap().unwrap();
+            project_ids.push(payload.run.project_id);
+        }
+
+        assert_eq!(project_ids, [Some("acme".to_string()), None]);
+    }
+
+    fn result_with_areas(areas: &[f64]) -> ExtractionResult {
+        use crate::types::TargetMetrics;
+
+        let mut result = extraction_result(Path::new("QC_A_01.raw"));
+        result.target_metrics = areas
+            .iter()
+            .enumerate()
+            .map(|(i, &peak_area)| TargetMetrics {
+                target_id: format!("T{}", i),
+                peptide_sequence: None,
+                precursor_mz: 500.0,
+                retention_time: 10.0,
+                rt_expected: None,
+                rt_delta: None,
+                peak_area,
+                peak_height: 1.0,
+                peak_width_fwhm: None,
+                peak_symmetry: None,
+                tailing_factor: None,
+                mass_error_ppm: None,
+                isotope_dot_product: None,
+                detected: true,
+                excluded: false,
+            })
+            .collect();
+        result
+    }
+
+    #[tokio::test]
+    async fn test_oversized_payload_is_split_or_truncated() {
+        let areas = [10.0, 50.0, 30.0, 40.0, 20.0];
+
+        // Split: 5 targets at 2 per payload spool as 3 parts
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path())
+            .unwrap()
+            .with_payload_limit(2, OversizedPayloadHandling::Split);
+        let result = result_with_areas(&areas);
+        let mut spooled = spool
+            .enqueue(
+                &result,
+                &classification("EXPLORIS01"),
+                Vendor::Thermo,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let mut pending = spool.get_pending().unwrap();
+        assert_eq!(pending.len(), 3);
+        pending.sort();
+        spooled.sort();
+        assert_eq!(spooled, pending);
+        let mut parts: Vec<QcPayload> = pending
+            .iter()
+            .map(|p| serde_json::from_str(&std::fs::read_to_string(p).unwrap()).unwrap())
+            .collect();
+        parts.sort_by_key(|p| p.part.unwrap().index);
+        let sizes: Vec<usize> = parts.iter().map(|p| p.target_metrics.len()).collect();
+        assert_eq!(sizes, [2, 2, 1]);
+        assert!(parts.iter().all(|p| p.part.unwrap().count == 3));
+        assert!(parts
+            .iter()
+            .all(|p| p.correlation_id == parts[0].correlation_id));
+        assert_ne!(parts[0].payload_id, parts[1].payload_id);
+        assert_eq!(spool.unsent_run_ids(), HashSet::from([result.run_id]));
+
+        // The parts are merged back when looking the run up
+        let merged = spool.find_payload(&result.run_id).unwrap().unwrap();
+        let ids: Vec<&str> = merged
+            .target_metrics
+            .iter()
+            .map(|t| t.target_id.as_str())
+            .collect();
+        assert_eq!(ids, ["T0", "T1", "T2", "T3", "T4"]);
+        assert!(merged.part.is_none());
+
+        // Truncate: the 2 largest peak areas are kept, in report order
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path())
+            .unwrap()
+            .with_payload_limit(2, OversizedPayloadHandling::Truncate);
+        let result = result_with_areas(&areas);
+        spool
+            .enqueue(
+                &result,
+                &classification("EXPLORIS01"),
+                Vendor::Thermo,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(spool.get_pending().unwrap().len(), 1);
+        let payload = spool.find_payload(&result.run_id).unwrap().unwrap();
+        let ids: Vec<&str> = payload
+            .target_metrics
+            .iter()
+            .map(|t| t.target_id.as_str())
+            .collect();
+        assert_eq!(ids, ["T1", "T3"]);
+        assert!(payload.truncated);
+        assert_eq!(payload.omitted_targets, 3);
+        assert!(payload.part.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_newer_heartbeat_supersedes_unsent_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path()).unwrap();
+        let result = extraction_result(Path::new("QC_A_01.raw"));
+        spool
+            .enqueue(
+                &result,
+                &classification("EXPLORIS01"),
+                Vendor::Thermo,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let heartbeat =
+            |counts| Heartbeat::new("agent-1", &[], &HashMap::new(), counts, Utc::now());
+        let first = spool.enqueue_heartbeat(&heartbeat(spool.counts())).unwrap();
+        // A heartbeat that gave up retrying is superseded too
+        spool.mark_failed(&first).unwrap();
+        let failed = dir.path().join("failed").join(first.file_name().unwrap());
+        assert!(failed.exists());
+        let second = spool.enqueue_heartbeat(&heartbeat(spool.counts())).unwrap();
+
+        assert!(!failed.exists());
+        let pending = spool.get_pending().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.contains(&second));
+        assert!(is_heartbeat(&second));
+
+        // Heartbeats are not QC payloads
+        let counts = spool.counts();
+        assert_eq!((counts.pending, counts.failed), (1, 0));
+        assert!(spool.unsent_run_ids().contains(&result.run_id));
+        assert_eq!(spool.unsent_run_ids().len(), 1);
+    }
+
+    #[test]
+    fn test_gc_applies_age_and_count_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SpoolConfig {
+            max_age_days: 7,
+            completed_retention_count: 2,
+            ..Default::default()
+        };
+        let spool = Spool::in_dir(&config, dir.path()).unwrap();
+
+        let now = std::time::SystemTime::now();
+        let days_ago = |days: u64| {
+            filetime::FileTime::from_system_time(now - std::time::Duration::from_secs(days * 86400))
+        };
+        let write = |dir: &Path, name: &str, age_days: u64| {
+            let path = dir.join(name);
+            std::fs::write(&path, b"{}").unwrap();
+            filetime::set_file_mtime(&path, days_ago(age_days)).unwrap();
+            path
+        };
+
+        let fresh_pending = write(&spool.pending_dir, "fresh.json", 1);
+        let old_pending = write(&spool.pending_dir, "old.json", 10);
+        let old_failed = write(&spool.failed_dir, "old.json", 8);
+        let fresh_failed = write(&spool.failed_dir, "fresh.json", 0);
+        // Over age, over count (oldest of the fresh ones), and the two kept
+        let old_completed = write(&spool.completed_dir, "c0.json", 30);
+        let extra_completed = write(&spool.completed_dir, "c1.json", 5);
+        let kept_completed = [
+            write(&spool.completed_dir, "c2.json", 3),
+            write(&spool.completed_dir, "c3.json", 1),
+        ];
+        let in_flight = write(&spool.uploading_dir, "old.json", 20);
+
+        let mut expected = vec![old_pending, old_failed, old_completed, extra_completed];
+        expected.sort();
+
+        // Dry run reports the same set without deleting anything
+        let mut dry = spool.gc(true).unwrap();
+        dry.removed.sort();
+        assert_eq!(dry.removed, expected);
+        assert_eq!(dry.bytes, 8);
+        assert!(expected.iter().all(|p| p.exists()));
+
+        let mut report = spool.gc(false).unwrap();
+        report.removed.sort();
+        assert_eq!(report.removed, expected);
+        assert!(expected.iter().all(|p| !p.exists()));
+        for kept in [&fresh_pending, &fresh_failed, &in_flight]
+            .into_iter()
+            .chain(&kept_completed)
+        {
+            assert!(kept.exists(), "{} was removed", kept.display());
+        }
+
+        // Nothing left to collect
+        assert!(spool.gc(false).unwrap().removed.is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! Opt-in anonymized telemetry.
+//!
+//! When `agent.share_telemetry` is enabled, the agent keeps aggregate
+//! counters (extraction successes/failures) and periodically POSTs a rollup
+//! to the cloud. The rollup never contains file names, paths, or peptide
+//! data; the agent is identified only by a salted SHA-256 hash.
+//!
+//! Telemetry is best-effort: failures are logged at debug level and never
+//! affect the processing pipeline.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::config::{paths, CloudConfig};
+
+/// How often a rollup is sent.
+const REPORT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often in-memory counters are written back to disk.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Schema version of the rollup payload.
+const ROLLUP_SCHEMA_VERSION: &str = "1.0";
+
+/// Anonymized rollup sent to the telemetry endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryRollup {
+    pub schema_version: String,
+    /// Salted SHA-256 of the agent ID
+    pub agent_hash: String,
+    pub agent_version: String,
+    pub os: String,
+    pub os_build: Option<String>,
+    pub instrument_count: usize,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub extractions_succeeded: u64,
+    pub extractions_failed: u64,
+}
+
+/// Persisted counters for the current reporting period.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryStore {
+    /// Random per-install salt for hashing the agent ID
+    pub salt: String,
+    pub period_start: DateTime<Utc>,
+    pub extractions_succeeded: u64,
+    pub extractions_failed: u64,
+}
+
+impl Default for TelemetryStore {
+    fn default() -> Self {
+        Self {
+            salt: format!("{:016x}", rand::random::<u64>()),
+            period_start: Utc::now(),
+            extractions_succeeded: 0,
+            extractions_failed: 0,
+        }
+    }
+}
+
+impl TelemetryStore {
+    /// Load the store from disk, creating a fresh one if missing.
+    pub fn load() -> Result<Self> {
+        let store_path = Self::store_path();
+
+        if !store_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&store_path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Save the store to disk.
+    pub fn save(&self) -> Result<()> {
+        let store_path = Self::store_path();
+
+        if let Some(parent) = store_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&store_path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("telemetry.json")
+    }
+
+    /// Build the rollup for the current period.
+    pub fn rollup(&self, agent_id: &str, instrument_count: usize) -> TelemetryRollup {
+        TelemetryRollup {
+            schema_version: ROLLUP_SCHEMA_VERSION.to_string(),
+            agent_hash: hash_agent_id(&self.salt, agent_id),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+            os_build: os_build(),
+            instrument_count,
+            period_start: self.period_start,
+            period_end: Utc::now(),
+            extractions_succeeded: self.extractions_succeeded,
+            extractions_failed: self.extractions_failed,
+        }
+    }
+
+    /// When the current period's rollup is due.
+    fn report_due_at(&self) -> DateTime<Utc> {
+        self.period_start + report_interval()
+    }
+
+    /// Start a new reporting period (keeps the salt).
+    fn reset_period(&mut self) {
+        self.period_start = Utc::now();
+        self.extractions_succeeded = 0;
+        self.extractions_failed = 0;
+    }
+}
+
+/// Thread-safe telemetry recorder. A disabled recorder is a no-op.
+#[derive(Clone)]
+pub struct Telemetry {
+    enabled: bool,
+    inner: Arc<Mutex<TelemetryStore>>,
+}
+
+impl Telemetry {
+    /// Create a recorder; counters are only loaded and kept when enabled.
+    pub fn new(enabled: bool) -> Self {
+        let store = if enabled {
+            let store = TelemetryStore::load().unwrap_or_default();
+            // Persist immediately so the salt is stable across restarts
+            let _ = store.save();
+            store
+        } else {
+            TelemetryStore::default()
+        };
+
+        Self {
+            enabled,
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Record the outcome of an extraction. Only touches the in-memory
+    /// counters; the reporter persists them.
+    pub fn record_extraction(&self, success: bool) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut store = self.inner.lock().unwrap();
+        if success {
+            store.extractions_succeeded += 1;
+        } else {
+            store.extractions_failed += 1;
+        }
+    }
+
+    /// Write the current counters to disk. Called periodically by the
+    /// reporter and once at shutdown.
+    pub fn persist(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        // Snapshot under the lock, write outside it
+        let store = self.inner.lock().unwrap().clone();
+        if let Err(e) = store.save() {
+            debug!(error = %e, "Failed to persist telemetry counters");
+        }
+    }
+
+    /// Periodically send rollups and persist counters until the task is aborted.
+    pub async fn run_reporter(
+        &self,
+        cloud: CloudConfig,
+        agent_id: String,
+        instrument_count: usize,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        info!("Anonymized telemetry enabled (see 'mdqc telemetry preview')");
+
+        // Report when the persisted period ends, not a full interval after
+        // this start, so restarts don't keep pushing the rollup back
+        let mut next_send = self.inner.lock().unwrap().report_due_at();
+
+        let mut persist = tokio::time::interval(PERSIST_INTERVAL);
+        // The first tick completes immediately; the store was just saved in new()
+        persist.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = persist.tick() => {
+                    self.persist();
+                    continue;
+                }
+                _ = tokio::time::sleep(delay_until(next_send)) => {}
+            }
+
+            let rollup = self
+                .inner
+                .lock()
+                .unwrap()
+                .rollup(&agent_id, instrument_count);
+
+            match send_rollup(&cloud, &rollup).await {
+                Ok(()) => {
+                    debug!("Telemetry rollup sent");
+                    let mut store = self.inner.lock().unwrap();
+                    store.reset_period();
+                    let _ = store.save();
+                    next_send = store.report_due_at();
+                }
+                Err(e) => {
+                    // Keep counters; they'll be included in the next attempt
+                    debug!(error = %e, "Failed to send telemetry rollup");
+                    next_send = Utc::now() + report_interval();
+                }
+            }
+        }
+    }
+}
+
+/// POST a rollup to the telemetry endpoint.
+async fn send_rollup(cloud: &CloudConfig, rollup: &TelemetryRollup) -> Result<()> {
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(10));
+    if let Some(ref proxy_url) = cloud.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    }
+
+    let url = format!("{}telemetry", cloud.endpoint);
+    let response = builder
+        .build()?
+        .post(&url)
+        .headers(cloud.header_map()?)
+        .json(rollup)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("status {}", response.status());
+    }
+
+    Ok(())
+}
+
+/// `REPORT_INTERVAL` as a chrono duration, for wall-clock arithmetic.
+fn report_interval() -> chrono::Duration {
+    chrono::Duration::from_std(REPORT_INTERVAL).unwrap_or_default()
+}
+
+/// Time left until `at`, or zero if it has already passed.
+fn delay_until(at: DateTime<Utc>) -> Duration {
+    (at - Utc::now()).to_std().unwrap_or(Duration::ZERO)
+}
+
+/// Salted SHA-256 of the agent ID, hex encoded.
+fn hash_agent_id(salt: &str, agent_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(agent_id.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// OS build number, where available.
+fn os_build() -> Option<String> {
+    #[cfg(windows)]
+    {
+        use winreg::enums::*;
+        use winreg::RegKey;
+
+        RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey(r"SOFTWARE\Microsoft\Windows NT\CurrentVersion")
+            .and_then(|key| key.get_value::<String, _>("CurrentBuildNumber"))
+            .ok()
+    }
+
+    #[cfg(not(windows))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup_is_anonymized() {
+        let store = TelemetryStore {
+            salt: "salt".to_string(),
+            period_start: Utc::now(),
+            extractions_succeeded: 3,
+            extractions_failed: 1,
+        };
+
+        let rollup = store.rollup("mdqc-LABPC01-1234abcd", 2);
+        let json = serde_json::to_string(&rollup).unwrap();
+
+        assert!(!json.contains("LABPC01"));
+        assert_eq!(rollup.agent_hash.len(), 64);
+        assert_eq!(rollup.extractions_succeeded, 3);
+        assert_eq!(rollup.extractions_failed, 1);
+
+        // Different salt gives a different hash for the same agent
+        assert_ne!(
+            rollup.agent_hash,
+            hash_agent_id("other", "mdqc-LABPC01-1234abcd")
+        );
+    }
+
+    #[test]
+    fn test_report_due_at_follows_persisted_period() {
+        let mut store = TelemetryStore {
+            salt: "salt".to_string(),
+            period_start: Utc::now() - chrono::Duration::hours(30),
+            extractions_succeeded: 0,
+            extractions_failed: 0,
+        };
+
+        // A period that started more than an interval ago is due now
+        assert_eq!(delay_until(store.report_due_at()), Duration::ZERO);
+
+        // A period that started an hour ago is due in about 23 hours
+        store.period_start = Utc::now() - chrono::Duration::hours(1);
+        let delay = delay_until(store.report_due_at());
+        assert!(delay > Duration::from_secs(22 * 60 * 60));
+        assert!(delay <= Duration::from_secs(23 * 60 * 60));
+    }
+}
@@ -4,7 +4,7 @@ use anyhow::Result;
 use std::os::windows::process::CommandExt;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use tray_icon::{
     menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
     TrayIcon, TrayIconBuilder,
@@ -14,20 +14,19 @@ use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::WindowId;
 
+use crate::agent_state::{AgentState, AgentStatus};
 use crate::config;
 use crate::extractor::skyline;
+use crate::update_check::{self, UpdateStatus};
 
 /// Mutex name for single instance check (per-user to avoid cross-privilege conflicts)
 const SINGLE_INSTANCE_MUTEX: &str = "Local\\MassDynamicsQCAgent";
 
-/// GitHub releases URL for update checks
-const RELEASES_URL: &str =
-    "https://github.com/webwebb56/MD-EVOSEP-system-suitability-control/releases";
-
 /// Menu item IDs
 mod menu_ids {
     pub const STATUS: &str = "status";
     pub const WATCHER_STATUS: &str = "watcher_status";
+    pub const ACTIVITY: &str = "activity";
     pub const HEALTH_STATUS: &str = "health_status";
     pub const INSTRUMENT_COUNT: &str = "instrument_count";
     pub const OPEN_CONFIG: &str = "open_config";
@@ -99,15 +98,35 @@ struct TrayApp {
     health_status: Option<HealthCheckResult>,
     /// Shutdown sender for the background watcher
     watcher_shutdown: Option<tokio::sync::mpsc::Sender<()>>,
+    /// Live state of the background watcher
+    agent_state: Option<AgentState>,
+    /// Watcher and activity lines, refreshed from `agent_state`
+    watcher_item: Option<MenuItem>,
+    activity_item: Option<MenuItem>,
+    /// Text last shown, so unchanged state isn't re-rendered
+    live_text: Option<(String, String)>,
+    /// "Check for Updates" menu item, relabelled when an update is found
+    updates_item: Option<MenuItem>,
+    /// Result of the background update check, if one was started
+    update_rx: Option<mpsc::Receiver<UpdateStatus>>,
+    update: Option<UpdateStatus>,
 }
 
 impl TrayApp {
-    fn new(watcher_shutdown: Option<tokio::sync::mpsc::Sender<()>>) -> Self {
+    fn new(watcher: Option<(tokio::sync::mpsc::Sender<()>, AgentState)>) -> Self {
+        let (watcher_shutdown, agent_state) = watcher.unzip();
         Self {
             tray_icon: None,
             running: Arc::new(AtomicBool::new(true)),
             health_status: None,
             watcher_shutdown,
+            agent_state,
+            watcher_item: None,
+            activity_item: None,
+            live_text: None,
+            updates_item: None,
+            update_rx: start_update_check(),
+            update: None,
         }
     }
 
@@ -135,14 +154,10 @@ impl TrayApp {
             }
         };
 
-        // Check 2: Skyline is installed
-        // Handle "auto" path - treat it as None to trigger auto-discovery
+        // Check 2: Skyline is installed (discovery is cached per process)
         let skyline_path = config
             .skyline
-            .path
-            .as_ref()
-            .filter(|p| !p.eq_ignore_ascii_case("auto") && !p.is_empty())
-            .map(std::path::PathBuf::from)
+            .configured_path()
             .or_else(skyline::discover_skyline);
 
         match skyline_path {
@@ -185,9 +200,7 @@ impl TrayApp {
             let full_path = if template_path.is_absolute() {
                 template_path.to_path_buf()
             } else {
-                config::paths::data_dir()
-                    .join("templates")
-                    .join(&instrument.template)
+                config::paths::template_dir().join(&instrument.template)
             };
 
             if !full_path.exists() {
@@ -198,9 +211,9 @@ impl TrayApp {
             }
         }
 
-        // Check 6: API token configured (warning only)
-        if config.cloud.api_token.is_none()
-            || config
+        // Check 6: API token or OAuth2 credentials configured (warning only)
+        if config.cloud.token_url.is_none()
+            && config
                 .cloud
                 .api_token
                 .as_ref()
@@ -224,7 +237,7 @@ impl TrayApp {
         }
     }
 
-    fn create_menu(&self, watcher_running: bool) -> Result<Menu> {
+    fn create_menu(&mut self, watcher_running: bool) -> Result<Menu> {
         let menu = Menu::new();
 
         // Status item (disabled, just shows info)
@@ -245,6 +258,15 @@ impl TrayApp {
         };
         let watcher_item = MenuItem::with_id(menu_ids::WATCHER_STATUS, &watcher_text, false, None);
         menu.append(&watcher_item)?;
+        self.watcher_item = Some(watcher_item);
+
+        // Live counts from the background watcher
+        if watcher_running {
+            let activity_item =
+                MenuItem::with_id(menu_ids::ACTIVITY, "No runs processed yet", false, None);
+            menu.append(&activity_item)?;
+            self.activity_item = Some(activity_item);
+        }
 
         // Health status from startup check
         let health_text = match &self.health_status {
@@ -306,6 +328,7 @@ impl TrayApp {
         let updates_item =
             MenuItem::with_id(menu_ids::CHECK_UPDATES, "Check for Updates...", true, None);
         menu.append(&updates_item)?;
+        self.updates_item = Some(updates_item);
 
         menu.append(&PredefinedMenuItem::separator())?;
 
@@ -316,6 +339,32 @@ impl TrayApp {
         Ok(menu)
     }
 
+    /// Show the background watcher's live state in the menu and tooltip.
+    fn refresh_live_status(&mut self) {
+        let Some(ref state) = self.agent_state else {
+            return;
+        };
+        let status = state.snapshot();
+        let text = (watcher_text(&status), activity_text(&status));
+        if self.live_text.as_ref() == Some(&text) {
+            return;
+        }
+
+        if let Some(ref item) = self.watcher_item {
+            item.set_text(&text.0);
+        }
+        if let Some(ref item) = self.activity_item {
+            item.set_text(&text.1);
+        }
+        if let Some(ref tray_icon) = self.tray_icon {
+            let _ = tray_icon.set_tooltip(Some(format!(
+                "MD QC Agent - {}",
+                text.0.trim_start_matches(['●', '○', '⏸', ' '])
+            )));
+        }
+        self.live_text = Some(text);
+    }
+
     fn get_instrument_status(&self) -> String {
         let config_path = config::paths::config_file();
 
@@ -399,7 +448,12 @@ impl TrayApp {
             menu_ids::OPEN_DATA_FOLDER => self.open_data_folder(),
             menu_ids::DOCTOR => self.run_doctor(),
             menu_ids::CHECK_UPDATES => {
-                open_url(RELEASES_URL);
+                match self.update {
+                    Some(ref status) if status.update_available() => {
+                        open_url(&status.release_url())
+                    }
+                    _ => open_url(update_check::RELEASES_URL),
+                }
                 Ok(())
             }
             menu_ids::EXIT => {
@@ -453,7 +507,7 @@ impl TrayApp {
         }
 
         // Fallback: open methods directory
-        let methods_dir = config::paths::data_dir().join("methods");
+        let methods_dir = config::paths::base_dir().join("methods");
         let _ = std::fs::create_dir_all(&methods_dir);
         shell_open(&methods_dir.to_string_lossy())?;
         Ok(())
@@ -608,6 +662,19 @@ impl ApplicationHandler for TrayApp {
             self.handle_menu_event(event);
         }
 
+        // Pick up the background update check once it answers
+        if let Some(status) = self.update_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            if status.update_available() {
+                if let Some(ref item) = self.updates_item {
+                    item.set_text(format!("Update available: v{}", status.latest));
+                }
+            }
+            self.update = Some(status);
+            self.update_rx = None;
+        }
+
+        self.refresh_live_status();
+
         // Check if we should exit
         if !self.running.load(Ordering::SeqCst) {
             event_loop.exit();
@@ -622,6 +689,32 @@ impl ApplicationHandler for TrayApp {
     }
 }
 
+/// Watcher line of the tray menu for the given live state.
+fn watcher_text(status: &AgentStatus) -> String {
+    if status.stopped {
+        "○ Watcher: Stopped".to_string()
+    } else if let Some(ref path) = status.processing {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        format!("● Processing: {}", name.to_string_lossy())
+    } else if let Some(reason) = status.paused {
+        format!("⏸ Watcher: Paused ({})", reason)
+    } else {
+        "● Watcher: Running".to_string()
+    }
+}
+
+/// Counts line of the tray menu for the given live state.
+fn activity_text(status: &AgentStatus) -> String {
+    let mut text = format!("Processed: {}  Failed: {}", status.processed, status.failed);
+    if let Some(at) = status.last_upload_at {
+        text.push_str(&format!(
+            "  Last upload: {}",
+            at.with_timezone(&chrono::Local).format("%H:%M")
+        ));
+    }
+    text
+}
+
 /// Show a Windows message box (ensures it appears in foreground)
 fn show_message_box(title: &str, message: &str, is_error: bool) {
     use std::ffi::OsStr;
@@ -734,7 +827,7 @@ timeout_seconds = 300
 
 [watcher]
 scan_interval_seconds = 30
-stability_window_seconds = 60
+# stability_window_seconds: unset = 60s for .raw/.wiff, 180s for .d folders
 
 # Configure your instrument(s) below:
 # Uncomment and edit the following section:
@@ -836,18 +929,10 @@ fn open_url(url: &str) {
 fn ensure_start_menu_shortcut() {
     use crate::notifications::APP_USER_MODEL_ID;
 
-    // Get the Start Menu Programs folder
-    let start_menu = match std::env::var("APPDATA") {
-        Ok(appdata) => std::path::PathBuf::from(appdata)
-            .join("Microsoft")
-            .join("Windows")
-            .join("Start Menu")
-            .join("Programs"),
-        Err(_) => return,
+    let Some(shortcut_path) = crate::config::paths::start_menu_shortcut() else {
+        return;
     };
 
-    let shortcut_path = start_menu.join("MD QC Agent.lnk");
-
     // Skip if shortcut already exists
     if shortcut_path.exists() {
         return;
@@ -1034,19 +1119,54 @@ async fn run_tray_inner() -> Result<()> {
 
     // Start the file watcher in a background thread
     // When this process exits, the thread dies automatically (no orphans)
-    let watcher_shutdown = start_background_watcher();
+    let watcher = start_background_watcher();
 
     let event_loop = EventLoop::new()?;
-    let mut app = TrayApp::new(watcher_shutdown);
+    let mut app = TrayApp::new(watcher);
 
     event_loop.run_app(&mut app)?;
 
     Ok(())
 }
 
+/// Check GitHub releases on a background thread, unless disabled in the config.
+///
+/// Uses the cached answer when it is less than a day old, so most starts
+/// don't reach GitHub at all.
+fn start_update_check() -> Option<mpsc::Receiver<UpdateStatus>> {
+    let config = config::Config::load().ok();
+    if config.as_ref().is_some_and(|c| !c.agent.check_for_updates) {
+        return None;
+    }
+    let cloud = config.map(|c| c.cloud).unwrap_or_default();
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => {
+                eprintln!("Update check: failed to create runtime: {}", e);
+                return;
+            }
+        };
+        match rt.block_on(update_check::check(&cloud, false)) {
+            Ok(status) => {
+                let _ = tx.send(status);
+            }
+            Err(e) => eprintln!("Update check failed: {:#}", e),
+        }
+    });
+
+    Some(rx)
+}
+
 /// Start the file watcher in a background thread.
-/// Returns a shutdown sender to stop the watcher gracefully.
-fn start_background_watcher() -> Option<tokio::sync::mpsc::Sender<()>> {
+/// Returns a shutdown sender to stop the watcher gracefully, and the state it
+/// reports its progress in.
+fn start_background_watcher() -> Option<(tokio::sync::mpsc::Sender<()>, AgentState)> {
     // Try to load config - if it fails, don't start the watcher
     let config = match config::Config::load() {
         Ok(c) => c,
@@ -1064,18 +1184,23 @@ fn start_background_watcher() -> Option<tokio::sync::mpsc::Sender<()>> {
 
     // Create shutdown channel
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
+    let agent_state = AgentState::new();
 
     // Spawn background thread with its own tokio runtime
+    let state = agent_state.clone();
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
         rt.block_on(async {
             println!("Background watcher started");
-            if let Err(e) = crate::cli::run::run_agent(config, &mut shutdown_rx).await {
+            if let Err(e) =
+                crate::cli::run::run_agent(config, &mut shutdown_rx, state.clone()).await
+            {
                 eprintln!("Watcher error: {}", e);
             }
+            state.set_stopped();
             println!("Background watcher stopped");
         });
     });
 
-    Some(shutdown_tx)
+    Some((shutdown_tx, agent_state))
 }
@@ -4,6 +4,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -41,6 +42,18 @@ impl ControlType {
             _ => None,
         }
     }
+
+    /// Parse control type from a token, trying configured aliases first.
+    pub fn from_token_with_aliases(
+        token: &str,
+        aliases: &std::collections::HashMap<String, ControlType>,
+    ) -> Option<Self> {
+        aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(token))
+            .map(|(_, control_type)| *control_type)
+            .or_else(|| Self::from_token(token))
+    }
 }
 
 impl std::fmt::Display for ControlType {
@@ -156,6 +169,31 @@ pub enum ClassificationConfidence {
     Low,
 }
 
+impl ClassificationConfidence {
+    /// Whether this confidence is at least `min`.
+    pub fn meets(self, min: ClassificationConfidence) -> bool {
+        self.rank() >= min.rank()
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            ClassificationConfidence::Low => 0,
+            ClassificationConfidence::Medium => 1,
+            ClassificationConfidence::High => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for ClassificationConfidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClassificationConfidence::High => write!(f, "HIGH"),
+            ClassificationConfidence::Medium => write!(f, "MEDIUM"),
+            ClassificationConfidence::Low => write!(f, "LOW"),
+        }
+    }
+}
+
 /// Source of classification decision.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -175,6 +213,47 @@ pub struct RunClassification {
     pub plate_id: Option<String>,
     pub confidence: ClassificationConfidence,
     pub source: ClassificationSource,
+    /// Filename text that decided the control type (token or well)
+    #[serde(default)]
+    pub matched_pattern: Option<String>,
+    /// Injection/replicate number from a token like `inj3`, `rep2` or `#4`
+    #[serde(default)]
+    pub injection_index: Option<u32>,
+    /// Run ID of the earlier attempt this run re-injects (same control type
+    /// and well, shortly before), when re-injection detection is enabled
+    #[serde(default)]
+    pub reinjection_of: Option<Uuid>,
+}
+
+/// Classification fields given explicitly for a run instead of being read
+/// from its name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunOverrides {
+    #[serde(default)]
+    pub control_type: Option<ControlType>,
+    #[serde(default)]
+    pub well_position: Option<WellPosition>,
+    #[serde(default)]
+    pub plate_id: Option<String>,
+}
+
+impl RunOverrides {
+    /// Apply the overrides to a classification. A given control type is
+    /// taken as certain.
+    pub fn apply(&self, classification: &mut RunClassification) {
+        if let Some(control_type) = self.control_type {
+            classification.control_type = control_type;
+            classification.confidence = ClassificationConfidence::High;
+            classification.source = ClassificationSource::Metadata;
+            classification.matched_pattern = None;
+        }
+        if let Some(well) = &self.well_position {
+            classification.well_position = Some(well.clone());
+        }
+        if let Some(plate_id) = &self.plate_id {
+            classification.plate_id = Some(plate_id.clone());
+        }
+    }
 }
 
 /// State of a file in the finalization process.
@@ -195,9 +274,53 @@ pub struct TrackedFile {
     pub state: FinalizationState,
     pub first_seen: DateTime<Utc>,
     pub last_size: u64,
-    pub last_modified: DateTime<Utc>,
+    /// Last observed mtime (`None` if the filesystem couldn't provide one)
+    pub last_modified: Option<DateTime<Utc>>,
     pub stable_since: Option<DateTime<Utc>>,
+    /// When the file was finalized and queued for processing
+    pub finalized_at: Option<DateTime<Utc>>,
     pub vendor: Vendor,
+    /// Consecutive checks where the mtime was unavailable
+    pub mtime_missing_checks: u32,
+    /// Stability is judged on size alone because the mtime is unavailable
+    pub size_only_stability: bool,
+    /// Consecutive checks with unchanged size (used in size-only mode)
+    pub unchanged_checks: u32,
+    /// Consecutive checks with no vendor lock file present
+    pub lock_free_checks: u32,
+    /// Wall-clock time of the last finalization check, to spot clock jumps
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Instrument named by a drop-queue job; otherwise the instrument is the
+    /// one whose watch path holds the file
+    pub instrument_id: Option<String>,
+    /// Classification fields set by a drop-queue job
+    pub overrides: Option<RunOverrides>,
+    /// Requeued on purpose (template change), so not a duplicate
+    pub reprocess: bool,
+}
+
+impl TrackedFile {
+    /// Start tracking a newly detected file.
+    pub fn new(path: PathBuf, vendor: Vendor, size: u64, modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            path,
+            state: FinalizationState::Detected,
+            first_seen: Utc::now(),
+            last_size: size,
+            last_modified: modified,
+            stable_since: None,
+            finalized_at: None,
+            vendor,
+            mtime_missing_checks: 0,
+            size_only_stability: false,
+            unchanged_checks: 0,
+            lock_free_checks: 0,
+            last_checked: None,
+            instrument_id: None,
+            overrides: None,
+            reprocess: false,
+        }
+    }
 }
 
 /// Metrics for a single target/peptide.
@@ -213,9 +336,29 @@ pub struct TargetMetrics {
     pub peak_height: f64,
     pub peak_width_fwhm: Option<f64>,
     pub peak_symmetry: Option<f64>,
+    #[serde(default)]
+    pub tailing_factor: Option<f64>,
     pub mass_error_ppm: Option<f64>,
     pub isotope_dot_product: Option<f64>,
     pub detected: bool,
+    /// Listed in the instrument's `excluded_targets`: reported, but left out
+    /// of run-level metrics
+    #[serde(default)]
+    pub excluded: bool,
+}
+
+/// Separates a repeated target's id from its occurrence index ("X#2").
+pub const DUPLICATE_TARGET_SEPARATOR: char = '#';
+
+impl TargetMetrics {
+    /// The id without a duplicate occurrence suffix, so every peak of a
+    /// target that appeared more than once maps back to the same target.
+    pub fn base_id(&self) -> &str {
+        match self.target_id.rsplit_once(DUPLICATE_TARGET_SEPARATOR) {
+            Some((base, index)) if index.parse::<usize>().is_ok() => base,
+            _ => &self.target_id,
+        }
+    }
 }
 
 /// Run-level aggregate metrics.
@@ -227,6 +370,22 @@ pub struct RunMetrics {
     pub median_rt_shift: Option<f64>,
     pub median_mass_error_ppm: Option<f64>,
     pub chromatography_score: Option<f64>,
+    /// Expected targets with no row in the report at all
+    #[serde(default)]
+    pub missing_targets: Vec<String>,
+    /// Expected targets with a report row but no detected peak
+    #[serde(default)]
+    pub undetected_targets: Vec<String>,
+    /// Total ion current of the run, when the report has a TIC column
+    #[serde(default)]
+    pub total_ion_current: Option<f64>,
+    /// Base peak intensity of the run, when the report has a BPI column
+    #[serde(default)]
+    pub base_peak_intensity: Option<f64>,
+    /// Weighted 0-100 composite of recovery, mass accuracy, chromatography
+    /// and baseline tolerance, with `[skyline.health_index_weights]`
+    #[serde(default)]
+    pub health_index: Option<f64>,
 }
 
 /// Extraction result from Skyline.
@@ -236,6 +395,9 @@ pub struct ExtractionResult {
     pub raw_file_path: PathBuf,
     pub raw_file_name: String,
     pub raw_file_hash: String,
+    /// How `raw_file_hash` was computed
+    #[serde(default)]
+    pub raw_file_hash_mode: RawHashMode,
     pub extraction_time_ms: u64,
     pub backend: String,
     pub backend_version: String,
@@ -243,6 +405,54 @@ pub struct ExtractionResult {
     pub template_hash: String,
     pub target_metrics: Vec<TargetMetrics>,
     pub run_metrics: RunMetrics,
+    /// Skyline warnings from an import that otherwise succeeded
+    #[serde(default)]
+    pub import_warnings: Vec<String>,
+    /// Acquisition metadata exported as report columns, when present
+    #[serde(default)]
+    pub acquisition: AcquisitionMetadata,
+    /// Report rows skipped because their field count didn't match the header
+    #[serde(default)]
+    pub skipped_rows: u32,
+    /// Extracted far faster than usual for the instrument with few targets
+    /// recovered, as when Skyline imports nothing yet exits 0
+    #[serde(default)]
+    pub suspect_empty_import: bool,
+    /// Consumable lots in use for the run (see `consumables`)
+    #[serde(default)]
+    pub consumables: HashMap<String, String>,
+}
+
+/// How the raw file hash is computed.
+///
+/// Hashing multi-GB raw files end to end adds noticeable latency per run; the
+/// cheaper modes trade strength for speed. Directory formats (Bruker/Agilent
+/// .d, Waters .raw) hash their key internal file in every mode but `Full`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawHashMode {
+    /// SHA-256 of the whole file (directories: names and sizes of entries)
+    #[default]
+    Full,
+    /// SHA-256 over the SHA-256 of each 64 MB chunk, hashed in parallel;
+    /// reads the whole file like `Full` but uses every core
+    Chunked,
+    /// SHA-256 of the size plus the first and last few MB
+    HeadTail,
+    /// SHA-256 of the size and modification time only
+    SizeAndMtime,
+    /// No hash; `raw_file_hash` is empty
+    None,
+}
+
+/// Acquisition metadata read from report columns (e.g. Skyline's
+/// AcquiredTime, InstrumentSerialNumber, InstrumentModel, AcquisitionMethod).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AcquisitionMetadata {
+    pub acquisition_time: Option<DateTime<Utc>>,
+    pub instrument_serial: Option<String>,
+    pub instrument_model: Option<String>,
+    pub method_name: Option<String>,
 }
 
 /// Complete payload for upload to MD cloud.
@@ -261,6 +471,44 @@ pub struct QcPayload {
     pub target_metrics: Vec<TargetMetrics>,
     pub run_metrics: RunMetrics,
     pub comparison_metrics: Option<ComparisonMetrics>,
+    #[serde(default)]
+    pub replicate_metrics: Option<ReplicateMetrics>,
+    /// Hash of the agent configuration that produced this payload
+    #[serde(default)]
+    pub config_hash: Option<String>,
+    /// Local processing timeline
+    #[serde(default)]
+    pub timing: Option<ProcessingTiming>,
+    /// Which part of the run this is, when its targets were split over
+    /// several payloads (`cloud.max_payload_targets`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<PayloadPart>,
+    /// Targets were cut to `cloud.max_payload_targets`, keeping the largest
+    #[serde(default)]
+    pub truncated: bool,
+    /// Targets left out by truncation
+    #[serde(default)]
+    pub omitted_targets: u32,
+}
+
+/// Position of a payload among the parts of a split run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadPart {
+    /// 1-based part number
+    pub index: u32,
+    pub count: u32,
+}
+
+/// Where a run spent its time on the agent: stabilization (detected to
+/// finalized), extraction, and spooling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessingTiming {
+    /// When the watcher first saw the file (`None` outside the watcher)
+    pub detected_at: Option<DateTime<Utc>>,
+    /// When the file was finalized and queued for extraction
+    pub finalized_at: Option<DateTime<Utc>>,
+    pub extraction_time_ms: u64,
+    pub spooled_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -268,12 +516,37 @@ pub struct RunInfo {
     pub run_id: Uuid,
     pub raw_file_name: String,
     pub raw_file_hash: String,
+    #[serde(default)]
+    pub raw_file_hash_mode: RawHashMode,
     pub acquisition_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub instrument_serial: Option<String>,
+    #[serde(default)]
+    pub instrument_model: Option<String>,
+    #[serde(default)]
+    pub method_name: Option<String>,
     pub instrument_id: String,
+    /// Cloud project (tenant) of the instrument
+    #[serde(default)]
+    pub project_id: Option<String>,
     pub vendor: Vendor,
     pub control_type: ControlType,
     pub well_position: Option<String>,
     pub plate_id: Option<String>,
+    #[serde(default)]
+    pub injection_index: Option<u32>,
+    /// Repeat of a recent run of the same control type and well
+    #[serde(default)]
+    pub is_reinjection: bool,
+    /// Run ID of the attempt this run repeats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reinjection_of: Option<Uuid>,
+    /// Extraction looked like a silent no-op import (see `ExtractionResult`)
+    #[serde(default)]
+    pub suspect_empty_import: bool,
+    /// Consumable lots in use, e.g. `column_serial`, `emitter_lot`
+    #[serde(default)]
+    pub consumables: HashMap<String, String>,
     pub classification_confidence: ClassificationConfidence,
     pub classification_source: ClassificationSource,
 }
@@ -286,6 +559,13 @@ pub struct ExtractionInfo {
     pub template_hash: String,
     pub extraction_time_ms: u64,
     pub status: String,
+    /// Skyline import warnings; a low recovery with warnings points at the
+    /// import rather than the instrument
+    #[serde(default)]
+    pub import_warnings: Vec<String>,
+    /// Malformed report rows left out of the metrics
+    #[serde(default)]
+    pub skipped_report_rows: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -293,11 +573,48 @@ pub struct BaselineContext {
     pub baseline_id: String,
     pub baseline_established: DateTime<Utc>,
     pub baseline_template_hash: String,
+    /// When the agent fetched the baseline from the cloud
+    #[serde(default)]
+    pub fetched_at: Option<DateTime<Utc>>,
+    /// The agent's latest refresh failed, so a newer baseline may exist
+    #[serde(default)]
+    pub stale: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComparisonMetrics {
-    pub vs_baseline: BaselineComparison,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vs_baseline: Option<BaselineComparison>,
+    /// Deltas against the previous run of the same control type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vs_previous: Option<PreviousRunComparison>,
+}
+
+/// Run-over-run change (this run minus the previous one).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviousRunComparison {
+    pub previous_run_id: Uuid,
+    pub previous_recorded_at: DateTime<Utc>,
+    pub target_recovery_pct_delta: f64,
+    pub median_rt_shift_delta: Option<f64>,
+    pub median_mass_error_ppm_delta: Option<f64>,
+}
+
+/// Peak-area precision across recent replicate injections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicateMetrics {
+    /// Number of runs in the window (including this one)
+    pub window_size: usize,
+    pub median_area_cv_pct: Option<f64>,
+    pub target_area_cv: Vec<TargetCv>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetCv {
+    pub target_id: String,
+    pub area_cv_pct: f64,
+    /// Number of runs the target was detected in
+    pub n: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -321,6 +638,19 @@ pub enum BaselineState {
     Failed,
 }
 
+impl std::fmt::Display for BaselineState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineState::Candidate => write!(f, "CANDIDATE"),
+            BaselineState::Validating => write!(f, "VALIDATING"),
+            BaselineState::Active => write!(f, "ACTIVE"),
+            BaselineState::Archived => write!(f, "ARCHIVED"),
+            BaselineState::Rejected => write!(f, "REJECTED"),
+            BaselineState::Failed => write!(f, "FAILED"),
+        }
+    }
+}
+
 /// Baseline record.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Baseline {
@@ -0,0 +1,199 @@
+//! Update check against the project's GitHub releases.
+//!
+//! The latest release tag is fetched from the GitHub API and compared to the
+//! running version with semver. The answer is cached in `update_check.json`
+//! for a day so the tray, `mdqc doctor` and `mdqc version --check` stay well
+//! inside GitHub's unauthenticated rate limit. Sites that don't want the
+//! outbound call set `agent.check_for_updates = false`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::debug;
+
+use crate::config::{paths, CloudConfig};
+
+/// Releases page opened from the tray and printed with update notices
+pub const RELEASES_URL: &str =
+    "https://github.com/webwebb56/MD-EVOSEP-system-suitability-control/releases";
+
+/// GitHub API endpoint for the most recent non-prerelease release
+const LATEST_RELEASE_API: &str =
+    "https://api.github.com/repos/webwebb56/MD-EVOSEP-system-suitability-control/releases/latest";
+
+/// How long a cached answer is reused before GitHub is asked again.
+const CACHE_TTL_HOURS: i64 = 24;
+
+/// Result of an update check.
+#[derive(Debug, Clone)]
+pub struct UpdateStatus {
+    pub current: Version,
+    pub latest: Version,
+    /// Release tag as published (e.g. "v0.6.0")
+    pub latest_tag: String,
+}
+
+impl UpdateStatus {
+    pub fn update_available(&self) -> bool {
+        self.latest > self.current
+    }
+
+    /// Page of the latest release.
+    pub fn release_url(&self) -> String {
+        format!("{}/tag/{}", RELEASES_URL, self.latest_tag)
+    }
+}
+
+/// Last answer from GitHub, persisted between runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UpdateCache {
+    checked_at: DateTime<Utc>,
+    latest_tag: String,
+}
+
+impl UpdateCache {
+    fn path() -> PathBuf {
+        paths::data_dir().join("update_check.json")
+    }
+
+    fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::write(Self::path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        now - self.checked_at < Duration::hours(CACHE_TTL_HOURS) && now >= self.checked_at
+    }
+
+    fn status(&self) -> Option<UpdateStatus> {
+        Some(UpdateStatus {
+            current: current_version(),
+            latest: parse_tag(&self.latest_tag)?,
+            latest_tag: self.latest_tag.clone(),
+        })
+    }
+}
+
+/// Version of the running agent.
+pub fn current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("CARGO_PKG_VERSION is semver")
+}
+
+/// Parse a release tag into a version.
+///
+/// Anything before the first digit is a prefix ("v0.6.0", "mdqc-v0.6.0").
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let start = tag.find(|c: char| c.is_ascii_digit())?;
+    Version::parse(tag[start..].trim()).ok()
+}
+
+/// Compare the running version to the latest release.
+///
+/// Uses the cached answer while it is less than a day old unless `force` is
+/// set.
+pub async fn check(cloud: &CloudConfig, force: bool) -> Result<UpdateStatus> {
+    if !force {
+        if let Some(status) = UpdateCache::load()
+            .filter(|cache| cache.is_fresh(Utc::now()))
+            .and_then(|cache| cache.status())
+        {
+            return Ok(status);
+        }
+    }
+
+    let latest_tag = fetch_latest_tag(cloud).await?;
+    let cache = UpdateCache {
+        checked_at: Utc::now(),
+        latest_tag,
+    };
+    let status = cache
+        .status()
+        .with_context(|| format!("Release tag '{}' is not a version", cache.latest_tag))?;
+
+    if let Err(e) = cache.save() {
+        debug!(error = %e, "Failed to cache update check");
+    }
+
+    Ok(status)
+}
+
+/// Ask GitHub for the latest release tag.
+async fn fetch_latest_tag(cloud: &CloudConfig) -> Result<String> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .user_agent(concat!("mdqc/", env!("CARGO_PKG_VERSION")));
+    if let Some(ref proxy_url) = cloud.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    }
+
+    let response = builder
+        .build()?
+        .get(LATEST_RELEASE_API)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .context("GitHub releases unreachable")?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("GitHub releases returned status {}", response.status());
+    }
+
+    Ok(response.json::<Release>().await?.tag_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(current: &str, latest_tag: &str) -> UpdateStatus {
+        UpdateStatus {
+            current: Version::parse(current).unwrap(),
+            latest: parse_tag(latest_tag).unwrap(),
+            latest_tag: latest_tag.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_release_tags_compare_as_semver() {
+        assert_eq!(parse_tag("v0.6.0"), Some(Version::new(0, 6, 0)));
+        assert_eq!(parse_tag("0.6.0"), Some(Version::new(0, 6, 0)));
+        assert_eq!(parse_tag("mdqc-v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse_tag("latest"), None);
+        assert_eq!(parse_tag("v1.2"), None);
+
+        assert!(status("0.5.5", "v0.6.0").update_available());
+        // Numeric, not lexical: 0.10 is newer than 0.9
+        assert!(status("0.9.0", "v0.10.0").update_available());
+        assert!(!status("0.5.5", "v0.5.5").update_available());
+        assert!(!status("0.6.0", "v0.5.9").update_available());
+        // A prerelease of the running version is older than it
+        assert!(!status("0.6.0", "v0.6.0-rc.1").update_available());
+        assert!(status("0.6.0-rc.1", "v0.6.0").update_available());
+    }
+
+    #[test]
+    fn test_cached_answer_expires_after_a_day() {
+        let now = Utc::now();
+        let cache = |age: Duration| UpdateCache {
+            checked_at: now - age,
+            latest_tag: "v0.6.0".to_string(),
+        };
+
+        assert!(cache(Duration::hours(1)).is_fresh(now));
+        assert!(!cache(Duration::hours(25)).is_fresh(now));
+        // A check "from the future" after a clock change is not trusted
+        assert!(!cache(Duration::hours(-1)).is_fresh(now));
+    }
+}
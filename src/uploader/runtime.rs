@@ -0,0 +1,196 @@
+//! Where the upload loop runs.
+//!
+//! Threading model: the agent's main runtime runs the watchers, the
+//! processing loop and Skyline extraction. Report parsing and metric
+//! computation run synchronously on its workers, so a burst of extractions
+//! can hold them long enough to delay every other task's timers and I/O. The
+//! uploader therefore gets its own multi-threaded runtime (with its own timer
+//! and I/O drivers) on dedicated threads; the two sides only share the spool
+//! directory. Setting `cloud.upload_threads = 0` runs the uploader on the
+//! shared runtime instead.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use super::Uploader;
+
+/// How long in-flight uploads get to finish once the runtime is stopped.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A running upload loop.
+pub struct UploadRuntime {
+    inner: Inner,
+}
+
+enum Inner {
+    /// Task on the agent's runtime
+    Shared(tokio::task::JoinHandle<()>),
+    /// Separate runtime on its own thread
+    Dedicated {
+        stop: oneshot::Sender<()>,
+        thread: std::thread::JoinHandle<()>,
+    },
+}
+
+impl UploadRuntime {
+    /// Start the upload loop on a runtime with `threads` workers, or on the
+    /// current runtime if `threads` is 0.
+    pub fn start(uploader: Uploader, threads: usize) -> Result<Self> {
+        Self::spawn(threads, async move { uploader.run().await })
+    }
+
+    fn spawn<F>(threads: usize, task: F) -> Result<Self>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if threads == 0 {
+            info!("Uploader running on the shared runtime");
+            return Ok(Self {
+                inner: Inner::Shared(tokio::spawn(task)),
+            });
+        }
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        // The runtime is built and dropped on its own thread; dropping a
+        // runtime from inside another one panics.
+        let thread = std::thread::Builder::new()
+            .name("mdqc-upload".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(threads)
+                    .thread_name("mdqc-upload-worker")
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => {
+                        let _ = ready_tx.send(Ok(()));
+                        runtime
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+
+                runtime.block_on(async {
+                    tokio::select! {
+                        _ = task => {}
+                        _ = stop_rx => {}
+                    }
+                });
+                runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+            })
+            .context("Failed to start uploader thread")?;
+
+        ready_rx
+            .recv()
+            .context("Uploader thread exited during startup")?
+            .context("Failed to build uploader runtime")?;
+
+        info!(threads, "Uploader running on a dedicated runtime");
+        Ok(Self {
+            inner: Inner::Dedicated {
+                stop: stop_tx,
+                thread,
+            },
+        })
+    }
+
+    /// Stop the upload loop. An interrupted upload is picked up again from
+    /// the spool on the next start.
+    pub async fn stop(self) {
+        match self.inner {
+            Inner::Shared(handle) => handle.abort(),
+            Inner::Dedicated { stop, thread } => {
+                let _ = stop.send(());
+                let joined = tokio::task::spawn_blocking(move || thread.join()).await;
+                if !matches!(joined, Ok(Ok(()))) {
+                    warn!("Uploader thread did not shut down cleanly");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CloudConfig, SpoolConfig};
+    use crate::spool::Spool;
+
+    /// Start an upload loop on `threads` workers that POSTs once to a local
+    /// server and reports on the returned channel when the response is in.
+    fn start_upload(threads: usize) -> (UploadRuntime, std::sync::mpsc::Receiver<()>) {
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                super::super::tests::read_request(&mut stream);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+                );
+            }
+        });
+
+        let config = CloudConfig {
+            endpoint: format!("http://127.0.0.1:{}/", port),
+            ..CloudConfig::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::in_dir(&SpoolConfig::default(), dir.path()).unwrap();
+        let uploader = Uploader::new(&config, spool).unwrap();
+
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let upload = UploadRuntime::spawn(threads, async move {
+            let _dir = dir;
+            let url = format!("{}ingest", uploader.config.endpoint);
+            let response = uploader
+                .send_authorized(|| uploader.client.post(&url).body("{}"))
+                .await
+                .unwrap();
+            assert!(response.status().is_success());
+            let _ = done_tx.send(());
+        })
+        .unwrap();
+
+        (upload, done_rx)
+    }
+
+    #[tokio::test]
+    async fn test_dedicated_runtime_uploads_while_shared_one_is_blocked() {
+        let (upload, done) = start_upload(1);
+
+        // Extraction-like work holding the test runtime's only worker without
+        // yielding; the upload completes regardless on its own runtime
+        done.recv_timeout(Duration::from_secs(30))
+            .expect("upload waited on the blocked runtime");
+
+        upload.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_shared_runtime_upload_runs_once_the_worker_yields() {
+        let (upload, done) = start_upload(0);
+
+        // Nothing has run yet: the only worker is still on this task
+        assert!(done.try_recv().is_err());
+
+        loop {
+            match done.try_recv() {
+                Ok(()) => break,
+                Err(std::sync::mpsc::TryRecvError::Empty) => tokio::task::yield_now().await,
+                Err(e) => panic!("upload failed: {}", e),
+            }
+        }
+
+        upload.stop().await;
+    }
+}
@@ -0,0 +1,108 @@
+//! OAuth2 client-credentials tokens for Bearer auth.
+//!
+//! Tokens are fetched from `cloud.token_url`, cached, and refreshed shortly
+//! before they expire or when the server rejects them.
+
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use crate::error::UploadError;
+
+/// Refresh this long before the server-reported expiry.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Lifetime assumed when the token response has no `expires_in`.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    access_token: String,
+    refresh_at: Instant,
+}
+
+/// Fetches and caches client-credentials tokens.
+#[derive(Debug, Clone)]
+pub struct TokenSource {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    cached: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl TokenSource {
+    pub fn new(token_url: String, client_id: String, client_secret: String) -> Self {
+        Self {
+            token_url,
+            client_id,
+            client_secret,
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// A valid access token, fetching a new one if the cached one is
+    /// missing or about to expire.
+    pub async fn token(&self, client: &reqwest::Client) -> Result<String, UploadError> {
+        let mut cached = self.cached.lock().await;
+
+        if let Some(ref token) = *cached {
+            if Instant::now() < token.refresh_at {
+                return Ok(token.access_token.clone());
+            }
+            debug!("Access token expiring, refreshing");
+        }
+
+        let token = self.fetch(client).await?;
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// Drop the cached token so the next call fetches a new one.
+    pub async fn invalidate(&self) {
+        *self.cached.lock().await = None;
+    }
+
+    async fn fetch(&self, client: &reqwest::Client) -> Result<CachedToken, UploadError> {
+        let response = client
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+            ])
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(UploadError::Authentication(format!(
+                "token endpoint returned status {}: {}",
+                status.as_u16(),
+                body
+            )));
+        }
+
+        let token: TokenResponse = response.json().await?;
+        let lifetime = token
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LIFETIME);
+
+        info!(expires_in_secs = lifetime.as_secs(), "Fetched access token");
+
+        Ok(CachedToken {
+            access_token: token.access_token,
+            refresh_at: Instant::now() + lifetime.saturating_sub(REFRESH_MARGIN),
+        })
+    }
+}
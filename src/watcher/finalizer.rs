@@ -5,7 +5,151 @@
 
 #![allow(dead_code)]
 
-use crate::types::FinalizationState;
+use chrono::{DateTime, Duration, Utc};
+use std::path::PathBuf;
+
+use crate::types::{FinalizationState, TrackedFile};
+
+/// Consecutive checks without an mtime before switching to size-only stability.
+pub const MTIME_UNAVAILABLE_THRESHOLD: u32 = 3;
+
+/// Consecutive unchanged-size checks required before a size-only file is ready.
+pub const SIZE_ONLY_STABLE_CHECKS: u32 = 6;
+
+/// A gap between checks longer than this many check intervals is taken as a
+/// clock jump (suspend, manual or NTP time change), not time passing.
+pub const CLOCK_JUMP_INTERVALS: i32 = 12;
+
+/// A file's modification time as seen by one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mtime {
+    Known(DateTime<Utc>),
+    /// The filesystem has no mtime for the file (`modified()` failed)
+    Unsupported,
+    /// Not read this check: the file is missing, locked or unreadable
+    Unread,
+}
+
+impl Mtime {
+    /// The modification time, when there is one.
+    pub fn known(self) -> Option<DateTime<Utc>> {
+        match self {
+            Mtime::Known(modified) => Some(modified),
+            Mtime::Unsupported | Mtime::Unread => None,
+        }
+    }
+}
+
+/// Result of one stabilization check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StabilizeOutcome {
+    /// Size or mtime changed; the stability window restarts
+    Changed,
+    /// Unchanged, but not yet ready
+    Stable,
+    /// Stable for long enough and complete; state is now `Ready`
+    Ready,
+    /// An abort marker (the given file) was found; the run won't complete
+    Aborted(PathBuf),
+}
+
+/// Advance a `Stabilizing` file by one check using the observed state.
+///
+/// Normally a file is stable when both size and mtime are unchanged. If the
+/// filesystem repeatedly can't report an mtime, comparing it is meaningless,
+/// so the file switches to size-only stability and additionally needs
+/// `SIZE_ONLY_STABLE_CHECKS` unchanged checks before it becomes ready. A
+/// check that couldn't read the file at all (`Mtime::Unread`) doesn't count
+/// towards that.
+pub fn advance_stabilizing(
+    file: &mut TrackedFile,
+    size: u64,
+    modified: Mtime,
+    is_complete: bool,
+    now: DateTime<Utc>,
+    stability_window: Duration,
+) -> StabilizeOutcome {
+    match modified {
+        Mtime::Known(_) => file.mtime_missing_checks = 0,
+        Mtime::Unsupported => {
+            file.mtime_missing_checks += 1;
+            if file.mtime_missing_checks >= MTIME_UNAVAILABLE_THRESHOLD {
+                file.size_only_stability = true;
+            }
+        }
+        Mtime::Unread => {}
+    }
+    let modified = modified.known();
+
+    let unchanged = if file.size_only_stability {
+        size == file.last_size
+    } else {
+        // Without an mtime we can't tell whether the file changed; be conservative
+        size == file.last_size && modified.is_some() && modified == file.last_modified
+    };
+
+    if !unchanged {
+        // File changed, reset stability
+        file.last_size = size;
+        file.last_modified = modified;
+        file.stable_since = None;
+        file.unchanged_checks = 0;
+        return StabilizeOutcome::Changed;
+    }
+
+    file.unchanged_checks += 1;
+    let stable_since = *file.stable_since.get_or_insert(now);
+
+    let enough_checks =
+        !file.size_only_stability || file.unchanged_checks >= SIZE_ONLY_STABLE_CHECKS;
+
+    if now - stable_since >= stability_window && is_complete && enough_checks {
+        file.state = FinalizationState::Ready;
+        StabilizeOutcome::Ready
+    } else {
+        StabilizeOutcome::Stable
+    }
+}
+
+/// Keep a file's timers sane across a wall-clock jump.
+///
+/// Stability and timeouts are measured with `Utc::now()`, so a backward time
+/// change makes the gap since the last check negative, and waking from
+/// suspend makes it hours long. Either way the gap is counted as one normal
+/// `check_interval`: `first_seen` and `stable_since` are shifted by the
+/// difference. Returns the jump, if one was absorbed.
+pub fn absorb_clock_jump(
+    file: &mut TrackedFile,
+    now: DateTime<Utc>,
+    check_interval: Duration,
+) -> Option<Duration> {
+    let last_checked = file.last_checked.replace(now)?;
+    let gap = now - last_checked;
+    if gap >= Duration::zero() && gap <= check_interval * CLOCK_JUMP_INTERVALS {
+        return None;
+    }
+
+    let jump = gap - check_interval;
+    file.first_seen += jump;
+    if let Some(stable_since) = file.stable_since.as_mut() {
+        *stable_since += jump;
+    }
+    Some(jump)
+}
+
+/// Record one lock-file observation for a file whose vendor uses lock files.
+///
+/// Returns true once the lock has been absent for `required_checks`
+/// consecutive observations; a reappearing lock restarts the count.
+pub fn confirm_lock_free(file: &mut TrackedFile, lock_present: bool, required_checks: u32) -> bool {
+    if lock_present {
+        file.lock_free_checks = 0;
+        return false;
+    }
+
+    file.lock_free_checks += 1;
+    file.lock_free_checks >= required_checks
+}
 
 /// Finalizer tracks a single file through the finalization process.
 #[derive(Debug)]
@@ -41,3 +185,121 @@ impl Default for Finalizer {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Vendor;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_size_only_fallback_when_mtime_unavailable() {
+        let mut file = TrackedFile::new(PathBuf::from("run.raw"), Vendor::Thermo, 1024, None);
+        file.state = FinalizationState::Stabilizing;
+
+        let window = Duration::seconds(10);
+        let start = Utc::now();
+        let mut outcome = StabilizeOutcome::Changed;
+
+        // Checks every 5s, mtime always errors, size stable
+        for i in 0..20 {
+            let now = start + Duration::seconds(5 * i);
+            outcome = advance_stabilizing(&mut file, 1024, Mtime::Unsupported, true, now, window);
+            if outcome == StabilizeOutcome::Ready {
+                break;
+            }
+        }
+
+        assert!(file.size_only_stability);
+        assert_eq!(outcome, StabilizeOutcome::Ready);
+        assert_eq!(file.state, FinalizationState::Ready);
+    }
+
+    #[test]
+    fn test_unreadable_checks_keep_mtime_stability() {
+        let mut file = TrackedFile::new(PathBuf::from("run.d"), Vendor::Bruker, 1024, None);
+        file.state = FinalizationState::Stabilizing;
+        let now = Utc::now();
+
+        // A lock file or a missing analysis file isn't a missing mtime
+        for i in 0..10 {
+            let now = now + Duration::seconds(5 * i);
+            advance_stabilizing(&mut file, 0, Mtime::Unread, false, now, Duration::zero());
+        }
+        assert_eq!(file.mtime_missing_checks, 0);
+        assert!(!file.size_only_stability);
+    }
+
+    #[test]
+    fn test_clock_jumps_count_as_one_interval() {
+        let mut file = TrackedFile::new(PathBuf::from("run.raw"), Vendor::Thermo, 1024, None);
+        file.state = FinalizationState::Stabilizing;
+        let modified = Utc::now();
+        file.last_modified = Some(modified);
+
+        let interval = Duration::seconds(5);
+        let window = Duration::seconds(60);
+        let start = Utc::now();
+        file.first_seen = start;
+
+        let check = |file: &mut TrackedFile, now| {
+            let jump = absorb_clock_jump(file, now, interval);
+            (
+                jump,
+                advance_stabilizing(file, 1024, Mtime::Known(modified), true, now, window),
+            )
+        };
+
+        // Stable for 20s of normal checks
+        for i in 0..5 {
+            let (jump, outcome) = check(&mut file, start + interval * i);
+            assert_eq!(jump, None);
+            assert_eq!(outcome, StabilizeOutcome::Stable);
+        }
+        let stable_since = file.stable_since.unwrap();
+
+        // Clock set back an hour: still 25s stable, not negative
+        let back = start + interval * 5 - Duration::hours(1);
+        let (jump, outcome) = check(&mut file, back);
+        assert_eq!(jump, Some(-Duration::hours(1)));
+        assert_eq!(outcome, StabilizeOutcome::Stable);
+        assert_eq!(back - file.stable_since.unwrap(), Duration::seconds(25));
+        assert_eq!(back - file.first_seen, Duration::seconds(25));
+
+        // Waking from a 3h suspend: 30s stable, not ready yet
+        let wake = back + Duration::hours(3);
+        let (jump, outcome) = check(&mut file, wake);
+        assert_eq!(jump, Some(Duration::hours(3) - interval));
+        assert_eq!(outcome, StabilizeOutcome::Stable);
+        assert_eq!(wake - file.stable_since.unwrap(), Duration::seconds(30));
+        assert!(file.stable_since.unwrap() != stable_since);
+
+        // Normal checks resume and the window completes on schedule
+        let mut outcome = StabilizeOutcome::Stable;
+        let mut checks = 0;
+        while outcome != StabilizeOutcome::Ready {
+            checks += 1;
+            outcome = check(&mut file, wake + interval * checks).1;
+        }
+        assert_eq!(checks, 6);
+    }
+
+    #[test]
+    fn test_size_change_resets_size_only_stability() {
+        let mut file = TrackedFile::new(PathBuf::from("run.raw"), Vendor::Thermo, 1024, None);
+        file.size_only_stability = true;
+        let now = Utc::now();
+
+        let outcome = advance_stabilizing(
+            &mut file,
+            2048,
+            Mtime::Unsupported,
+            true,
+            now,
+            Duration::zero(),
+        );
+        assert_eq!(outcome, StabilizeOutcome::Changed);
+        assert_eq!(file.unchanged_checks, 0);
+        assert!(file.stable_since.is_none());
+    }
+}
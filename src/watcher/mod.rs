@@ -18,11 +18,22 @@ use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::config::{InstrumentConfig, WatcherConfig};
+use crate::config::{paths, InstrumentConfig, WatcherConfig};
 use crate::failed_files::FailedFiles;
 use crate::types::{FinalizationState, TrackedFile, Vendor};
 
 mod finalizer;
+pub mod watchdog;
+
+pub(crate) use finalizer::Mtime;
+
+use watchdog::{Heartbeat, LoopRestarts, RestartBackoff};
+
+/// How long `stop` waits for the event watcher thread to exit.
+const STOP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often the event watcher thread checks whether it should stop.
+const EVENT_THREAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
 
 /// File watcher for a single instrument.
 pub struct Watcher {
@@ -36,18 +47,33 @@ pub struct Watcher {
     is_network_path: bool,
     /// Whether to show toast notifications
     enable_notifications: bool,
+    /// Filesystem event watcher thread (owns the `notify` handle)
+    event_thread: Mutex<Option<EventThread>>,
+    /// Scan and finalization loop tasks
+    tasks: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+    /// Handle for aborting the current scan loop when restarting it
+    scan_abort: Mutex<Option<tokio::task::AbortHandle>>,
+    event_heartbeat: Heartbeat,
+    scan_heartbeat: Heartbeat,
+    /// Loops restarted by the watchdog since startup
+    restarts: Mutex<LoopRestarts>,
+    event_backoff: Mutex<RestartBackoff>,
+    scan_backoff: Mutex<RestartBackoff>,
+    /// Minimum heartbeat age before a loop counts as stalled
+    min_stall: std::time::Duration,
 }
 
 impl Watcher {
     /// Create a new watcher for an instrument.
     pub fn new(
         instrument: InstrumentConfig,
-        config: WatcherConfig,
+        mut config: WatcherConfig,
         ready_tx: mpsc::Sender<TrackedFile>,
         enable_notifications: bool,
     ) -> Result<Self> {
         let watch_path = PathBuf::from(&instrument.watch_path);
-        let is_network_path = Self::detect_network_path(&watch_path);
+        let drive_kind = DriveKind::of(&watch_path);
+        let is_network_path = drive_kind == DriveKind::Network;
 
         if is_network_path {
             warn!(
@@ -56,6 +82,22 @@ impl Watcher {
                 "Network path detected - using polling-only mode (filesystem events unreliable on SMB/CIFS)"
             );
         }
+        if drive_kind == DriveKind::Removable {
+            warn!(
+                instrument = %instrument.id,
+                path = %watch_path.display(),
+                "Watch path is on a removable drive - processing pauses if it is unplugged"
+            );
+        }
+
+        // Resolve the default now so the finalization loop sees a plain flag
+        let skip_exclusive_open = *config.skip_exclusive_open.get_or_insert(is_network_path);
+        if skip_exclusive_open {
+            info!(
+                instrument = %instrument.id,
+                "Skipping exclusive-open test; finalizing on size/mtime stability and vendor checks"
+            );
+        }
 
         Ok(Self {
             instrument,
@@ -66,47 +108,18 @@ impl Watcher {
             running: Arc::new(Mutex::new(false)),
             is_network_path,
             enable_notifications,
+            event_thread: Mutex::new(None),
+            tasks: Mutex::new(Vec::new()),
+            scan_abort: Mutex::new(None),
+            event_heartbeat: Heartbeat::new(),
+            scan_heartbeat: Heartbeat::new(),
+            restarts: Mutex::new(LoopRestarts::default()),
+            event_backoff: Mutex::new(RestartBackoff::new()),
+            scan_backoff: Mutex::new(RestartBackoff::new()),
+            min_stall: watchdog::MIN_STALL_THRESHOLD,
         })
     }
 
-    /// Detect if a path is a network share.
-    fn detect_network_path(path: &Path) -> bool {
-        // Check for UNC path (\\server\share)
-        let path_str = path.to_string_lossy();
-        if path_str.starts_with(r"\\") {
-            return true;
-        }
-
-        // On Windows, check drive type
-        #[cfg(windows)]
-        {
-            use std::os::windows::ffi::OsStrExt;
-
-            if let Some(prefix) = path.components().next() {
-                let prefix_str = prefix.as_os_str().to_string_lossy();
-                if prefix_str.len() >= 2 && prefix_str.chars().nth(1) == Some(':') {
-                    let drive = format!("{}\\", &prefix_str[..2]);
-                    let drive_wide: Vec<u16> = std::ffi::OsStr::new(&drive)
-                        .encode_wide()
-                        .chain(std::iter::once(0))
-                        .collect();
-
-                    // GetDriveTypeW returns DRIVE_REMOTE (4) for network drives
-                    let drive_type = unsafe {
-                        windows_sys::Win32::Storage::FileSystem::GetDriveTypeW(drive_wide.as_ptr())
-                    };
-
-                    // DRIVE_REMOTE = 4
-                    if drive_type == 4 {
-                        return true;
-                    }
-                }
-            }
-        }
-
-        false
-    }
-
     /// Start watching for files.
     pub fn start(&self) -> Result<()> {
         let watch_path = PathBuf::from(&self.instrument.watch_path);
@@ -125,34 +138,8 @@ impl Watcher {
         *self.running.lock().unwrap() = true;
 
         // Start filesystem event watcher if enabled and not a network path
-        if self.config.use_filesystem_events && !self.is_network_path {
-            let tracked_files = Arc::clone(&self.tracked_files);
-            let processed_files = Arc::clone(&self.processed_files);
-            let watch_path_clone = watch_path.clone();
-            let vendor = self.instrument.vendor;
-            let instrument_id = self.instrument.id.clone();
-            let running = Arc::clone(&self.running);
-            let enable_notifications = self.enable_notifications;
-            let stability_window = self.config.stability_window_seconds;
-
-            std::thread::spawn(move || {
-                if let Err(e) = run_event_watcher(
-                    tracked_files,
-                    processed_files,
-                    watch_path_clone,
-                    vendor,
-                    instrument_id.clone(),
-                    running,
-                    enable_notifications,
-                    stability_window,
-                ) {
-                    error!(
-                        instrument = %instrument_id,
-                        error = %e,
-                        "Event watcher failed, falling back to polling only"
-                    );
-                }
-            });
+        if self.uses_events() {
+            self.spawn_event_thread();
         }
 
         // Start the finalization loop
@@ -160,61 +147,251 @@ impl Watcher {
         let processed_files = Arc::clone(&self.processed_files);
         let ready_tx = self.ready_tx.clone();
         let config = self.config.clone();
+        let completion_sentinel = self.instrument.completion_sentinel.clone();
         let instrument_id = self.instrument.id.clone();
         let running = Arc::clone(&self.running);
         let failed_files = FailedFiles::new();
 
-        tokio::spawn(async move {
+        let finalization_task = tokio::spawn(async move {
             run_finalization_loop(
                 tracked_files,
                 processed_files,
                 ready_tx,
                 config,
+                completion_sentinel,
                 instrument_id,
                 running,
                 failed_files,
             )
             .await
         });
+        self.tasks.lock().unwrap().push(finalization_task);
 
         // Start the scan loop (always runs as fallback/supplement)
+        self.spawn_scan_loop();
+
+        Ok(())
+    }
+
+    /// Whether this instrument uses filesystem events as well as scanning.
+    fn uses_events(&self) -> bool {
+        self.config.use_filesystem_events && !self.is_network_path
+    }
+
+    /// Spawn the filesystem event watcher thread, telling any previous one to
+    /// exit and returning its handle.
+    ///
+    /// A previous thread that is wedged can't be killed; it exits once it
+    /// next checks its stop flag. Events it still delivers until then are
+    /// deduplicated against tracked files.
+    fn spawn_event_thread(&self) -> Option<std::thread::JoinHandle<()>> {
+        let tracked_files = Arc::clone(&self.tracked_files);
+        let processed_files = Arc::clone(&self.processed_files);
+        let watch_path = PathBuf::from(&self.instrument.watch_path);
+        let vendor = self.instrument.vendor;
+        let allow_zipped = self.instrument.allow_zipped;
+        let instrument_id = self.instrument.id.clone();
+        // Each thread gets its own flag so a replaced one can be stopped alone
+        let running = Arc::new(Mutex::new(true));
+        let thread_running = Arc::clone(&running);
+        let enable_notifications = self.enable_notifications;
+        let stability_window = self.config.stability_window(self.instrument.vendor);
+        let quarantine_dir = self.quarantine_dir();
+        let heartbeat = self.event_heartbeat.clone();
+        heartbeat.beat();
+
+        let handle = std::thread::spawn(move || {
+            if let Err(e) = run_event_watcher(
+                tracked_files,
+                processed_files,
+                watch_path,
+                quarantine_dir,
+                vendor,
+                allow_zipped,
+                instrument_id.clone(),
+                thread_running,
+                enable_notifications,
+                stability_window,
+                heartbeat,
+            ) {
+                error!(
+                    instrument = %instrument_id,
+                    error = %e,
+                    "Event watcher failed, falling back to polling only"
+                );
+            }
+        });
+        self.event_thread
+            .lock()
+            .unwrap()
+            .replace(EventThread { handle, running })
+            .map(EventThread::signal_stop)
+    }
+
+    /// Spawn the directory scan loop, aborting any previous one.
+    fn spawn_scan_loop(&self) {
         let tracked_files = Arc::clone(&self.tracked_files);
         let processed_files = Arc::clone(&self.processed_files);
-        let watch_path_clone = watch_path.clone();
+        let watch_path = PathBuf::from(&self.instrument.watch_path);
         let file_pattern = self.instrument.file_pattern.clone();
         let vendor = self.instrument.vendor;
+        let allow_zipped = self.instrument.allow_zipped;
         let scan_interval = self.config.scan_interval_seconds;
-        let stability_window = self.config.stability_window_seconds;
+        let stability_window = self.config.stability_window(self.instrument.vendor);
         let instrument_id = self.instrument.id.clone();
         let running = Arc::clone(&self.running);
         let enable_notifications = self.enable_notifications;
+        let quarantine_dir = self.quarantine_dir();
+        let heartbeat = self.scan_heartbeat.clone();
+        heartbeat.beat();
 
-        tokio::spawn(async move {
+        let scan_task = tokio::spawn(async move {
             run_scan_loop(
                 tracked_files,
                 processed_files,
-                watch_path_clone,
+                watch_path,
+                quarantine_dir,
                 file_pattern,
                 vendor,
+                allow_zipped,
                 scan_interval,
                 stability_window,
                 instrument_id,
                 running,
                 enable_notifications,
+                heartbeat,
             )
             .await
         });
 
-        Ok(())
+        if let Some(previous) = self
+            .scan_abort
+            .lock()
+            .unwrap()
+            .replace(scan_task.abort_handle())
+        {
+            previous.abort();
+        }
+
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(scan_task);
     }
 
-    /// Stop watching.
-    pub fn stop(&self) -> Result<()> {
+    /// Restart the event or scan loop if its heartbeat has gone stale.
+    ///
+    /// A loop that keeps stalling is restarted with exponential backoff.
+    /// Returns the number of loops restarted.
+    pub fn restart_stalled_loops(&self) -> usize {
+        if !*self.running.lock().unwrap() {
+            return 0;
+        }
+
+        let mut restarted = 0;
+
+        if self.uses_events() {
+            let mut backoff = self.event_backoff.lock().unwrap();
+            if !self
+                .event_heartbeat
+                .is_stalled(EVENT_THREAD_POLL_INTERVAL, self.min_stall)
+            {
+                backoff.healthy();
+            } else if backoff.try_restart() {
+                warn!(
+                    instrument = %self.instrument.id,
+                    stalled_secs = self.event_heartbeat.elapsed().as_secs(),
+                    "Event watcher stopped responding, restarting it"
+                );
+                // A wedged thread can't be joined here; it exits once unblocked
+                drop(self.spawn_event_thread());
+                self.restarts.lock().unwrap().event += 1;
+                restarted += 1;
+            }
+        }
+
+        let scan_interval = std::time::Duration::from_secs(self.config.scan_interval_seconds);
+        let mut backoff = self.scan_backoff.lock().unwrap();
+        if !self
+            .scan_heartbeat
+            .is_stalled(scan_interval, self.min_stall)
+        {
+            backoff.healthy();
+        } else if backoff.try_restart() {
+            warn!(
+                instrument = %self.instrument.id,
+                stalled_secs = self.scan_heartbeat.elapsed().as_secs(),
+                "Scan loop stopped responding, restarting it"
+            );
+            self.spawn_scan_loop();
+            self.restarts.lock().unwrap().scan += 1;
+            restarted += 1;
+        }
+
+        if restarted > 0 {
+            self.restarts.lock().unwrap().last_restart = Some(Utc::now());
+        }
+
+        restarted
+    }
+
+    /// Loops restarted by the watchdog since startup.
+    pub fn restarts(&self) -> LoopRestarts {
+        self.restarts.lock().unwrap().clone()
+    }
+
+    /// The instrument this watcher serves.
+    pub fn instrument_id(&self) -> &str {
+        &self.instrument.id
+    }
+
+    /// Stop watching and wait for spawned threads and tasks to exit.
+    ///
+    /// The event watcher thread drops its `notify` handle on exit, releasing
+    /// the OS watch handles, so watchers can be restarted cleanly.
+    pub async fn stop(&self) -> Result<()> {
         info!(instrument = %self.instrument.id, "Stopping watcher");
         *self.running.lock().unwrap() = false;
+
+        // The loops only check `running` between ticks; abort rather than wait out the interval
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in tasks {
+            task.abort();
+            let _ = task.await;
+        }
+
+        let event_thread = self.event_thread.lock().unwrap().take();
+        if let Some(handle) = event_thread.map(EventThread::signal_stop) {
+            let joined =
+                tokio::time::timeout(STOP_TIMEOUT, tokio::task::spawn_blocking(|| handle.join()))
+                    .await;
+
+            if joined.is_err() {
+                warn!(
+                    instrument = %self.instrument.id,
+                    "Event watcher thread did not exit within timeout"
+                );
+            }
+        }
+
         Ok(())
     }
 
+    /// The instrument's quarantine directory, whose contents are never tracked.
+    fn quarantine_dir(&self) -> Option<PathBuf> {
+        self.instrument.quarantine_dir.as_ref().map(PathBuf::from)
+    }
+
+    /// Track a file restored from the ready queue as already handed off, so
+    /// scans don't queue it a second time.
+    pub fn track_restored(&self, file: &TrackedFile) {
+        let mut file = file.clone();
+        file.state = FinalizationState::Processing;
+        self.tracked_files
+            .lock()
+            .unwrap()
+            .insert(file.path.clone(), file);
+    }
+
     /// Mark a file as done (called after successful processing).
     pub fn mark_done(&self, path: &Path) {
         let mut tracked = self.tracked_files.lock().unwrap();
@@ -234,17 +411,108 @@ impl Watcher {
     }
 }
 
+/// `GetDriveTypeW` result for removable media (USB sticks, card readers)
+const DRIVE_REMOVABLE: u32 = 2;
+
+/// `GetDriveTypeW` result for mapped network drives
+const DRIVE_REMOTE: u32 = 4;
+
+/// Kind of storage a watch path lives on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DriveKind {
+    Local,
+    Network,
+    Removable,
+}
+
+impl DriveKind {
+    /// Classify the drive `path` is on.
+    pub(crate) fn of(path: &Path) -> Self {
+        Self::classify(path, drive_type)
+    }
+
+    /// Classify from the path's form, looking up the type of its drive
+    /// letter with `drive_type` (a `GetDriveTypeW` code).
+    fn classify(path: &Path, drive_type: impl Fn(&str) -> u32) -> Self {
+        // UNC path (\\server\share)
+        if path.to_string_lossy().starts_with(r"\\") {
+            return Self::Network;
+        }
+
+        match drive_root(path).as_deref().map(drive_type) {
+            Some(DRIVE_REMOTE) => Self::Network,
+            Some(DRIVE_REMOVABLE) => Self::Removable,
+            _ => Self::Local,
+        }
+    }
+}
+
+/// Root of the drive letter `path` starts with (e.g. `E:\`).
+fn drive_root(path: &Path) -> Option<String> {
+    let path_str = path.to_string_lossy();
+    let mut chars = path_str.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => {
+            Some(format!("{}:\\", letter.to_ascii_uppercase()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn drive_type(root: &str) -> u32 {
+    use std::os::windows::ffi::OsStrExt;
+
+    let root_wide: Vec<u16> = std::ffi::OsStr::new(root)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    unsafe { windows_sys::Win32::Storage::FileSystem::GetDriveTypeW(root_wide.as_ptr()) }
+}
+
+#[cfg(not(windows))]
+fn drive_type(_root: &str) -> u32 {
+    // DRIVE_UNKNOWN
+    0
+}
+
+/// Whether the drive holding `path` is still there. For paths without a
+/// drive letter (UNC shares, Unix mounts) this is whether `path` exists.
+pub(crate) fn drive_present(path: &Path) -> bool {
+    match drive_root(path) {
+        Some(root) if cfg!(windows) => Path::new(&root).exists(),
+        _ => path.exists(),
+    }
+}
+
+/// A running filesystem event watcher thread and its stop flag.
+struct EventThread {
+    handle: std::thread::JoinHandle<()>,
+    running: Arc<Mutex<bool>>,
+}
+
+impl EventThread {
+    /// Tell the thread to exit, returning its handle to join.
+    fn signal_stop(self) -> std::thread::JoinHandle<()> {
+        *self.running.lock().unwrap() = false;
+        self.handle
+    }
+}
+
 /// Run filesystem event watcher using notify crate.
 #[allow(clippy::too_many_arguments)]
 fn run_event_watcher(
     tracked_files: Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
     processed_files: Arc<Mutex<std::collections::HashSet<PathBuf>>>,
     watch_path: PathBuf,
+    quarantine_dir: Option<PathBuf>,
     vendor: Vendor,
+    allow_zipped: bool,
     instrument_id: String,
     running: Arc<Mutex<bool>>,
     enable_notifications: bool,
     stability_window_secs: u64,
+    heartbeat: Heartbeat,
 ) -> Result<()> {
     let tracked_files_clone = Arc::clone(&tracked_files);
     let processed_files_clone = Arc::clone(&processed_files);
@@ -266,7 +534,12 @@ fn run_event_watcher(
 
                     for path in event.paths {
                         // Check if it's a valid raw file
-                        if !is_valid_raw_file(&path, vendor) {
+                        if !is_watched_run(&path, vendor, allow_zipped) {
+                            continue;
+                        }
+
+                        // Never pick up quarantined files
+                        if is_quarantined(&path, quarantine_dir.as_deref()) {
                             continue;
                         }
 
@@ -285,27 +558,23 @@ fn run_event_watcher(
                         }
 
                         // Get file metadata
-                        let metadata = match std::fs::metadata(&path) {
+                        let metadata = match std::fs::metadata(paths::long_path(&path)) {
                             Ok(m) => m,
                             Err(_) => continue,
                         };
 
                         let size = metadata.len();
-                        let modified: DateTime<Utc> = metadata
-                            .modified()
-                            .map(|t| t.into())
-                            .unwrap_or_else(|_| Utc::now());
-
-                        // Start tracking
-                        let tracked_file = TrackedFile {
-                            path: path.clone(),
-                            state: FinalizationState::Detected,
-                            first_seen: Utc::now(),
-                            last_size: size,
-                            last_modified: modified,
-                            stable_since: None,
+
+                        // Start tracking, unless the scan loop got there first
+                        let tracked_file = TrackedFile::new(
+                            path.clone(),
                             vendor,
-                        };
+                            size,
+                            modified_time(&metadata).known(),
+                        );
+                        if !track_detected(&tracked_files_clone, tracked_file) {
+                            continue;
+                        }
 
                         let file_name = path
                             .file_name()
@@ -328,11 +597,6 @@ fn run_event_watcher(
                                 stability_window_secs,
                             );
                         }
-
-                        tracked_files_clone
-                            .lock()
-                            .unwrap()
-                            .insert(path, tracked_file);
                     }
                 }
                 Err(e) => {
@@ -357,25 +621,48 @@ fn run_event_watcher(
 
     // Keep the watcher alive until stopped
     while *running.lock().unwrap() {
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        heartbeat.beat();
+        std::thread::sleep(EVENT_THREAD_POLL_INTERVAL);
     }
 
+    // Release the OS watch handles before the thread exits
+    drop(watcher);
+
     Ok(())
 }
 
+/// Start tracking a newly detected file.
+///
+/// The event watcher and the scan loop both detect files, and either may
+/// have added the path since its own `contains_key` check. The entry is only
+/// created if still absent, so a file that is already stabilizing (or being
+/// processed) keeps its state. Returns whether this call added it.
+fn track_detected(tracked_files: &Mutex<HashMap<PathBuf, TrackedFile>>, file: TrackedFile) -> bool {
+    match tracked_files.lock().unwrap().entry(file.path.clone()) {
+        std::collections::hash_map::Entry::Occupied(_) => false,
+        std::collections::hash_map::Entry::Vacant(slot) => {
+            slot.insert(file);
+            true
+        }
+    }
+}
+
 /// Run the periodic directory scan loop.
 #[allow(clippy::too_many_arguments)]
 async fn run_scan_loop(
     tracked_files: Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
     processed_files: Arc<Mutex<std::collections::HashSet<PathBuf>>>,
     watch_path: PathBuf,
+    quarantine_dir: Option<PathBuf>,
     file_pattern: String,
     vendor: Vendor,
+    allow_zipped: bool,
     scan_interval_secs: u64,
     stability_window_secs: u64,
     instrument_id: String,
     running: Arc<Mutex<bool>>,
     enable_notifications: bool,
+    heartbeat: Heartbeat,
 ) {
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(scan_interval_secs));
 
@@ -386,6 +673,8 @@ async fn run_scan_loop(
             break;
         }
 
+        heartbeat.beat();
+
         trace!(instrument = %instrument_id, "Scanning directory");
 
         // Scan for files matching the pattern
@@ -419,12 +708,17 @@ async fn run_scan_loop(
             }
 
             // Check if this is a valid raw file for the vendor
-            if !is_valid_raw_file(&entry, vendor) {
+            if !is_watched_run(&entry, vendor, allow_zipped) {
+                continue;
+            }
+
+            // Never pick up quarantined files
+            if is_quarantined(&entry, quarantine_dir.as_deref()) {
                 continue;
             }
 
             // Get file metadata
-            let metadata = match std::fs::metadata(&entry) {
+            let metadata = match std::fs::metadata(paths::long_path(&entry)) {
                 Ok(m) => m,
                 Err(e) => {
                     trace!(
@@ -437,21 +731,17 @@ async fn run_scan_loop(
             };
 
             let size = metadata.len();
-            let modified: DateTime<Utc> = metadata
-                .modified()
-                .map(|t| t.into())
-                .unwrap_or_else(|_| Utc::now());
-
-            // Start tracking
-            let tracked_file = TrackedFile {
-                path: entry.clone(),
-                state: FinalizationState::Detected,
-                first_seen: Utc::now(),
-                last_size: size,
-                last_modified: modified,
-                stable_since: None,
+
+            // Start tracking, unless the event watcher got there first
+            let tracked_file = TrackedFile::new(
+                entry.clone(),
                 vendor,
-            };
+                size,
+                modified_time(&metadata).known(),
+            );
+            if !track_detected(&tracked_files, tracked_file) {
+                continue;
+            }
 
             let file_name = entry
                 .file_name()
@@ -474,26 +764,27 @@ async fn run_scan_loop(
                     stability_window_secs,
                 );
             }
-
-            tracked_files.lock().unwrap().insert(entry, tracked_file);
         }
     }
 }
 
 /// Run the finalization state machine loop.
+#[allow(clippy::too_many_arguments)]
 async fn run_finalization_loop(
     tracked_files: Arc<Mutex<HashMap<PathBuf, TrackedFile>>>,
     processed_files: Arc<Mutex<std::collections::HashSet<PathBuf>>>,
     ready_tx: mpsc::Sender<TrackedFile>,
     config: WatcherConfig,
+    completion_sentinel: Option<String>,
     instrument_id: String,
     running: Arc<Mutex<bool>>,
     failed_files: FailedFiles,
 ) {
     let check_interval = tokio::time::Duration::from_secs(5);
     let mut interval = tokio::time::interval(check_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let wall_interval = Duration::seconds(check_interval.as_secs() as i64);
 
-    let stability_window = Duration::seconds(config.stability_window_seconds as i64);
     let stabilization_timeout = Duration::seconds(config.stabilization_timeout_seconds as i64);
 
     loop {
@@ -506,11 +797,18 @@ async fn run_finalization_loop(
         let mut to_remove = Vec::new();
         let mut to_ready = Vec::new();
         let mut to_record_failed: Vec<(PathBuf, String)> = Vec::new();
+        let mut to_record_aborted: Vec<(PathBuf, String)> = Vec::new();
 
         {
             let mut tracked = tracked_files.lock().unwrap();
+            let now = Utc::now();
+            let mut clock_jump = None;
 
             for (path, file) in tracked.iter_mut() {
+                if let Some(jump) = finalizer::absorb_clock_jump(file, now, wall_interval) {
+                    clock_jump = Some(jump);
+                }
+
                 match file.state {
                     FinalizationState::Detected => {
                         // Transition to stabilizing
@@ -524,8 +822,14 @@ async fn run_finalization_loop(
 
                     FinalizationState::Stabilizing => {
                         // Check for timeout
-                        let elapsed = Utc::now() - file.first_seen;
-                        if elapsed > stabilization_timeout {
+                        let elapsed = now - file.first_seen;
+                        let timed_out = elapsed > stabilization_timeout;
+                        let abort_marker = if timed_out {
+                            find_abort_marker(path, &config.abort_marker_patterns)
+                        } else {
+                            None
+                        };
+                        if timed_out && abort_marker.is_none() {
                             warn!(
                                 instrument = %instrument_id,
                                 path = %path.display(),
@@ -542,46 +846,67 @@ async fn run_finalization_loop(
                             continue;
                         }
 
-                        // Check current state based on vendor type
-                        let (current_size, current_modified, is_complete) =
-                            check_file_state(path, file.vendor);
-
-                        // Check if stable
-                        if current_size == file.last_size && current_modified == file.last_modified
-                        {
-                            // Still stable
-                            if file.stable_since.is_none() {
-                                file.stable_since = Some(Utc::now());
-                            }
+                        let was_size_only = file.size_only_stability;
+                        let outcome = match abort_marker {
+                            // A run that gave up is aborted, not timed out
+                            Some(marker) => finalizer::StabilizeOutcome::Aborted(marker),
+                            None => check_stabilizing(
+                                file,
+                                path,
+                                &config,
+                                completion_sentinel.as_deref(),
+                                now,
+                            ),
+                        };
 
-                            let stable_duration = Utc::now() - file.stable_since.unwrap();
+                        if file.size_only_stability && !was_size_only {
+                            warn!(
+                                instrument = %instrument_id,
+                                path = %path.display(),
+                                "Modification time unavailable, falling back to size-only stability"
+                            );
+                        }
 
-                            if stable_duration >= stability_window && is_complete {
-                                file.state = FinalizationState::Ready;
+                        match outcome {
+                            finalizer::StabilizeOutcome::Ready => {
                                 debug!(
                                     instrument = %instrument_id,
                                     path = %path.display(),
                                     "File ready for processing"
                                 );
                             }
-                        } else {
-                            // File changed, reset stability
-                            file.last_size = current_size;
-                            file.last_modified = current_modified;
-                            file.stable_since = None;
-                            trace!(
-                                instrument = %instrument_id,
-                                path = %path.display(),
-                                size = current_size,
-                                "File still changing"
-                            );
+                            finalizer::StabilizeOutcome::Changed => {
+                                trace!(
+                                    instrument = %instrument_id,
+                                    path = %path.display(),
+                                    size = file.last_size,
+                                    "File still changing"
+                                );
+                            }
+                            finalizer::StabilizeOutcome::Stable => {}
+                            finalizer::StabilizeOutcome::Aborted(marker) => {
+                                warn!(
+                                    instrument = %instrument_id,
+                                    path = %path.display(),
+                                    marker = %marker.display(),
+                                    "Acquisition was aborted, not processing this run"
+                                );
+                                // Not picked up again until the agent restarts
+                                processed_files.lock().unwrap().insert(path.clone());
+                                to_record_aborted.push((
+                                    path.clone(),
+                                    format!("Acquisition aborted ({} found)", marker.display()),
+                                ));
+                                to_remove.push(path.clone());
+                            }
                         }
                     }
 
                     FinalizationState::Ready => {
                         // Try non-sharing open test
-                        if try_exclusive_open(path, file.vendor) {
+                        if is_unlocked(path, file.vendor, &config) {
                             file.state = FinalizationState::Processing;
+                            file.finalized_at = Some(now);
                             to_ready.push(file.clone());
                             info!(
                                 instrument = %instrument_id,
@@ -602,7 +927,7 @@ async fn run_finalization_loop(
                         // Check for processing timeout (e.g., 30 minutes)
                         let processing_timeout = Duration::minutes(30);
                         if let Some(stable_since) = file.stable_since {
-                            if Utc::now() - stable_since > processing_timeout {
+                            if now - stable_since > processing_timeout {
                                 warn!(
                                     instrument = %instrument_id,
                                     path = %path.display(),
@@ -638,6 +963,14 @@ async fn run_finalization_loop(
                     }
                 }
             }
+
+            if let Some(jump) = clock_jump {
+                warn!(
+                    instrument = %instrument_id,
+                    jump_seconds = jump.num_seconds(),
+                    "System clock jumped (suspend or time change), counting it as one check interval"
+                );
+            }
         }
 
         // Send ready files
@@ -655,6 +988,9 @@ async fn run_finalization_loop(
         for (path, reason) in to_record_failed {
             failed_files.record_failure(path, instrument_id.clone(), reason);
         }
+        for (path, reason) in to_record_aborted {
+            failed_files.record_aborted(path, instrument_id.clone(), reason);
+        }
 
         // Remove completed/failed files from tracking
         if !to_remove.is_empty() {
@@ -666,49 +1002,100 @@ async fn run_finalization_loop(
     }
 }
 
+/// Run one stabilization check of a tracked file against the filesystem.
+///
+/// With a `completion_sentinel` pattern, the sentinel's presence decides
+/// completeness in place of the vendor checks; size/mtime stability still
+/// applies.
+///
+/// Abort markers are looked for only when the run settles: on the first check
+/// after it stops changing, and before it is declared ready. Globbing the
+/// folder on every tick would cost a directory listing per tracked run.
+fn check_stabilizing(
+    file: &mut TrackedFile,
+    path: &Path,
+    config: &WatcherConfig,
+    completion_sentinel: Option<&str>,
+    now: DateTime<Utc>,
+) -> finalizer::StabilizeOutcome {
+    let (size, modified, mut is_complete) = check_file_state(path, file.vendor);
+
+    if let Some(pattern) = completion_sentinel {
+        is_complete = find_relative_file(path, pattern).is_some();
+    } else if !lock_file_names(file.vendor).is_empty() {
+        // Require the lock to stay gone, not just vanish once
+        let lock_present = find_lock_file(path, file.vendor).is_some();
+        is_complete &= finalizer::confirm_lock_free(file, lock_present, config.lock_free_checks);
+    }
+
+    let outcome = finalizer::advance_stabilizing(
+        file,
+        size,
+        modified,
+        is_complete,
+        now,
+        Duration::seconds(config.stability_window(file.vendor) as i64),
+    );
+
+    let settled = match outcome {
+        finalizer::StabilizeOutcome::Ready => true,
+        finalizer::StabilizeOutcome::Stable => file.unchanged_checks == 1,
+        _ => false,
+    };
+    if settled {
+        if let Some(marker) = find_abort_marker(path, &config.abort_marker_patterns) {
+            file.state = FinalizationState::Stabilizing;
+            return finalizer::StabilizeOutcome::Aborted(marker);
+        }
+    }
+    outcome
+}
+
 /// Check file state including vendor-specific internal file checks.
 /// Returns (size, modified_time, is_complete).
-fn check_file_state(path: &Path, vendor: Vendor) -> (u64, DateTime<Utc>, bool) {
-    let default_time = Utc::now();
+///
+/// `modified_time` is `Mtime::Unsupported` when the filesystem can't report
+/// an mtime, and `Mtime::Unread` when the file couldn't be checked (missing,
+/// locked, unreadable); callers decide how to treat those.
+pub(crate) fn check_file_state(path: &Path, vendor: Vendor) -> (u64, Mtime, bool) {
+    let path = &*paths::long_path(path);
+
+    // A zip is written once, after acquisition; plain size/mtime stability
+    if is_zipped_raw_file(path, vendor) {
+        return match std::fs::metadata(path) {
+            Ok(metadata) => (metadata.len(), modified_time(&metadata), true),
+            Err(_) => (0, Mtime::Unread, false),
+        };
+    }
 
     match vendor {
         Vendor::Thermo => {
             // Thermo .raw: single file
             let metadata = match std::fs::metadata(path) {
                 Ok(m) => m,
-                Err(_) => return (0, default_time, false),
+                Err(_) => return (0, Mtime::Unread, false),
             };
-            let modified: DateTime<Utc> = metadata
-                .modified()
-                .map(|t| t.into())
-                .unwrap_or(default_time);
-            (metadata.len(), modified, true)
+            (metadata.len(), modified_time(&metadata), true)
         }
 
         Vendor::Bruker => {
-            // Bruker .d: check analysis.tdf stability and lock file absence
-            let analysis_tdf = path.join("analysis.tdf");
-            let lock_file = path.join("analysis.tdf-journal");
-            let lock_file2 = path.join("analysis.tdf-lock");
+            // Bruker .d: check analysis.tdf (or .tsf) stability and lock file absence
+            let analysis = bruker_analysis_file(path);
 
-            if lock_file.exists() || lock_file2.exists() {
+            if find_lock_file(path, vendor).is_some() {
                 // Lock file present - acquisition in progress
-                return (0, default_time, false);
+                return (0, Mtime::Unread, false);
             }
 
-            if !analysis_tdf.exists() {
-                return (0, default_time, false);
+            if !analysis.exists() {
+                return (0, Mtime::Unread, false);
             }
 
-            let metadata = match std::fs::metadata(&analysis_tdf) {
+            let metadata = match std::fs::metadata(&analysis) {
                 Ok(m) => m,
-                Err(_) => return (0, default_time, false),
+                Err(_) => return (0, Mtime::Unread, false),
             };
-            let modified: DateTime<Utc> = metadata
-                .modified()
-                .map(|t| t.into())
-                .unwrap_or(default_time);
-            (metadata.len(), modified, true)
+            (metadata.len(), modified_time(&metadata), true)
         }
 
         Vendor::Sciex => {
@@ -717,38 +1104,25 @@ fn check_file_state(path: &Path, vendor: Vendor) -> (u64, DateTime<Utc>, bool) {
 
             let wiff_metadata = match std::fs::metadata(path) {
                 Ok(m) => m,
-                Err(_) => return (0, default_time, false),
+                Err(_) => return (0, Mtime::Unread, false),
             };
 
             // .wiff.scan might not exist in newer versions
             let (total_size, latest_modified) = if scan_file.exists() {
                 let scan_metadata = match std::fs::metadata(&scan_file) {
                     Ok(m) => m,
-                    Err(_) => return (0, default_time, false),
+                    Err(_) => return (0, Mtime::Unread, false),
                 };
 
-                let wiff_modified: DateTime<Utc> = wiff_metadata
-                    .modified()
-                    .map(|t| t.into())
-                    .unwrap_or(default_time);
-                let scan_modified: DateTime<Utc> = scan_metadata
-                    .modified()
-                    .map(|t| t.into())
-                    .unwrap_or(default_time);
-
-                let latest = if scan_modified > wiff_modified {
-                    scan_modified
-                } else {
-                    wiff_modified
+                let latest = match (modified_time(&wiff_metadata), modified_time(&scan_metadata)) {
+                    (Mtime::Known(wiff), Mtime::Known(scan)) => Mtime::Known(wiff.max(scan)),
+                    // If either mtime is unavailable the combined mtime is too
+                    _ => Mtime::Unsupported,
                 };
 
                 (wiff_metadata.len() + scan_metadata.len(), latest)
             } else {
-                let modified: DateTime<Utc> = wiff_metadata
-                    .modified()
-                    .map(|t| t.into())
-                    .unwrap_or(default_time);
-                (wiff_metadata.len(), modified)
+                (wiff_metadata.len(), modified_time(&wiff_metadata))
             };
 
             (total_size, latest_modified, true)
@@ -758,30 +1132,28 @@ fn check_file_state(path: &Path, vendor: Vendor) -> (u64, DateTime<Utc>, bool) {
             // Waters .raw directory: check _FUNC001.DAT and _extern.inf
             let func_file = path.join("_FUNC001.DAT");
             let extern_inf = path.join("_extern.inf");
-            let lock_file = path.join("_LOCK_");
 
-            if lock_file.exists() {
-                return (0, default_time, false);
+            if find_lock_file(path, vendor).is_some() {
+                return (0, Mtime::Unread, false);
             }
 
             if !func_file.exists() {
-                return (0, default_time, false);
+                return (0, Mtime::Unread, false);
             }
 
             let func_metadata = match std::fs::metadata(&func_file) {
                 Ok(m) => m,
-                Err(_) => return (0, default_time, false),
+                Err(_) => return (0, Mtime::Unread, false),
             };
 
-            let modified: DateTime<Utc> = func_metadata
-                .modified()
-                .map(|t| t.into())
-                .unwrap_or(default_time);
-
             // Also check _extern.inf if it exists (indicates acquisition complete)
             let is_complete = extern_inf.exists();
 
-            (func_metadata.len(), modified, is_complete)
+            (
+                func_metadata.len(),
+                modified_time(&func_metadata),
+                is_complete,
+            )
         }
 
         Vendor::Agilent => {
@@ -790,7 +1162,7 @@ fn check_file_state(path: &Path, vendor: Vendor) -> (u64, DateTime<Utc>, bool) {
             let ms_scan = acq_data.join("MSScan.bin");
 
             if !acq_data.exists() || !acq_data.is_dir() {
-                return (0, default_time, false);
+                return (0, Mtime::Unread, false);
             }
 
             let check_file = if ms_scan.exists() {
@@ -802,21 +1174,105 @@ fn check_file_state(path: &Path, vendor: Vendor) -> (u64, DateTime<Utc>, bool) {
 
             let metadata = match std::fs::metadata(&check_file) {
                 Ok(m) => m,
-                Err(_) => return (0, default_time, false),
+                Err(_) => return (0, Mtime::Unread, false),
             };
 
-            let modified: DateTime<Utc> = metadata
-                .modified()
-                .map(|t| t.into())
-                .unwrap_or(default_time);
+            (metadata.len(), modified_time(&metadata), true)
+        }
+    }
+}
+
+/// Modification time from metadata, if the filesystem provides one.
+fn modified_time(metadata: &std::fs::Metadata) -> Mtime {
+    match metadata.modified() {
+        Ok(modified) => Mtime::Known(modified.into()),
+        Err(_) => Mtime::Unsupported,
+    }
+}
+
+/// Return the first vendor lock file present inside a raw file, if any.
+///
+/// Bruker and Waters write lock/journal files while acquisition is running;
+/// other vendors have no lock file convention.
+pub(crate) fn find_lock_file(path: &Path, vendor: Vendor) -> Option<PathBuf> {
+    lock_file_names(vendor)
+        .iter()
+        .map(|name| path.join(name))
+        .find(|p| p.exists())
+}
+
+/// The first file matching one of `patterns` that marks the run at `path` as
+/// aborted. See [`find_relative_file`] for the pattern syntax.
+pub(crate) fn find_abort_marker(path: &Path, patterns: &[String]) -> Option<PathBuf> {
+    patterns
+        .iter()
+        .find_map(|pattern| find_relative_file(path, pattern))
+}
+
+/// The first file other than the run itself matching `pattern`, a glob
+/// relative to the run's folder in which `{stem}` and `{name}` stand for the
+/// run's name without and with its extension.
+pub(crate) fn find_relative_file(path: &Path, pattern: &str) -> Option<PathBuf> {
+    let folder = glob::Pattern::escape(&path.parent()?.to_string_lossy());
+    let name = glob::Pattern::escape(&path.file_name()?.to_string_lossy());
+    let stem = glob::Pattern::escape(&path.file_stem()?.to_string_lossy());
+
+    let pattern = pattern.replace("{stem}", &stem).replace("{name}", &name);
+    let pattern = Path::new(&folder).join(pattern);
+    glob::glob(&pattern.to_string_lossy())
+        .ok()?
+        .flatten()
+        .find(|found| found != path)
+}
+
+/// Lock/journal file names a vendor writes inside a run during acquisition.
+fn lock_file_names(vendor: Vendor) -> &'static [&'static str] {
+    match vendor {
+        Vendor::Bruker => &[
+            "analysis.tdf-journal",
+            "analysis.tdf-lock",
+            "analysis.tsf-journal",
+            "analysis.tsf-lock",
+        ],
+        Vendor::Waters => &["_LOCK_"],
+        Vendor::Thermo | Vendor::Sciex | Vendor::Agilent => &[],
+    }
+}
+
+/// Infer the vendor of a raw file from its extension and layout.
+///
+/// `.raw` files are Thermo and `.raw` directories are Waters; `.d` directories
+/// containing `analysis.tdf` or `analysis.tsf` are Bruker, otherwise Agilent.
+pub(crate) fn infer_vendor(path: &Path) -> Option<Vendor> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
 
-            (metadata.len(), modified, true)
+    match extension.as_deref() {
+        Some("raw") if path.is_dir() => Some(Vendor::Waters),
+        Some("raw") => Some(Vendor::Thermo),
+        Some("wiff") | Some("wiff2") => Some(Vendor::Sciex),
+        Some("d")
+            if BRUKER_ANALYSIS_FILES.iter().any(|name| {
+                path.join(name).exists() || path.join(format!("{}_bin", name)).exists()
+            }) =>
+        {
+            Some(Vendor::Bruker)
         }
+        Some("d") if path.join("AcqData").is_dir() => Some(Vendor::Agilent),
+        Some("d") => Some(Vendor::Bruker),
+        _ => None,
     }
 }
 
+/// Whether a path is inside the instrument's quarantine directory.
+fn is_quarantined(path: &Path, quarantine_dir: Option<&Path>) -> bool {
+    quarantine_dir.is_some_and(|dir| path.starts_with(dir))
+}
+
 /// Check if a path is a valid raw file for the given vendor.
-fn is_valid_raw_file(path: &Path, vendor: Vendor) -> bool {
+pub(crate) fn is_valid_raw_file(path: &Path, vendor: Vendor) -> bool {
     let extension = path
         .extension()
         .and_then(|e| e.to_str())
@@ -833,16 +1289,80 @@ fn is_valid_raw_file(path: &Path, vendor: Vendor) -> bool {
     }
 }
 
-/// Try to open a file exclusively to verify it's not in use.
-fn try_exclusive_open(path: &Path, vendor: Vendor) -> bool {
-    // For directory-based formats, check the key internal file
-    let file_to_check = match vendor {
+/// Whether a zipped run (`X.d.zip`, `X.raw.zip`) holds a run of the vendor.
+pub(crate) fn is_zipped_raw_file(path: &Path, vendor: Vendor) -> bool {
+    let Some(inner) = zipped_run_name(path) else {
+        return false;
+    };
+    let extension = Path::new(inner)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    let matches_vendor = match vendor {
+        Vendor::Thermo | Vendor::Waters => extension.as_deref() == Some("raw"),
+        Vendor::Bruker | Vendor::Agilent => extension.as_deref() == Some("d"),
+        Vendor::Sciex => matches!(extension.as_deref(), Some("wiff") | Some("wiff2")),
+    };
+    matches_vendor && path.is_file()
+}
+
+/// Name of the run a zip holds: `X.d` for `X.d.zip`.
+pub(crate) fn zipped_run_name(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let split = name.len().checked_sub(".zip".len())?;
+    if !name.is_char_boundary(split) || !name[split..].eq_ignore_ascii_case(".zip") {
+        return None;
+    }
+    Some(&name[..split])
+}
+
+/// Whether the watcher should track `path`: a raw file of the vendor, or a
+/// zipped one if the instrument allows them.
+fn is_watched_run(path: &Path, vendor: Vendor, allow_zipped: bool) -> bool {
+    is_valid_raw_file(path, vendor) || (allow_zipped && is_zipped_raw_file(path, vendor))
+}
+
+/// Whether a ready file is free to process, per the exclusive-open test
+/// unless the config skips it.
+fn is_unlocked(path: &Path, vendor: Vendor, config: &WatcherConfig) -> bool {
+    config.skip_exclusive_open == Some(true) || try_exclusive_open(path, vendor)
+}
+
+/// The file that stands for a run: the run itself for single-file formats,
+/// the key internal file for directory formats.
+pub(crate) fn key_file(path: &Path, vendor: Vendor) -> PathBuf {
+    if is_zipped_raw_file(path, vendor) {
+        return path.to_path_buf();
+    }
+
+    match vendor {
         Vendor::Thermo => path.to_path_buf(),
-        Vendor::Bruker => path.join("analysis.tdf"),
+        Vendor::Bruker => bruker_analysis_file(path),
         Vendor::Sciex => path.to_path_buf(),
         Vendor::Waters => path.join("_FUNC001.DAT"),
         Vendor::Agilent => path.join("AcqData").join("MSScan.bin"),
-    };
+    }
+}
+
+/// Analysis databases a Bruker `.d` may hold: `.tdf` for TIMS/PASEF
+/// acquisitions, `.tsf` for non-TIMS ones (e.g. MALDI).
+const BRUKER_ANALYSIS_FILES: [&str; 2] = ["analysis.tdf", "analysis.tsf"];
+
+/// The analysis database of a Bruker `.d`: whichever of `.tdf`/`.tsf`
+/// exists, `analysis.tdf` if neither does yet.
+fn bruker_analysis_file(path: &Path) -> PathBuf {
+    BRUKER_ANALYSIS_FILES
+        .iter()
+        .map(|name| path.join(name))
+        .find(|p| p.exists())
+        .unwrap_or_else(|| path.join(BRUKER_ANALYSIS_FILES[0]))
+}
+
+/// Try to open a file exclusively to verify it's not in use.
+pub(crate) fn try_exclusive_open(path: &Path, vendor: Vendor) -> bool {
+    // For directory-based formats, check the key internal file
+    let file_to_check = key_file(&paths::long_path(path), vendor);
 
     if !file_to_check.exists() || file_to_check.is_dir() {
         return true; // Can't check directories, assume OK if vendor checks passed
@@ -863,7 +1383,552 @@ fn try_exclusive_open(path: &Path, vendor: Vendor) -> bool {
 
     #[cfg(not(windows))]
     {
-        // On non-Windows, just check if we can open for reading
-        std::fs::File::open(&file_to_check).is_ok()
+        // Unix has no share modes; an exclusive advisory lock fails if a
+        // writer holds one. Filesystems without lock support are assumed OK.
+        match std::fs::File::open(&file_to_check) {
+            Ok(file) => !matches!(file.try_lock(), Err(std::fs::TryLockError::WouldBlock)),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_joins_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let instrument = InstrumentConfig {
+            file_pattern: "*.raw".to_string(),
+            ..InstrumentConfig::new(
+                "TEST01",
+                Vendor::Thermo,
+                &dir.path().to_string_lossy(),
+                "template.sky",
+            )
+        };
+        let (tx, _rx) = mpsc::channel(1);
+
+        let watcher = Watcher::new(instrument, WatcherConfig::default(), tx, false).unwrap();
+        watcher.start().unwrap();
+        assert!(watcher.event_thread.lock().unwrap().is_some());
+        assert_eq!(watcher.tasks.lock().unwrap().len(), 2);
+
+        let started = std::time::Instant::now();
+        watcher.stop().await.unwrap();
+
+        // Joined well before the timeout, and all handles were consumed
+        assert!(started.elapsed() < STOP_TIMEOUT);
+        assert!(watcher.event_thread.lock().unwrap().is_none());
+        assert!(watcher.tasks.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replaced_event_thread_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let instrument = InstrumentConfig {
+            file_pattern: "*.raw".to_string(),
+            ..InstrumentConfig::new(
+                "TEST01",
+                Vendor::Thermo,
+                &dir.path().to_string_lossy(),
+                "template.sky",
+            )
+        };
+        let (tx, _rx) = mpsc::channel(1);
+
+        let watcher = Watcher::new(instrument, WatcherConfig::default(), tx, false).unwrap();
+        watcher.start().unwrap();
+
+        let previous = watcher.spawn_event_thread().unwrap();
+        let deadline = std::time::Instant::now() + STOP_TIMEOUT;
+        while !previous.is_finished() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "old thread still running"
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        // The replacement keeps running until stopped
+        let current_finished = watcher
+            .event_thread
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|thread| thread.handle.is_finished());
+        assert_eq!(current_finished, Some(false));
+
+        watcher.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stalled_scan_loop_is_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let instrument = InstrumentConfig {
+            file_pattern: "*.raw".to_string(),
+            ..InstrumentConfig::new(
+                "TEST01",
+                Vendor::Thermo,
+                &dir.path().to_string_lossy(),
+                "template.sky",
+            )
+        };
+        let config = WatcherConfig {
+            use_filesystem_events: false,
+            scan_interval_seconds: 1,
+            ..WatcherConfig::default()
+        };
+        let (tx, _rx) = mpsc::channel(1);
+
+        let mut watcher = Watcher::new(instrument, config, tx, false).unwrap();
+        watcher.min_stall = std::time::Duration::ZERO;
+        watcher.start().unwrap();
+
+        // A ticking loop is left alone
+        assert_eq!(watcher.restart_stalled_loops(), 0);
+
+        // Wedge the scan loop: it stops ticking and its heartbeat goes stale
+        watcher.scan_abort.lock().unwrap().as_ref().unwrap().abort();
+        watcher
+            .scan_heartbeat
+            .set_elapsed(std::time::Duration::from_secs(10));
+
+        assert_eq!(watcher.restart_stalled_loops(), 1);
+        let restarts = watcher.restarts();
+        assert_eq!((restarts.event, restarts.scan), (0, 1));
+        assert!(restarts.last_restart.is_some());
+        assert_eq!(watcher.restart_stalled_loops(), 0);
+
+        // Stalling again straight away waits out the backoff
+        watcher
+            .scan_heartbeat
+            .set_elapsed(std::time::Duration::from_secs(10));
+        assert_eq!(watcher.restart_stalled_loops(), 0);
+        assert_eq!(watcher.restarts().scan, 1);
+        watcher.scan_heartbeat.beat();
+
+        // The replacement loop picks up new files
+        let raw = dir.path().join("run.raw");
+        std::fs::write(&raw, b"data").unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !watcher.tracked_files.lock().unwrap().contains_key(&raw) {
+            assert!(std::time::Instant::now() < deadline, "file never detected");
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        watcher.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_drive_kind_classification() {
+        // E: is a USB stick, Z: a mapped share, anything else DRIVE_FIXED
+        let drives = |root: &str| match root {
+            "E:\\" => DRIVE_REMOVABLE,
+            "Z:\\" => DRIVE_REMOTE,
+            _ => 3,
+        };
+
+        let kind = |path: &str| DriveKind::classify(Path::new(path), drives);
+        assert_eq!(kind(r"E:\QC\Exploris"), DriveKind::Removable);
+        assert_eq!(kind(r"e:\QC"), DriveKind::Removable);
+        assert_eq!(kind(r"Z:\Instruments\TIMSTOF01"), DriveKind::Network);
+        assert_eq!(kind(r"\\nas01\instruments"), DriveKind::Network);
+        assert_eq!(kind(r"D:\Data"), DriveKind::Local);
+        assert_eq!(kind("/mnt/usb/QC"), DriveKind::Local);
+
+        assert_eq!(drive_root(Path::new(r"E:\QC")).as_deref(), Some("E:\\"));
+        assert_eq!(drive_root(Path::new("/mnt/usb")), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(drive_present(dir.path()));
+        assert!(!drive_present(&dir.path().join("unplugged")));
+    }
+
+    #[test]
+    fn test_infer_vendor() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let thermo = dir.path().join("run.raw");
+        std::fs::write(&thermo, b"").unwrap();
+        assert_eq!(infer_vendor(&thermo), Some(Vendor::Thermo));
+
+        let waters = dir.path().join("waters.raw");
+        std::fs::create_dir(&waters).unwrap();
+        assert_eq!(infer_vendor(&waters), Some(Vendor::Waters));
+
+        let bruker = dir.path().join("bruker.d");
+        std::fs::create_dir(&bruker).unwrap();
+        std::fs::write(bruker.join("analysis.tdf"), b"").unwrap();
+        assert_eq!(infer_vendor(&bruker), Some(Vendor::Bruker));
+
+        let agilent = dir.path().join("agilent.d");
+        std::fs::create_dir_all(agilent.join("AcqData")).unwrap();
+        assert_eq!(infer_vendor(&agilent), Some(Vendor::Agilent));
+
+        assert_eq!(infer_vendor(&dir.path().join("notes.txt")), None);
+    }
+
+    #[test]
+    fn test_find_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let bruker = dir.path().join("bruker.d");
+        std::fs::create_dir(&bruker).unwrap();
+
+        assert_eq!(find_lock_file(&bruker, Vendor::Bruker), None);
+
+        std::fs::write(bruker.join("analysis.tdf-journal"), b"").unwrap();
+        assert_eq!(
+            find_lock_file(&bruker, Vendor::Bruker),
+            Some(bruker.join("analysis.tdf-journal"))
+        );
+        assert_eq!(find_lock_file(&bruker, Vendor::Thermo), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_try_exclusive_open_respects_advisory_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("run.raw");
+        std::fs::write(&raw, b"data").unwrap();
+
+        assert!(try_exclusive_open(&raw, Vendor::Thermo));
+
+        // Simulate a writer holding the file
+        let writer = std::fs::OpenOptions::new().write(true).open(&raw).unwrap();
+        writer.lock().unwrap();
+        assert!(!try_exclusive_open(&raw, Vendor::Thermo));
+
+        drop(writer);
+        assert!(try_exclusive_open(&raw, Vendor::Thermo));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_network_path_finalizes_without_exclusive_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("run.raw");
+        std::fs::write(&raw, b"data").unwrap();
+
+        // A held lock stands in for an SMB oplock that never releases
+        let writer = std::fs::OpenOptions::new().write(true).open(&raw).unwrap();
+        writer.lock().unwrap();
+        assert!(!try_exclusive_open(&raw, Vendor::Thermo));
+
+        let instrument = |watch_path: &str| InstrumentConfig {
+            file_pattern: "*.raw".to_string(),
+            ..InstrumentConfig::new("SMB01", Vendor::Thermo, watch_path, "template.sky")
+        };
+        let (tx, _rx) = mpsc::channel(1);
+
+        // Network paths skip the open test by default
+        let watcher = Watcher::new(
+            instrument(r"\\qc-nas\instruments\SMB01"),
+            WatcherConfig::default(),
+            tx.clone(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(watcher.config.skip_exclusive_open, Some(true));
+        assert!(is_unlocked(&raw, Vendor::Thermo, &watcher.config));
+
+        // Local paths keep it
+        let local = dir.path().to_string_lossy().to_string();
+        let watcher = Watcher::new(
+            instrument(&local),
+            WatcherConfig::default(),
+            tx.clone(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(watcher.config.skip_exclusive_open, Some(false));
+        assert!(!is_unlocked(&raw, Vendor::Thermo, &watcher.config));
+
+        // An explicit setting overrides the network default
+        let config = WatcherConfig {
+            skip_exclusive_open: Some(false),
+            ..WatcherConfig::default()
+        };
+        let watcher =
+            Watcher::new(instrument(r"\\qc-nas\instruments\SMB01"), config, tx, false).unwrap();
+        assert!(!is_unlocked(&raw, Vendor::Thermo, &watcher.config));
+    }
+
+    #[test]
+    fn test_concurrent_detection_tracks_once() {
+        let tracked_files: Arc<Mutex<HashMap<PathBuf, TrackedFile>>> = Arc::default();
+        let path = PathBuf::from("QC_A_A1.raw");
+
+        // Event watcher and scan loop both see the new file at once
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+        let added: usize = (0..8)
+            .map(|i| {
+                let tracked_files = Arc::clone(&tracked_files);
+                let barrier = Arc::clone(&barrier);
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let file = TrackedFile::new(path, Vendor::Thermo, i, None);
+                    track_detected(&tracked_files, file) as usize
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum();
+        assert_eq!(added, 1);
+        assert_eq!(tracked_files.lock().unwrap().len(), 1);
+
+        // A late detection doesn't reset a file that is already stabilizing
+        let stable_since = Some(Utc::now());
+        {
+            let mut tracked = tracked_files.lock().unwrap();
+            let file = tracked.get_mut(&path).unwrap();
+            file.state = FinalizationState::Stabilizing;
+            file.stable_since = stable_since;
+        }
+        let late = TrackedFile::new(path.clone(), Vendor::Thermo, 999, None);
+        assert!(!track_detected(&tracked_files, late));
+
+        let tracked = tracked_files.lock().unwrap();
+        let file = &tracked[&path];
+        assert_eq!(file.state, FinalizationState::Stabilizing);
+        assert_eq!(file.stable_since, stable_since);
+        assert_ne!(file.last_size, 999);
+    }
+
+    #[test]
+    fn test_zipped_run_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        let zipped = dir.path().join("QC_A_A1.d.ZIP");
+        std::fs::write(&zipped, b"PK zipped run").unwrap();
+        let unrelated = dir.path().join("logs.zip");
+        std::fs::write(&unrelated, b"PK").unwrap();
+
+        assert_eq!(zipped_run_name(&zipped), Some("QC_A_A1.d"));
+        assert!(is_zipped_raw_file(&zipped, Vendor::Bruker));
+        assert!(!is_zipped_raw_file(&zipped, Vendor::Thermo));
+        assert!(!is_zipped_raw_file(&unrelated, Vendor::Bruker));
+
+        // Only picked up when the instrument allows zips
+        assert!(!is_valid_raw_file(&zipped, Vendor::Bruker));
+        assert!(!is_watched_run(&zipped, Vendor::Bruker, false));
+        assert!(is_watched_run(&zipped, Vendor::Bruker, true));
+
+        // Plain size/mtime stability; the zip stands for the run
+        let (size, modified, is_complete) = check_file_state(&zipped, Vendor::Bruker);
+        assert_eq!(size, 13);
+        assert!(modified.known().is_some());
+        assert!(is_complete);
+        assert_eq!(key_file(&zipped, Vendor::Bruker), zipped);
+    }
+
+    #[test]
+    fn test_tsf_bruker_run_finalizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let bruker = dir.path().join("maldi.d");
+        std::fs::create_dir(&bruker).unwrap();
+
+        // Nothing written yet
+        assert!(!check_file_state(&bruker, Vendor::Bruker).2);
+
+        std::fs::write(bruker.join("analysis.tsf"), b"metadata").unwrap();
+        std::fs::write(bruker.join("analysis.tsf_bin"), b"spectra").unwrap();
+        let journal = bruker.join("analysis.tsf-journal");
+        std::fs::write(&journal, b"").unwrap();
+        assert_eq!(infer_vendor(&bruker), Some(Vendor::Bruker));
+        assert_eq!(
+            key_file(&bruker, Vendor::Bruker),
+            bruker.join("analysis.tsf")
+        );
+
+        let config = WatcherConfig {
+            stability_window_seconds: Some(0),
+            lock_free_checks: 1,
+            ..WatcherConfig::default()
+        };
+        let mut file = TrackedFile::new(bruker.clone(), Vendor::Bruker, 0, None);
+        file.state = FinalizationState::Stabilizing;
+        let start = Utc::now();
+
+        // Still acquiring while the journal exists
+        let (_, _, is_complete) = check_file_state(&bruker, Vendor::Bruker);
+        assert!(!is_complete);
+        assert_ne!(
+            check_stabilizing(&mut file, &bruker, &config, None, start),
+            finalizer::StabilizeOutcome::Ready
+        );
+
+        std::fs::remove_file(&journal).unwrap();
+        let (size, _, is_complete) = check_file_state(&bruker, Vendor::Bruker);
+        assert!(is_complete);
+        assert_eq!(size, 8);
+        assert!(try_exclusive_open(&bruker, Vendor::Bruker));
+
+        assert_ne!(
+            check_stabilizing(
+                &mut file,
+                &bruker,
+                &config,
+                None,
+                start + Duration::seconds(5)
+            ),
+            finalizer::StabilizeOutcome::Ready
+        );
+        assert_eq!(
+            check_stabilizing(
+                &mut file,
+                &bruker,
+                &config,
+                None,
+                start + Duration::seconds(10)
+            ),
+            finalizer::StabilizeOutcome::Ready
+        );
+    }
+
+    #[test]
+    fn test_reappearing_journal_delays_finalization() {
+        let dir = tempfile::tempdir().unwrap();
+        let bruker = dir.path().join("bruker.d");
+        std::fs::create_dir(&bruker).unwrap();
+        std::fs::write(bruker.join("analysis.tdf"), b"data").unwrap();
+        let journal = bruker.join("analysis.tdf-journal");
+
+        let config = WatcherConfig {
+            stability_window_seconds: Some(0),
+            lock_free_checks: 3,
+            ..WatcherConfig::default()
+        };
+        let mut file = TrackedFile::new(bruker.clone(), Vendor::Bruker, 0, None);
+        file.state = FinalizationState::Stabilizing;
+
+        let start = Utc::now();
+        let mut check = |i: i64| {
+            check_stabilizing(
+                &mut file,
+                &bruker,
+                &config,
+                None,
+                start + Duration::seconds(5 * i),
+            )
+        };
+
+        // Journal vanishes, then SQLite re-creates it once for a final commit
+        assert_ne!(check(0), finalizer::StabilizeOutcome::Ready);
+        std::fs::write(&journal, b"").unwrap();
+        assert_ne!(check(1), finalizer::StabilizeOutcome::Ready);
+        std::fs::remove_file(&journal).unwrap();
+
+        // Lock-free count restarted; ready only after three clean checks
+        assert_ne!(check(2), finalizer::StabilizeOutcome::Ready);
+        assert_ne!(check(3), finalizer::StabilizeOutcome::Ready);
+        assert_eq!(check(4), finalizer::StabilizeOutcome::Ready);
+    }
+
+    #[test]
+    fn test_run_not_finalized_until_sentinel_appears() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("QC_A_A1.raw");
+        std::fs::write(&raw, b"complete raw data").unwrap();
+        // Another run's sentinel doesn't count
+        std::fs::write(dir.path().join("QC_A_A2.done"), b"").unwrap();
+
+        let config = WatcherConfig {
+            stability_window_seconds: Some(0),
+            ..WatcherConfig::default()
+        };
+        let mut file = TrackedFile::new(raw.clone(), Vendor::Thermo, 0, None);
+        file.state = FinalizationState::Stabilizing;
+        let start = Utc::now();
+        let mut check = |i: i64| {
+            check_stabilizing(
+                &mut file,
+                &raw,
+                &config,
+                Some("{stem}.done"),
+                start + Duration::seconds(5 * i),
+            )
+        };
+
+        // Stable and complete by the Thermo heuristics, but no sentinel yet
+        for i in 0..5 {
+            assert_ne!(check(i), finalizer::StabilizeOutcome::Ready);
+        }
+
+        std::fs::write(dir.path().join("QC_A_A1.done"), b"").unwrap();
+        assert_eq!(check(5), finalizer::StabilizeOutcome::Ready);
+
+        // Sentinels inside a run folder
+        let bruker = dir.path().join("QC_B_A3.d");
+        std::fs::create_dir(&bruker).unwrap();
+        assert_eq!(find_relative_file(&bruker, "{name}/complete.txt"), None);
+        std::fs::write(bruker.join("complete.txt"), b"").unwrap();
+        assert_eq!(
+            find_relative_file(&bruker, "{name}/complete.txt"),
+            Some(bruker.join("complete.txt"))
+        );
+    }
+
+    #[test]
+    fn test_abort_marker_skips_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = dir.path().join("QC_A_A1 [rerun].raw");
+        std::fs::write(&raw, b"partial").unwrap();
+        // Another run's marker doesn't count
+        std::fs::write(dir.path().join("QC_A_A2.err"), b"").unwrap();
+
+        // Off unless configured
+        assert!(WatcherConfig::default().abort_marker_patterns.is_empty());
+        let config = WatcherConfig {
+            stability_window_seconds: Some(0),
+            abort_marker_patterns: vec!["{stem}.err".to_string(), "{name}.err".to_string()],
+            ..WatcherConfig::default()
+        };
+        let mut file = TrackedFile::new(raw.clone(), Vendor::Thermo, 0, None);
+        file.state = FinalizationState::Stabilizing;
+        let start = Utc::now();
+
+        assert_eq!(find_abort_marker(&raw, &config.abort_marker_patterns), None);
+        assert_eq!(
+            check_stabilizing(&mut file, &raw, &config, None, start),
+            finalizer::StabilizeOutcome::Changed
+        );
+
+        // The acquisition software gave up and left a marker; it is seen once
+        // the run stops changing
+        let marker = dir.path().join("QC_A_A1 [rerun].err");
+        std::fs::write(&marker, b"").unwrap();
+        std::fs::write(&raw, b"partial, more").unwrap();
+        assert_eq!(
+            check_stabilizing(&mut file, &raw, &config, None, start + Duration::seconds(5)),
+            finalizer::StabilizeOutcome::Changed
+        );
+        assert_eq!(
+            check_stabilizing(
+                &mut file,
+                &raw,
+                &config,
+                None,
+                start + Duration::seconds(10)
+            ),
+            finalizer::StabilizeOutcome::Aborted(marker.clone())
+        );
+        assert_eq!(file.state, FinalizationState::Stabilizing);
+
+        // Markers inside a run folder, and disabling detection
+        let bruker = dir.path().join("QC_B_A3.d");
+        std::fs::create_dir(&bruker).unwrap();
+        std::fs::write(bruker.join("Aborted.txt"), b"").unwrap();
+        assert_eq!(
+            find_abort_marker(&bruker, &config.abort_marker_patterns),
+            None
+        );
+        assert_eq!(
+            find_abort_marker(&bruker, &["{name}/Aborted.txt".to_string()]),
+            Some(bruker.join("Aborted.txt"))
+        );
+        assert_eq!(find_abort_marker(&raw, &[]), None);
     }
 }
@@ -0,0 +1,178 @@
+//! Heartbeats and restart bookkeeping for the watcher loops.
+//!
+//! The event and scan loops stamp a heartbeat each time round. If a loop dies
+//! or wedges (e.g. a glob blocked on a dead share), its heartbeat goes stale
+//! and the agent's supervisor restarts it, backing off exponentially while it
+//! keeps stalling. Restart counts are written to the data directory so
+//! `mdqc status` can show them.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::paths;
+
+/// A loop is stalled once its heartbeat is this many intervals old...
+const STALL_MULTIPLE: u32 = 3;
+
+/// ...and at least this old, so short intervals don't trip on a busy machine.
+pub const MIN_STALL_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Wait after a restart before the same loop may be restarted again...
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// ...doubling on each further restart up to this.
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Last time a loop made progress.
+#[derive(Debug, Clone)]
+pub struct Heartbeat(Arc<Mutex<Instant>>);
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Record progress.
+    pub fn beat(&self) {
+        *self.0.lock().unwrap() = Instant::now();
+    }
+
+    /// Time since the last beat.
+    pub fn elapsed(&self) -> Duration {
+        self.0.lock().unwrap().elapsed()
+    }
+
+    /// Whether the loop has missed several ticks of `interval`.
+    pub fn is_stalled(&self, interval: Duration, min_threshold: Duration) -> bool {
+        self.elapsed() > (interval * STALL_MULTIPLE).max(min_threshold)
+    }
+
+    #[cfg(test)]
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        *self.0.lock().unwrap() = Instant::now() - elapsed;
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponential backoff between restarts of one loop, so a loop that dies
+/// straight away (e.g. a watch path that can't be opened) isn't restarted
+/// every watchdog tick.
+#[derive(Debug)]
+pub struct RestartBackoff {
+    delay: Duration,
+    last_restart: Option<Instant>,
+}
+
+impl RestartBackoff {
+    pub fn new() -> Self {
+        Self {
+            delay: INITIAL_RESTART_BACKOFF,
+            last_restart: None,
+        }
+    }
+
+    /// Whether a stalled loop may be restarted now. Records the restart and
+    /// doubles the delay if so.
+    pub fn try_restart(&mut self) -> bool {
+        if let Some(last) = self.last_restart {
+            if last.elapsed() < self.delay {
+                return false;
+            }
+            self.delay = (self.delay * 2).min(MAX_RESTART_BACKOFF);
+        }
+        self.last_restart = Some(Instant::now());
+        true
+    }
+
+    /// Note that the loop is ticking. Once it has kept going for twice the
+    /// current delay since its last restart, the backoff starts over.
+    pub fn healthy(&mut self) {
+        if self
+            .last_restart
+            .is_some_and(|last| last.elapsed() > self.delay * 2)
+        {
+            *self = Self::new();
+        }
+    }
+}
+
+impl Default for RestartBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Restarts of one instrument's watcher loops.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopRestarts {
+    pub event: u32,
+    pub scan: u32,
+    pub last_restart: Option<DateTime<Utc>>,
+}
+
+impl LoopRestarts {
+    pub fn total(&self) -> u32 {
+        self.event + self.scan
+    }
+}
+
+/// Restart counts since the agent started, persisted for `mdqc status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogState {
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub instruments: BTreeMap<String, LoopRestarts>,
+}
+
+impl WatchdogState {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            instruments: BTreeMap::new(),
+        }
+    }
+
+    /// Load the state written by the running agent, if any.
+    pub fn load() -> Result<Option<Self>> {
+        let path = Self::store_path();
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Save the state to disk.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::store_path();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn store_path() -> PathBuf {
+        paths::data_dir().join("watchdog.json")
+    }
+}
+
+impl Default for WatchdogState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,60 @@
+//! Runs acquired before an instrument's `process_acquired_after` cutoff are
+//! skipped (and audited as such) instead of being spooled.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::TestInstall;
+
+/// Stub SkylineCmd step dating runs named "*backlog*" to 2025 and every
+/// other run to after go-live.
+const REPORT_ACQUIRED: &str = r#"case "$raw" in
+    *backlog*) acquired="2025-03-01T09:00:00Z" ;;
+    *) acquired="2026-10-16T09:00:00Z" ;;
+esac
+printf 'Peptide Sequence,Precursor Mz,Retention Time,Total Area,Acquired Time\nPEPTIDEA,500.25,12.3,1000,%s\n' "$acquired" > "$report"
+"#;
+
+#[test]
+fn test_runs_acquired_before_cutoff_are_skipped() {
+    let install = TestInstall::new(REPORT_ACQUIRED);
+    let watch_dir = &install.watch_dir;
+
+    // Both files were copied in just now; only the report knows their age
+    let backlog_run = watch_dir.join("backlog_QC_A_A1.raw");
+    let live_run = watch_dir.join("live_QC_A_A1.raw");
+    std::fs::write(&backlog_run, b"fake raw data").unwrap();
+    std::fs::write(&live_run, b"fake raw data").unwrap();
+    install.queue_ready(&[&backlog_run, &live_run]);
+
+    let config = install
+        .config("cutoff-test")
+        .instrument(
+            "EXPLORIS01",
+            watch_dir,
+            r#"process_acquired_after = "2026-10-01T00:00:00Z""#,
+        )
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // Only the run acquired after go-live was spooled
+    let spooled: Vec<String> = install
+        .payloads(&["pending", "uploading", "failed", "observed"])
+        .iter()
+        .map(|p| p["run"]["raw_file_name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(spooled, ["live_QC_A_A1.raw"]);
+
+    // The backlog run is recorded as intentionally skipped, not failed
+    let skipped = install.decision(&backlog_run);
+    assert_eq!(skipped["decision"], "acquired_before_cutoff");
+    assert!(skipped["detail"].as_str().unwrap().contains("2025-03-01"));
+    assert_eq!(install.decision(&live_run)["decision"], "processed");
+
+    let failed_list = install.data_dir.join("failed_files.json");
+    if failed_list.exists() {
+        let failed = std::fs::read_to_string(failed_list).unwrap();
+        assert!(!failed.contains("backlog_QC_A_A1.raw"), "{}", failed);
+    }
+}
@@ -0,0 +1,183 @@
+//! `mdqc baseline refresh` fetches baselines from the cloud, caches the
+//! active one and prints what was returned, retrying failed fetches.
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+
+/// Serve one canned response per connection, collecting the request lines.
+fn serve(responses: Vec<(u16, String)>) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = Vec::new();
+            let mut buf = [0; 4096];
+            while !data.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buf[..n]);
+            }
+            let head = String::from_utf8_lossy(&data).to_string();
+            requests.push(head.lines().next().unwrap_or_default().to_string());
+            write!(
+                stream,
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+        requests
+    });
+    (port, handle)
+}
+
+fn baseline(id: &str, state: &str, established: &str) -> serde_json::Value {
+    serde_json::json!({
+        "baseline_id": id,
+        "instrument_id": "EXPLORIS01",
+        "method_id": null,
+        "template_hash": "sha256:abc123",
+        "kit_install_id": null,
+        "state": state,
+        "established": established,
+        "run_metrics": {
+            "targets_found": 2,
+            "targets_expected": 2,
+            "target_recovery_pct": 100.0,
+            "median_rt_shift": null,
+            "median_mass_error_ppm": null,
+            "chromatography_score": null
+        },
+        "target_metrics": [
+            {
+                "target_id": "PEPTIDEA", "peptide_sequence": "PEPTIDEA", "precursor_mz": 500.25,
+                "retention_time": 12.3, "rt_expected": null, "rt_delta": null, "peak_area": 1000.0,
+                "peak_height": 100.0, "peak_width_fwhm": null, "peak_symmetry": null,
+                "mass_error_ppm": null, "isotope_dot_product": null, "detected": true
+            },
+            {
+                "target_id": "PEPTIDEB", "peptide_sequence": "PEPTIDEB", "precursor_mz": 600.3,
+                "retention_time": 15.1, "rt_expected": null, "rt_delta": null, "peak_area": 2000.0,
+                "peak_height": 200.0, "peak_width_fwhm": null, "peak_symmetry": null,
+                "mass_error_ppm": null, "isotope_dot_product": null, "detected": true
+            }
+        ]
+    })
+}
+
+fn write_config(dir: &std::path::Path, port: u16) -> std::path::PathBuf {
+    let config_path = dir.join("config.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"
+[agent]
+agent_id = "baseline-test"
+
+[cloud]
+endpoint = "http://127.0.0.1:{port}/v1/"
+api_token = "token"
+baseline_fetch_retries = 1
+baseline_fetch_retry_delay_seconds = 0
+
+[[instruments]]
+id = "EXPLORIS01"
+vendor = "thermo"
+watch_path = "{watch}"
+template = "qc.sky"
+"#,
+            watch = dir.display(),
+        ),
+    )
+    .unwrap();
+    config_path
+}
+
+#[test]
+fn test_fetched_baseline_is_cached_and_printed() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("data");
+    let body = serde_json::json!({
+        "baselines": [
+            baseline("base_old", "ARCHIVED", "2026-01-15T10:00:00Z"),
+            baseline("base_new", "ACTIVE", "2026-10-01T10:00:00Z"),
+        ]
+    });
+    let (port, server) = serve(vec![(200, body.to_string())]);
+    let config_path = write_config(dir.path(), port);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(["baseline", "refresh", "--instrument", "EXPLORIS01"])
+        .env("MDQC_CONFIG", &config_path)
+        .env("MDQC_DATA_DIR", &data_dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+
+    let requests = server.join().unwrap();
+    assert_eq!(
+        requests,
+        ["GET /v1/baselines?instrument_id=EXPLORIS01 HTTP/1.1"]
+    );
+
+    // Both baselines are listed with their summaries
+    assert!(stdout.contains("[ARCHIVED  ] base_old  established 2026-01-15 10:00 UTC  template sha256:abc123  2 targets"), "{}", stdout);
+    assert!(stdout.contains("[ACTIVE    ] base_new"), "{}", stdout);
+    assert!(
+        stdout.contains("Cached active baseline: base_new"),
+        "{}",
+        stdout
+    );
+
+    // Only the active one is cached, with when it was fetched
+    let cache: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(data_dir.join("baselines/EXPLORIS01.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(cache["baseline"]["baseline_id"], "base_new");
+    assert_eq!(
+        cache["baseline"]["target_metrics"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(cache["fetched_at"].is_string());
+    assert_eq!(cache["stale"], false);
+}
+
+#[test]
+fn test_http_error_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let (port, server) = serve(vec![
+        (503, "maintenance".to_string()),
+        (503, "maintenance".to_string()),
+    ]);
+    let config_path = write_config(dir.path(), port);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(["baseline", "refresh"])
+        .env("MDQC_CONFIG", &config_path)
+        .env("MDQC_DATA_DIR", dir.path().join("data"))
+        .output()
+        .unwrap();
+
+    // Retried once before giving up
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(
+        stdout.contains("Refresh failed: Server error: 503 - maintenance"),
+        "{}",
+        stdout
+    );
+}
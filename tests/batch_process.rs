@@ -0,0 +1,64 @@
+//! `mdqc process --batch` hands Skyline only the folder's QC runs, never the
+//! samples acquired alongside them.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::TestInstall;
+
+/// Stub SkylineCmd step for `--import-all`: lists what it was given in
+/// `imported.txt` and reports one row per run.
+const IMPORT_ALL: &str = r#"[ -n "$folder" ] || exit 0
+ls "$folder" > "$(dirname "$0")/imported.txt"
+printf 'File Name,Peptide Sequence,Precursor Mz,Total Area\n' > "$report"
+for run in "$folder"/*; do
+    printf '%s,PEPTIDEA,500.25,1000\n' "$(basename "$run")" >> "$report"
+done
+"#;
+
+#[test]
+fn test_batch_imports_only_qc_runs() {
+    let install = TestInstall::new(IMPORT_ALL);
+    let folder = &install.watch_dir;
+    for name in [
+        "20261016_QC_A_A1.raw",
+        "20261016_SAMPLE_P1_B1.raw",
+        "20261016_QC_A_A2.raw",
+        "20261016_SAMPLE_P1_B2.raw",
+    ] {
+        std::fs::write(folder.join(name), b"fake raw data").unwrap();
+    }
+
+    let config = install
+        .config("batch-test")
+        .instrument("EXPLORIS01", folder, "")
+        .write();
+    let output = install
+        .mdqc(&config)
+        .args(["process", "--batch", folder.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let imported = std::fs::read_to_string(install.path().join("imported.txt")).unwrap();
+    assert_eq!(
+        imported.lines().collect::<Vec<_>>(),
+        ["20261016_QC_A_A1.raw", "20261016_QC_A_A2.raw"]
+    );
+
+    // Both QC runs were split out of the combined report and spooled
+    assert_eq!(install.payloads(&["pending"]).len(), 2);
+
+    // The staging folder is gone and the runs are still in place
+    let work = install.data_dir.join("spool").join("work");
+    let leftovers: Vec<_> = std::fs::read_dir(&work)
+        .map(|entries| entries.flatten().map(|e| e.file_name()).collect())
+        .unwrap_or_default();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+    assert_eq!(std::fs::read_dir(folder).unwrap().count(), 4);
+}
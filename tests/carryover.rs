@@ -0,0 +1,65 @@
+//! With `[instruments.sample_carryover_check]`, a qualifying SAMPLE run is
+//! extracted for the QC targets and audited as a carryover check; other
+//! SAMPLE runs are skipped as usual.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::TestInstall;
+
+/// Stub SkylineCmd step reporting a trace of the QC peptide in SAMPLE runs.
+const REPORT_TRACE: &str = r#"case "$raw" in
+    *SAMPLE*) area=5 ;;
+    *) area=1000 ;;
+esac
+printf 'Peptide Sequence,Precursor Mz,Retention Time,Total Area\nPEPTIDEA,500.25,12.3,%s\n' "$area" > "$report"
+"#;
+
+#[test]
+fn test_sample_after_qc_gets_carryover_check() {
+    let install = TestInstall::new(REPORT_TRACE);
+    let watch_dir = &install.watch_dir;
+
+    let qc_run = watch_dir.join("20261016_QC_A_A1.raw");
+    let checked_sample = watch_dir.join("20261016_SAMPLE_C5.raw");
+    let skipped_sample = watch_dir.join("20261016_SAMPLE_C6.raw");
+    for run in [&qc_run, &checked_sample, &skipped_sample] {
+        std::fs::write(run, b"fake raw data").unwrap();
+    }
+    install.queue_ready(&[&qc_run, &checked_sample, &skipped_sample]);
+
+    let config = install
+        .config("carryover-test")
+        .instrument(
+            "EXPLORIS01",
+            watch_dir,
+            "[instruments.sample_carryover_check]\nafter_qc = true",
+        )
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    assert_eq!(install.decision(&qc_run)["decision"], "processed");
+
+    // The first sample after QC is checked against the QC run's area
+    let checked = install.decision(&checked_sample);
+    assert_eq!(checked["decision"], "carryover_checked");
+    assert_eq!(
+        checked["detail"],
+        "1/1 QC targets detected, carryover 0.50% of the last QC run"
+    );
+
+    // The next one doesn't qualify
+    assert_eq!(
+        install.decision(&skipped_sample)["decision"],
+        "skipped_non_qc"
+    );
+
+    // Only the QC run was spooled
+    assert_eq!(
+        install
+            .payload_files(&["pending", "uploading", "failed"])
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,264 @@
+//! Setup shared by the integration tests that run the `mdqc` binary: a temp
+//! install with a watch folder, data directory, QC template and stub
+//! SkylineCmd, and a builder for its config file.
+
+#![allow(dead_code)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Start of every stub SkylineCmd: answers `--version` and leaves the run in
+/// `$raw` (or the batch folder in `$folder`) and the report path in `$report`.
+const SKYLINE_ARGS: &str = r#"#!/bin/sh
+for arg in "$@"; do
+    case "$arg" in
+        --version) echo "Skyline 24.1.0.198"; exit 0 ;;
+        --import-file=*) raw="${arg#--import-file=}" ;;
+        --import-all=*) folder="${arg#--import-all=}" ;;
+        --report-file=*) report="${arg#--report-file=}" ;;
+    esac
+done
+"#;
+
+/// Stub script step writing a one-row report.
+pub const WRITE_REPORT: &str = r#"printf 'Peptide Sequence,Precursor Mz,Retention Time,Total Area\nPEPTIDEA,500.25,12.3,1000\n' > "$report"
+"#;
+
+/// Stub script step appending the imported run to `imported.txt` in the
+/// install folder.
+pub const LOG_IMPORT: &str = r#"echo "$raw" >> "$(dirname "$0")/imported.txt"
+"#;
+
+/// A temp install; removed when dropped.
+pub struct TestInstall {
+    dir: tempfile::TempDir,
+    /// The instrument's watch folder
+    pub watch_dir: PathBuf,
+    /// `MDQC_DATA_DIR`
+    pub data_dir: PathBuf,
+    pub skyline: PathBuf,
+    pub template: PathBuf,
+}
+
+impl TestInstall {
+    /// An install whose SkylineCmd runs `script` after parsing its arguments.
+    pub fn new(script: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let watch_dir = dir.path().join("instrument");
+        let data_dir = dir.path().join("data");
+        std::fs::create_dir_all(&watch_dir).unwrap();
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let skyline = dir.path().join("SkylineCmd");
+        std::fs::write(&skyline, format!("{}{}", SKYLINE_ARGS, script)).unwrap();
+        std::fs::set_permissions(&skyline, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let template = dir.path().join("qc.sky");
+        std::fs::write(&template, "template").unwrap();
+
+        Self {
+            dir,
+            watch_dir,
+            data_dir,
+            skyline,
+            template,
+        }
+    }
+
+    /// Root of the install.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// A config file for this install, to be filled in and written.
+    pub fn config(&self, agent_id: &str) -> ConfigBuilder<'_> {
+        ConfigBuilder {
+            install: self,
+            agent: format!(
+                "agent_id = \"{}\"\nenable_toast_notifications = false\n",
+                agent_id
+            ),
+            endpoint: "http://127.0.0.1:9/v1/".to_string(),
+            cloud: String::new(),
+            skyline: String::new(),
+            sections: String::new(),
+            instruments: String::new(),
+        }
+    }
+
+    /// Leave Thermo `runs` in the ready queue, a minute apart in that order,
+    /// so the agent processes them at startup without waiting on a watcher.
+    pub fn queue_ready(&self, runs: &[&Path]) {
+        let files: Vec<_> = runs
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                serde_json::json!({
+                    "path": path,
+                    "vendor": "thermo",
+                    "queued_at": format!("2026-10-16T09:{:02}:00Z", i),
+                })
+            })
+            .collect();
+        std::fs::write(
+            self.data_dir.join("ready_queue.json"),
+            serde_json::json!({ "files": files }).to_string(),
+        )
+        .unwrap();
+    }
+
+    /// `mdqc` run against this install and `config`.
+    pub fn mdqc(&self, config: &Path) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_mdqc"));
+        command
+            .env("MDQC_CONFIG", config)
+            .env("MDQC_DATA_DIR", &self.data_dir)
+            .env("NO_COLOR", "1");
+        command
+    }
+
+    /// Run the foreground agent until `exit_after` (e.g. "5s"), output
+    /// discarded.
+    pub fn run_agent(&self, config: &Path, exit_after: &str) -> ExitStatus {
+        self.mdqc(config)
+            .args(["run", "--foreground", "--exit-after", exit_after])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+    }
+
+    /// Records of the audit log.
+    pub fn audit(&self) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(self.data_dir.join("audit.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// The audit record of the run at `path`.
+    pub fn decision(&self, path: &Path) -> serde_json::Value {
+        self.audit()
+            .into_iter()
+            .find(|record| record["path"] == path.to_str().unwrap())
+            .unwrap_or_else(|| panic!("no audit record for {}", path.display()))
+    }
+
+    /// QC payloads in the given spool folders (`pending`, `observed`, ...).
+    pub fn payloads(&self, states: &[&str]) -> Vec<serde_json::Value> {
+        self.payload_files(states)
+            .iter()
+            .map(|path| serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap())
+            .collect()
+    }
+
+    /// Paths of the QC payloads in the given spool folders.
+    pub fn payload_files(&self, states: &[&str]) -> Vec<PathBuf> {
+        let spool = self.data_dir.join("spool");
+        states
+            .iter()
+            .filter_map(|state| std::fs::read_dir(spool.join(state)).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter(|e| e.file_name().to_string_lossy().ends_with("_payload.json"))
+            .map(|e| e.path())
+            .collect()
+    }
+
+    /// Contents of `failed_files.json`.
+    pub fn failed_files(&self) -> serde_json::Value {
+        let content = std::fs::read_to_string(self.data_dir.join("failed_files.json")).unwrap();
+        serde_json::from_str(&content).unwrap()
+    }
+}
+
+/// Config file of a [`TestInstall`]: its SkylineCmd, an unreachable cloud
+/// endpoint, and whatever the test adds.
+pub struct ConfigBuilder<'a> {
+    install: &'a TestInstall,
+    agent: String,
+    endpoint: String,
+    cloud: String,
+    skyline: String,
+    sections: String,
+    instruments: String,
+}
+
+impl ConfigBuilder<'_> {
+    /// Add keys to `[agent]`.
+    pub fn agent(mut self, toml: &str) -> Self {
+        self.agent.push_str(toml);
+        self.agent.push('\n');
+        self
+    }
+
+    /// Replace the cloud endpoint.
+    pub fn endpoint(mut self, url: &str) -> Self {
+        self.endpoint = url.to_string();
+        self
+    }
+
+    /// Add keys to `[cloud]`.
+    pub fn cloud(mut self, toml: &str) -> Self {
+        self.cloud.push_str(toml);
+        self.cloud.push('\n');
+        self
+    }
+
+    /// Add keys to `[skyline]`.
+    pub fn skyline(mut self, toml: &str) -> Self {
+        self.skyline.push_str(toml);
+        self.skyline.push('\n');
+        self
+    }
+
+    /// Add whole tables, e.g. `[watcher]`.
+    pub fn section(mut self, toml: &str) -> Self {
+        self.sections.push_str(toml);
+        self.sections.push('\n');
+        self
+    }
+
+    /// Add an instrument using the install's template. Unless `toml` sets
+    /// them, it is a Thermo instrument whose `file_pattern` matches nothing,
+    /// so runs only arrive through the ready queue.
+    pub fn instrument(mut self, id: &str, watch_dir: &Path, toml: &str) -> Self {
+        let sets = |key: &str| {
+            toml.lines()
+                .take_while(|line| !line.starts_with('['))
+                .any(|line| line.split('=').next().unwrap().trim() == key)
+        };
+        self.instruments.push_str(&format!(
+            "\n[[instruments]]\nid = \"{}\"\nwatch_path = \"{}\"\ntemplate = \"{}\"\n",
+            id,
+            watch_dir.display(),
+            self.install.template.display()
+        ));
+        if !sets("vendor") {
+            self.instruments.push_str("vendor = \"thermo\"\n");
+        }
+        if !sets("file_pattern") {
+            self.instruments.push_str("file_pattern = \"*.never\"\n");
+        }
+        self.instruments.push_str(toml);
+        self.instruments.push('\n');
+        self
+    }
+
+    /// Write the file as `config.toml` in the install and return its path.
+    pub fn write(self) -> PathBuf {
+        let content = format!(
+            "[agent]\n{}\n[cloud]\nendpoint = \"{}\"\n{}\n[skyline]\npath = \"{}\"\ntimeout_seconds = 30\n{}\n{}{}",
+            self.agent,
+            self.endpoint,
+            self.cloud,
+            self.install.skyline.display(),
+            self.skyline,
+            self.sections,
+            self.instruments,
+        );
+        let path = self.install.path().join("config.toml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
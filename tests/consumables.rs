@@ -0,0 +1,86 @@
+//! Consumable lots from the config and the run folder's `.mdqc_context.json`
+//! are attached to the payload, and a change since the previous run is
+//! logged.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+#[test]
+fn test_consumables_flow_into_payload_and_changes_are_logged() {
+    let install = TestInstall::new(WRITE_REPORT);
+    let (watch_dir, data_dir) = (&install.watch_dir, &install.data_dir);
+
+    // The previous run was on column COL-1
+    std::fs::write(
+        data_dir.join("consumables.json"),
+        r#"{"instruments": {"EXPLORIS01": {"column_serial": "COL-1", "trap": "T-7"}}}"#,
+    )
+    .unwrap();
+    // The technician swapped the column and noted it next to the runs
+    std::fs::write(
+        watch_dir.join(".mdqc_context.json"),
+        r#"{"consumables": {"column_serial": "COL-2", "emitter_lot": "E-2231"}}"#,
+    )
+    .unwrap();
+
+    let raw_file = watch_dir.join("20261016_QC_A_A1.raw");
+    std::fs::write(&raw_file, b"fake raw data").unwrap();
+    install.queue_ready(&[&raw_file]);
+
+    let config = install
+        .config("consumables-test")
+        .instrument(
+            "EXPLORIS01",
+            watch_dir,
+            "[instruments.consumables]\ncolumn_serial = \"COL-1\"\ntrap = \"T-7\"",
+        )
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // The payload carries the configured values with the sidecar's on top
+    let payloads = install.payloads(&["pending", "uploading", "failed"]);
+    let payload = payloads.first().expect("payload spooled");
+    assert_eq!(
+        payload["run"]["consumables"],
+        serde_json::json!({
+            "column_serial": "COL-2",
+            "trap": "T-7",
+            "emitter_lot": "E-2231",
+        })
+    );
+
+    // The column swap and the new emitter lot are logged; the trap is unchanged
+    let mut changes: Vec<String> = std::fs::read_dir(install.data_dir.join("logs"))
+        .unwrap()
+        .flatten()
+        .flat_map(|e| {
+            std::fs::read_to_string(e.path())
+                .unwrap()
+                .lines()
+                .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                .filter(|event| {
+                    event["fields"]["message"] == "Consumable changed since the previous run"
+                })
+                .map(|event| event["fields"]["change"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    changes.sort();
+    assert_eq!(
+        changes,
+        [
+            "column_serial: COL-1 -> COL-2",
+            "emitter_lot: (none) -> E-2231"
+        ]
+    );
+
+    // ...and the new values are remembered for the next run
+    let store: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(install.data_dir.join("consumables.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(store["instruments"]["EXPLORIS01"]["column_serial"], "COL-2");
+}
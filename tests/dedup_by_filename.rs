@@ -0,0 +1,64 @@
+//! With `agent.dedup_by_filename`, a QC run whose file name was already
+//! processed on the instrument is skipped, even from another folder.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+#[test]
+fn test_repeated_file_name_is_skipped() {
+    let install = TestInstall::new(WRITE_REPORT);
+    let (watch_dir, data_dir) = (&install.watch_dir, &install.data_dir);
+
+    // The same name was processed yesterday from an export folder
+    std::fs::write(
+        data_dir.join("history.json"),
+        serde_json::json!({
+            "runs": [{
+                "path": install.path().join("exports").join("20261016_QC_A_A1.raw"),
+                "instrument_id": "EXPLORIS01",
+                "template_hash": "aaa",
+                "processed_at": chrono::Utc::now() - chrono::Duration::days(1),
+            }]
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let repeated_run = watch_dir.join("20261016_QC_A_A1.raw");
+    let new_run = watch_dir.join("20261016_QC_B_A2.raw");
+    for run in [&repeated_run, &new_run] {
+        std::fs::write(run, b"fake raw data").unwrap();
+    }
+    install.queue_ready(&[&repeated_run, &new_run]);
+
+    let config = install
+        .config("dedup-test")
+        .agent("dedup_by_filename = true")
+        .instrument("EXPLORIS01", watch_dir, "")
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    let skipped = install.decision(&repeated_run);
+    assert_eq!(skipped["decision"], "skipped_duplicate");
+    assert!(skipped["detail"]
+        .as_str()
+        .unwrap()
+        .contains("exports/20261016_QC_A_A1.raw"));
+    assert_eq!(install.decision(&new_run)["decision"], "processed");
+
+    // Only the new run was spooled
+    let spooled: Vec<String> = install
+        .payloads(&["pending", "uploading", "failed"])
+        .iter()
+        .map(|payload| {
+            payload["run"]["raw_file_name"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(spooled, ["20261016_QC_B_A2.raw"]);
+}
@@ -0,0 +1,87 @@
+//! `mdqc diff` compares two spooled payloads target by target.
+
+use std::process::Command;
+
+const RUN_A: &str = "11111111-1111-4111-8111-111111111111";
+const RUN_B: &str = "22222222-2222-4222-8222-222222222222";
+
+fn mdqc(data_dir: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(args)
+        .env("MDQC_CONFIG", data_dir.join("config.toml"))
+        .env("MDQC_DATA_DIR", data_dir)
+        // Console logs share stdout with the JSON
+        .env("MDQC_LOG_LEVEL", "error")
+        .env_remove("MDQC_PROFILE")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_diff_two_payloads() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("config.toml"), "").unwrap();
+
+    // One run already uploaded, the other still pending
+    let completed = dir.path().join("spool").join("completed");
+    let pending = dir.path().join("spool").join("pending");
+    std::fs::create_dir_all(&completed).unwrap();
+    std::fs::create_dir_all(&pending).unwrap();
+    std::fs::write(
+        completed.join(format!("{}_payload.json", RUN_A)),
+        include_str!("fixtures/payload_a.json"),
+    )
+    .unwrap();
+    std::fs::write(
+        pending.join(format!("{}_payload.json", RUN_B)),
+        include_str!("fixtures/payload_b.json"),
+    )
+    .unwrap();
+
+    let output = mdqc(dir.path(), &["diff", RUN_A, RUN_B, "--json"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let diff = &json["diff"];
+    let approx = |value: &serde_json::Value, expected: f64| {
+        (value.as_f64().unwrap() - expected).abs() < 1e-6
+    };
+
+    let targets = diff["targets"].as_array().unwrap();
+    assert_eq!(targets.len(), 2);
+
+    assert_eq!(targets[0]["target_id"], "LVNELTEFAK");
+    assert!(approx(&targets[0]["rt_shift"], 0.3));
+    assert!(approx(&targets[0]["area_ratio"], 0.9));
+    assert!(approx(&targets[0]["mass_error_delta_ppm"], 1.0));
+    assert_eq!(targets[0]["outlier"], false);
+
+    // Area dropped to 40% of run A
+    assert_eq!(targets[1]["target_id"], "HLVDEPQNLIK");
+    assert!(approx(&targets[1]["area_ratio"], 0.4));
+    assert_eq!(targets[1]["outlier"], true);
+
+    assert_eq!(diff["only_in_a"], serde_json::json!(["YLYEIAR"]));
+    assert_eq!(diff["only_in_b"], serde_json::json!([]));
+
+    let run_metrics = diff["run_metrics"].as_array().unwrap();
+    let delta =
+        |name: &str| run_metrics.iter().find(|m| m["metric"] == name).unwrap()["delta"].clone();
+    assert!(approx(&delta("targets_found"), -1.0));
+    assert!(approx(&delta("median_mass_error_ppm"), 0.9));
+
+    // The text report flags the outlier
+    let output = mdqc(dir.path(), &["diff", RUN_A, RUN_B]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("! HLVDEPQNLIK"), "{}", stdout);
+    assert!(stdout.contains("Only in A: YLYEIAR"), "{}", stdout);
+
+    let output = mdqc(dir.path(), &["diff", RUN_A, "QC_MISSING.raw"]);
+    assert!(!output.status.success());
+}
@@ -0,0 +1,65 @@
+//! A job file in the drop-queue directory hands a run to the agent, with the
+//! job's classification fields taking precedence over the file name.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+#[test]
+fn test_drop_queue_job_triggers_processing() {
+    let install = TestInstall::new(WRITE_REPORT);
+    let export_dir = install.path().join("exports");
+    let queue_dir = install.path().join("queue");
+    for d in [&export_dir, &queue_dir] {
+        std::fs::create_dir_all(d).unwrap();
+    }
+
+    // Outside the watch folder, and nothing in the name says it's a QC run
+    let raw_file = export_dir.join("plate7_17.raw");
+    std::fs::write(&raw_file, b"fake raw data").unwrap();
+    std::fs::write(
+        queue_dir.join("job-1.json"),
+        serde_json::json!({
+            "path": raw_file,
+            "instrument": "EXPLORIS01",
+            "control_type": "QC_B",
+            "well_position": "A3",
+            "plate_id": "P-7",
+        })
+        .to_string(),
+    )
+    .unwrap();
+    std::fs::write(
+        queue_dir.join("job-2.json"),
+        serde_json::json!({ "path": raw_file, "instrument": "UNKNOWN01" }).to_string(),
+    )
+    .unwrap();
+
+    let config = install
+        .config("drop-queue-test")
+        .section(&format!(
+            "[drop_queue]\npath = \"{}\"\nscan_interval_seconds = 1",
+            queue_dir.display()
+        ))
+        .instrument("EXPLORIS01", &install.watch_dir, "")
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // The run was extracted and spooled with the job's classification
+    let payloads = install.payloads(&["pending", "uploading", "failed"]);
+    assert_eq!(payloads.len(), 1);
+    let run = &payloads[0]["run"];
+    assert_eq!(run["raw_file_name"], "plate7_17.raw");
+    assert_eq!(run["instrument_id"], "EXPLORIS01");
+    assert_eq!(run["control_type"], "QC_B");
+    assert_eq!(run["well_position"], "A3");
+    assert_eq!(run["plate_id"], "P-7");
+
+    // The job was archived and the invalid one set aside
+    assert!(queue_dir.join("archive").join("job-1.json").exists());
+    assert!(queue_dir.join("rejected").join("job-2.json").exists());
+    assert!(!queue_dir.join("job-1.json").exists());
+    assert!(!queue_dir.join("job-2.json").exists());
+}
@@ -0,0 +1,87 @@
+//! `mdqc failed retry all` classifies, extracts and spools each failed file
+//! in-process, without a running agent, and keeps the ones that still fail.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+/// Stub SkylineCmd step failing on runs named BROKEN.
+const FAIL_BROKEN: &str = r#"case "$raw" in
+    *BROKEN*) echo "Failed importing the results file" >&2; exit 1 ;;
+esac
+"#;
+
+#[test]
+fn test_retry_all_reprocesses_in_process() {
+    let install = TestInstall::new(&format!("{}{}", FAIL_BROKEN, WRITE_REPORT));
+    let watch_dir = &install.watch_dir;
+
+    let fixed = watch_dir.join("20261016_QC_A_A1.raw");
+    let broken = watch_dir.join("20261016_QC_B_A3_BROKEN.raw");
+    for run in [&fixed, &broken] {
+        std::fs::write(run, b"fake raw data").unwrap();
+    }
+    let failed_entry = |path: &std::path::Path| {
+        serde_json::json!({
+            "path": path,
+            "instrument_id": "EXPLORIS01",
+            "reason": "Skyline timed out",
+            "failed_at": "2026-10-16T09:00:00Z",
+            "retry_count": 0,
+        })
+    };
+    std::fs::write(
+        install.data_dir.join("failed_files.json"),
+        serde_json::json!({
+            "files": {
+                fixed.to_str().unwrap(): failed_entry(&fixed),
+                broken.to_str().unwrap(): failed_entry(&broken),
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let config = install
+        .config("retry-test")
+        .instrument("EXPLORIS01", watch_dir, "")
+        .write();
+
+    let output = install
+        .mdqc(&config)
+        .args(["failed", "retry", "all"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Extracted: 1/1 targets"), "{stdout}");
+    assert!(stdout.contains("Summary"), "{stdout}");
+    assert!(stdout.contains("1 succeeded, 1 failed"), "{stdout}");
+
+    // The fixed run was spooled and left the failed list
+    let pending = install.payload_files(&["pending"]);
+    assert_eq!(pending.len(), 1);
+    assert!(stdout.contains(&pending[0].display().to_string()));
+
+    // Only the fixed run is recorded in the run history
+    let history: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(install.data_dir.join("history.json")).unwrap(),
+    )
+    .unwrap();
+    let runs = history["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["path"], fixed.to_str().unwrap());
+
+    // The broken one stays, with the retry counted once and the new reason
+    let failed = install.failed_files();
+    let files = failed["files"].as_object().unwrap();
+    assert_eq!(files.len(), 1);
+    let entry = &files[broken.to_str().unwrap()];
+    assert_eq!(entry["retry_count"], 1);
+    assert!(entry["reason"]
+        .as_str()
+        .unwrap()
+        .starts_with("Extraction failed"));
+}
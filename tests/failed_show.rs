@@ -0,0 +1,87 @@
+//! A failed extraction keeps Skyline's exit code and output, and
+//! `mdqc failed show` prints them back.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Stdio;
+
+use common::TestInstall;
+
+/// Stub SkylineCmd step failing every import the way a missing vendor
+/// reader does.
+const FAIL_IMPORT: &str = r#"[ -n "$raw" ] || exit 0
+echo "Importing results..."
+echo "Error: Failed to open raw file"
+echo "vendor reader not registered" >&2
+exit 3
+"#;
+
+#[test]
+fn test_failed_extraction_diagnostics_are_retrievable() {
+    let install = TestInstall::new(FAIL_IMPORT);
+
+    let raw_file = install.watch_dir.join("20261016_QC_A_A1.raw");
+    std::fs::write(&raw_file, b"fake raw data").unwrap();
+    install.queue_ready(&[&raw_file]);
+
+    let config = install
+        .config("failed-show-test")
+        .instrument("EXPLORIS01", &install.watch_dir, "")
+        .write();
+    let mdqc = |args: &[&str]| {
+        install
+            .mdqc(&config)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    let run = mdqc(&["run", "--foreground", "--exit-after", "5s"]);
+    assert!(run.status.success());
+
+    // The failed entry carries what Skyline reported
+    let store = install.failed_files();
+    let entry = &store["files"][raw_file.to_str().unwrap()];
+    let diagnostics = &entry["diagnostics"];
+    assert_eq!(diagnostics["exit_code"], 3);
+    assert_eq!(diagnostics["stderr"], "vendor reader not registered");
+    assert!(diagnostics["stdout_tail"]
+        .as_str()
+        .unwrap()
+        .ends_with("Error: Failed to open raw file"));
+    assert_eq!(diagnostics["template"], install.template.to_str().unwrap());
+    assert_eq!(diagnostics["classification"]["control_type"], "QC_A");
+
+    // ...and `failed show` prints it, by path or as the latest failure
+    for target in [raw_file.to_str().unwrap(), "latest"] {
+        let show = mdqc(&["failed", "show", target]);
+        assert!(show.status.success());
+        let stdout = String::from_utf8_lossy(&show.stdout);
+        assert!(stdout.contains("Exit code:  3"), "{}", stdout);
+        assert!(
+            stdout.contains("vendor reader not registered"),
+            "{}",
+            stdout
+        );
+        assert!(
+            stdout.contains("Error: Failed to open raw file"),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("Classified: QC_A (well A1"), "{}", stdout);
+    }
+
+    // Re-running reports the fresh failure without touching the stored one
+    let rerun = mdqc(&["failed", "show", "latest", "--rerun"]);
+    assert!(rerun.status.success());
+    let stdout = String::from_utf8_lossy(&rerun.stdout);
+    assert!(stdout.contains("Re-running extraction of"), "{}", stdout);
+    assert!(
+        stdout.contains("Extraction failed: Skyline exited with code 3"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,128 @@
+//! Observe-only instruments extract without spooling for upload; detect-only
+//! instruments never extract, in the agent, `mdqc process` and
+//! `mdqc failed retry` alike.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, LOG_IMPORT, WRITE_REPORT};
+
+fn install() -> TestInstall {
+    TestInstall::new(&format!("{}{}", LOG_IMPORT, WRITE_REPORT))
+}
+
+#[test]
+fn test_observe_only_and_detect_only_instruments() {
+    let install = install();
+    let observe_dir = install.path().join("observe");
+    let detect_dir = install.path().join("detect");
+    for d in [&observe_dir, &detect_dir] {
+        std::fs::create_dir_all(d).unwrap();
+    }
+
+    let observed_run = observe_dir.join("20261016_QC_A_A1.raw");
+    let detected_run = detect_dir.join("20261016_QC_B_A2.raw");
+    std::fs::write(&observed_run, b"fake raw data").unwrap();
+    std::fs::write(&detected_run, b"fake raw data").unwrap();
+    install.queue_ready(&[&observed_run, &detected_run]);
+
+    let config = install
+        .config("mode-test")
+        .instrument("OBSERVE01", &observe_dir, r#"mode = "observe_only""#)
+        .instrument("DETECT01", &detect_dir, r#"mode = "detect_only""#)
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // Only the observe-only run reached Skyline (other calls import nothing)
+    let imported = std::fs::read_to_string(install.path().join("imported.txt")).unwrap();
+    let imported: Vec<&str> = imported.lines().filter(|l| !l.is_empty()).collect();
+    assert_eq!(imported, [observed_run.to_str().unwrap()]);
+
+    // Nothing was spooled for upload; the observed payload is kept aside
+    for state in ["pending", "uploading", "failed", "completed"] {
+        assert!(install.payloads(&[state]).is_empty(), "{}", state);
+    }
+    let observed = install.payloads(&["observed"]);
+    assert_eq!(observed.len(), 1);
+    assert_eq!(observed[0]["run"]["instrument_id"], "OBSERVE01");
+
+    // Both decisions are in the audit log
+    let decisions: Vec<(String, String)> = install
+        .audit()
+        .iter()
+        .map(|record| {
+            (
+                record["instrument_id"].as_str().unwrap().to_string(),
+                record["decision"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert!(decisions.contains(&("OBSERVE01".into(), "observed".into())));
+    assert!(decisions.contains(&("DETECT01".into(), "detected".into())));
+}
+
+#[test]
+fn test_detect_only_in_process_and_failed_retry() {
+    let install = install();
+    let detect_dir = &install.watch_dir;
+
+    let batch_run = detect_dir.join("20261016_QC_A_A1.raw");
+    let failed_run = detect_dir.join("20261016_QC_B_A2.raw");
+    std::fs::write(&batch_run, b"fake raw data").unwrap();
+    std::fs::write(&failed_run, b"fake raw data").unwrap();
+
+    let config = install
+        .config("mode-test")
+        .instrument("DETECT01", detect_dir, r#"mode = "detect_only""#)
+        .write();
+    let mdqc = |args: &[&str]| {
+        let output = install.mdqc(&config).args(args).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let stdout = mdqc(&["process", "--batch", detect_dir.to_str().unwrap()]);
+    assert!(
+        stdout.contains("Detect-only instrument, not extracting"),
+        "{stdout}"
+    );
+
+    std::fs::write(
+        install.data_dir.join("failed_files.json"),
+        serde_json::json!({
+            "files": {
+                failed_run.to_str().unwrap(): {
+                    "path": failed_run,
+                    "instrument_id": "DETECT01",
+                    "reason": "Skyline timed out",
+                    "failed_at": "2026-10-16T09:00:00Z",
+                    "retry_count": 0,
+                }
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let stdout = mdqc(&["failed", "retry", "all"]);
+    assert!(
+        stdout.contains("detect-only instrument, not extracted"),
+        "{stdout}"
+    );
+
+    // Neither reached Skyline nor the spool, and the retried run is done
+    assert!(!install.path().join("imported.txt").exists());
+    assert!(install.payloads(&["pending", "observed"]).is_empty());
+    assert!(install.failed_files()["files"]
+        .as_object()
+        .unwrap()
+        .is_empty());
+
+    let detected = install
+        .audit()
+        .iter()
+        .filter(|record| record["decision"] == "detected")
+        .count();
+    // Both runs in the batch, then the retried one
+    assert_eq!(detected, 3);
+}
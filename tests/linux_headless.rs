@@ -0,0 +1,152 @@
+//! End-to-end test of the headless Linux foreground agent.
+//!
+//! Runs the real `mdqc` binary against a temp watch folder, a stub SkylineCmd
+//! script, and a minimal HTTP server standing in for the ingest endpoint.
+
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+/// Stub SkylineCmd step printing an import warning.
+const IMPORT_WARNING: &str = "echo \"Warning: Failed to import some transitions for PEPTIDEB\"\n";
+
+/// Kills the agent if the test fails before shutting it down.
+struct Agent(Child);
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Accept HTTP requests, forwarding each `(request line, body)` and replying 200.
+fn spawn_mock_ingest(listener: TcpListener) -> mpsc::Receiver<(String, String)> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}")
+                .unwrap();
+
+            let _ = tx.send((
+                request_line.trim().to_string(),
+                String::from_utf8_lossy(&body).to_string(),
+            ));
+        }
+    });
+
+    rx
+}
+
+#[test]
+fn test_headless_watch_extract_spool_upload() {
+    let install = TestInstall::new(&format!("{}{}", IMPORT_WARNING, WRITE_REPORT));
+    let watch_dir = &install.watch_dir;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = spawn_mock_ingest(listener);
+
+    let config = install
+        .config("linux-e2e")
+        .endpoint(&format!("http://127.0.0.1:{port}/v1/"))
+        .cloud(r#"api_token = "test-token""#)
+        .section("[watcher]\nscan_interval_seconds = 1\nstability_window_seconds = 0")
+        .instrument("LINUX01", watch_dir, r#"file_pattern = "*.raw""#)
+        .write();
+
+    let mut agent = Agent(
+        install
+            .mdqc(&config)
+            .arg("run")
+            .env("NO_PROXY", "127.0.0.1")
+            .env("no_proxy", "127.0.0.1")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    // Give the watchers a moment to start before the run "completes"
+    std::thread::sleep(Duration::from_secs(1));
+    std::fs::write(watch_dir.join("20261016_QC_A_A1.raw"), b"fake raw data").unwrap();
+
+    // The agent also fetches baselines at startup; wait for the upload
+    let (request_line, body) =
+        std::iter::from_fn(|| requests.recv_timeout(Duration::from_secs(90)).ok())
+            .find(|(request_line, _)| !request_line.starts_with("GET /v1/baselines"))
+            .expect("no upload received");
+    assert!(
+        request_line.starts_with("POST /v1/ingest"),
+        "{}",
+        request_line
+    );
+
+    let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["agent_id"], "linux-e2e");
+    assert_eq!(payload["run"]["raw_file_name"], "20261016_QC_A_A1.raw");
+    assert_eq!(payload["run"]["instrument_id"], "LINUX01");
+    assert_eq!(payload["run"]["control_type"], "QC_A");
+    assert_eq!(
+        payload["extraction"]["import_warnings"][0],
+        "Warning: Failed to import some transitions for PEPTIDEB"
+    );
+
+    // The spooled payload moves to completed once the upload is acknowledged
+    let completed_dir = install.data_dir.join("spool").join("completed");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while std::fs::read_dir(&completed_dir).map_or(0, |d| d.count()) == 0 {
+        assert!(Instant::now() < deadline, "payload never marked completed");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // SIGTERM stops the foreground agent cleanly
+    let status = Command::new("kill")
+        .arg(agent.0.id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let exit = loop {
+        if let Some(exit) = agent.0.try_wait().unwrap() {
+            break exit;
+        }
+        assert!(Instant::now() < deadline, "agent did not exit on SIGTERM");
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(exit.success(), "agent exited with {}", exit);
+}
@@ -0,0 +1,80 @@
+//! With `skyline.preflight_probe`, a raw file Skyline can't open fails on the
+//! quick probe import and the full extraction is never run; a file that
+//! can't even be read never reaches Skyline.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+/// Stub SkylineCmd step that logs "probe" for an import without a report and
+/// "extract" for a full extraction, and can't open the A2 run.
+const PROBE_STUB: &str = r#"[ -n "$raw" ] || exit 0
+if [ -n "$report" ]; then
+    echo "extract $(basename "$raw")" >> "$(dirname "$0")/calls.txt"
+else
+    echo "probe $(basename "$raw")" >> "$(dirname "$0")/calls.txt"
+fi
+case "$raw" in
+    *_A2.raw) echo "Error: The file could not be opened."; exit 1 ;;
+esac
+[ -z "$report" ] && exit 0
+"#;
+
+#[test]
+fn test_failed_preflight_probe_skips_extraction() {
+    let install = TestInstall::new(&format!("{}{}", PROBE_STUB, WRITE_REPORT));
+
+    // Fed in through the ready queue so the test doesn't wait on the watcher:
+    // an empty run, one Skyline can't open, and a good one
+    let empty = install.watch_dir.join("20261016_QC_A_A1.raw");
+    std::fs::write(&empty, b"").unwrap();
+    let unopenable = install.watch_dir.join("20261016_QC_A_A2.raw");
+    std::fs::write(&unopenable, b"fake raw data").unwrap();
+    let good = install.watch_dir.join("20261016_QC_A_A3.raw");
+    std::fs::write(&good, b"fake raw data").unwrap();
+    install.queue_ready(&[&empty, &unopenable, &good]);
+
+    let config = install
+        .config("preflight-test")
+        .skyline("preflight_probe = true\npreflight_timeout_seconds = 10")
+        .instrument("EXPLORIS01", &install.watch_dir, "")
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // The empty run never reached Skyline, the unopenable one stopped at the
+    // probe, and only the good one was extracted
+    let calls = std::fs::read_to_string(install.path().join("calls.txt")).unwrap();
+    assert_eq!(
+        calls.lines().collect::<Vec<_>>(),
+        [
+            "probe 20261016_QC_A_A2.raw",
+            "probe 20261016_QC_A_A3.raw",
+            "extract 20261016_QC_A_A3.raw",
+        ]
+    );
+
+    // The failures say why
+    let store = install.failed_files();
+    let reason = |path: &std::path::Path| {
+        store["files"][path.to_str().unwrap()]["reason"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    let empty_reason = reason(&empty);
+    assert!(empty_reason.contains("preflight probe"), "{}", empty_reason);
+    assert!(empty_reason.contains("file is empty"), "{}", empty_reason);
+    let unopenable_reason = reason(&unopenable);
+    assert!(
+        unopenable_reason.contains("preflight probe"),
+        "{}",
+        unopenable_reason
+    );
+    assert!(
+        unopenable_reason.contains("could not be opened"),
+        "{}",
+        unopenable_reason
+    );
+}
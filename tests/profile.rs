@@ -0,0 +1,97 @@
+//! `--profile <name>` selects `config.<name>.toml` from the data directory
+//! and keeps the profile's state under `profiles/<name>`.
+
+use std::process::Command;
+
+fn mdqc(data_dir: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(args)
+        .env_remove("MDQC_CONFIG")
+        .env_remove("MDQC_PROFILE")
+        .env("MDQC_DATA_DIR", data_dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_profile_selects_config_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("config.toml"),
+        "[cloud]\nendpoint = \"https://prod.example.com/\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("config.dev.toml"),
+        "[cloud]\nendpoint = \"https://dev.example.com/\"\n",
+    )
+    .unwrap();
+
+    let output = mdqc(dir.path(), &["--profile", "dev", "config", "validate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("Configuration is valid."), "{}", stdout);
+    assert!(stdout.contains("https://dev.example.com/"), "{}", stdout);
+
+    let output = mdqc(dir.path(), &["--profile", "dev", "status"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Profile: dev"));
+
+    // Without a profile the default file is used
+    let output = mdqc(dir.path(), &["config", "validate"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("https://prod.example.com/"));
+}
+
+#[test]
+fn test_missing_profile_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("config.toml"), "").unwrap();
+
+    let output = mdqc(
+        dir.path(),
+        &[
+            "--profile",
+            "staging",
+            "run",
+            "--foreground",
+            "--exit-after",
+            "1s",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Config profile 'staging' not found"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_profile_keeps_state_apart() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("config.dev.toml"),
+        "[cloud]\nendpoint = \"http://127.0.0.1:9/v1/\"\n",
+    )
+    .unwrap();
+
+    let output = mdqc(
+        dir.path(),
+        &[
+            "--profile",
+            "dev",
+            "run",
+            "--foreground",
+            "--exit-after",
+            "1s",
+        ],
+    );
+    assert!(output.status.success());
+
+    // Spool, lock and state are the profile's own
+    let profile_dir = dir.path().join("profiles").join("dev");
+    assert!(profile_dir.join("spool").join("pending").is_dir());
+    assert!(profile_dir.join("agent.lock").exists());
+    assert!(!dir.path().join("spool").exists());
+    assert!(!dir.path().join("agent.lock").exists());
+}
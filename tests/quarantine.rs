@@ -0,0 +1,92 @@
+//! Files that keep failing extraction are moved to the quarantine directory.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::TestInstall;
+
+/// Stub SkylineCmd step failing every import, counting the attempts in
+/// `attempts` (the startup column probe imports nothing and isn't counted).
+const FAIL_IMPORT: &str = r#"[ -n "$raw" ] || exit 0
+echo attempt >> "$(dirname "$0")/attempts"
+echo "Error: Failed to open raw file"
+exit 1
+"#;
+
+#[test]
+fn test_repeatedly_failing_file_is_quarantined() {
+    let install = TestInstall::new(FAIL_IMPORT);
+    let watch_dir = &install.watch_dir;
+    // Inside the watch folder, so the recursive pattern would match it
+    let quarantine_dir = watch_dir.join("quarantine");
+    let attempts = install.path().join("attempts");
+    let count_attempts = || std::fs::read_to_string(&attempts).unwrap().lines().count();
+    let instrument = |file_pattern: &str| {
+        format!(
+            "file_pattern = \"{}\"\nquarantine_dir = \"{}\"\nmax_retries = 1",
+            file_pattern,
+            quarantine_dir.display()
+        )
+    };
+
+    let raw_file = watch_dir.join("20261016_QC_A_A1.raw");
+    std::fs::write(&raw_file, b"fake raw data").unwrap();
+    let quarantined = quarantine_dir.join("20261016_QC_A_A1.raw");
+
+    // Each run processes the file once from the ready queue
+    let config = install
+        .config("quarantine-test")
+        .instrument("LINUX01", watch_dir, &instrument("*.never"))
+        .write();
+
+    // The first failure is kept for a retry
+    install.queue_ready(&[&raw_file]);
+    assert!(install.run_agent(&config, "3s").success());
+    assert_eq!(count_attempts(), 1);
+    assert!(raw_file.exists());
+    assert_eq!(
+        install.failed_files()["files"][raw_file.to_str().unwrap()]["retry_count"],
+        0
+    );
+
+    // The second exceeds max_retries
+    install.queue_ready(&[&raw_file]);
+    assert!(install.run_agent(&config, "3s").success());
+    assert_eq!(count_attempts(), 2);
+    assert!(quarantined.exists());
+    assert!(!raw_file.exists());
+
+    // The failed store points at the new location
+    let store = install.failed_files();
+    let entry = &store["files"][quarantined.to_str().unwrap()];
+    assert_eq!(entry["path"], quarantined.to_str().unwrap());
+    assert_eq!(entry["quarantined_from"], raw_file.to_str().unwrap());
+    assert!(store["files"][raw_file.to_str().unwrap()].is_null());
+
+    // The startup scan picks up a new run but not the quarantined one
+    let new_run = watch_dir.join("20261016_QC_B_A2.raw");
+    std::fs::write(&new_run, b"fake raw data").unwrap();
+    let config = install
+        .config("quarantine-test")
+        .section("[watcher]\nuse_filesystem_events = false")
+        .instrument("LINUX01", watch_dir, &instrument("**/*.raw"))
+        .write();
+    assert!(install.run_agent(&config, "2s").success());
+
+    let detected: Vec<String> = std::fs::read_dir(install.data_dir.join("logs"))
+        .unwrap()
+        .flatten()
+        .flat_map(|e| {
+            std::fs::read_to_string(e.path())
+                .unwrap()
+                .lines()
+                .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                .filter(|event| event["fields"]["message"] == "File detected via directory scan")
+                .map(|event| event["fields"]["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(detected, [new_run.to_str().unwrap()]);
+    assert_eq!(count_attempts(), 2);
+}
@@ -0,0 +1,35 @@
+//! Runs left in the ready queue when the agent stopped are processed on restart.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+#[test]
+fn test_queued_run_is_processed_after_restart() {
+    let install = TestInstall::new(WRITE_REPORT);
+
+    // The watcher's pattern never matches, so the run can only come back
+    // through the ready queue left by the "previous" agent
+    let raw = install.watch_dir.join("20261016_QC_A_A1.raw");
+    std::fs::write(&raw, b"fake raw data").unwrap();
+    install.queue_ready(&[&raw]);
+
+    let config = install
+        .config("ready-queue-test")
+        .instrument("LINUX01", &install.watch_dir, "")
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // Spooled (the upload itself fails; nothing is listening)
+    let spooled = install.payload_files(&["pending", "uploading", "failed", "completed"]);
+    assert_eq!(spooled.len(), 1);
+
+    // ...and no longer queued
+    let queue: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(install.data_dir.join("ready_queue.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(queue["files"].as_array().unwrap().len(), 0);
+}
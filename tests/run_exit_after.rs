@@ -0,0 +1,40 @@
+//! `mdqc run --foreground --exit-after` shuts the agent down on its own.
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_exit_after_stops_agent_cleanly() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "[agent]\nagent_id = \"exit-after-test\"\nenable_toast_notifications = false\n",
+    )
+    .unwrap();
+
+    let started = Instant::now();
+    let mut agent = Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(["run", "--foreground", "--exit-after", "2s"])
+        .env("MDQC_CONFIG", &config_path)
+        .env("MDQC_DATA_DIR", dir.path().join("data"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let deadline = started + Duration::from_secs(20);
+    let exit = loop {
+        if let Some(exit) = agent.try_wait().unwrap() {
+            break exit;
+        }
+        if Instant::now() > deadline {
+            let _ = agent.kill();
+            panic!("agent did not exit after --exit-after elapsed");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+
+    assert!(exit.success(), "agent exited with {}", exit);
+    assert!(started.elapsed() >= Duration::from_secs(2));
+}
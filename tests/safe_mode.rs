@@ -0,0 +1,50 @@
+//! `mdqc safe` works with a config that can't be loaded.
+
+use std::process::Command;
+
+fn mdqc(config_path: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_mdqc"))
+        .args(args)
+        .env("MDQC_CONFIG", config_path)
+        .env("MDQC_DATA_DIR", config_path.parent().unwrap())
+        .env("NO_PROXY", "*")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_safe_mode_with_invalid_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(&config_path, "[agent\nthis is not toml").unwrap();
+
+    // The doctor runs and reports the broken config instead of failing
+    let output = mdqc(&config_path, &["safe"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("System Health Check"), "{}", stdout);
+    assert!(stdout.contains("invalid"), "{}", stdout);
+    assert!(stdout.contains("mdqc safe --reset-config"), "{}", stdout);
+
+    // Resetting keeps the broken file and leaves a config that loads
+    let output = mdqc(&config_path, &["safe", "--reset-config"]);
+    assert!(output.status.success());
+    let backups: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .starts_with("config.toml.bak-")
+        })
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(backups[0].path()).unwrap(),
+        "[agent\nthis is not toml"
+    );
+
+    let output = mdqc(&config_path, &["config", "validate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Configuration is valid."), "{}", stdout);
+}
@@ -0,0 +1,55 @@
+//! Runs classified below `min_upload_confidence` are extracted and kept
+//! locally, but never spooled for upload.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{TestInstall, LOG_IMPORT, WRITE_REPORT};
+
+#[test]
+fn test_low_confidence_run_is_extracted_but_not_uploaded() {
+    let install = TestInstall::new(&format!("{}{}", LOG_IMPORT, WRITE_REPORT));
+
+    // With a well position the classification is HIGH, without one MEDIUM
+    let confident_run = install.watch_dir.join("20261016_QC_A_A1.raw");
+    let uncertain_run = install.watch_dir.join("20261016_QC_A.raw");
+    std::fs::write(&confident_run, b"fake raw data").unwrap();
+    std::fs::write(&uncertain_run, b"fake raw data").unwrap();
+    install.queue_ready(&[&confident_run, &uncertain_run]);
+
+    // The instrument's threshold overrides the cloud-wide one
+    let config = install
+        .config("confidence-test")
+        .cloud(r#"min_upload_confidence = "LOW""#)
+        .instrument(
+            "EXPLORIS01",
+            &install.watch_dir,
+            r#"min_upload_confidence = "HIGH""#,
+        )
+        .write();
+    assert!(install.run_agent(&config, "5s").success());
+
+    // Both runs were extracted
+    let imported = std::fs::read_to_string(install.path().join("imported.txt")).unwrap();
+    assert_eq!(imported.lines().filter(|l| !l.is_empty()).count(), 2);
+
+    // Only the confident run was spooled for upload (the endpoint is
+    // unreachable, so it is still pending or waiting to retry)
+    let spooled: Vec<String> = install
+        .payloads(&["pending", "uploading", "failed"])
+        .iter()
+        .map(|p| p["run"]["raw_file_name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(spooled, ["20261016_QC_A_A1.raw"]);
+
+    // The uncertain one is kept locally
+    let kept = install.payloads(&["observed"]);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0]["run"]["raw_file_name"], "20261016_QC_A.raw");
+
+    // And marked as such in the audit log
+    let record = install.decision(&uncertain_run);
+    assert_eq!(record["decision"], "below_upload_confidence");
+    assert!(record["detail"].as_str().unwrap().contains("MEDIUM"));
+}
@@ -0,0 +1,70 @@
+//! A zipped Bruker run is unpacked for Skyline and cleaned up afterwards.
+
+#![cfg(target_os = "linux")]
+
+use std::io::Write;
+
+mod common;
+
+use common::{TestInstall, WRITE_REPORT};
+
+/// Stub SkylineCmd step that only reports a row if it was handed an unpacked
+/// `.d` folder, and records what it was given.
+const CHECK_UNPACKED: &str = r#"echo "$raw" > "$(dirname "$0")/imported.txt"
+[ -f "$raw/analysis.tdf" ] || { echo "Error: cannot open $raw" >&2; exit 1; }
+"#;
+
+#[test]
+fn test_zipped_bruker_run_is_extracted() {
+    let install = TestInstall::new(&format!("{}{}", CHECK_UNPACKED, WRITE_REPORT));
+
+    // Archived after acquisition; stable from the start
+    let zipped = install.watch_dir.join("20261016_QC_A_A1.d.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zipped).unwrap());
+    for (name, content) in [
+        ("20261016_QC_A_A1.d/analysis.tdf", &b"tdf"[..]),
+        ("20261016_QC_A_A1.d/analysis.tdf_bin", &b"frames"[..]),
+    ] {
+        zip.start_file(name, zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(content).unwrap();
+    }
+    zip.finish().unwrap();
+
+    let config = install
+        .config("zipped-test")
+        .section(
+            "[watcher]\n\
+             use_filesystem_events = false\n\
+             scan_interval_seconds = 1\n\
+             stability_window_seconds = 0\n\
+             lock_free_checks = 1",
+        )
+        .instrument(
+            "TIMSTOF01",
+            &install.watch_dir,
+            "vendor = \"bruker\"\nfile_pattern = \"*\"\nallow_zipped = true",
+        )
+        .write();
+    assert!(install.run_agent(&config, "20s").success());
+
+    // Skyline got the unpacked folder, which is gone again
+    let imported = std::fs::read_to_string(install.path().join("imported.txt")).unwrap();
+    let imported = std::path::Path::new(imported.trim());
+    assert!(
+        imported.ends_with("20261016_QC_A_A1.d"),
+        "{}",
+        imported.display()
+    );
+    assert!(imported.starts_with(install.data_dir.join("spool").join("work")));
+    assert!(!imported.exists());
+
+    let payloads = install.payloads(&["pending", "uploading", "failed", "completed"]);
+    assert_eq!(payloads.len(), 1);
+    let payload = &payloads[0];
+    assert_eq!(payload["run"]["raw_file_name"], "20261016_QC_A_A1.d.zip");
+    assert_eq!(payload["run"]["control_type"], "QC_A");
+
+    // The zip itself is left alone
+    assert!(zipped.is_file());
+}